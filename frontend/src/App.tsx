@@ -32,6 +32,7 @@ export default function App() {
 
     const [authState, setAuthState] = useState<AuthState>('loading');
     const [authRequired, setAuthRequired] = useState(false);
+    const [oidcEnabled, setOidcEnabled] = useState(false);
     const [folders, setFolders] = useState<Folder[]>([]);
     const [appReady, setAppReady] = useState(false);
     const [isInitialLoading, setIsInitialLoading] = useState(true);
@@ -68,6 +69,7 @@ export default function App() {
             try {
                 const data = await apiClient.checkAuth();
                 setAuthRequired(data.required ?? false);
+                setOidcEnabled(data.oidc ?? false);
                 if (data.authenticated) {
                     setAuthState('authenticated');
                     // Fetch folders immediately
@@ -226,7 +228,7 @@ export default function App() {
 
     // Login screen - only show after initial load finishes and we are definitely unauthenticated
     if (authState === 'unauthenticated') {
-        return <LoginView onLogin={handleLogin} />;
+        return <LoginView onLogin={handleLogin} oidc={oidcEnabled} />;
     }
 
     return (
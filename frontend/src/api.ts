@@ -12,6 +12,37 @@ export interface DownloadPlan {
     parts: DownloadPart[];
 }
 
+export type JobState = 'queued' | 'running' | 'done' | 'failed' | 'cancelled';
+
+/** A background job on the server (see `/api/jobs`). */
+export interface Job {
+    id: string;
+    kind: string;
+    state: JobState;
+    done: number;
+    total: number;
+    attempts: number;
+    error: string | null;
+    result: unknown;
+}
+
+/**
+ * Identifies this page to the server: uploads send it as `X-Upload-Session` and the
+ * WebSocket subscribes with it, so per-file progress only reaches this client.
+ */
+export const UPLOAD_SESSION: string = (() => {
+    if (typeof crypto.randomUUID === 'function') return crypto.randomUUID();
+    // randomUUID needs a secure context; plain-HTTP installs on a LAN are common
+    const bytes = crypto.getRandomValues(new Uint8Array(16));
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    const hex = Array.from(bytes, b => b.toString(16).padStart(2, '0')).join('');
+    return `${hex.slice(0, 8)}-${hex.slice(8, 12)}-${hex.slice(12, 16)}-${hex.slice(16, 20)}-${hex.slice(20)}`;
+})();
+
+/** How often a waiting client checks on its job. */
+const JOB_POLL_MS = 1000;
+
 export interface ProgressInfo {
     received: number;
     total: number | null;
@@ -28,9 +59,10 @@ export class ApiClient {
         return `http://localhost:3000${path}`;
     }
 
-    async checkAuth(): Promise<{ authenticated: boolean; required: boolean }> {
+    async checkAuth(): Promise<{ authenticated: boolean; required: boolean; oidc?: boolean }> {
         const res = await apiFetch(this.getUrl('/api/auth-check'));
-        if (!res.ok) throw new Error('Failed to check auth');
+        // 401 still carries which login methods are available
+        if (!res.ok && res.status !== 401) throw new Error('Failed to check auth');
         return res.json();
     }
 
@@ -194,9 +226,34 @@ export class ApiClient {
             body: JSON.stringify(params),
         });
         if (!res.ok) throw new Error('Failed to fetch groups');
+        // Grouping runs as a background job; its result is the groups
+        const job: Job = await res.json();
+        const finished = await this.waitForJob(job.id);
+        if (finished.state !== 'done') throw new Error(finished.error || 'Failed to fetch groups');
+        return finished.result as MediaGroup[];
+    }
+
+    async getJob(id: string): Promise<Job> {
+        const res = await apiFetch(this.getUrl(`/api/jobs/${id}`));
+        if (!res.ok) throw new Error('Failed to fetch job');
         return res.json();
     }
 
+    /** Resolve once the job is done, failed or cancelled. Retries in between keep waiting. */
+    async waitForJob(id: string, signal?: AbortSignal): Promise<Job> {
+        for (;;) {
+            const job = await this.getJob(id);
+            if (job.state === 'done' || job.state === 'failed' || job.state === 'cancelled') return job;
+            await new Promise<void>((resolve, reject) => {
+                const timer = setTimeout(resolve, JOB_POLL_MS);
+                signal?.addEventListener('abort', () => {
+                    clearTimeout(timer);
+                    reject(new DOMException('Aborted', 'AbortError'));
+                }, { once: true });
+            });
+        }
+    }
+
     async toggleFavorite(id: string, favorite: boolean): Promise<void> {
         const res = await apiFetch(this.getUrl(`/api/media/${id}/favorite`), {
             method: 'POST',
@@ -1,13 +1,13 @@
 import { useState, useEffect, useRef, useCallback, Fragment, forwardRef, useImperativeHandle } from 'react';
 import { useParams, useSearchParams } from 'react-router-dom';
-import type { MediaItem, MediaFilter, Folder, MediaGroup, TagDetail } from '../types';
+import type { MediaItem, MediaFilter, Folder, MediaGroup, TagDetail, UploadProgress, UploadStage } from '../types';
 
 import { PhotoIcon, UploadIcon, PlusIcon, HeartIcon, TagIcon, LogoutIcon } from './Icons';
 import MediaCard from './MediaCard';
 import MediaModal from './MediaModal';
 import TagFilter from './TagFilter';
 import TagInput from './TagInput';
-import { apiClient } from '../api';
+import { apiClient, UPLOAD_SESSION } from '../api';
 import type { DownloadPlan } from '../api';
 import { fireUnauthorized } from '../auth';
 import { fireMediaUpdate } from '../events';
@@ -111,6 +111,16 @@ const FILTERS: { value: MediaFilter; label: string }[] = [
     { value: 'video', label: 'Videos' },
 ];
 
+const UPLOAD_STAGE_LABELS: Record<UploadStage['stage'], string> = {
+    received: 'Queued for processing',
+    hashing: 'Creating thumbnail',
+    extracting_frames: 'Extracting frames',
+    embedding: 'Indexing for search',
+    saved: 'Done',
+    duplicate: 'Duplicate',
+    failed: 'Failed',
+};
+
 const GalleryView = forwardRef<GalleryViewHandle, GalleryViewProps>(function GalleryView({ filter, onFilterChange, refreshKey, folderId, folderName, onBackToGallery, folders, onFoldersChanged, onUploadComplete, onBusyChange, isPicker, onPick, onCancelPick, onFindSimilar, favoritesOnly, singleSelect, onLogout, isActive = true }, ref) {
     const { folderId: routeFolderId } = useParams();
     const [searchParams, setSearchParams] = useSearchParams();
@@ -317,6 +327,8 @@ const GalleryView = forwardRef<GalleryViewHandle, GalleryViewProps>(function Gal
     const uploadActiveRef = useRef(0);
     const uploadQueueRef = useRef<File[]>([]);
     const UPLOAD_CONCURRENCY = 3;
+    /** Uploaded files the server is still working on, by media id */
+    const [processingUploads, setProcessingUploads] = useState<Record<string, { file: string; stage: UploadStage['stage'] }>>({});
 
     const sentinelRef = useRef<HTMLDivElement>(null);
     const scrollContainerRef = useRef<HTMLDivElement>(null);
@@ -431,6 +443,7 @@ const GalleryView = forwardRef<GalleryViewHandle, GalleryViewProps>(function Gal
             });
 
             xhr.open('POST', '/api/upload');
+            xhr.setRequestHeader('X-Upload-Session', UPLOAD_SESSION);
             xhr.send(formData);
         }
     }, [activeFolderId, onUploadComplete]);
@@ -452,8 +465,36 @@ const GalleryView = forwardRef<GalleryViewHandle, GalleryViewProps>(function Gal
 
     const clearUploadState = useCallback(() => {
         setUploadState(null);
+        setProcessingUploads({});
     }, []);
 
+    // Server-side progress of the files uploaded from this view
+    useEffect(() => {
+        if (!uploadState) return;
+        const handler = (e: Event) => {
+            const progress = (e as CustomEvent<UploadProgress>).detail;
+            const mediaId = progress.media_id;
+            if (!mediaId) return; // Rejected before it was stored; the upload response reports it
+            if (progress.stage === 'failed') {
+                setUploadState(prev => prev ? {
+                    ...prev,
+                    errors: [...prev.errors, { filename: processingUploads[mediaId]?.file ?? mediaId, reason: progress.error }],
+                } : prev);
+            }
+            setProcessingUploads(prev => {
+                const next = { ...prev };
+                if (progress.stage === 'saved' || progress.stage === 'duplicate' || progress.stage === 'failed') {
+                    delete next[mediaId];
+                } else {
+                    next[mediaId] = { file: progress.file ?? prev[mediaId]?.file ?? mediaId, stage: progress.stage };
+                }
+                return next;
+            });
+        };
+        window.addEventListener('gallerynet-upload-progress', handler);
+        return () => window.removeEventListener('gallerynet-upload-progress', handler);
+    }, [uploadState, processingUploads]);
+
     // --- Fetch groups ---
     const fetchGroups = useCallback(async (similarityOverride?: number) => {
         setIsLoading(true);
@@ -1584,7 +1625,7 @@ const GalleryView = forwardRef<GalleryViewHandle, GalleryViewProps>(function Gal
                                     <input
                                         ref={fileInputRef}
                                         type="file"
-                                        accept="image/*,video/*"
+                                        accept="image/*,video/*,.dng,.cr2,.nef,.arw,.raf"
                                         multiple
                                         className="hidden"
                                         onChange={(e) => {
@@ -1898,6 +1939,15 @@ const GalleryView = forwardRef<GalleryViewHandle, GalleryViewProps>(function Gal
                             style={{ width: `${uploadState.total > 0 ? Math.round(((uploadState.done + uploadState.skipped + uploadState.failed) / uploadState.total) * 100) : 0}%` }}
                         />
                     </div>
+                    {Object.keys(processingUploads).length > 0 && (
+                        <ul className="mt-2 space-y-0.5">
+                            {Object.entries(processingUploads).map(([id, { file, stage }]) => (
+                                <li key={id} className="text-xs text-gray-500 dark:text-gray-400 truncate">
+                                    <span className="font-medium">{file}</span> &mdash; {UPLOAD_STAGE_LABELS[stage]}
+                                </li>
+                            ))}
+                        </ul>
+                    )}
                     {uploadState.errors.length > 0 && (
                         <ul className="mt-2 space-y-1">
                             {uploadState.errors.map((err, i) => (
@@ -1974,6 +2024,7 @@ const GalleryView = forwardRef<GalleryViewHandle, GalleryViewProps>(function Gal
                                         onToggleFavorite={() => handleToggleFavorite(item)}
                                         onDragStart={(e) => handleDragStartMedia(item, e)}
                                         showSize={sortBy === 'size'}
+                                        thumbSize={thumbSize}
                                     focused={focusedId === item.id}
                                 />
                                 </Fragment>
@@ -2023,6 +2074,7 @@ const GalleryView = forwardRef<GalleryViewHandle, GalleryViewProps>(function Gal
                                             onSelect={(e) => handleSelect(item.id!, e)}
                                             onToggleFavorite={() => handleToggleFavorite(item)}
                                             onDragStart={(e) => handleDragStartMedia(item, e)}
+                                            thumbSize={thumbSize}
                                             focused={focusedId === item.id}
                                         />
                                     ))}
@@ -2,11 +2,17 @@ import { useState, useCallback, type FormEvent } from 'react';
 
 interface LoginViewProps {
     onLogin: () => void;
+    /** Offer single sign-on through the configured OIDC provider. */
+    oidc?: boolean;
 }
 
-export default function LoginView({ onLogin }: LoginViewProps) {
+export default function LoginView({ onLogin, oidc = false }: LoginViewProps) {
+    const [username, setUsername] = useState('');
     const [password, setPassword] = useState('');
-    const [error, setError] = useState('');
+    // The SSO callback returns here with ?sso_error=1 when the provider login failed
+    const [error, setError] = useState(() =>
+        new URLSearchParams(window.location.search).has('sso_error') ? 'Single sign-on failed' : ''
+    );
     const [loading, setLoading] = useState(false);
 
     const handleSubmit = useCallback(async (e: FormEvent) => {
@@ -18,21 +24,22 @@ export default function LoginView({ onLogin }: LoginViewProps) {
             const res = await fetch('/api/login', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ password }),
+                // An empty username signs in as the admin account created from GALLERY_PASSWORD
+                body: JSON.stringify({ username: username.trim() || undefined, password }),
             });
 
             if (res.ok) {
                 onLogin();
             } else {
                 const data = await res.json().catch(() => ({}));
-                setError(data.error || 'Invalid password');
+                setError(data.error || 'Invalid username or password');
             }
         } catch {
             setError('Connection failed');
         } finally {
             setLoading(false);
         }
-    }, [password, onLogin]);
+    }, [username, password, onLogin]);
 
     return (
         <div className="flex items-center justify-center min-h-screen bg-gray-50 dark:bg-gray-900">
@@ -42,17 +49,28 @@ export default function LoginView({ onLogin }: LoginViewProps) {
                         <h1 className="text-3xl font-bold text-transparent bg-clip-text bg-gradient-to-r from-blue-600 to-purple-600">
                             GalleryNet
                         </h1>
-                        <p className="text-sm text-gray-500 dark:text-gray-400 mt-2">Enter your password to continue</p>
+                        <p className="text-sm text-gray-500 dark:text-gray-400 mt-2">Sign in to continue</p>
                     </div>
 
                     <form onSubmit={handleSubmit} className="space-y-4">
+                        <div>
+                            <input
+                                type="text"
+                                value={username}
+                                onChange={e => setUsername(e.target.value)}
+                                placeholder="Username (optional)"
+                                autoComplete="username"
+                                autoFocus
+                                className="w-full px-4 py-3 rounded-lg border border-gray-300 dark:border-gray-600 focus:border-blue-500 focus:ring-2 focus:ring-blue-200 dark:focus:ring-blue-800 outline-none transition-all text-sm dark:bg-gray-700 dark:text-gray-100 dark:placeholder:text-gray-400"
+                            />
+                        </div>
                         <div>
                             <input
                                 type="password"
                                 value={password}
                                 onChange={e => setPassword(e.target.value)}
                                 placeholder="Password"
-                                autoFocus
+                                autoComplete="current-password"
                                 required
                                 className="w-full px-4 py-3 rounded-lg border border-gray-300 dark:border-gray-600 focus:border-blue-500 focus:ring-2 focus:ring-blue-200 dark:focus:ring-blue-800 outline-none transition-all text-sm dark:bg-gray-700 dark:text-gray-100 dark:placeholder:text-gray-400"
                             />
@@ -70,6 +88,22 @@ export default function LoginView({ onLogin }: LoginViewProps) {
                             {loading ? 'Signing in...' : 'Sign In'}
                         </button>
                     </form>
+
+                    {oidc && (
+                        <>
+                            <div className="flex items-center gap-3 my-6">
+                                <div className="flex-1 h-px bg-gray-200 dark:bg-gray-700" />
+                                <span className="text-xs text-gray-400">or</span>
+                                <div className="flex-1 h-px bg-gray-200 dark:bg-gray-700" />
+                            </div>
+                            <a
+                                href="/api/oidc/login"
+                                className="block w-full py-3 rounded-lg border border-gray-300 dark:border-gray-600 text-center text-sm font-medium text-gray-700 dark:text-gray-200 hover:bg-gray-50 dark:hover:bg-gray-700 transition-all"
+                            >
+                                Sign in with SSO
+                            </a>
+                        </>
+                    )}
                 </div>
             </div>
         </div>
@@ -1,3 +1,4 @@
+import { useState } from 'react';
 import type { MediaItem } from '../types';
 import { HeartIcon } from './Icons';
 
@@ -15,6 +16,8 @@ interface MediaCardProps {
     showSize?: boolean;
     /** Whether the card is focused via keyboard navigation */
     focused?: boolean;
+    /** Minimum width of a grid cell in CSS pixels, so the browser can pick a sharp enough image */
+    thumbSize?: number;
 }
 
 
@@ -28,6 +31,17 @@ function thumbnailUrl(filename: string): string {
     return `/thumbnails/${base}.jpg`;
 }
 
+/** Square thumbnail for small cells, the smallest preview for large or high-DPI ones */
+function thumbnailSrcSet(item: MediaItem): string | undefined {
+    if (!item.id) return undefined;
+    return `${thumbnailUrl(item.filename)} 224w, /api/media/${item.id}/preview/720 720w`;
+}
+
+/** Looping MP4 the backend generates for videos and animated GIFs when ANIMATED_PREVIEWS is on */
+function animationUrl(id: string): string {
+    return `/thumbnails/${id.substring(0, 2)}/${id.substring(2, 4)}/${id}_animated.mp4`;
+}
+
 function isVideo(filename: string): boolean {
     const ext = filename.split('.').pop()?.toLowerCase() ?? '';
     return VIDEO_EXTENSIONS.has(ext);
@@ -41,10 +55,14 @@ function formatBytes(bytes: number): string {
     return `${val.toFixed(i > 0 ? 1 : 0)} ${units[i]}`;
 }
 
-export default function MediaCard({ item, onClick, selected, selectionMode, onSelect, onToggleFavorite, onDragStart, showSize, focused }: MediaCardProps) {
+export default function MediaCard({ item, onClick, selected, selectionMode, onSelect, onToggleFavorite, onDragStart, showSize, focused, thumbSize }: MediaCardProps) {
 
 
     const video = isVideo(item.filename);
+    const [hovering, setHovering] = useState(false);
+    // Missing when animated previews are off or the GIF is a still; don't ask again
+    const [animationMissing, setAnimationMissing] = useState(false);
+    const animatable = !!item.id && (video || item.filename.toLowerCase().endsWith('.gif'));
 
     const handleKeyDown = (e: React.KeyboardEvent) => {
         if (e.key === 'Enter' || e.key === ' ') {
@@ -65,6 +83,8 @@ export default function MediaCard({ item, onClick, selected, selectionMode, onSe
             onKeyDown={handleKeyDown}
             draggable={!!onDragStart}
             onDragStart={onDragStart}
+            onMouseEnter={() => setHovering(true)}
+            onMouseLeave={() => setHovering(false)}
             tabIndex={0}
             className={`group relative block overflow-hidden rounded-lg bg-gray-100 dark:bg-gray-800 border shadow-sm transition-all hover:shadow-md hover:-translate-y-0.5 w-full text-left cursor-pointer outline-none ${
 
@@ -73,9 +93,11 @@ export default function MediaCard({ item, onClick, selected, selectionMode, onSe
                     : 'border-gray-200/60 dark:border-gray-700/60'
             }`}
         >
-            <div className="aspect-square w-full">
+            <div className="relative aspect-square w-full">
                 <img
                     src={thumbnailUrl(item.filename)}
+                    srcSet={thumbnailSrcSet(item)}
+                    sizes={`${thumbSize ?? 224}px`}
                     alt={item.original_filename || item.filename}
                     loading="lazy"
                     decoding="async"
@@ -89,6 +111,17 @@ export default function MediaCard({ item, onClick, selected, selectionMode, onSe
                         }
                     }}
                 />
+                {hovering && animatable && !animationMissing && !selectionMode && (
+                    <video
+                        src={animationUrl(item.id!)}
+                        autoPlay
+                        muted
+                        loop
+                        playsInline
+                        className="pointer-events-none absolute inset-0 h-full w-full object-cover"
+                        onError={() => setAnimationMissing(true)}
+                    />
+                )}
             </div>
 
             {/* Selection checkbox — visible in selection mode or on hover */}
@@ -46,6 +46,8 @@ export default function MediaModal({ item, onClose, onPrev, onNext, onFindSimila
     const backdropRef = useRef<HTMLDivElement>(null);
     const video = isVideo(item.filename);
     const mediaUrl = `/uploads/${item.filename}`;
+    // Large downscaled copy; also the only way to show formats browsers cannot decode
+    const previewUrl = item.id ? `/api/media/${item.id}/preview/2048` : mediaUrl;
 
     const [detail, setDetail] = useState<MediaItem | null>(null);
     const [exifOpen, setExifOpen] = useState(false);
@@ -301,8 +303,15 @@ export default function MediaModal({ item, onClose, onPrev, onNext, onFindSimila
 
                         <img
                             key={item.filename}
-                            src={mediaUrl}
+                            src={previewUrl}
                             alt={item.original_filename || item.filename}
+                            onError={(e) => {
+                                const img = e.currentTarget;
+                                // Only attempt fallback once to prevent infinite loop
+                                if (!img.src.endsWith(mediaUrl)) {
+                                    img.src = mediaUrl;
+                                }
+                            }}
                             draggable
                             onDragStart={handleDragStart}
                             className="max-w-full max-h-[90vh] lg:max-h-[94vh] rounded-lg shadow-2xl object-contain cursor-grab active:cursor-grabbing"
@@ -0,0 +1,204 @@
+import { useState, useEffect, useCallback, type FormEvent } from 'react';
+import { useParams } from 'react-router-dom';
+import type { DownloadPlan } from '../api';
+
+interface ShareInfo {
+    title: string;
+    kind: 'folder' | 'media';
+    allow_download: boolean;
+    expires_at: string | null;
+}
+
+interface SharedMedia {
+    id: string;
+    original_filename: string;
+    media_type: string;
+}
+
+const PAGE_SIZE = 60;
+
+/** Public page for a share link. Works without an account; the server checks the link. */
+export default function ShareView() {
+    const { slug = '' } = useParams();
+    const base = `/api/s/${encodeURIComponent(slug)}`;
+
+    const [info, setInfo] = useState<ShareInfo | null>(null);
+    const [items, setItems] = useState<SharedMedia[]>([]);
+    const [hasMore, setHasMore] = useState(false);
+    const [page, setPage] = useState(1);
+    const [needsPassword, setNeedsPassword] = useState(false);
+    const [password, setPassword] = useState('');
+    const [error, setError] = useState('');
+    const [loading, setLoading] = useState(true);
+
+    const loadPage = useCallback(async (nextPage: number) => {
+        const res = await fetch(`${base}/media?page=${nextPage}&limit=${PAGE_SIZE}`);
+        if (!res.ok) return;
+        const data: SharedMedia[] = await res.json();
+        setItems(prev => nextPage === 1 ? data : [...prev, ...data]);
+        setHasMore(data.length === PAGE_SIZE);
+        setPage(nextPage);
+    }, [base]);
+
+    const loadShare = useCallback(async () => {
+        setLoading(true);
+        try {
+            const res = await fetch(base);
+            if (res.status === 401) {
+                setNeedsPassword(true);
+                return;
+            }
+            if (!res.ok) {
+                setError('This link does not exist or has expired');
+                return;
+            }
+            setNeedsPassword(false);
+            setInfo(await res.json());
+            await loadPage(1);
+        } catch {
+            setError('Connection failed');
+        } finally {
+            setLoading(false);
+        }
+    }, [base, loadPage]);
+
+    useEffect(() => {
+        loadShare();
+    }, [loadShare]);
+
+    const handleUnlock = useCallback(async (e: FormEvent) => {
+        e.preventDefault();
+        setError('');
+        try {
+            const res = await fetch(`${base}/unlock`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ password }),
+            });
+            if (res.ok) {
+                setPassword('');
+                await loadShare();
+            } else {
+                const data = await res.json().catch(() => ({}));
+                setError(data.error || 'Wrong password');
+            }
+        } catch {
+            setError('Connection failed');
+        }
+    }, [base, password, loadShare]);
+
+    const handleDownload = useCallback(async () => {
+        const res = await fetch(`${base}/download`);
+        if (!res.ok) return;
+        const plan: DownloadPlan = await res.json();
+        // Each part is a plain attachment; let the browser stream it to disk
+        for (const part of plan.parts) {
+            const a = document.createElement('a');
+            a.href = `${base}/download/${part.id}`;
+            a.download = part.filename;
+            document.body.appendChild(a);
+            a.click();
+            document.body.removeChild(a);
+        }
+    }, [base]);
+
+    if (needsPassword) {
+        return (
+            <div className="flex items-center justify-center min-h-screen bg-gray-50 dark:bg-gray-900">
+                <div className="w-full max-w-sm">
+                    <div className="bg-white dark:bg-gray-800 rounded-2xl shadow-lg border border-gray-200 dark:border-gray-700 p-8">
+                        <div className="text-center mb-8">
+                            <h1 className="text-3xl font-bold text-transparent bg-clip-text bg-gradient-to-r from-blue-600 to-purple-600">
+                                GalleryNet
+                            </h1>
+                            <p className="text-sm text-gray-500 dark:text-gray-400 mt-2">This link is password protected</p>
+                        </div>
+
+                        <form onSubmit={handleUnlock} className="space-y-4">
+                            <input
+                                type="password"
+                                value={password}
+                                onChange={e => setPassword(e.target.value)}
+                                placeholder="Password"
+                                autoFocus
+                                required
+                                className="w-full px-4 py-3 rounded-lg border border-gray-300 dark:border-gray-600 focus:border-blue-500 focus:ring-2 focus:ring-blue-200 dark:focus:ring-blue-800 outline-none transition-all text-sm dark:bg-gray-700 dark:text-gray-100 dark:placeholder:text-gray-400"
+                            />
+
+                            {error && (
+                                <p className="text-sm text-red-600 dark:text-red-400 text-center">{error}</p>
+                            )}
+
+                            <button
+                                type="submit"
+                                disabled={!password}
+                                className="w-full py-3 rounded-lg bg-gradient-to-r from-blue-600 to-purple-600 text-white font-medium text-sm hover:from-blue-700 hover:to-purple-700 disabled:opacity-50 disabled:cursor-not-allowed transition-all"
+                            >
+                                Open
+                            </button>
+                        </form>
+                    </div>
+                </div>
+            </div>
+        );
+    }
+
+    return (
+        <div className="min-h-screen bg-gray-50 dark:bg-gray-900">
+            <header className="flex items-center justify-between px-6 py-4 border-b border-gray-200 dark:border-gray-700 bg-white dark:bg-gray-800">
+                <h1 className="text-lg font-semibold text-gray-800 dark:text-gray-100 truncate">
+                    {info?.title ?? 'GalleryNet'}
+                </h1>
+                {info?.allow_download && items.length > 0 && (
+                    <button
+                        onClick={handleDownload}
+                        className="px-4 py-2 rounded-lg bg-gradient-to-r from-blue-600 to-purple-600 text-white text-sm font-medium hover:from-blue-700 hover:to-purple-700 transition-all"
+                    >
+                        Download
+                    </button>
+                )}
+            </header>
+
+            {error && (
+                <p className="text-sm text-red-600 dark:text-red-400 text-center mt-16">{error}</p>
+            )}
+            {loading && !info && !error && (
+                <p className="text-sm text-gray-500 dark:text-gray-400 text-center mt-16">Loading...</p>
+            )}
+
+            <main className="grid grid-cols-2 sm:grid-cols-3 md:grid-cols-4 lg:grid-cols-6 gap-2 p-4">
+                {items.map(item => (
+                    <a
+                        key={item.id}
+                        href={info?.allow_download ? `${base}/media/${item.id}` : `${base}/preview/${item.id}`}
+                        target="_blank"
+                        rel="noopener noreferrer"
+                        title={item.original_filename}
+                        className="relative aspect-square overflow-hidden rounded-lg bg-gray-200 dark:bg-gray-800"
+                    >
+                        <img
+                            src={`${base}/thumbnails/${item.id}`}
+                            alt={item.original_filename}
+                            loading="lazy"
+                            className="w-full h-full object-cover"
+                        />
+                        {item.media_type === 'video' && (
+                            <span className="absolute bottom-1 right-1 px-1.5 py-0.5 rounded bg-black/60 text-white text-xs">Video</span>
+                        )}
+                    </a>
+                ))}
+            </main>
+
+            {hasMore && (
+                <div className="flex justify-center pb-8">
+                    <button
+                        onClick={() => loadPage(page + 1)}
+                        className="px-4 py-2 rounded-lg border border-gray-300 dark:border-gray-600 text-sm text-gray-700 dark:text-gray-200 hover:bg-gray-100 dark:hover:bg-gray-800"
+                    >
+                        Load more
+                    </button>
+                </div>
+            )}
+        </div>
+    );
+}
@@ -1,4 +1,4 @@
-import type { MediaItem } from './types';
+import type { MediaItem, UploadProgress } from './types';
 
 /**
  * Dispatches a custom 'gallerynet-media-update' event.
@@ -11,3 +11,12 @@ export function fireMediaUpdate(id: string, item: Partial<MediaItem>, action: 'u
         }));
     }
 }
+
+/**
+ * Dispatches a 'gallerynet-upload-progress' event for a file this client uploaded.
+ */
+export function fireUploadProgress(progress: UploadProgress) {
+    if (typeof window !== 'undefined' && window.dispatchEvent) {
+        window.dispatchEvent(new CustomEvent('gallerynet-upload-progress', { detail: progress }));
+    }
+}
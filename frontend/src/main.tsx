@@ -1,7 +1,8 @@
 import {StrictMode} from 'react'
 import {createRoot} from 'react-dom/client'
-import {BrowserRouter} from 'react-router-dom'
+import {BrowserRouter, Routes, Route} from 'react-router-dom'
 import App from './App.tsx'
+import ShareView from './components/ShareView.tsx'
 
 // THIS LINE IS CRITICAL: It tells React to load Tailwind
 import './index.css'
@@ -9,7 +10,11 @@ import './index.css'
 createRoot(document.getElementById('root')!).render(
     <StrictMode>
         <BrowserRouter>
-            <App/>
+            <Routes>
+                {/* Public share links render without the login gate */}
+                <Route path="/s/:slug" element={<ShareView/>}/>
+                <Route path="*" element={<App/>}/>
+            </Routes>
         </BrowserRouter>
     </StrictMode>,
 )
\ No newline at end of file
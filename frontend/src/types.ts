@@ -22,8 +22,23 @@ export interface MediaItem {
     exif_json?: string;
     is_favorite?: boolean;
     tags?: TagDetail[];
+    /** Accepted upload still waiting for its thumbnail and embedding. */
+    processing?: boolean;
 }
 
+/** How far the server got with one uploaded file (`UploadProgress` WebSocket message). */
+export type UploadStage =
+    | { stage: 'received' | 'hashing' | 'extracting_frames' | 'embedding' | 'saved' }
+    | { stage: 'duplicate'; existing: string }
+    | { stage: 'failed'; error: string };
+
+export type UploadProgress = UploadStage & {
+    /** Name of the uploaded file; only sent while its request is handled. */
+    file?: string;
+    /** Set once the file is stored. */
+    media_id: string | null;
+};
+
 export type MediaFilter = 'all' | 'image' | 'video';
 
 export interface Folder {
@@ -2,10 +2,12 @@ import { describe, it, expect, vi, beforeEach, afterEach } from 'vitest';
 import { renderHook } from '@testing-library/react';
 import { useWebSocket } from './useWebSocket';
 import * as events from './events';
+import { UPLOAD_SESSION } from './api';
 
 // Mock the events module
 vi.mock('./events', () => ({
     fireMediaUpdate: vi.fn(),
+    fireUploadProgress: vi.fn(),
 }));
 
 class MockWebSocket {
@@ -59,7 +61,7 @@ describe('useWebSocket', () => {
 
     it('connects to the correct URL', () => {
         renderHook(() => useWebSocket(onFoldersChanged, onUploadComplete, onThumbnailFixStatusChange, true));
-        expect(WebSocket).toHaveBeenCalledWith('ws://localhost:3000/api/ws');
+        expect(WebSocket).toHaveBeenCalledWith(`ws://localhost:3000/api/ws?upload_session=${UPLOAD_SESSION}`);
     });
 
     it('handles MediaCreated message', () => {
@@ -95,13 +97,13 @@ describe('useWebSocket', () => {
         expect(events.fireMediaUpdate).toHaveBeenCalledWith('media-1', mediaItem);
     });
 
-    it('handles MediaDeleted message', () => {
+    it('handles MediaTrashed message', () => {
         renderHook(() => useWebSocket(onFoldersChanged, onUploadComplete, onThumbnailFixStatusChange, true));
         
         const event = {
             data: JSON.stringify({
-                type: 'MediaDeleted',
-                data: { id: 'media-1' }
+                type: 'MediaTrashed',
+                data: { ids: ['media-1'] }
             })
         };
 
@@ -164,6 +166,16 @@ describe('useWebSocket', () => {
         expect(onUploadComplete).not.toHaveBeenCalled();
     });
 
+    it('forwards UploadProgress messages', () => {
+        renderHook(() => useWebSocket(onFoldersChanged, onUploadComplete, onThumbnailFixStatusChange, true));
+
+        const progress = { media_id: 'media-1', stage: 'extracting_frames' };
+        mockWebSocket.onmessage?.({ data: JSON.stringify({ type: 'UploadProgress', data: progress }) });
+
+        expect(events.fireUploadProgress).toHaveBeenCalledWith(progress);
+        expect(onUploadComplete).not.toHaveBeenCalled();
+    });
+
     it('reconnects on close', () => {
         renderHook(() => useWebSocket(onFoldersChanged, onUploadComplete, onThumbnailFixStatusChange, true));
         
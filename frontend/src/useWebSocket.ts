@@ -1,6 +1,7 @@
 import { useEffect, useRef, useCallback } from 'react';
-import type { MediaItem, Folder } from './types';
-import { fireMediaUpdate } from './events';
+import type { MediaItem, Folder, UploadProgress } from './types';
+import { fireMediaUpdate, fireUploadProgress } from './events';
+import { UPLOAD_SESSION } from './api';
 
 type WsMessage = {
     type: 'MediaCreated',
@@ -9,8 +10,11 @@ type WsMessage = {
     type: 'MediaUpdated',
     data: { id: string, item: Partial<MediaItem> }
 } | {
-    type: 'MediaDeleted',
-    data: { id: string }
+    type: 'MediaTrashed',
+    data: { ids: string[] }
+} | {
+    type: 'MediaRestored',
+    data: { ids: string[] }
 } | {
     type: 'MediaBatchDeleted',
     data: { ids: string[] }
@@ -50,6 +54,9 @@ type WsMessage = {
 } | {
     type: 'ThumbnailFixCompleted',
     data: { count: number }
+} | {
+    type: 'UploadProgress',
+    data: UploadProgress
 };
 
 export function useWebSocket(
@@ -80,7 +87,7 @@ export function useWebSocket(
         const connect = () => {
             const protocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
             const host = window.location.host;
-            const wsUrl = `${protocol}//${host}/api/ws`;
+            const wsUrl = `${protocol}//${host}/api/ws?upload_session=${UPLOAD_SESSION}`;
 
             socket = new WebSocket(wsUrl);
             socketRef.current = socket;
@@ -104,10 +111,7 @@ export function useWebSocket(
                         case 'MediaUpdated':
                             fireMediaUpdate(msg.data.id, msg.data.item);
                             break;
-                        case 'MediaDeleted':
-                            fireMediaUpdate(msg.data.id, {}, 'delete');
-                            onFoldersChanged();
-                            break;
+                        case 'MediaTrashed':
                         case 'MediaBatchDeleted':
                             msg.data.ids.forEach(id => fireMediaUpdate(id, {}, 'delete'));
                             onFoldersChanged();
@@ -123,6 +127,10 @@ export function useWebSocket(
                         case 'MediaRemovedFromFolder':
                             onFoldersChanged();
                             break;
+                        case 'MediaRestored':
+                            onFoldersChanged();
+                            debouncedUploadComplete();
+                            break;
                         case 'TagLearningComplete':
                         case 'FullRefresh':
                         case 'UploadComplete':
@@ -134,6 +142,9 @@ export function useWebSocket(
                         case 'ThumbnailFixCompleted':
                             onThumbnailFixStatusChange(false);
                             break;
+                        case 'UploadProgress':
+                            fireUploadProgress(msg.data);
+                            break;
                     }
                 } catch (err) {
                     console.error('Failed to parse WS message', err);
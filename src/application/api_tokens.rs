@@ -0,0 +1,116 @@
+use crate::domain::{ApiToken, DomainError, MediaRepository, TokenScope, User};
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::sessions::{hash_token, new_token};
+
+/// Prefix of every API token, so leaked tokens are easy to recognise in logs and scans.
+const TOKEN_PREFIX: &str = "gn_";
+
+/// `last_used_at` is only written when it is older than this.
+const TOUCH_INTERVAL_SECS: i64 = 60;
+
+/// Long-lived bearer tokens for scripts. A token can never do more than its owner's
+/// role allows; the scope narrows that further.
+pub struct ApiTokensUseCase {
+    repo: Arc<dyn MediaRepository>,
+}
+
+impl ApiTokensUseCase {
+    pub fn new(repo: Arc<dyn MediaRepository>) -> Self {
+        Self { repo }
+    }
+
+    /// Create a token for `user_id`. The returned secret is shown once and never stored.
+    pub fn create(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        scope: TokenScope,
+    ) -> Result<(ApiToken, String), DomainError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(DomainError::Io("Token name cannot be empty".to_string()));
+        }
+
+        let secret = format!("{}{}", TOKEN_PREFIX, new_token());
+        let token = ApiToken {
+            id: Uuid::new_v4(),
+            user_id,
+            name: name.to_string(),
+            scope,
+            created_at: Utc::now(),
+            last_used_at: None,
+        };
+        self.repo.create_api_token(&token, &hash_token(&secret))?;
+        Ok((token, secret))
+    }
+
+    /// Resolve a bearer token to the token and its owner.
+    pub fn authenticate(&self, secret: &str) -> Result<Option<(ApiToken, User)>, DomainError> {
+        if !secret.starts_with(TOKEN_PREFIX) {
+            return Ok(None);
+        }
+        let Some(mut token) = self.repo.find_api_token(&hash_token(secret))? else {
+            return Ok(None);
+        };
+        let Some(user) = self.repo.find_user_by_id(token.user_id)? else {
+            return Ok(None);
+        };
+        let now = Utc::now();
+        let stale = token
+            .last_used_at
+            .is_none_or(|at| now - at >= Duration::seconds(TOUCH_INTERVAL_SECS));
+        if stale {
+            self.repo.touch_api_token(token.id, now)?;
+            token.last_used_at = Some(now);
+        }
+        Ok(Some((token, user)))
+    }
+
+    pub fn list(&self, user_id: Uuid) -> Result<Vec<ApiToken>, DomainError> {
+        self.repo.list_api_tokens(user_id)
+    }
+
+    /// Revoke one of the user's own tokens.
+    pub fn revoke(&self, user_id: Uuid, id: Uuid) -> Result<(), DomainError> {
+        if !self.repo.delete_api_token(user_id, id)? {
+            return Err(DomainError::NotFound);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Role;
+    use crate::infrastructure::{SqliteRepository, TestDb};
+
+    #[test]
+    fn create_authenticate_and_revoke() {
+        let db = TestDb::new("api_tokens_use_case_test");
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let user = User { id: Uuid::new_v4(), username: "kim".to_string(), role: Role::Member, created_at: Utc::now() };
+        repo.create_user(&user, "hash").unwrap();
+        let tokens = ApiTokensUseCase::new(repo.clone());
+
+        assert!(tokens.create(user.id, "  ", TokenScope::Read).is_err());
+        let (token, secret) = tokens.create(user.id, "backup script", TokenScope::Read).unwrap();
+        assert!(secret.starts_with(TOKEN_PREFIX));
+
+        let (found, owner) = tokens.authenticate(&secret).unwrap().unwrap();
+        assert_eq!(found.id, token.id);
+        assert_eq!(owner.id, user.id);
+        // The first use is recorded
+        assert!(tokens.list(user.id).unwrap()[0].last_used_at.is_some());
+
+        assert!(tokens.authenticate(&secret[TOKEN_PREFIX.len()..]).unwrap().is_none());
+        assert!(tokens.authenticate("gn_deadbeef").unwrap().is_none());
+
+        assert!(matches!(tokens.revoke(Uuid::new_v4(), token.id), Err(DomainError::NotFound)));
+        tokens.revoke(user.id, token.id).unwrap();
+        assert!(tokens.authenticate(&secret).unwrap().is_none());
+    }
+}
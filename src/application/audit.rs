@@ -0,0 +1,99 @@
+use crate::domain::{AuditEntry, DomainError, MediaRepository, User};
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use tracing::error;
+
+/// Longest `detail` kept per entry; it can carry user input such as tag lists.
+const MAX_DETAIL_LEN: usize = 1024;
+
+/// Append-only record of who deleted, purged, imported or changed what, and from where.
+pub struct AuditUseCase {
+    repo: Arc<dyn MediaRepository>,
+    /// 0 keeps entries forever.
+    retention_days: u64,
+}
+
+impl AuditUseCase {
+    pub fn new(repo: Arc<dyn MediaRepository>, retention_days: u64) -> Self {
+        Self { repo, retention_days }
+    }
+
+    /// Record an action. A failed write is logged rather than returned: the action
+    /// itself already happened and the request should not fail because of its audit.
+    pub fn record(
+        &self,
+        user: Option<&User>,
+        ip: Option<String>,
+        action: &str,
+        target_ids: Vec<String>,
+        detail: Option<String>,
+    ) {
+        let entry = AuditEntry {
+            id: 0,
+            created_at: Utc::now(),
+            user_id: user.map(|u| u.id),
+            username: user.map(|u| u.username.clone()),
+            ip,
+            action: action.to_string(),
+            target_ids,
+            detail: detail.map(|d| d.chars().take(MAX_DETAIL_LEN).collect()),
+        };
+        if let Err(e) = self.repo.append_audit(&entry) {
+            error!("Failed to write audit entry for {}: {}", action, e);
+        }
+    }
+
+    /// One page of entries, newest first.
+    pub fn list(
+        &self,
+        action: Option<&str>,
+        page: usize,
+        limit: usize,
+    ) -> Result<Vec<AuditEntry>, DomainError> {
+        let offset = (page.max(1) - 1) * limit;
+        self.repo.list_audit(action, limit, offset)
+    }
+
+    /// Delete entries older than the retention period. Returns how many were removed.
+    pub fn prune(&self) -> Result<usize, DomainError> {
+        if self.retention_days == 0 {
+            return Ok(0);
+        }
+        self.repo
+            .delete_audit_before(Utc::now() - Duration::days(self.retention_days as i64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Role;
+    use crate::infrastructure::{SqliteRepository, TestDb};
+    use uuid::Uuid;
+
+    #[test]
+    fn records_and_pages_entries() {
+        let db = TestDb::new("audit_use_case_test");
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let audit = AuditUseCase::new(repo.clone(), 0);
+        let kim = User { id: Uuid::new_v4(), username: "kim".to_string(), role: Role::Admin, created_at: Utc::now() };
+
+        audit.record(None, Some("203.0.113.9".to_string()), "auth.login_failed", vec![], Some("kim".to_string()));
+        for i in 0..3 {
+            audit.record(Some(&kim), None, "media.delete", vec![i.to_string()], None);
+        }
+
+        let first = audit.list(None, 1, 2).unwrap();
+        assert_eq!(first.iter().map(|e| e.target_ids[0].as_str()).collect::<Vec<_>>(), ["2", "1"]);
+        assert_eq!(first[0].username.as_deref(), Some("kim"));
+        let second = audit.list(None, 2, 2).unwrap();
+        assert_eq!(second.len(), 2);
+        assert_eq!(second[1].action, "auth.login_failed");
+        assert_eq!(second[1].user_id, None);
+        assert_eq!(audit.list(Some("media.delete"), 1, 10).unwrap().len(), 3);
+
+        // Retention 0 keeps everything
+        assert_eq!(audit.prune().unwrap(), 0);
+        assert_eq!(AuditUseCase::new(repo, 30).prune().unwrap(), 0);
+    }
+}
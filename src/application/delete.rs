@@ -1,56 +1,94 @@
-use crate::domain::{MediaRepository, DomainError};
-use std::path::PathBuf;
+use crate::domain::{is_external_filename, MediaRepository, MediaStorage, DomainError};
 use std::sync::Arc;
-use tokio::fs;
 use uuid::Uuid;
 
+use super::renditions::{rendition_key, thumbnail_key, ThumbnailFormat};
+
 pub struct DeleteMediaUseCase {
     repo: Arc<dyn MediaRepository>,
-    storage_path: PathBuf,
-    thumbnail_path: PathBuf,
+    storage: Arc<dyn MediaStorage>,
+    thumbnails: Arc<dyn MediaStorage>,
 }
 
 impl DeleteMediaUseCase {
     pub fn new(
         repo: Arc<dyn MediaRepository>,
-        storage_path: PathBuf,
-        thumbnail_path: PathBuf,
+        storage: Arc<dyn MediaStorage>,
+        thumbnails: Arc<dyn MediaStorage>,
     ) -> Self {
-        Self { repo, storage_path, thumbnail_path }
+        Self { repo, storage, thumbnails }
     }
 
+    /// Move a single item to the trash. Files stay on disk until the item is purged.
     pub async fn execute(&self, id: Uuid) -> Result<(), DomainError> {
+        if self.repo.trash_media(&[id])? == 0 {
+            return Err(DomainError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Move several items to the trash. Returns how many were trashed.
+    pub async fn execute_batch(&self, ids: &[Uuid]) -> Result<usize, DomainError> {
+        self.repo.trash_media(ids)
+    }
+
+    /// Permanently delete a single item and its files.
+    pub async fn purge_one(&self, id: Uuid) -> Result<(), DomainError> {
         let media = self.repo.find_by_id(id)?
             .ok_or(DomainError::NotFound)?;
+        let companion = self.repo.find_companion(id)?;
+        let renditions = self.repo.find_renditions(id)?;
 
         self.repo.delete(id)?;
-        self.delete_files(&media.filename, id).await;
+        self.delete_files(&media.filename, id, &renditions).await;
+        if let Some(companion) = companion {
+            let _ = self.storage.delete(&companion.filename).await;
+        }
 
         Ok(())
     }
 
-    pub async fn execute_batch(&self, ids: &[Uuid]) -> Result<usize, DomainError> {
+    /// Permanently delete items and their files.
+    pub async fn purge(&self, ids: &[Uuid]) -> Result<usize, DomainError> {
         // Look up filenames before deleting from DB
         let items: Vec<_> = ids.iter()
             .filter_map(|id| self.repo.find_by_id(*id).ok().flatten())
+            .map(|item| {
+                let renditions = self.repo.find_renditions(item.id).unwrap_or_default();
+                (item, renditions)
+            })
+            .collect();
+        let companions: Vec<_> = ids.iter()
+            .filter_map(|id| self.repo.find_companion(*id).ok().flatten())
             .collect();
 
         let deleted = self.repo.delete_many(ids)?;
 
         // Clean up files for all found items
-        for item in &items {
-            self.delete_files(&item.filename, item.id).await;
+        for (item, renditions) in &items {
+            self.delete_files(&item.filename, item.id, renditions).await;
+        }
+        for companion in &companions {
+            let _ = self.storage.delete(&companion.filename).await;
         }
 
         Ok(deleted)
     }
 
-    async fn delete_files(&self, filename: &str, id: Uuid) {
-        let _ = fs::remove_file(self.storage_path.join(filename)).await;
+    async fn delete_files(&self, filename: &str, id: Uuid, renditions: &[String]) {
+        // Originals referenced in place (library mode) are never touched
+        if !is_external_filename(filename) {
+            let _ = self.storage.delete(filename).await;
+        }
 
-        let id_str = id.to_string();
-        let (p1, p2) = (&id_str[0..2], &id_str[2..4]);
-        let thumb_path = self.thumbnail_path.join(p1).join(p2).join(format!("{}.jpg", id));
-        let _ = fs::remove_file(&thumb_path).await;
+        // Thumbnails from before renditions were recorded are deleted all the same
+        let legacy = thumbnail_key(id, ThumbnailFormat::Jpeg);
+        let _ = self.thumbnails.delete(&legacy).await;
+        for name in renditions {
+            let key = rendition_key(id, name);
+            if key != legacy {
+                let _ = self.thumbnails.delete(&key).await;
+            }
+        }
     }
 }
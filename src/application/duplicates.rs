@@ -0,0 +1,324 @@
+use crate::domain::{
+    decode_phash_frames, frames_match, DomainError, DuplicateCluster, LibraryScope, MediaItem, MediaRepository,
+    DEFAULT_DUPLICATE_THRESHOLD,
+};
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::delete::DeleteMediaUseCase;
+use super::group::{GroupMediaUseCase, UnionFind, MAX_EDGES, MAX_GROUPABLE_ITEMS};
+
+/// Which signals link two items into the same cluster. Enabled criteria are combined
+/// with OR: a pair is linked if either distance is within its threshold.
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicateCriteria {
+    /// Max differing phash bits (per frame for videos).
+    pub phash_distance: Option<u32>,
+    /// Max cosine distance between embeddings (0.0 - 2.0).
+    pub embedding_distance: Option<f32>,
+}
+
+impl Default for DuplicateCriteria {
+    fn default() -> Self {
+        Self {
+            phash_distance: Some(DEFAULT_DUPLICATE_THRESHOLD),
+            embedding_distance: None,
+        }
+    }
+}
+
+/// Finds near-duplicate clusters in the existing library and resolves them by keeping
+/// one copy, merging the others' metadata into it and trashing them.
+pub struct DuplicatesUseCase {
+    repo: Arc<dyn MediaRepository>,
+    group_use_case: Arc<GroupMediaUseCase>,
+    delete_use_case: Arc<DeleteMediaUseCase>,
+}
+
+impl DuplicatesUseCase {
+    pub fn new(
+        repo: Arc<dyn MediaRepository>,
+        group_use_case: Arc<GroupMediaUseCase>,
+        delete_use_case: Arc<DeleteMediaUseCase>,
+    ) -> Self {
+        Self { repo, group_use_case, delete_use_case }
+    }
+
+    /// Clusters of items in `scope`, optionally limited to one folder.
+    pub async fn find_clusters(
+        &self,
+        scope: &LibraryScope,
+        folder_id: Option<Uuid>,
+        criteria: DuplicateCriteria,
+    ) -> Result<Vec<DuplicateCluster>, DomainError> {
+        let mut ids: Vec<Uuid> = Vec::new();
+        let mut index: HashMap<Uuid, usize> = HashMap::new();
+        let mut edges: Vec<(Uuid, Uuid)> = Vec::new();
+
+        if let Some(max_distance) = criteria.phash_distance {
+            let hashes = self.repo.get_all_phashes(scope, folder_id)?;
+            edges.extend(phash_edges(hashes, max_distance).await?);
+        }
+
+        if let Some(threshold) = criteria.embedding_distance {
+            // Embedding clusters come from the similarity grouping; link each member to the first
+            for group in self.group_use_case.execute(scope, folder_id, threshold).await? {
+                let first = group.items[0].id;
+                edges.extend(group.items[1..].iter().map(|item| (first, item.id)));
+            }
+        }
+
+        for &(a, b) in &edges {
+            for id in [a, b] {
+                index.entry(id).or_insert_with(|| {
+                    ids.push(id);
+                    ids.len() - 1
+                });
+            }
+        }
+
+        let mut uf = UnionFind::new(ids.len());
+        for (a, b) in edges {
+            uf.union(index[&a], index[&b]);
+        }
+
+        let mut components: HashMap<usize, Vec<Uuid>> = HashMap::new();
+        for (i, id) in ids.iter().enumerate() {
+            components.entry(uf.find(i)).or_default().push(*id);
+        }
+
+        let mut clusters = Vec::new();
+        for members in components.into_values() {
+            let mut items: Vec<MediaItem> = members
+                .into_iter()
+                .filter_map(|id| self.repo.find_by_id(id).ok().flatten())
+                .filter(|item| scope.can_see(item.owner_id))
+                .collect();
+            if items.len() < 2 {
+                continue;
+            }
+            rank_copies(&mut items);
+            clusters.push(DuplicateCluster { id: 0, best: items[0].id, items });
+        }
+
+        // Largest clusters first, then newest
+        clusters.sort_by(|a, b| {
+            b.items
+                .len()
+                .cmp(&a.items.len())
+                .then(b.items[0].original_date.cmp(&a.items[0].original_date))
+        });
+        for (i, cluster) in clusters.iter_mut().enumerate() {
+            cluster.id = i;
+        }
+
+        Ok(clusters)
+    }
+
+    /// Keep `keep`, copy manual tags, folder memberships and favorite status of `remove`
+    /// onto it, then move `remove` to the trash. Returns the updated kept item. All of
+    /// them must be in one of the clusters `find_clusters` reports for the same arguments.
+    pub async fn resolve(
+        &self,
+        scope: &LibraryScope,
+        folder_id: Option<Uuid>,
+        criteria: DuplicateCriteria,
+        keep: Uuid,
+        remove: &[Uuid],
+    ) -> Result<MediaItem, DomainError> {
+        if remove.contains(&keep) {
+            return Err(DomainError::Io(
+                "Duplicate resolve: the kept item cannot also be deleted".to_string(),
+            ));
+        }
+        self.repo.find_by_id(keep)?.ok_or(DomainError::NotFound)?;
+
+        let clusters = self.find_clusters(scope, folder_id, criteria).await?;
+        let in_cluster = clusters.iter().any(|cluster| {
+            let contains = |id: &Uuid| cluster.items.iter().any(|item| item.id == *id);
+            contains(&keep) && remove.iter().all(contains)
+        });
+        if !in_cluster {
+            return Err(DomainError::Io(
+                "Duplicate resolve: the items are not copies in one cluster".to_string(),
+            ));
+        }
+
+        self.repo.merge_media_metadata(keep, remove)?;
+        self.delete_use_case.execute_batch(remove).await?;
+
+        self.repo.find_by_id(keep)?.ok_or(DomainError::NotFound)
+    }
+}
+
+/// Pairs of same-type items whose phashes are within `max_distance` bits. Compares every
+/// pair, so it is capped like the similarity grouping.
+async fn phash_edges(
+    hashes: Vec<(Uuid, String, String)>,
+    max_distance: u32,
+) -> Result<Vec<(Uuid, Uuid)>, DomainError> {
+    tokio::task::spawn_blocking(move || {
+        let decoded: Vec<(Uuid, String, Vec<Vec<u8>>)> = hashes
+            .into_iter()
+            .map(|(id, media_type, phash)| (id, media_type, decode_phash_frames(&phash)))
+            .filter(|(_, _, frames)| !frames.is_empty())
+            .collect();
+        if decoded.len() > MAX_GROUPABLE_ITEMS {
+            return Err(DomainError::Io(format!(
+                "Too many items to compare: {} (max {}) — pick a folder",
+                decoded.len(),
+                MAX_GROUPABLE_ITEMS
+            )));
+        }
+
+        let edge_count = AtomicUsize::new(0);
+        let exceeded = AtomicBool::new(false);
+        let edges = (0..decoded.len())
+            .into_par_iter()
+            .flat_map_iter(|i| {
+                let (id_i, type_i, frames_i) = &decoded[i];
+                let mut local_edges = Vec::new();
+                for (id_j, type_j, frames_j) in &decoded[i + 1..] {
+                    if exceeded.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    if type_i == type_j && frames_match(frames_i, frames_j, max_distance) {
+                        local_edges.push((*id_i, *id_j));
+                        if edge_count.fetch_add(1, Ordering::Relaxed) + 1 > MAX_EDGES {
+                            exceeded.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                    }
+                }
+                local_edges
+            })
+            .collect();
+
+        if exceeded.load(Ordering::Relaxed) {
+            return Err(DomainError::Io(format!(
+                "Too many similar pairs (>{}) — try a lower distance",
+                MAX_EDGES
+            )));
+        }
+        Ok(edges)
+    })
+    .await
+    .map_err(|e| DomainError::Io(e.to_string()))?
+}
+
+/// Order copies best first: higher resolution, then larger file, then more EXIF fields,
+/// then the earliest upload.
+pub fn rank_copies(items: &mut [MediaItem]) {
+    items.sort_by_key(|item| {
+        let pixels = item.width.unwrap_or(0) as u64 * item.height.unwrap_or(0) as u64;
+        Reverse((pixels, item.size_bytes, exif_field_count(item), Reverse(item.uploaded_at)))
+    });
+}
+
+fn exif_field_count(item: &MediaItem) -> usize {
+    item.exif_json
+        .as_deref()
+        .and_then(|json| serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(json).ok())
+        .map(|map| map.values().filter(|v| !v.is_null()).count())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::{LocalStorage, SqliteRepository, TestDb};
+    use crate::domain::LibraryScope;
+    use base64::{engine::general_purpose, Engine as _};
+    use chrono::{Duration, Utc};
+
+    fn item(phash: &[u8], width: u32, size_bytes: i64, exif: Option<&str>) -> MediaItem {
+        MediaItem {
+            id: Uuid::new_v4(),
+            filename: "ab/cd/x.jpg".to_string(),
+            original_filename: "x.jpg".to_string(),
+            media_type: "image".to_string(),
+            phash: general_purpose::STANDARD.encode(phash),
+            content_hash: None,
+            owner_id: None,
+            uploaded_at: Utc::now(),
+            original_date: Utc::now(),
+            width: Some(width),
+            height: Some(width),
+            size_bytes,
+            exif_json: exif.map(str::to_string),
+            is_favorite: false,
+            tags: vec![],
+            processing: false,
+        }
+    }
+
+    #[test]
+    fn rank_prefers_resolution_then_size_then_exif() {
+        let small = item(&[0], 100, 5000, None);
+        let big = item(&[0], 200, 100, None);
+        let big_heavier = item(&[0], 200, 300, None);
+        let mut exif = item(&[0], 200, 300, Some(r#"{"Make":"X","Model":"Y"}"#));
+        exif.uploaded_at = Utc::now() + Duration::seconds(10);
+
+        let mut items = vec![small.clone(), big.clone(), big_heavier.clone(), exif.clone()];
+        rank_copies(&mut items);
+        let order: Vec<_> = items.iter().map(|i| i.id).collect();
+        assert_eq!(order, vec![exif.id, big_heavier.id, big.id, small.id]);
+    }
+
+    #[tokio::test]
+    async fn find_and_resolve_clusters() {
+        let db = TestDb::new("duplicates_use_case_test");
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let use_case = DuplicatesUseCase::new(
+            repo.clone(),
+            Arc::new(GroupMediaUseCase::new(repo.clone())),
+            Arc::new(DeleteMediaUseCase::new(
+                repo.clone(),
+                Arc::new(LocalStorage::new(dir.path().join("uploads"))),
+                Arc::new(LocalStorage::new(dir.path().join("thumbnails"))),
+            )),
+        );
+
+        let original = item(&[0b1111_0000, 0], 400, 1000, None);
+        let resized = item(&[0b1111_0001, 0], 200, 500, None);
+        let unrelated = item(&[0b0000_1111, 0xff], 400, 1000, None);
+        for m in [&original, &resized, &unrelated] {
+            repo.save_metadata_and_vector(m, None).unwrap();
+        }
+        repo.update_media_tags(resized.id, vec!["trip".to_string()]).unwrap();
+        repo.set_favorite(&LibraryScope::ALL, resized.id, true).unwrap();
+
+        let all = LibraryScope::ALL;
+        let default = DuplicateCriteria::default();
+        let clusters = use_case.find_clusters(&all, None, default).await.unwrap();
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].best, original.id);
+        assert_eq!(clusters[0].items.len(), 2);
+
+        // Exact matching finds nothing
+        let exact = DuplicateCriteria { phash_distance: Some(0), embedding_distance: None };
+        assert!(use_case.find_clusters(&all, None, exact).await.unwrap().is_empty());
+
+        assert!(use_case.resolve(&all, None, default, original.id, &[original.id]).await.is_err());
+        // Only copies in the same cluster can be resolved
+        assert!(use_case.resolve(&all, None, default, original.id, &[unrelated.id]).await.is_err());
+        assert!(use_case.resolve(&all, None, exact, original.id, &[resized.id]).await.is_err());
+        assert!(repo.find_by_id(unrelated.id).unwrap().is_some());
+
+        let kept = use_case.resolve(&all, None, default, original.id, &[resized.id]).await.unwrap();
+        assert!(kept.is_favorite);
+        assert_eq!(kept.tags.len(), 1);
+        assert_eq!(kept.tags[0].name, "trip");
+        // The removed copy goes to the trash
+        let trashed = repo.find_trashed(&LibraryScope::ALL, 10, 0).unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].media.id, resized.id);
+        assert!(use_case.find_clusters(&all, None, default).await.unwrap().is_empty());
+    }
+}
@@ -1,37 +1,37 @@
-use crate::domain::{DomainError, MediaGroup, MediaRepository, MediaSummary};
+use crate::domain::{DomainError, LibraryScope, MediaGroup, MediaRepository, MediaSummary};
 use rayon::prelude::*;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use uuid::Uuid;
 
 /// Maximum number of items that can be grouped at once.
-const MAX_GROUPABLE_ITEMS: usize = 10_000;
+pub(crate) const MAX_GROUPABLE_ITEMS: usize = 10_000;
 
 /// Maximum number of edges (similar pairs) before aborting to prevent OOM.
-const MAX_EDGES: usize = 5_000_000;
+pub(crate) const MAX_EDGES: usize = 5_000_000;
 
 /// Disjoint-set (Union-Find) with path compression and union by rank.
-struct UnionFind {
+pub(crate) struct UnionFind {
     parent: Vec<usize>,
     rank: Vec<usize>,
 }
 
 impl UnionFind {
-    fn new(n: usize) -> Self {
+    pub(crate) fn new(n: usize) -> Self {
         Self {
             parent: (0..n).collect(),
             rank: vec![0; n],
         }
     }
 
-    fn find(&mut self, x: usize) -> usize {
+    pub(crate) fn find(&mut self, x: usize) -> usize {
         if self.parent[x] != x {
             self.parent[x] = self.find(self.parent[x]);
         }
         self.parent[x]
     }
 
-    fn union(&mut self, a: usize, b: usize) {
+    pub(crate) fn union(&mut self, a: usize, b: usize) {
         let ra = self.find(a);
         let rb = self.find(b);
         if ra == rb {
@@ -68,13 +68,21 @@ impl GroupMediaUseCase {
         Self { repo }
     }
 
+    /// Groups of similar items in `scope`, optionally limited to one folder.
     pub async fn execute(
         &self,
+        scope: &LibraryScope,
         folder_id: Option<Uuid>,
         threshold: f32,
     ) -> Result<Vec<MediaGroup>, DomainError> {
-        // 1. Load all embeddings (pre-normalized by the repo).
-        let items = self.repo.get_all_embeddings(folder_id)?;
+        // 1. Load all embeddings (pre-normalized by the repo). Items outside the scope are
+        //    dropped first so they cannot link two visible items into one group.
+        let items: Vec<_> = self
+            .repo
+            .get_all_embeddings(folder_id)?
+            .into_iter()
+            .filter(|(summary, _)| scope.can_see(summary.owner_id))
+            .collect();
 
         if items.is_empty() {
             return Ok(Vec::new());
@@ -223,6 +231,58 @@ mod tests {
         assert!((dot(&a, &b) - 0.0).abs() < 1e-6);
     }
 
+    #[tokio::test]
+    async fn hidden_items_do_not_link_groups() {
+        use crate::domain::{MediaItem, Role, User};
+        use crate::infrastructure::{SqliteRepository, TestDb};
+        use chrono::Utc;
+
+        let db = TestDb::new("group_scope_test");
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let other = User { id: Uuid::new_v4(), username: "kim".to_string(), role: Role::Member, created_at: Utc::now() };
+        repo.create_user(&other, "hash").unwrap();
+
+        let embedding = |x: f32, y: f32| {
+            let mut v = vec![0.0; 1280];
+            v[0] = x;
+            v[1] = y;
+            v
+        };
+        let save = |owner_id, vector: Vec<f32>| {
+            let item = MediaItem {
+                id: Uuid::new_v4(),
+                filename: "ab/cd/x.jpg".to_string(),
+                original_filename: "x.jpg".to_string(),
+                media_type: "image".to_string(),
+                phash: "no_hash".to_string(),
+                content_hash: None,
+                owner_id,
+                uploaded_at: Utc::now(),
+                original_date: Utc::now(),
+                width: None,
+                height: None,
+                size_bytes: 1,
+                exif_json: None,
+                is_favorite: false,
+                tags: vec![],
+                processing: false,
+            };
+            repo.save_metadata_and_vector(&item, Some(&vector)).unwrap();
+        };
+        // Two unrelated shared items, both close to another member's private one
+        save(None, embedding(1.0, 0.0));
+        save(None, embedding(0.0, 1.0));
+        save(Some(other.id), embedding(1.0, 1.0));
+
+        let use_case = GroupMediaUseCase::new(repo);
+        let all = use_case.execute(&LibraryScope::ALL, None, 0.3).await.unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].items.len(), 3);
+
+        let member = LibraryScope { user_id: Some(Uuid::new_v4()), all_owners: false };
+        assert!(use_case.execute(&member, None, 0.3).await.unwrap().is_empty());
+    }
+
     #[test]
     fn constants_are_valid() {
         assert!(MAX_GROUPABLE_ITEMS > 0);
@@ -0,0 +1,577 @@
+use crate::domain::{DomainError, Folder, LibraryScope, MediaItem, MediaRepository};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::upload::{is_allowed_extension, UploadMediaUseCase};
+
+/// Emit a progress event after this many processed files.
+const PROGRESS_INTERVAL: usize = 10;
+
+/// Running totals for a server-side import.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    /// Number of candidate files found under the source directory.
+    pub total: usize,
+    pub processed: usize,
+    pub imported: usize,
+    /// Files already handled by a previous import run.
+    pub skipped: usize,
+    pub duplicates: usize,
+    pub failed: usize,
+}
+
+/// Events emitted while an import is running, so callers can relay them to clients.
+pub enum ImportEvent {
+    Started { total: usize },
+    MediaCreated(MediaItem),
+    FolderCreated(Folder),
+    MediaAddedToFolder { folder_id: Uuid, media_id: Uuid },
+    Progress(ImportReport),
+}
+
+/// Ingests an existing directory tree on the server through the regular upload pipeline.
+pub struct ImportMediaUseCase {
+    repo: Arc<dyn MediaRepository>,
+    upload_use_case: Arc<UploadMediaUseCase>,
+    import_root: Option<PathBuf>,
+    /// Library mode: reference originals where they are instead of copying them.
+    reference_in_place: bool,
+    running: AtomicBool,
+}
+
+/// Resets the `running` flag when an import finishes, even on early return.
+struct RunningGuard<'a>(&'a AtomicBool);
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+impl ImportMediaUseCase {
+    pub fn new(
+        repo: Arc<dyn MediaRepository>,
+        upload_use_case: Arc<UploadMediaUseCase>,
+        import_root: Option<PathBuf>,
+        reference_in_place: bool,
+    ) -> Self {
+        Self {
+            repo,
+            upload_use_case,
+            import_root,
+            reference_in_place,
+            running: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Resolve an optional sub-directory of the configured import root.
+    /// Absolute paths and `..` components are rejected, and symlinks must resolve inside
+    /// the root, so requests cannot escape it.
+    pub fn resolve_source(&self, sub_path: Option<&str>) -> Result<PathBuf, DomainError> {
+        let root = self.import_root.as_ref().ok_or_else(|| {
+            DomainError::Io("Import directory not configured (set IMPORT_DIR)".to_string())
+        })?;
+
+        let mut source = root.clone();
+        if let Some(sub) = sub_path.map(str::trim).filter(|s| !s.is_empty()) {
+            let sub = Path::new(sub);
+            if sub
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+            {
+                return Err(DomainError::Io(
+                    "Import path must be relative to the import directory".to_string(),
+                ));
+            }
+            source = source.join(sub);
+        }
+
+        let source = source
+            .canonicalize()
+            .map_err(|_| DomainError::Io("Import path not found".to_string()))?;
+        let root = root
+            .canonicalize()
+            .map_err(|_| DomainError::Io("Import directory not found".to_string()))?;
+        if !source.starts_with(&root) {
+            return Err(DomainError::Io(
+                "Import path must be inside the import directory".to_string(),
+            ));
+        }
+        if !source.is_dir() {
+            return Err(DomainError::Io("Import path is not a directory".to_string()));
+        }
+        Ok(source)
+    }
+
+    /// Import every allowed file below `source`. Files recorded by an earlier run are skipped,
+    /// so an interrupted import can simply be started again.
+    /// With `mirror_folders`, files in sub-directories are added to a virtual folder named
+    /// after their relative directory (e.g. `2023/Holiday`).
+    pub async fn execute<F>(
+        &self,
+        source: &Path,
+        mirror_folders: bool,
+        on_event: F,
+    ) -> Result<ImportReport, DomainError>
+    where
+        F: Fn(ImportEvent),
+    {
+        if self
+            .running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(DomainError::Io("Import already running".to_string()));
+        }
+        let _guard = RunningGuard(&self.running);
+
+        let root = source.to_path_buf();
+        let files = tokio::task::spawn_blocking(move || collect_files(&root))
+            .await
+            .map_err(|e| DomainError::Io(e.to_string()))?;
+
+        let mut report = ImportReport {
+            total: files.len(),
+            ..Default::default()
+        };
+        on_event(ImportEvent::Started { total: report.total });
+
+        // Folder name -> id, seeded with existing folders so re-runs reuse them. Imported
+        // items belong to the shared library, so only shared folders are mirrored into
+        let mut folder_ids: HashMap<String, Uuid> = HashMap::new();
+        if mirror_folders {
+            for folder in self.repo.list_folders(&LibraryScope::ALL)? {
+                if folder.owner_id.is_none() {
+                    folder_ids.entry(folder.name).or_insert(folder.id);
+                }
+            }
+        }
+
+        for path in files {
+            let key = path.to_string_lossy().to_string();
+            self.import_file(
+                &path,
+                &key,
+                source,
+                mirror_folders,
+                &mut folder_ids,
+                &mut report,
+                &on_event,
+            )
+            .await;
+
+            report.processed += 1;
+            if report.processed.is_multiple_of(PROGRESS_INTERVAL) {
+                on_event(ImportEvent::Progress(report.clone()));
+            }
+        }
+
+        on_event(ImportEvent::Progress(report.clone()));
+        Ok(report)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn import_file<F>(
+        &self,
+        path: &Path,
+        key: &str,
+        source: &Path,
+        mirror_folders: bool,
+        folder_ids: &mut HashMap<String, Uuid>,
+        report: &mut ImportReport,
+        on_event: &F,
+    ) where
+        F: Fn(ImportEvent),
+    {
+        match self.repo.is_path_imported(key) {
+            Ok(true) => {
+                report.skipped += 1;
+                return;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                warn!("Import: failed to check {}: {}", key, e);
+                report.failed += 1;
+                return;
+            }
+        }
+
+        let result = if self.reference_in_place {
+            self.upload_use_case.execute_in_place(path).await
+        } else {
+            let filename = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| key.to_string());
+            self.upload_use_case.execute(filename, path).await
+        };
+
+        match result {
+            Ok(media) => {
+                if let Err(e) = self.repo.mark_path_imported(key, Some(media.id)) {
+                    warn!("Import: failed to record {}: {}", key, e);
+                }
+                report.imported += 1;
+                let media_id = media.id;
+                on_event(ImportEvent::MediaCreated(media));
+
+                if mirror_folders {
+                    if let Some(name) = folder_name_for(source, path) {
+                        self.add_to_mirrored_folder(&name, media_id, folder_ids, on_event);
+                    }
+                }
+            }
+            Err(DomainError::DuplicateMedia(existing)) => {
+                info!("Import: {} duplicates {}", key, existing);
+                if let Err(e) = self.repo.mark_path_imported(key, None) {
+                    warn!("Import: failed to record {}: {}", key, e);
+                }
+                report.duplicates += 1;
+            }
+            Err(e) => {
+                warn!("Import: failed to process {}: {}", key, e);
+                report.failed += 1;
+            }
+        }
+    }
+
+    fn add_to_mirrored_folder<F>(
+        &self,
+        name: &str,
+        media_id: Uuid,
+        folder_ids: &mut HashMap<String, Uuid>,
+        on_event: &F,
+    ) where
+        F: Fn(ImportEvent),
+    {
+        let folder_id = match folder_ids.get(name) {
+            Some(id) => *id,
+            None => match self.repo.create_folder(Uuid::new_v4(), name, None) {
+                Ok(folder) => {
+                    let id = folder.id;
+                    folder_ids.insert(name.to_string(), id);
+                    on_event(ImportEvent::FolderCreated(folder));
+                    id
+                }
+                Err(e) => {
+                    warn!("Import: failed to create folder {}: {}", name, e);
+                    return;
+                }
+            },
+        };
+
+        match self.repo.add_media_to_folder(folder_id, &[media_id]) {
+            Ok(_) => on_event(ImportEvent::MediaAddedToFolder { folder_id, media_id }),
+            Err(e) => warn!("Import: failed to add {} to folder {}: {}", media_id, name, e),
+        }
+    }
+}
+
+/// Recursively collect importable files below `root`, sorted for a stable processing order.
+/// Hidden entries (e.g. `.stfolder`, `.DS_Store`) are skipped, symlinked directories
+/// are not followed and symlinked files only count when they resolve inside `root`.
+pub(crate) fn collect_files(root: &Path) -> Vec<PathBuf> {
+    let real_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let mut files = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(e) => e,
+            Err(e) => {
+                warn!("Import: cannot read directory {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_symlink() {
+                match path.canonicalize() {
+                    Ok(target) if target.starts_with(&real_root) && target.is_file() => {}
+                    _ => continue,
+                }
+            }
+            if file_type.is_dir() {
+                stack.push(path);
+            } else if has_allowed_extension(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+fn has_allowed_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| is_allowed_extension(&ext.to_lowercase()))
+        .unwrap_or(false)
+}
+
+/// Virtual folder name for a file: its parent directory relative to the import source,
+/// or `None` for files directly in the source directory.
+fn folder_name_for(source: &Path, path: &Path) -> Option<String> {
+    let parent = path.parent()?.strip_prefix(source).ok()?;
+    let parts: Vec<String> = parent
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("/"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{AiProcessor, HashGenerator, Role, User};
+    use crate::infrastructure::{LocalStorage, SqliteRepository, TestDb};
+    use chrono::Utc;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    struct MockAiProcessor;
+    impl AiProcessor for MockAiProcessor {
+        fn extract_features(&self, _image_data: &[u8]) -> Result<Vec<f32>, DomainError> {
+            Ok(vec![0.1; 1280])
+        }
+    }
+
+    /// Returns a distinct hash per call so every file counts as new.
+    struct CountingHashGenerator(AtomicUsize);
+    impl HashGenerator for CountingHashGenerator {
+        fn generate_phash(&self, _image_data: &[u8]) -> Result<String, DomainError> {
+            Ok(format!("hash_{}", self.0.fetch_add(1, Ordering::SeqCst)))
+        }
+    }
+
+    // 1x1 PNG
+    const VALID_PNG: [u8; 67] = [
+        137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 1, 0, 0, 0, 1,
+        8, 6, 0, 0, 0, 31, 21, 196, 137, 0, 0, 0, 10, 73, 68, 65, 84, 120, 156, 99, 0, 1, 0,
+        0, 5, 0, 1, 13, 10, 45, 180, 0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130,
+    ];
+
+    /// A PNG with different bytes from `VALID_PNG`, so it is not an exact duplicate.
+    fn other_png() -> Vec<u8> {
+        let img = image::RgbImage::from_pixel(2, 2, image::Rgb([200, 10, 10]));
+        let mut buf = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut buf, image::ImageFormat::Png).unwrap();
+        buf.into_inner()
+    }
+
+    #[test]
+    fn collect_files_filters_and_recurses() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("2023/Holiday")).unwrap();
+        std::fs::create_dir_all(root.join(".stfolder")).unwrap();
+        std::fs::write(root.join("a.JPG"), b"x").unwrap();
+        std::fs::write(root.join("notes.txt"), b"x").unwrap();
+        std::fs::write(root.join("2023/Holiday/b.mp4"), b"x").unwrap();
+        std::fs::write(root.join(".stfolder/c.jpg"), b"x").unwrap();
+
+        let files = collect_files(root);
+        assert_eq!(files, vec![root.join("2023/Holiday/b.mp4"), root.join("a.JPG")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn collect_files_stays_inside_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("import");
+        let outside = dir.path().join("outside");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(root.join("a.jpg"), b"x").unwrap();
+        std::fs::write(outside.join("secret.jpg"), b"x").unwrap();
+        std::os::unix::fs::symlink(root.join("a.jpg"), root.join("alias.jpg")).unwrap();
+        std::os::unix::fs::symlink(outside.join("secret.jpg"), root.join("secret.jpg")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("linked")).unwrap();
+
+        let files = collect_files(&root);
+        assert_eq!(files, vec![root.join("a.jpg"), root.join("alias.jpg")]);
+    }
+
+    #[test]
+    fn folder_name_uses_relative_directory() {
+        let source = Path::new("/import");
+        assert_eq!(
+            folder_name_for(source, Path::new("/import/2023/Holiday/a.jpg")),
+            Some("2023/Holiday".to_string())
+        );
+        assert_eq!(folder_name_for(source, Path::new("/import/a.jpg")), None);
+    }
+
+    #[test]
+    fn resolve_source_rejects_escapes() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("sub")).unwrap();
+        let repo = Arc::new(SqliteRepository::new_in_memory().unwrap());
+        let upload = Arc::new(UploadMediaUseCase::new(
+            repo.clone(),
+            Arc::new(MockAiProcessor),
+            Arc::new(CountingHashGenerator(AtomicUsize::new(0))),
+            Arc::new(LocalStorage::new(dir.path().join("uploads"))),
+            Arc::new(LocalStorage::new(dir.path().join("thumbnails"))),
+            0,
+        ));
+        let use_case = ImportMediaUseCase::new(repo, upload, Some(dir.path().to_path_buf()), false);
+
+        assert!(use_case.resolve_source(None).is_ok());
+        assert!(use_case.resolve_source(Some("sub")).is_ok());
+        assert!(use_case.resolve_source(Some("../")).is_err());
+        assert!(use_case.resolve_source(Some("/etc")).is_err());
+        assert!(use_case.resolve_source(Some("missing")).is_err());
+
+        let unconfigured = ImportMediaUseCase::new(
+            Arc::new(SqliteRepository::new_in_memory().unwrap()),
+            use_case.upload_use_case.clone(),
+            None,
+            false,
+        );
+        assert!(unconfigured.resolve_source(None).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_source_rejects_symlink_escapes() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("escape")).unwrap();
+        let repo = Arc::new(SqliteRepository::new_in_memory().unwrap());
+        let upload = Arc::new(UploadMediaUseCase::new(
+            repo.clone(),
+            Arc::new(MockAiProcessor),
+            Arc::new(CountingHashGenerator(AtomicUsize::new(0))),
+            Arc::new(LocalStorage::new(dir.path().join("uploads"))),
+            Arc::new(LocalStorage::new(dir.path().join("thumbnails"))),
+            0,
+        ));
+        let use_case = ImportMediaUseCase::new(repo, upload, Some(dir.path().to_path_buf()), false);
+
+        assert!(use_case.resolve_source(Some("escape")).is_err());
+    }
+
+    #[tokio::test]
+    async fn import_is_resumable_and_mirrors_folders() {
+        let db = TestDb::new("import_test");
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("source");
+        std::fs::create_dir_all(source.join("Holiday")).unwrap();
+        std::fs::write(source.join("root.png"), VALID_PNG).unwrap();
+        std::fs::write(source.join("Holiday/beach.png"), other_png()).unwrap();
+        std::fs::write(source.join("Holiday/readme.txt"), b"ignored").unwrap();
+
+        let upload = Arc::new(UploadMediaUseCase::new(
+            repo.clone(),
+            Arc::new(MockAiProcessor),
+            Arc::new(CountingHashGenerator(AtomicUsize::new(0))),
+            Arc::new(LocalStorage::new(temp_dir.path().join("uploads"))),
+            Arc::new(LocalStorage::new(temp_dir.path().join("thumbnails"))),
+            0,
+        ));
+        let use_case = ImportMediaUseCase::new(repo.clone(), upload, Some(source.clone()), false);
+        let source = use_case.resolve_source(None).unwrap();
+
+        // A member's private folder of the same name is left alone
+        let member = User { id: Uuid::new_v4(), username: "kim".to_string(), role: Role::Member, created_at: Utc::now() };
+        repo.create_user(&member, "hash").unwrap();
+        let private = repo.create_folder(Uuid::new_v4(), "Holiday", Some(member.id)).unwrap();
+
+        let created = Mutex::new(Vec::new());
+        let report = use_case
+            .execute(&source, true, |event| {
+                if let ImportEvent::MediaCreated(item) = event {
+                    created.lock().unwrap().push(item.id);
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(report.total, 2);
+        assert_eq!(report.imported, 2);
+        assert_eq!(report.failed, 0);
+        assert_eq!(created.lock().unwrap().len(), 2);
+        assert!(!use_case.is_running());
+
+        let folders: Vec<Folder> = repo
+            .list_folders(&LibraryScope::ALL)
+            .unwrap()
+            .into_iter()
+            .filter(|f| f.id != private.id)
+            .collect();
+        assert_eq!(folders.len(), 1);
+        assert_eq!(folders[0].name, "Holiday");
+        assert_eq!(folders[0].owner_id, None);
+        assert_eq!(folders[0].item_count, 1);
+        assert_eq!(repo.get_folder(private.id).unwrap().unwrap().item_count, 0);
+
+        // Second run skips everything that was already imported
+        let report = use_case.execute(&source, true, |_| {}).await.unwrap();
+        assert_eq!(report.imported, 0);
+        assert_eq!(report.skipped, 2);
+        assert_eq!(repo.list_folders(&LibraryScope::ALL).unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn library_mode_references_originals_in_place() {
+        let db = TestDb::new("import_in_place_test");
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("archive");
+        let uploads = temp_dir.path().join("uploads");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("photo.png"), VALID_PNG).unwrap();
+
+        let upload = Arc::new(UploadMediaUseCase::new(
+            repo.clone(),
+            Arc::new(MockAiProcessor),
+            Arc::new(CountingHashGenerator(AtomicUsize::new(0))),
+            Arc::new(LocalStorage::new(uploads.clone())),
+            Arc::new(LocalStorage::new(temp_dir.path().join("thumbnails"))),
+            0,
+        ));
+        let use_case = ImportMediaUseCase::new(repo.clone(), upload, Some(source.clone()), true);
+        let source = use_case.resolve_source(None).unwrap();
+
+        let created = Mutex::new(Vec::new());
+        use_case
+            .execute(&source, false, |event| {
+                if let ImportEvent::MediaCreated(item) = event {
+                    created.lock().unwrap().push(item);
+                }
+            })
+            .await
+            .unwrap();
+
+        let created = created.into_inner().unwrap();
+        assert_eq!(created.len(), 1);
+        assert_eq!(Path::new(&created[0].filename), source.join("photo.png"));
+        assert_eq!(created[0].original_filename, "photo.png");
+        assert!(!uploads.exists(), "originals must not be copied in library mode");
+        assert_eq!(repo.find_external_media().unwrap().len(), 1);
+    }
+}
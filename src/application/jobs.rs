@@ -0,0 +1,458 @@
+use crate::domain::{DomainError, Job, JobKind, JobState, LibraryScope, MediaItem, MediaRepository};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{FixThumbnailsUseCase, GroupMediaUseCase, TagLearningUseCase, UploadMediaUseCase, UploadStage};
+
+/// Runs per job before it is marked failed.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubles with every further attempt.
+const RETRY_BASE_SECS: i64 = 30;
+
+/// Longest delay between retries.
+const MAX_RETRY_DELAY_SECS: i64 = 3600;
+
+/// Finished jobs, and their results, are kept this long.
+const FINISHED_JOB_RETENTION_DAYS: i64 = 7;
+
+/// What happened to a job, for live progress in the UI.
+#[derive(Debug, Clone)]
+pub enum JobEvent {
+    /// Queued, or queued again for a retry.
+    Queued(Job),
+    Started(Job),
+    Progress { id: Uuid, done: usize, total: usize },
+    Finished(Job),
+    /// An item the job changed, e.g. one whose thumbnail was repaired.
+    MediaUpdated(MediaItem),
+    /// An item the job deleted, e.g. an upload that turned out to be a duplicate.
+    MediaRemoved(Uuid),
+    /// Progress of an upload, for the client session that sent it.
+    UploadStage { session: Uuid, media_id: Uuid, stage: UploadStage },
+}
+
+/// Receives `JobEvent`s from the workers. Shared with blocking tasks, hence `Arc`.
+pub type JobEventSink = Arc<dyn Fn(JobEvent) + Send + Sync>;
+
+/// Saves a running job's progress, reports it and tells the job whether to go on.
+#[derive(Clone)]
+struct ProgressReporter {
+    repo: Arc<dyn MediaRepository>,
+    id: Uuid,
+    cancel: Arc<AtomicBool>,
+    on_event: JobEventSink,
+}
+
+impl ProgressReporter {
+    fn report(&self, done: usize, total: usize) -> bool {
+        if let Err(e) = self.repo.update_job_progress(self.id, done, total) {
+            warn!("Failed to save progress of job {}: {}", self.id, e);
+        }
+        (self.on_event)(JobEvent::Progress { id: self.id, done, total });
+        !self.cancel.load(Ordering::Relaxed)
+    }
+}
+
+/// Errors worth another attempt. Missing data and failed training will fail again.
+fn is_retryable(error: &DomainError) -> bool {
+    matches!(error, DomainError::Database(_) | DomainError::Io(_))
+}
+
+/// Whether a failed run of `job` gets another attempt.
+fn will_retry(job: &Job, error: &DomainError) -> bool {
+    job.attempts < job.max_attempts && is_retryable(error)
+}
+
+fn retry_delay(attempts: u32) -> Duration {
+    let secs = RETRY_BASE_SECS.saturating_mul(1 << attempts.saturating_sub(1).min(16));
+    Duration::seconds(secs.min(MAX_RETRY_DELAY_SECS))
+}
+
+/// Long operations queued in the database and run by the `TaskRunner` workers, so
+/// they survive closed browsers and restarts, report progress and can be cancelled.
+pub struct JobsUseCase {
+    repo: Arc<dyn MediaRepository>,
+    upload: Arc<UploadMediaUseCase>,
+    fix_thumbnails: Arc<FixThumbnailsUseCase>,
+    tag_learning: Arc<TagLearningUseCase>,
+    group: Arc<GroupMediaUseCase>,
+    /// Cancel flags of the jobs running in this process.
+    running: Mutex<HashMap<Uuid, Arc<AtomicBool>>>,
+    /// Wakes an idle worker when a job is queued.
+    wake: Notify,
+}
+
+impl JobsUseCase {
+    pub fn new(
+        repo: Arc<dyn MediaRepository>,
+        upload: Arc<UploadMediaUseCase>,
+        fix_thumbnails: Arc<FixThumbnailsUseCase>,
+        tag_learning: Arc<TagLearningUseCase>,
+        group: Arc<GroupMediaUseCase>,
+    ) -> Self {
+        Self {
+            repo,
+            upload,
+            fix_thumbnails,
+            tag_learning,
+            group,
+            running: Mutex::new(HashMap::new()),
+            wake: Notify::new(),
+        }
+    }
+
+    pub fn enqueue(&self, kind: JobKind, created_by: Option<Uuid>) -> Result<Job, DomainError> {
+        let now = Utc::now();
+        let job = Job {
+            id: Uuid::new_v4(),
+            kind,
+            state: JobState::Queued,
+            done: 0,
+            total: 0,
+            attempts: 0,
+            max_attempts: MAX_ATTEMPTS,
+            error: None,
+            result: None,
+            created_by,
+            created_at: now,
+            updated_at: now,
+            run_after: now,
+        };
+        self.repo.create_job(&job)?;
+        self.wake.notify_one();
+        Ok(job)
+    }
+
+    pub fn get(&self, id: Uuid) -> Result<Job, DomainError> {
+        self.repo.find_job(id)?.ok_or(DomainError::NotFound)
+    }
+
+    /// Jobs queued by `created_by`, or every job for `None`. Newest first.
+    pub fn list(
+        &self,
+        created_by: Option<Uuid>,
+        page: usize,
+        limit: usize,
+    ) -> Result<Vec<Job>, DomainError> {
+        let offset = (page.max(1) - 1) * limit;
+        self.repo.list_jobs(created_by, limit, offset)
+    }
+
+    /// Cancel a job. Queued jobs never start; running ones stop at their next progress
+    /// report and keep what they finished so far.
+    pub fn cancel(&self, id: Uuid) -> Result<Job, DomainError> {
+        if !self.repo.cancel_queued_job(id)? {
+            if let Some(flag) = self.running.lock().unwrap().get(&id) {
+                flag.store(true, Ordering::Relaxed);
+            }
+        }
+        self.get(id)
+    }
+
+    /// Queue a failed or cancelled job again.
+    pub fn retry(&self, id: Uuid) -> Result<Job, DomainError> {
+        if !self.repo.retry_job(id, Utc::now())? {
+            self.get(id)?;
+            return Err(DomainError::Io("Job can only be retried after it failed or was cancelled".to_string()));
+        }
+        self.wake.notify_one();
+        self.get(id)
+    }
+
+    /// Requeue jobs that were running when the server stopped. Call before starting
+    /// workers.
+    pub fn recover(&self) -> Result<usize, DomainError> {
+        self.repo.requeue_running_jobs()
+    }
+
+    /// Delete finished jobs past the retention period.
+    pub fn prune(&self) -> Result<usize, DomainError> {
+        self.repo
+            .delete_finished_jobs_before(Utc::now() - Duration::days(FINISHED_JOB_RETENTION_DAYS))
+    }
+
+    /// Wait until a job is queued or `timeout` passes (retries become due by time).
+    pub async fn wait_for_work(&self, timeout: std::time::Duration) {
+        let _ = tokio::time::timeout(timeout, self.wake.notified()).await;
+    }
+
+    /// Claim and run the next due job. Returns false if there was none.
+    pub async fn run_next(&self, on_event: &JobEventSink) -> Result<bool, DomainError> {
+        let Some(job) = self.repo.claim_next_job(Utc::now())? else {
+            return Ok(false);
+        };
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.running.lock().unwrap().insert(job.id, cancel.clone());
+        info!("Starting job {} ({}), attempt {}", job.id, job.kind.name(), job.attempts);
+        on_event(JobEvent::Started(job.clone()));
+
+        let reporter = ProgressReporter {
+            repo: self.repo.clone(),
+            id: job.id,
+            cancel: cancel.clone(),
+            on_event: on_event.clone(),
+        };
+        let outcome = self.execute(&job, reporter).await;
+        self.running.lock().unwrap().remove(&job.id);
+
+        match outcome {
+            _ if cancel.load(Ordering::Relaxed) => {
+                info!("Job {} cancelled", job.id);
+                self.repo.finish_job(job.id, JobState::Cancelled, None, None)?;
+            }
+            Ok(result) => {
+                info!("Job {} ({}) done", job.id, job.kind.name());
+                self.repo.finish_job(job.id, JobState::Done, None, result.as_ref())?;
+            }
+            Err(e) if will_retry(&job, &e) => {
+                let delay = retry_delay(job.attempts);
+                warn!("Job {} failed, retrying in {}s: {}", job.id, delay.num_seconds(), e);
+                self.repo.reschedule_job(job.id, Utc::now() + delay, &e.to_string())?;
+            }
+            Err(e) => {
+                warn!("Job {} ({}) failed: {}", job.id, job.kind.name(), e);
+                self.repo.finish_job(job.id, JobState::Failed, Some(&e.to_string()), None)?;
+            }
+        }
+
+        if let Some(job) = self.repo.find_job(job.id)? {
+            on_event(if job.state.is_finished() { JobEvent::Finished(job) } else { JobEvent::Queued(job) });
+        }
+        Ok(true)
+    }
+
+    async fn execute(
+        &self,
+        job: &Job,
+        reporter: ProgressReporter,
+    ) -> Result<Option<serde_json::Value>, DomainError> {
+        match job.kind.clone() {
+            JobKind::ProcessUpload { media_id, upload_session } => {
+                reporter.report(0, 1);
+                let on_event = reporter.on_event.clone();
+                let on_stage = move |stage| {
+                    if let Some(session) = upload_session {
+                        on_event(JobEvent::UploadStage { session, media_id, stage });
+                    }
+                };
+                match self.upload.process(media_id, &on_stage).await {
+                    Ok(item) => {
+                        reporter.report(1, 1);
+                        if let Some(item) = item {
+                            (reporter.on_event)(JobEvent::MediaUpdated(item));
+                        }
+                        Ok(None)
+                    }
+                    Err(DomainError::DuplicateMedia(existing)) => {
+                        (reporter.on_event)(JobEvent::MediaRemoved(media_id));
+                        on_stage(UploadStage::Duplicate { existing });
+                        Err(DomainError::DuplicateMedia(existing))
+                    }
+                    Err(e) => {
+                        // A retry may still get it through
+                        if !will_retry(job, &e) {
+                            on_stage(UploadStage::Failed { error: e.to_string() });
+                            match self.upload.abandon(media_id) {
+                                Ok(Some(item)) => (reporter.on_event)(JobEvent::MediaUpdated(item)),
+                                Ok(None) => {}
+                                Err(e) => warn!("Failed to release upload {}: {}", media_id, e),
+                            }
+                        }
+                        Err(e)
+                    }
+                }
+            }
+            JobKind::FixThumbnails => {
+                let progress = reporter.clone();
+                let fixed = self
+                    .fix_thumbnails
+                    .execute_with_progress(move |done, total| progress.report(done, total))
+                    .await?;
+                reporter.report(fixed.len(), fixed.len());
+                let fixed_count = fixed.len();
+                for item in fixed {
+                    (reporter.on_event)(JobEvent::MediaUpdated(item));
+                }
+                Ok(Some(json!({ "fixed_count": fixed_count })))
+            }
+            JobKind::AutoTag { folder_id } => {
+                let tag_learning = self.tag_learning.clone();
+                let progress = reporter.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    tag_learning.run_auto_tagging(folder_id, |done, total| progress.report(done, total))
+                })
+                .await
+                .map_err(|e| DomainError::Ai(e.to_string()))??;
+                reporter.report(result.models_processed, result.models_processed);
+                Ok(Some(json!({
+                    "before": result.before,
+                    "after": result.after,
+                    "models_processed": result.models_processed
+                })))
+            }
+            JobKind::LearnTag { tag_name } => {
+                reporter.report(0, 1);
+                let tag_learning = self.tag_learning.clone();
+                let count = tokio::task::spawn_blocking(move || tag_learning.learn_tag(&tag_name))
+                    .await
+                    .map_err(|e| DomainError::Ai(e.to_string()))??;
+                reporter.report(1, 1);
+                Ok(Some(json!({ "auto_tagged_count": count })))
+            }
+            JobKind::GroupMedia { folder_id, threshold } => {
+                reporter.report(0, 1);
+                // Groups only contain what the user who asked may see
+                let scope = match job.created_by {
+                    Some(id) => LibraryScope::for_user(&self.repo.find_user_by_id(id)?.ok_or(DomainError::NotFound)?),
+                    None => LibraryScope::ALL,
+                };
+                let groups = self.group.execute(&scope, folder_id, threshold).await?;
+                reporter.report(1, 1);
+                Ok(Some(serde_json::to_value(groups).map_err(|e| DomainError::Io(e.to_string()))?))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{AiProcessor, HashGenerator, MediaStorage};
+    use crate::infrastructure::{LocalStorage, SqliteRepository, TestDb};
+
+    struct NoAi;
+
+    impl AiProcessor for NoAi {
+        fn extract_features(&self, _image_bytes: &[u8]) -> Result<Vec<f32>, DomainError> {
+            Err(DomainError::Ai("not in tests".to_string()))
+        }
+    }
+
+    struct NoHash;
+
+    impl HashGenerator for NoHash {
+        fn generate_phash(&self, _image_bytes: &[u8]) -> Result<String, DomainError> {
+            Ok("no_hash".to_string())
+        }
+    }
+
+    fn jobs(db: &TestDb, dir: &std::path::Path) -> JobsUseCase {
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let storage: Arc<dyn MediaStorage> = Arc::new(LocalStorage::new(dir.to_path_buf()));
+        let upload = Arc::new(UploadMediaUseCase::new(
+            repo.clone(),
+            Arc::new(NoAi),
+            Arc::new(NoHash),
+            storage.clone(),
+            storage.clone(),
+            0,
+        ));
+        let fix = Arc::new(FixThumbnailsUseCase::new(
+            repo.clone(),
+            Arc::new(NoAi),
+            Arc::new(NoHash),
+            storage.clone(),
+            storage,
+        ));
+        JobsUseCase::new(
+            repo.clone(),
+            upload,
+            fix,
+            Arc::new(TagLearningUseCase::new(repo.clone())),
+            Arc::new(GroupMediaUseCase::new(repo)),
+        )
+    }
+
+    #[tokio::test]
+    async fn runs_fails_and_cancels_jobs() {
+        let db = TestDb::new("jobs_use_case_test");
+        let dir = tempfile::tempdir().unwrap();
+        let jobs = jobs(&db, dir.path());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink: JobEventSink = {
+            let events = events.clone();
+            Arc::new(move |event| events.lock().unwrap().push(event))
+        };
+
+        let group = jobs.enqueue(JobKind::GroupMedia { folder_id: None, threshold: 0.2 }, None).unwrap();
+        assert!(jobs.run_next(&sink).await.unwrap());
+        let group = jobs.get(group.id).unwrap();
+        assert_eq!(group.state, JobState::Done);
+        assert_eq!(group.result, Some(json!([])));
+        assert!(matches!(events.lock().unwrap().last(), Some(JobEvent::Finished(job)) if job.id == group.id));
+        assert!(!jobs.run_next(&sink).await.unwrap());
+
+        // An unknown tag will never train: no retries
+        let learn = jobs.enqueue(JobKind::LearnTag { tag_name: "nope".to_string() }, None).unwrap();
+        assert!(jobs.run_next(&sink).await.unwrap());
+        let learn = jobs.get(learn.id).unwrap();
+        assert_eq!((learn.state, learn.attempts), (JobState::Failed, 1));
+        assert!(learn.error.is_some());
+
+        // Retried by hand it runs again; a queued job can be cancelled before it starts
+        assert_eq!(jobs.retry(learn.id).unwrap().state, JobState::Queued);
+        assert!(jobs.retry(group.id).is_err());
+        assert_eq!(jobs.cancel(learn.id).unwrap().state, JobState::Cancelled);
+        assert!(!jobs.run_next(&sink).await.unwrap());
+
+        // An upload deleted before it was processed leaves nothing to do
+        let upload = jobs.enqueue(JobKind::ProcessUpload { media_id: Uuid::new_v4(), upload_session: None }, None).unwrap();
+        assert!(jobs.run_next(&sink).await.unwrap());
+        assert_eq!(jobs.get(upload.id).unwrap().state, JobState::Done);
+
+        assert_eq!(jobs.list(None, 1, 10).unwrap().len(), 3);
+        assert!(jobs.list(Some(Uuid::new_v4()), 1, 10).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_upload_is_released_for_repair() {
+        let db = TestDb::new("jobs_failed_upload_test");
+        let dir = tempfile::tempdir().unwrap();
+        let jobs = jobs(&db, dir.path());
+        let sink: JobEventSink = Arc::new(|_| {});
+
+        // Its original is gone, which no retry will fix
+        let item = MediaItem {
+            id: Uuid::new_v4(),
+            filename: "ab/cd/missing.jpg".to_string(),
+            original_filename: "missing.jpg".to_string(),
+            media_type: "image".to_string(),
+            phash: "no_hash".to_string(),
+            content_hash: None,
+            owner_id: None,
+            uploaded_at: Utc::now(),
+            original_date: Utc::now(),
+            width: None,
+            height: None,
+            size_bytes: 1,
+            exif_json: None,
+            is_favorite: false,
+            tags: vec![],
+            processing: true,
+        };
+        jobs.repo.save_metadata_and_vector(&item, None).unwrap();
+
+        let job = jobs.enqueue(JobKind::ProcessUpload { media_id: item.id, upload_session: None }, None).unwrap();
+        assert!(jobs.run_next(&sink).await.unwrap());
+        assert_eq!(jobs.get(job.id).unwrap().state, JobState::Failed);
+
+        assert!(!jobs.repo.find_by_id(item.id).unwrap().unwrap().processing);
+        let repairable = jobs.repo.find_media_without_phash().unwrap();
+        assert_eq!(repairable.iter().map(|m| m.id).collect::<Vec<_>>(), vec![item.id]);
+    }
+
+    #[test]
+    fn retry_delay_backs_off() {
+        assert_eq!(retry_delay(1).num_seconds(), 30);
+        assert_eq!(retry_delay(2).num_seconds(), 60);
+        assert_eq!(retry_delay(3).num_seconds(), 120);
+        assert_eq!(retry_delay(40).num_seconds(), MAX_RETRY_DELAY_SECS);
+    }
+}
@@ -1,16 +1,20 @@
-use crate::domain::{AiProcessor, DomainError, HashGenerator, MediaRepository, MediaItem};
-use std::path::PathBuf;
+use crate::domain::{AiProcessor, DomainError, HashGenerator, MediaRepository, MediaItem, MediaStorage};
+use serde::Serialize;
 use std::sync::Arc;
 use tokio::fs;
+use uuid::Uuid;
 
+use super::originals::{hash_original, local_original};
 use super::processor;
+use super::renditions::{store_renditions, RenditionSizes};
 
 pub struct FixThumbnailsUseCase {
     repo: Arc<dyn MediaRepository>,
     ai: Arc<dyn AiProcessor>,
     hasher: Arc<dyn HashGenerator>,
-    storage_path: PathBuf,
-    thumbnail_path: PathBuf,
+    storage: Arc<dyn MediaStorage>,
+    thumbnails: Arc<dyn MediaStorage>,
+    renditions: RenditionSizes,
 }
 
 impl FixThumbnailsUseCase {
@@ -18,40 +22,52 @@ impl FixThumbnailsUseCase {
         repo: Arc<dyn MediaRepository>,
         ai: Arc<dyn AiProcessor>,
         hasher: Arc<dyn HashGenerator>,
-        storage_path: PathBuf,
-        thumbnail_path: PathBuf,
+        storage: Arc<dyn MediaStorage>,
+        thumbnails: Arc<dyn MediaStorage>,
     ) -> Self {
         Self {
             repo,
             ai,
             hasher,
-            storage_path,
-            thumbnail_path,
+            storage,
+            thumbnails,
+            renditions: RenditionSizes::default(),
         }
     }
 
+    /// Generate these thumbnail and preview sizes instead of the default ones.
+    pub fn with_renditions(mut self, renditions: RenditionSizes) -> Self {
+        self.renditions = renditions;
+        self
+    }
+
     pub async fn execute(&self) -> Result<Vec<MediaItem>, DomainError> {
+        self.execute_with_progress(|_, _| true).await
+    }
+
+    /// Like `execute`, reporting `(processed, total)` before each item. Stops early,
+    /// keeping what was fixed so far, when `on_progress` returns false.
+    pub async fn execute_with_progress<F>(&self, mut on_progress: F) -> Result<Vec<MediaItem>, DomainError>
+    where
+        F: FnMut(usize, usize) -> bool + Send,
+    {
         // Find media with 'no_hash' phash
         let candidates = self.repo.find_media_without_phash()?;
+        let total = candidates.len();
         let mut fixed_items = Vec::new();
 
-        for mut media in candidates {
-            // Load original file
-            let id_str = media.id.to_string();
-            let (p1, p2) = (&id_str[0..2], &id_str[2..4]);
-            
-            // media.filename is like "ab/cd/uuid.mp4"
-            // We need to resolve it against storage_path
-            // Note: media.filename already contains the sharding structure relative to upload dir
-            let file_path = self.storage_path.join(&media.filename);
-
-            if !file_path.exists() {
-                println!("Original file missing for {}: {:?}", media.id, file_path);
-                continue;
+        for (processed, mut media) in candidates.into_iter().enumerate() {
+            if !on_progress(processed, total) {
+                break;
             }
-
-            let data = match fs::read(&file_path).await {
+            // media.filename is like "ab/cd/uuid.mp4" (a storage key),
+            // or an absolute path for originals referenced in place
+            let original = match local_original(self.storage.as_ref(), &media.filename).await {
                 Ok(d) => d,
+                Err(DomainError::NotFound) => {
+                    println!("Original file missing for {}: {}", media.id, media.filename);
+                    continue;
+                }
                 Err(e) => {
                     println!("Failed to read file {}: {}", media.id, e);
                     continue;
@@ -59,7 +75,7 @@ impl FixThumbnailsUseCase {
             };
 
             // Process media
-            let processed = match processor::process_media(&media.original_filename, &data, self.hasher.as_ref()).await {
+            let processed = match processor::process_media(&media.original_filename, original.path(), self.hasher.as_ref(), &self.renditions).await {
                 Ok(p) => p,
                 Err(e) => {
                     println!("Failed to process media {}: {}", media.id, e);
@@ -67,16 +83,16 @@ impl FixThumbnailsUseCase {
                 }
             };
 
-            // Save thumbnail
-            if !processed.thumbnail_bytes.is_empty() {
-                let thumb_sub_path = self.thumbnail_path.join(p1).join(p2);
-                if let Err(e) = fs::create_dir_all(&thumb_sub_path).await {
-                    println!("Failed to create thumbnail dir {}: {}", media.id, e);
-                    continue;
-                }
-                
-                let thumb_name = format!("{}.jpg", media.id);
-                if let Err(e) = fs::write(thumb_sub_path.join(thumb_name), &processed.thumbnail_bytes).await {
+            // Save thumbnail and previews
+            if let Some(renditions) = &processed.renditions {
+                let stored = store_renditions(
+                    self.repo.as_ref(),
+                    self.thumbnails.as_ref(),
+                    media.id,
+                    renditions,
+                )
+                .await;
+                if let Err(e) = stored {
                     println!("Failed to write thumbnail {}: {}", media.id, e);
                     continue;
                 }
@@ -106,3 +122,86 @@ impl FixThumbnailsUseCase {
         Ok(fixed_items)
     }
 }
+
+/// Detects originals referenced in place (library mode) that were moved or deleted
+/// outside of the gallery.
+pub struct CheckExternalMediaUseCase {
+    repo: Arc<dyn MediaRepository>,
+}
+
+impl CheckExternalMediaUseCase {
+    pub fn new(repo: Arc<dyn MediaRepository>) -> Self {
+        Self { repo }
+    }
+
+    /// Returns the ids of external media whose original can no longer be found.
+    pub async fn execute(&self) -> Result<Vec<Uuid>, DomainError> {
+        let mut missing = Vec::new();
+        for (id, path) in self.repo.find_external_media()? {
+            if !fs::try_exists(&path).await.unwrap_or(false) {
+                println!("External original missing for {}: {}", id, path);
+                missing.push(id);
+            }
+        }
+        Ok(missing)
+    }
+}
+
+/// Outcome of an integrity scan over stored originals.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IntegrityReport {
+    pub checked: usize,
+    /// Items stored before content hashes were tracked, hashed for the first time.
+    pub backfilled: usize,
+    /// Originals whose bytes no longer match their recorded SHA-256.
+    pub corrupted: Vec<Uuid>,
+    pub missing: Vec<Uuid>,
+}
+
+/// Re-hashes every original in media storage and compares it with the SHA-256 recorded at
+/// upload, flagging bit rot or tampering. Originals referenced in place are skipped: they
+/// belong to the user and may legitimately change.
+pub struct VerifyIntegrityUseCase {
+    repo: Arc<dyn MediaRepository>,
+    storage: Arc<dyn MediaStorage>,
+}
+
+impl VerifyIntegrityUseCase {
+    pub fn new(repo: Arc<dyn MediaRepository>, storage: Arc<dyn MediaStorage>) -> Self {
+        Self { repo, storage }
+    }
+
+    pub async fn execute(&self) -> Result<IntegrityReport, DomainError> {
+        let mut report = IntegrityReport::default();
+
+        for (id, filename, expected) in self.repo.find_stored_originals()? {
+            let actual = match hash_original(self.storage.as_ref(), &filename).await {
+                Ok(hash) => hash,
+                Err(DomainError::NotFound) => {
+                    println!("Original missing for {}: {}", id, filename);
+                    report.missing.push(id);
+                    continue;
+                }
+                Err(e) => {
+                    println!("Failed to hash original of {}: {}", id, e);
+                    continue;
+                }
+            };
+            report.checked += 1;
+
+            match expected {
+                Some(expected) if expected != actual => {
+                    println!("Integrity check failed for {}: {}", id, filename);
+                    report.corrupted.push(id);
+                }
+                Some(_) => {}
+                None => {
+                    self.repo.set_content_hash(id, &actual)?;
+                    report.backfilled += 1;
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
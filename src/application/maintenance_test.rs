@@ -1,8 +1,8 @@
 #[cfg(test)]
 mod tests {
-    use crate::application::FixThumbnailsUseCase;
+    use crate::application::{content_hash, CheckExternalMediaUseCase, FixThumbnailsUseCase, VerifyIntegrityUseCase};
     use crate::domain::{AiProcessor, DomainError, HashGenerator, MediaItem, MediaRepository};
-    use crate::infrastructure::{SqliteRepository, TestDb};
+    use crate::infrastructure::{LocalStorage, SqliteRepository, TestDb};
     use std::sync::Arc;
     use uuid::Uuid;
     use tokio::fs;
@@ -53,8 +53,8 @@ mod tests {
             repo.clone(),
             ai,
             hasher,
-            upload_dir.clone(),
-            thumbnail_dir.clone(),
+            Arc::new(LocalStorage::new(upload_dir.clone())),
+            Arc::new(LocalStorage::new(thumbnail_dir.clone())),
         );
 
         // 1. Insert media with 'no_hash'
@@ -81,6 +81,8 @@ mod tests {
             original_filename: "test.png".to_string(), // .png so processor knows
             media_type: "image".to_string(),
             phash: "no_hash".to_string(),
+            content_hash: None,
+            owner_id: None,
             uploaded_at: chrono::Utc::now(),
             original_date: chrono::Utc::now(),
             width: None,
@@ -89,6 +91,7 @@ mod tests {
             exif_json: None,
             is_favorite: false,
             tags: vec![],
+            processing: false,
         };
         repo.save_metadata_and_vector(&media, None).unwrap();
 
@@ -113,4 +116,94 @@ mod tests {
         let fixed_again = use_case.execute().await.unwrap();
         assert_eq!(fixed_again.len(), 0);
     }
+
+    #[tokio::test]
+    async fn test_check_external_media_reports_missing() {
+        let db = TestDb::new("check_external_test");
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let present = temp_dir.path().join("present.jpg");
+        fs::write(&present, b"x").await.unwrap();
+        let gone = temp_dir.path().join("gone.jpg");
+
+        let mut ids = Vec::new();
+        for path in [&present, &gone] {
+            let media = MediaItem {
+                id: Uuid::new_v4(),
+                filename: path.to_string_lossy().to_string(),
+                original_filename: "photo.jpg".to_string(),
+                media_type: "image".to_string(),
+                phash: Uuid::new_v4().to_string(),
+                content_hash: None,
+                owner_id: None,
+                uploaded_at: chrono::Utc::now(),
+                original_date: chrono::Utc::now(),
+                width: None,
+                height: None,
+                size_bytes: 1,
+                exif_json: None,
+                is_favorite: false,
+                tags: vec![],
+                processing: false,
+            };
+            repo.save_metadata_and_vector(&media, None).unwrap();
+            ids.push(media.id);
+        }
+
+        let use_case = CheckExternalMediaUseCase::new(repo);
+        let missing = use_case.execute().await.unwrap();
+        assert_eq!(missing, vec![ids[1]]);
+    }
+
+    #[tokio::test]
+    async fn test_verify_integrity_flags_changed_originals() {
+        let db = TestDb::new("verify_integrity_test");
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let temp_dir = tempfile::tempdir().unwrap();
+        let upload_dir = temp_dir.path().join("uploads");
+        fs::create_dir_all(upload_dir.join("ab/cd")).await.unwrap();
+
+        // intact, bit-rotted, missing, and legacy (no recorded hash)
+        let files = [("intact", Some(b"good".as_slice())), ("rotted", Some(b"good")), ("missing", None), ("legacy", Some(b"old"))];
+        let mut ids = Vec::new();
+        for (name, data) in files {
+            let filename = format!("ab/cd/{}.jpg", name);
+            if let Some(data) = data {
+                fs::write(upload_dir.join(&filename), data).await.unwrap();
+            }
+            let media = MediaItem {
+                id: Uuid::new_v4(),
+                filename,
+                original_filename: "photo.jpg".to_string(),
+                media_type: "image".to_string(),
+                phash: Uuid::new_v4().to_string(),
+                content_hash: (name != "legacy").then(|| content_hash(b"good")),
+                owner_id: None,
+                uploaded_at: chrono::Utc::now(),
+                original_date: chrono::Utc::now(),
+                width: None,
+                height: None,
+                size_bytes: 4,
+                exif_json: None,
+                is_favorite: false,
+                tags: vec![],
+                processing: false,
+            };
+            repo.save_metadata_and_vector(&media, None).unwrap();
+            ids.push(media.id);
+        }
+        fs::write(upload_dir.join("ab/cd/rotted.jpg"), b"gooe").await.unwrap();
+
+        let use_case = VerifyIntegrityUseCase::new(repo.clone(), Arc::new(LocalStorage::new(upload_dir)));
+        let report = use_case.execute().await.unwrap();
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.corrupted, vec![ids[1]]);
+        assert_eq!(report.missing, vec![ids[2]]);
+        assert_eq!(report.backfilled, 1);
+        assert_eq!(repo.find_by_id(ids[3]).unwrap().unwrap().content_hash, Some(content_hash(b"old")));
+
+        // Backfilled hashes are verified on the next run
+        assert_eq!(use_case.execute().await.unwrap().backfilled, 0);
+    }
 }
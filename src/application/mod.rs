@@ -1,21 +1,50 @@
+pub mod api_tokens;
+pub mod audit;
 pub mod upload;
 pub mod search;
 pub mod list;
 pub mod delete;
+pub mod duplicates;
 pub mod group;
+pub mod import;
+pub mod jobs;
 pub mod tag_learning;
 pub mod processor;
+pub mod raw;
+pub mod renditions;
 pub mod maintenance;
+pub mod oidc;
+pub mod originals;
+pub mod resumable;
+pub mod watch;
+pub mod sessions;
+pub mod shares;
+pub mod trash;
+pub mod users;
 #[cfg(test)]
 mod maintenance_test;
 
+pub use api_tokens::*;
+pub use audit::*;
 pub use delete::*;
+pub use duplicates::*;
 pub use group::*;
+pub use import::*;
+pub use jobs::*;
 pub use list::*;
 pub use maintenance::*;
+pub use oidc::*;
+pub use originals::*;
+pub use renditions::*;
+pub use resumable::*;
 pub use search::*;
+pub use sessions::*;
+pub use shares::*;
 pub use tag_learning::*;
+pub use trash::*;
 pub use upload::*;
+pub use users::*;
+pub use watch::*;
 pub mod tasks;
 pub use tasks::TaskRunner;
 
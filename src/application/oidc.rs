@@ -0,0 +1,245 @@
+use crate::domain::{DomainError, IdentityClaims, IdentityProvider, MediaRepository, Role, User};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::sessions::new_token;
+
+/// How long a user may take at the provider's login page.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Cap on logins in flight, so anonymous `/oidc/login` requests cannot grow memory. The
+/// route also counts against the per-IP login rate limit.
+const MAX_PENDING_LOGINS: usize = 1024;
+
+/// PKCE verifier and nonce of a login that went to the provider, keyed by `state`.
+struct PendingLogin {
+    code_verifier: String,
+    nonce: String,
+    started: Instant,
+}
+
+/// Single sign-on through an OpenID Connect provider. Provider accounts are mapped to
+/// local users by a stored link; on first sign-in a new account is created.
+pub struct OidcUseCase {
+    provider: Arc<dyn IdentityProvider>,
+    repo: Arc<dyn MediaRepository>,
+    /// Role of accounts created on first sign-in.
+    default_role: Role,
+    /// Members of this provider group are admins; everyone else gets `default_role`.
+    admin_group: Option<String>,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+impl OidcUseCase {
+    pub fn new(
+        provider: Arc<dyn IdentityProvider>,
+        repo: Arc<dyn MediaRepository>,
+        default_role: Role,
+        admin_group: Option<String>,
+    ) -> Self {
+        Self {
+            provider,
+            repo,
+            default_role,
+            admin_group,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Start a login. Returns the `state` to bind to the browser and the provider URL
+    /// to redirect it to.
+    pub async fn begin(&self) -> Result<(String, String), DomainError> {
+        let state = new_token();
+        let login = PendingLogin {
+            code_verifier: new_token(),
+            nonce: new_token(),
+            started: Instant::now(),
+        };
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(login.code_verifier.as_bytes()));
+        let url = self
+            .provider
+            .authorization_url(&state, &login.nonce, &challenge)
+            .await?;
+
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, p| p.started.elapsed() < LOGIN_TIMEOUT);
+        if pending.len() >= MAX_PENDING_LOGINS {
+            return Err(DomainError::Io("Too many logins in progress. Try again later.".to_string()));
+        }
+        pending.insert(state.clone(), login);
+        Ok((state, url))
+    }
+
+    /// Finish a login started by `begin`: redeem the code and return the local user.
+    pub async fn complete(&self, state: &str, code: &str) -> Result<User, DomainError> {
+        let login = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(state)
+            .filter(|p| p.started.elapsed() < LOGIN_TIMEOUT)
+            .ok_or_else(|| DomainError::Io("Login expired. Please try again.".to_string()))?;
+        let claims = self
+            .provider
+            .exchange_code(code, &login.code_verifier, &login.nonce)
+            .await?;
+        self.local_user(&claims)
+    }
+
+    /// Find or create the local user for a provider identity and sync its role.
+    fn local_user(&self, claims: &IdentityClaims) -> Result<User, DomainError> {
+        let user = match self.repo.find_user_by_identity(&claims.issuer, &claims.subject)? {
+            Some(user) => user,
+            None => {
+                let username = claims
+                    .username
+                    .as_deref()
+                    .or(claims.email.as_deref())
+                    .unwrap_or(&claims.subject)
+                    .trim()
+                    .to_string();
+                // Never take over an existing local account on the provider's say-so:
+                // a provider identity gets an account of its own, renamed when taken.
+                let user = User {
+                    id: Uuid::new_v4(),
+                    username: self.free_username(&username)?,
+                    role: self.role_for(claims).unwrap_or(self.default_role),
+                    created_at: Utc::now(),
+                };
+                // An empty hash never verifies, so the account has no password
+                // until an admin sets one
+                self.repo.create_user(&user, "")?;
+                // Only fails when a concurrent first sign-in of the same identity won
+                if !self.repo.link_identity(user.id, &claims.issuer, &claims.subject)? {
+                    self.repo.delete_user(user.id)?;
+                    return Err(DomainError::Io("Sign-in already in progress. Please try again.".to_string()));
+                }
+                user
+            }
+        };
+        self.sync_role(user, claims)
+    }
+
+    /// `username`, or the first of `username-2`, `username-3`, ... no local account uses.
+    fn free_username(&self, username: &str) -> Result<String, DomainError> {
+        let mut candidate = username.to_string();
+        let mut n = 1;
+        while self.repo.find_user_by_username(&candidate)?.is_some() {
+            n += 1;
+            candidate = format!("{}-{}", username, n);
+        }
+        Ok(candidate)
+    }
+
+    /// Role implied by the admin group, if one is configured.
+    fn role_for(&self, claims: &IdentityClaims) -> Option<Role> {
+        let group = self.admin_group.as_ref()?;
+        Some(if claims.groups.contains(group) { Role::Admin } else { self.default_role })
+    }
+
+    fn sync_role(&self, mut user: User, claims: &IdentityClaims) -> Result<User, DomainError> {
+        let Some(role) = self.role_for(claims) else {
+            return Ok(user);
+        };
+        // Leaving the admin group demotes to the default role, but never the last admin
+        let demotes_last_admin = user.role == Role::Admin
+            && role != Role::Admin
+            && !self
+                .repo
+                .list_users()?
+                .iter()
+                .any(|u| u.role == Role::Admin && u.id != user.id);
+        if role != user.role && !demotes_last_admin {
+            self.repo.update_user_role(user.id, role)?;
+            user.role = role;
+        }
+        Ok(user)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::{SqliteRepository, TestDb};
+
+    /// Provider that hands out fixed claims for any code.
+    struct FakeProvider {
+        claims: Mutex<IdentityClaims>,
+    }
+
+    #[async_trait::async_trait]
+    impl IdentityProvider for FakeProvider {
+        async fn authorization_url(
+            &self,
+            state: &str,
+            _nonce: &str,
+            code_challenge: &str,
+        ) -> Result<String, DomainError> {
+            Ok(format!("https://id.example.com/auth?state={}&code_challenge={}", state, code_challenge))
+        }
+
+        async fn exchange_code(
+            &self,
+            _code: &str,
+            _code_verifier: &str,
+            _nonce: &str,
+        ) -> Result<IdentityClaims, DomainError> {
+            Ok(self.claims.lock().unwrap().clone())
+        }
+    }
+
+    fn claims(subject: &str, username: &str, groups: &[&str]) -> IdentityClaims {
+        IdentityClaims {
+            issuer: "https://id.example.com".to_string(),
+            subject: subject.to_string(),
+            username: Some(username.to_string()),
+            email: None,
+            groups: groups.iter().map(|g| g.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn maps_provider_accounts_to_local_users() {
+        let db = TestDb::new("oidc_use_case_test");
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let admin = User { id: Uuid::new_v4(), username: "admin".to_string(), role: Role::Admin, created_at: Utc::now() };
+        repo.create_user(&admin, "hash").unwrap();
+        let provider = Arc::new(FakeProvider { claims: Mutex::new(claims("sub-kim", "kim", &[])) });
+        let oidc = OidcUseCase::new(provider.clone(), repo.clone(), Role::Viewer, Some("admins".to_string()));
+
+        let (state, url) = oidc.begin().await.unwrap();
+        assert!(url.contains(&state));
+        let kim = oidc.complete(&state, "code").await.unwrap();
+        assert_eq!((kim.username.as_str(), kim.role), ("kim", Role::Viewer));
+        // A state is good for one login only
+        assert!(oidc.complete(&state, "code").await.is_err());
+
+        // Joining the admin group at the provider promotes the same local account
+        *provider.claims.lock().unwrap() = claims("sub-kim", "kim-renamed", &["admins"]);
+        let (state, _) = oidc.begin().await.unwrap();
+        let again = oidc.complete(&state, "code").await.unwrap();
+        assert_eq!((again.id, again.role), (kim.id, Role::Admin));
+
+        // A provider identity never takes over a local account with the same username
+        *provider.claims.lock().unwrap() = claims("sub-mallory", "admin", &[]);
+        let (state, _) = oidc.begin().await.unwrap();
+        let mallory = oidc.complete(&state, "code").await.unwrap();
+        assert_ne!(mallory.id, admin.id);
+        assert_eq!((mallory.username.as_str(), mallory.role), ("admin-2", Role::Viewer));
+        let (state, _) = oidc.begin().await.unwrap();
+        assert_eq!(oidc.complete(&state, "code").await.unwrap().id, mallory.id);
+
+        // The last admin is never demoted by leaving the admin group
+        repo.update_user_role(admin.id, Role::Viewer).unwrap();
+        repo.update_user_role(kim.id, Role::Admin).unwrap();
+        *provider.claims.lock().unwrap() = claims("sub-kim", "kim", &[]);
+        let (state, _) = oidc.begin().await.unwrap();
+        assert_eq!(oidc.complete(&state, "code").await.unwrap().role, Role::Admin);
+    }
+}
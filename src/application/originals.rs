@@ -0,0 +1,122 @@
+use crate::domain::{is_external_filename, ByteStream, DomainError, MediaStorage};
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+// Originals live in the configured `MediaStorage`, except for those referenced in place
+// (library mode), which are read straight from their absolute path.
+
+fn map_io_error(e: std::io::Error) -> DomainError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        DomainError::NotFound
+    } else {
+        DomainError::Io(e.to_string())
+    }
+}
+
+/// The original of a media item as a local file. Originals in remote storage are
+/// downloaded to a temporary file, which is removed again on drop.
+pub struct LocalOriginal {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl LocalOriginal {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for LocalOriginal {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Make the original of a media item available as a local file, for tools like ffmpeg
+/// that read from a path.
+pub async fn local_original(storage: &dyn MediaStorage, filename: &str) -> Result<LocalOriginal, DomainError> {
+    let path = if is_external_filename(filename) {
+        Some(PathBuf::from(filename))
+    } else {
+        storage.local_path(filename)
+    };
+    if let Some(path) = path {
+        if !fs::try_exists(&path).await.unwrap_or(false) {
+            return Err(DomainError::NotFound);
+        }
+        return Ok(LocalOriginal { path, temporary: false });
+    }
+
+    let (_, mut stream) = storage.stream(filename).await?;
+    let local = LocalOriginal {
+        path: std::env::temp_dir().join(format!("gallerynet_original_{}.tmp", Uuid::new_v4())),
+        temporary: true,
+    };
+    let mut file = fs::File::create(local.path())
+        .await
+        .map_err(|e| DomainError::Io(format!("Failed to create temp file: {}", e)))?;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| DomainError::Io(e.to_string()))?;
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| DomainError::Io(format!("Failed to write temp file: {}", e)))?;
+    }
+    file.flush().await.map_err(|e| DomainError::Io(e.to_string()))?;
+    Ok(local)
+}
+
+/// Open the original file of a media item for streaming.
+pub async fn stream_original(
+    storage: &dyn MediaStorage,
+    filename: &str,
+) -> Result<(u64, ByteStream), DomainError> {
+    if is_external_filename(filename) {
+        let file = fs::File::open(filename).await.map_err(map_io_error)?;
+        let size = file
+            .metadata()
+            .await
+            .map_err(|e| DomainError::Io(e.to_string()))?
+            .len();
+        return Ok((size, Box::pin(ReaderStream::with_capacity(file, 128 * 1024))));
+    }
+    storage.stream(filename).await
+}
+
+/// Hex SHA-256 of a byte buffer, as stored in `MediaItem::content_hash`.
+#[cfg(test)]
+pub fn content_hash(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Hex SHA-256 of a stored original, hashed incrementally so large videos are never
+/// held in memory.
+pub async fn hash_original(storage: &dyn MediaStorage, filename: &str) -> Result<String, DomainError> {
+    let (_, mut stream) = stream_original(storage, filename).await?;
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = stream.next().await {
+        hasher.update(chunk.map_err(|e| DomainError::Io(e.to_string()))?);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Hex SHA-256 of a local file, read in chunks.
+pub async fn hash_file(path: &Path) -> Result<String, DomainError> {
+    let mut file = fs::File::open(path).await.map_err(map_io_error)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 128 * 1024];
+    loop {
+        let read = file.read(&mut buf).await.map_err(|e| DomainError::Io(e.to_string()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
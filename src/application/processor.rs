@@ -1,23 +1,43 @@
 use crate::domain::{DomainError, HashGenerator};
 use chrono::{DateTime, NaiveDateTime, Utc};
 use exif::Tag;
+use image::codecs::avif::AvifEncoder;
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegEncoder;
 use image::imageops::FilterType;
+use image::AnimationDecoder;
 use std::io::Cursor;
 use std::path::Path;
 use tokio::fs;
 use uuid::Uuid;
 
+use super::raw;
+use super::renditions::{self, RenditionSizes, Renditions, ThumbnailFormat};
+
 /// Maximum image dimension (width or height) in pixels.
 pub const MAX_IMAGE_DIMENSION: u32 = 65_000;
 
 /// Maximum memory allocation for image decoding (~500 MB).
 pub const MAX_IMAGE_ALLOC: u64 = 500 * 1024 * 1024;
 
+const THUMBNAIL_QUALITY: u8 = 75;
+const PREVIEW_QUALITY: u8 = 85;
+/// rav1e speed (1-10) for AVIF renditions; slower settings gain little at these sizes.
+const AVIF_SPEED: u8 = 8;
+
+/// Longest edge of animated previews, in pixels.
+const ANIMATION_SIZE: u32 = 480;
+/// Frames per second of the loop stitched from a video's representative frames.
+const ANIMATION_FRAMERATE: f32 = 1.5;
+/// Longest part of an animated GIF that is kept in its preview.
+const ANIMATION_MAX_SECS: u32 = 6;
+
 pub struct ProcessedMedia {
     pub width: Option<u32>,
     pub height: Option<u32>,
     pub exif_json: Option<String>,
-    pub thumbnail_bytes: Vec<u8>,
+    /// `None` if no image could be decoded.
+    pub renditions: Option<Renditions>,
     pub phash: String,
     pub feature_input: Option<Vec<u8>>,
     pub original_date: Option<DateTime<Utc>>,
@@ -83,15 +103,12 @@ pub fn parse_exif_datetime(s: &str) -> Option<DateTime<Utc>> {
 /// Extract up to 5 representative video frames using ffmpeg's thumbnail filter.
 /// The filter picks the most visually distinct frame from each group, avoiding
 /// dark/blank frames that are common at video boundaries.
-pub async fn extract_video_frames(data: &[u8]) -> Result<Vec<Vec<u8>>, DomainError> {
+pub async fn extract_video_frames(input: &Path) -> Result<Vec<Vec<u8>>, DomainError> {
     let temp_dir = std::env::temp_dir();
     let id = Uuid::new_v4();
-    let input_path = temp_dir.join(format!("gallerynet_{}.tmp", id));
     let output_pattern = temp_dir.join(format!("gallerynet_{}_%03d.jpg", id));
-
-    fs::write(&input_path, data)
-        .await
-        .map_err(|e| DomainError::Io(e.to_string()))?;
+    // The file: prefix keeps ffmpeg from reading names like "-x" or "http:..." as anything else
+    let input = format!("file:{}", input.display());
 
     // thumbnail=60 picks the best frame from every 60 frames (~2s at 30fps)
     // scale limits max resolution to 1080p to reduce memory usage during buffering
@@ -99,7 +116,7 @@ pub async fn extract_video_frames(data: &[u8]) -> Result<Vec<Vec<u8>>, DomainErr
         .args([
             "-y",
             "-i",
-            input_path.to_str().unwrap(),
+            &input,
             "-vf",
             "scale='min(1920,iw)':-2,thumbnail=60",
             "-frames:v",
@@ -114,8 +131,6 @@ pub async fn extract_video_frames(data: &[u8]) -> Result<Vec<Vec<u8>>, DomainErr
         .await
         .map_err(|e| DomainError::Io(format!("ffmpeg not available: {}", e)))?;
 
-    let _ = fs::remove_file(&input_path).await;
-
     if !output.status.success() {
         // Clean up any partial output
         for i in 1..=5 {
@@ -144,43 +159,248 @@ pub async fn extract_video_frames(data: &[u8]) -> Result<Vec<Vec<u8>>, DomainErr
     Ok(frames)
 }
 
-pub async fn process_media(
-    filename: &str,
-    data: &[u8],
-    hasher: &dyn HashGenerator,
-) -> Result<ProcessedMedia, DomainError> {
-    let extension = Path::new(filename)
+/// Whether a GIF has more than one frame.
+fn is_animated_gif(data: &[u8]) -> bool {
+    let Ok(decoder) = GifDecoder::new(Cursor::new(data)) else { return false };
+    decoder.into_frames().take(2).filter(Result::is_ok).count() == 2
+}
+
+/// Stitch frames, as picked by `extract_video_frames`, into a short silent MP4 loop.
+pub async fn encode_animation_from_frames(frames: &[Vec<u8>]) -> Result<Vec<u8>, DomainError> {
+    let dir = std::env::temp_dir().join(format!("gallerynet_{}", Uuid::new_v4()));
+    let result = async {
+        fs::create_dir_all(&dir).await.map_err(|e| DomainError::Io(e.to_string()))?;
+        for (i, frame) in frames.iter().enumerate() {
+            fs::write(dir.join(format!("{:03}.jpg", i)), frame)
+                .await
+                .map_err(|e| DomainError::Io(e.to_string()))?;
+        }
+        let pattern = dir.join("%03d.jpg");
+        encode_animation(&["-framerate", &ANIMATION_FRAMERATE.to_string(), "-i", pattern.to_str().unwrap()]).await
+    }
+    .await;
+    let _ = fs::remove_dir_all(&dir).await;
+    result
+}
+
+/// Re-encode the first seconds of an animated GIF as a silent MP4 loop.
+pub async fn encode_animation_from_file(input: &Path) -> Result<Vec<u8>, DomainError> {
+    let input = format!("file:{}", input.display());
+    let duration = ANIMATION_MAX_SECS.to_string();
+    encode_animation(&["-t", &duration, "-i", &input]).await
+}
+
+/// Run ffmpeg on `input_args` to write a small H.264 MP4 that browsers play inline.
+async fn encode_animation(input_args: &[&str]) -> Result<Vec<u8>, DomainError> {
+    let output = std::env::temp_dir().join(format!("gallerynet_{}.mp4", Uuid::new_v4()));
+    // Fit into a square of ANIMATION_SIZE, never scaling up; H.264 needs even dimensions
+    let filter = format!(
+        "scale='min({0},iw)':'min({0},ih)':force_original_aspect_ratio=decrease,\
+         scale=trunc(iw/2)*2:trunc(ih/2)*2,format=yuv420p",
+        ANIMATION_SIZE
+    );
+    let status = tokio::process::Command::new("ffmpeg")
+        .arg("-y")
+        .args(input_args)
+        .args(["-vf", &filter, "-c:v", "libx264", "-preset", "veryfast", "-crf", "28"])
+        .args(["-an", "-movflags", "+faststart"])
+        .arg(&output)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .await
+        .map_err(|e| DomainError::Io(format!("ffmpeg not available: {}", e)))?;
+
+    let result = if status.success() {
+        fs::read(&output).await.map_err(|e| DomainError::Io(e.to_string()))
+    } else {
+        Err(DomainError::Io("ffmpeg failed to encode animation".to_string()))
+    };
+    let _ = fs::remove_file(&output).await;
+    result
+}
+
+/// Still-image formats the `image` crate cannot decode.
+fn needs_external_decoder(extension: &str) -> bool {
+    matches!(extension, "heic" | "heif" | "avif")
+}
+
+/// Decode a HEIC/HEIF or AVIF still into PNG bytes with libheif's `heif-convert`,
+/// falling back to ffmpeg (which needs 7.0+ for the tiled HEICs phones write). Both
+/// apply the container's rotation and mirroring, so EXIF orientation must not be.
+pub async fn decode_with_external_tool(input: &Path) -> Result<Vec<u8>, DomainError> {
+    let output = std::env::temp_dir().join(format!("gallerynet_{}.png", Uuid::new_v4()));
+
+    let heif_convert = tokio::process::Command::new("heif-convert")
+        .arg("--")
+        .arg(input)
+        .arg(&output)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .await;
+    if !matches!(heif_convert, Ok(status) if status.success()) {
+        let ffmpeg = tokio::process::Command::new("ffmpeg")
+            .args(["-y", "-i", &format!("file:{}", input.display()), "-frames:v", "1"])
+            .arg(&output)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .await
+            .map_err(|e| DomainError::Io(format!("No HEIF/AVIF decoder available: {}", e)))?;
+        if !ffmpeg.success() {
+            let _ = fs::remove_file(&output).await;
+            return Err(DomainError::Io("Failed to decode HEIF/AVIF image".to_string()));
+        }
+    }
+
+    let decoded = fs::read(&output).await;
+    let _ = fs::remove_file(&output).await;
+    decoded.map_err(|e| DomainError::Io(format!("Decoder produced no image: {}", e)))
+}
+
+fn is_video_extension(extension: &str) -> bool {
+    matches!(extension, "mp4" | "mov" | "avi" | "mkv" | "webm")
+}
+
+fn extension_of(filename: &str) -> String {
+    Path::new(filename)
         .extension()
         .and_then(|ext| ext.to_str())
         .unwrap_or("bin")
-        .to_lowercase();
+        .to_lowercase()
+}
+
+/// EXIF of an image file. RAF is no TIFF container, but its preview carries the
+/// camera's EXIF.
+fn read_exif(data: &[u8], is_raw: bool) -> Option<exif::Exif> {
+    let reader = exif::Reader::new();
+    let exif = reader.read_from_container(&mut Cursor::new(data)).ok();
+    if exif.is_some() || !is_raw {
+        return exif;
+    }
+    raw::embedded_previews(data)
+        .into_iter()
+        .find_map(|jpeg| reader.read_from_container(&mut Cursor::new(jpeg)).ok())
+}
+
+fn exif_orientation(exif: &exif::Exif) -> Option<u32> {
+    exif.get_field(Tag::Orientation, exif::In::PRIMARY)?.value.get_uint(0)
+}
+
+/// Decodable bytes of the image file `data` read from `path`, and whether its EXIF
+/// orientation still has to be applied. HEIC/HEIF and AVIF go through an external
+/// decoder (which applies it itself) and RAW files are shown by their embedded
+/// preview; the rest is decoded as is.
+async fn still_pixels(extension: &str, path: &Path, data: Vec<u8>) -> (Option<Vec<u8>>, bool) {
+    if needs_external_decoder(extension) {
+        (decode_with_external_tool(path).await.ok(), false)
+    } else if raw::is_raw_extension(extension) {
+        (raw::render(path, &data).await, true)
+    } else {
+        (Some(data), true)
+    }
+}
 
-    let is_video = matches!(extension.as_str(), "mp4" | "mov" | "avi" | "mkv" | "webm");
+/// The upright image shown for the media file at `path`: the image itself, or the
+/// first representative frame of a video.
+pub async fn load_still(filename: &str, path: &Path) -> Result<image::DynamicImage, DomainError> {
+    let extension = extension_of(filename);
+    if is_video_extension(&extension) {
+        let frames = extract_video_frames(path).await?;
+        return load_image_with_limits(&frames[0]);
+    }
+
+    let data = fs::read(path)
+        .await
+        .map_err(|e| DomainError::Io(format!("Failed to read {}: {}", filename, e)))?;
+    let orientation = read_exif(&data, raw::is_raw_extension(&extension))
+        .and_then(|exif| exif_orientation(&exif))
+        .unwrap_or(1);
+    let (pixels, needs_orientation) = still_pixels(&extension, path, data).await;
+    let pixels = pixels.ok_or_else(|| DomainError::Io(format!("Failed to decode {}", filename)))?;
+    let img = load_image_with_limits(&pixels)?;
+    Ok(if needs_orientation { apply_orientation(img, orientation) } else { img })
+}
+
+/// Encode the square thumbnail and the previews of an upright image, in every configured
+/// encoding. Previews are never scaled up, so a small image's previews are the image itself.
+pub fn encode_renditions(img: &image::DynamicImage, sizes: &RenditionSizes) -> Result<Renditions, DomainError> {
+    let mut files = Vec::with_capacity(sizes.formats.len() * (sizes.previews.len() + 1));
+
+    // None of the encoders keep an alpha channel
+    let thumbnail = img
+        .resize_to_fill(sizes.thumbnail, sizes.thumbnail, FilterType::CatmullRom)
+        .to_rgb8();
+    for &format in &sizes.formats {
+        let bytes = encode_rendition(&thumbnail, format, THUMBNAIL_QUALITY)?;
+        files.push((renditions::thumbnail_name(sizes.thumbnail, format), bytes));
+    }
+    for &size in &sizes.previews {
+        let preview = if img.width().max(img.height()) > size {
+            img.resize(size, size, FilterType::Lanczos3)
+        } else {
+            img.clone()
+        };
+        let preview = preview.to_rgb8();
+        for &format in &sizes.formats {
+            let bytes = encode_rendition(&preview, format, PREVIEW_QUALITY)?;
+            files.push((renditions::preview_name(size, format), bytes));
+        }
+    }
+    Ok(Renditions { files })
+}
+
+fn encode_rendition(img: &image::RgbImage, format: ThumbnailFormat, quality: u8) -> Result<Vec<u8>, DomainError> {
+    let mut bytes = Vec::new();
+    let encoded = match format {
+        ThumbnailFormat::Jpeg => img.write_with_encoder(JpegEncoder::new_with_quality(&mut bytes, quality)),
+        ThumbnailFormat::Avif => {
+            img.write_with_encoder(AvifEncoder::new_with_speed_quality(&mut bytes, AVIF_SPEED, quality))
+        }
+    };
+    encoded.map_err(|e| DomainError::Io(format!("Failed to encode {} rendition: {}", format.extension(), e)))?;
+    Ok(bytes)
+}
+
+/// Analyse the media file at `path`. Videos are read by ffmpeg directly; only images,
+/// which are decoded in full anyway, are loaded into memory.
+pub async fn process_media(
+    filename: &str,
+    path: &Path,
+    hasher: &dyn HashGenerator,
+    sizes: &RenditionSizes,
+) -> Result<ProcessedMedia, DomainError> {
+    let extension = extension_of(filename);
 
     let mut width = None;
     let mut height = None;
     let mut exif_json = None;
-    let mut thumbnail_bytes = Vec::new();
+    let mut renditions = None;
     let mut phash = "no_hash".to_string();
     let mut feature_input: Option<Vec<u8>> = None;
     let mut original_date: Option<DateTime<Utc>> = None;
 
-    if is_video {
-        // Extract representative frames via ffmpeg for phash, thumbnail, and features
-        if let Ok(frames) = extract_video_frames(data).await {
-            // Use the first representative frame for thumbnail and features
+    if is_video_extension(&extension) {
+        // Extract representative frames via ffmpeg for phash, renditions, and features
+        if let Ok(frames) = extract_video_frames(path).await {
+            // Use the first representative frame for renditions and features
             if let Some(first) = frames.first() {
                 if let Ok(img) = load_image_with_limits(first) {
                     width = Some(img.width());
                     height = Some(img.height());
-
-                    let thumb = img.resize_to_fill(224, 224, FilterType::CatmullRom);
-                    let mut cursor = Cursor::new(&mut thumbnail_bytes);
-                    let _ = thumb.write_to(&mut cursor, image::ImageFormat::Jpeg);
+                    renditions = encode_renditions(&img, sizes).ok();
                 }
                 feature_input = Some(first.clone());
             }
 
+            // Stitch the same frames into a short loop
+            if let Some(renditions) = renditions.as_mut().filter(|_| sizes.animated && frames.len() > 1) {
+                if let Ok(animation) = encode_animation_from_frames(&frames).await {
+                    renditions.files.push((renditions::ANIMATION.to_string(), animation));
+                }
+            }
+
             // Combine phashes from all frames for robust duplicate detection
             let frame_hashes: Vec<String> = frames
                 .iter()
@@ -191,10 +411,13 @@ pub async fn process_media(
             }
         }
     } else {
+        let data = fs::read(path)
+            .await
+            .map_err(|e| DomainError::Io(format!("Failed to read {}: {}", filename, e)))?;
+
         // Parse EXIF
         let mut orientation = 1u32;
-        let reader = exif::Reader::new();
-        if let Ok(exif) = reader.read_from_container(&mut Cursor::new(data)) {
+        if let Some(exif) = read_exif(&data, raw::is_raw_extension(&extension)) {
             let mut map = serde_json::Map::new();
             for f in exif.fields() {
                 let key = f.tag.to_string();
@@ -203,10 +426,8 @@ pub async fn process_media(
             }
             exif_json = serde_json::to_string(&map).ok();
 
-            if let Some(field) = exif.get_field(Tag::Orientation, exif::In::PRIMARY) {
-                if let Some(val) = field.value.get_uint(0) {
-                    orientation = val;
-                }
+            if let Some(val) = exif_orientation(&exif) {
+                orientation = val;
             }
 
             // Extract original date from EXIF (try DateTimeOriginal, DateTimeDigitized, DateTime)
@@ -221,7 +442,13 @@ pub async fn process_media(
             }
         }
 
-        if let Ok(mut img) = load_image_with_limits(data) {
+        let animated_gif = sizes.animated && extension == "gif" && is_animated_gif(&data);
+        let (pixels, needs_orientation) = still_pixels(&extension, path, data).await;
+        if !needs_orientation {
+            orientation = 1;
+        }
+
+        if let Some(Ok(mut img)) = pixels.as_deref().map(load_image_with_limits) {
             img = apply_orientation(img, orientation);
 
             width = Some(img.width());
@@ -235,22 +462,23 @@ pub async fn process_media(
                 .generate_phash(&phash_buf)
                 .unwrap_or_else(|_| "no_hash".to_string());
 
-            // Thumbnail
-            let thumb = img.resize_to_fill(224, 224, FilterType::CatmullRom);
-            let mut cursor = Cursor::new(&mut thumbnail_bytes);
-            thumb
-                .write_to(&mut cursor, image::ImageFormat::Jpeg)
-                .map_err(|e| DomainError::Io(format!("Failed to encode thumbnail: {}", e)))?;
+            let mut encoded = encode_renditions(&img, sizes)?;
+            if animated_gif {
+                if let Ok(animation) = encode_animation_from_file(path).await {
+                    encoded.files.push((renditions::ANIMATION.to_string(), animation));
+                }
+            }
+            renditions = Some(encoded);
         }
 
-        feature_input = Some(data.to_vec());
+        feature_input = pixels;
     }
 
     Ok(ProcessedMedia {
         width,
         height,
         exif_json,
-        thumbnail_bytes,
+        renditions,
         phash,
         feature_input,
         original_date,
@@ -0,0 +1,257 @@
+use crate::domain::DomainError;
+use std::path::Path;
+
+use super::processor::load_image_with_limits;
+
+/// Camera RAW formats accepted for upload. All but Fujifilm's RAF are TIFF containers.
+pub const RAW_EXTENSIONS: &[&str] = &["dng", "cr2", "nef", "arw", "raf"];
+
+/// Most IFDs followed in one file, so a corrupt offset chain cannot loop forever.
+const MAX_IFDS: usize = 64;
+
+const RAF_MAGIC: &[u8] = b"FUJIFILMCCD-RAW";
+
+pub fn is_raw_extension(extension: &str) -> bool {
+    RAW_EXTENSIONS.contains(&extension)
+}
+
+/// JPEG previews the camera embedded in a RAW file, largest first. Most cameras store
+/// a full-size or near full-size one next to the sensor data.
+pub fn embedded_previews(data: &[u8]) -> Vec<&[u8]> {
+    let ranges = if data.starts_with(RAF_MAGIC) {
+        raf_preview(data).into_iter().collect()
+    } else {
+        tiff_previews(data)
+    };
+
+    let mut previews: Vec<&[u8]> = ranges
+        .into_iter()
+        .filter_map(|(offset, length)| data.get(offset..offset.checked_add(length)?))
+        .filter(|jpeg| jpeg.starts_with(&[0xFF, 0xD8]))
+        .collect();
+    previews.sort_by_key(|jpeg| std::cmp::Reverse(jpeg.len()));
+    previews.dedup();
+    previews
+}
+
+/// Image bytes to show for a RAW file: its largest embedded preview that decodes,
+/// otherwise a demosaiced render. Neither applies the EXIF orientation.
+pub async fn render(path: &Path, data: &[u8]) -> Option<Vec<u8>> {
+    if let Some(preview) = embedded_previews(data)
+        .into_iter()
+        .find(|jpeg| load_image_with_limits(jpeg).is_ok())
+    {
+        return Some(preview.to_vec());
+    }
+    demosaic(path).await.ok()
+}
+
+/// Render the sensor data with dcraw as a half-size PPM, for files without a usable
+/// preview. Rotation is left to the EXIF orientation like for previews.
+pub async fn demosaic(input: &Path) -> Result<Vec<u8>, DomainError> {
+    // dcraw has no "--"; a relative path must not look like an option
+    let input = if input.is_absolute() { input.to_path_buf() } else { Path::new(".").join(input) };
+    let output = tokio::process::Command::new("dcraw")
+        .args(["-c", "-w", "-h", "-t", "0"])
+        .arg(&input)
+        .stderr(std::process::Stdio::null())
+        .output()
+        .await
+        .map_err(|e| DomainError::Io(format!("dcraw not available: {}", e)))?;
+
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(DomainError::Io("dcraw failed to render RAW file".to_string()));
+    }
+    Ok(output.stdout)
+}
+
+/// Offset and length of the JPEG a RAF header points to.
+fn raf_preview(data: &[u8]) -> Option<(usize, usize)> {
+    let offset = u32::from_be_bytes(data.get(84..88)?.try_into().ok()?);
+    let length = u32::from_be_bytes(data.get(88..92)?.try_into().ok()?);
+    Some((offset as usize, length as usize))
+}
+
+/// Reads values in the byte order of a TIFF header.
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl Tiff<'_> {
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    /// First value of the IFD entry at `entry`, which must be a SHORT or LONG.
+    fn value(&self, entry: usize) -> Option<u32> {
+        match self.u16(entry + 2)? {
+            3 => self.u16(entry + 8).map(u32::from),
+            4 | 13 => self.u32(entry + 8),
+            _ => None,
+        }
+    }
+
+    /// All LONG (or IFD) values of the entry at `entry`.
+    fn values(&self, entry: usize) -> Vec<u32> {
+        let count = self.u32(entry + 4).unwrap_or(0) as usize;
+        if !matches!(self.u16(entry + 2), Some(4 | 13)) || count == 0 {
+            return vec![];
+        }
+        let start = if count == 1 {
+            entry + 8
+        } else {
+            match self.u32(entry + 8) {
+                Some(offset) => offset as usize,
+                None => return vec![],
+            }
+        };
+        (0..count.min(MAX_IFDS)).map_while(|i| self.u32(start + i * 4)).collect()
+    }
+}
+
+/// Offset and length of every JPEG referenced from the IFDs of a TIFF-based RAW file:
+/// JPEGInterchangeFormat pointers and single-strip JPEG images, in IFD0, the IFDs
+/// chained after it, and their SubIFDs. Strips holding sensor data are skipped.
+fn tiff_previews(data: &[u8]) -> Vec<(usize, usize)> {
+    let big_endian = match data.get(0..4) {
+        Some(b"II*\0") => false,
+        Some(b"MM\0*") => true,
+        _ => return vec![],
+    };
+    let tiff = Tiff { data, big_endian };
+
+    let mut previews = Vec::new();
+    let mut pending: Vec<u32> = tiff.u32(4).into_iter().collect();
+    let mut visited = Vec::new();
+    while let Some(ifd) = pending.pop() {
+        let ifd = ifd as usize;
+        if ifd == 0 || visited.contains(&ifd) || visited.len() >= MAX_IFDS {
+            continue;
+        }
+        visited.push(ifd);
+        let Some(count) = tiff.u16(ifd) else { continue };
+
+        let (mut jpeg_offset, mut jpeg_length) = (None, None);
+        let (mut strip_offset, mut strip_length) = (None, None);
+        let mut compression = None;
+        let mut sensor_data = false;
+        for i in 0..count as usize {
+            let entry = ifd + 2 + i * 12;
+            let Some(tag) = tiff.u16(entry) else { break };
+            match tag {
+                0x0103 => compression = tiff.value(entry),
+                // Colour filter array or linear raw, as in DNG
+                0x0106 => sensor_data |= matches!(tiff.value(entry), Some(32803 | 34892)),
+                0x0111 if tiff.u32(entry + 4) == Some(1) => strip_offset = tiff.value(entry),
+                0x0117 if tiff.u32(entry + 4) == Some(1) => strip_length = tiff.value(entry),
+                0x014A => pending.extend(tiff.values(entry)),
+                0x0201 => jpeg_offset = tiff.value(entry),
+                0x0202 => jpeg_length = tiff.value(entry),
+                // Canon's slicing of the sensor data
+                0xC640 => sensor_data = true,
+                _ => {}
+            }
+        }
+
+        if let (Some(offset), Some(length)) = (jpeg_offset, jpeg_length) {
+            previews.push((offset as usize, length as usize));
+        }
+        if let (Some(offset), Some(length), Some(6 | 7), false) =
+            (strip_offset, strip_length, compression, sensor_data)
+        {
+            previews.push((offset as usize, length as usize));
+        }
+        if let Some(next) = tiff.u32(ifd + 2 + count as usize * 12) {
+            pending.push(next);
+        }
+    }
+    previews
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::RgbImage::from_pixel(width, height, image::Rgb([90, 120, 150]))
+            .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Jpeg)
+            .unwrap();
+        bytes
+    }
+
+    /// Little-endian IFD entry holding a single LONG.
+    fn entry(tag: u16, value: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(tag.to_le_bytes());
+        bytes.extend(4u16.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(value.to_le_bytes());
+        bytes
+    }
+
+    /// A NEF-like file: IFD0 with a small thumbnail and a SubIFD with a larger preview.
+    fn tiff_raw(thumbnail: &[u8], preview: &[u8]) -> Vec<u8> {
+        // Header (8), IFD0 with 3 entries (2 + 36 + 4), SubIFD with 2 entries (2 + 24 + 4)
+        let sub_ifd = 8 + 42;
+        let thumbnail_at = sub_ifd + 30;
+        let preview_at = thumbnail_at + thumbnail.len();
+
+        let mut data = b"II*\0".to_vec();
+        data.extend(8u32.to_le_bytes());
+        data.extend(3u16.to_le_bytes());
+        data.extend(entry(0x014A, sub_ifd as u32));
+        data.extend(entry(0x0201, thumbnail_at as u32));
+        data.extend(entry(0x0202, thumbnail.len() as u32));
+        data.extend(0u32.to_le_bytes());
+        data.extend(2u16.to_le_bytes());
+        data.extend(entry(0x0201, preview_at as u32));
+        data.extend(entry(0x0202, preview.len() as u32));
+        data.extend(0u32.to_le_bytes());
+        data.extend(thumbnail);
+        data.extend(preview);
+        data
+    }
+
+    #[test]
+    fn finds_previews_in_tiff_containers_largest_first() {
+        let (thumbnail, preview) = (jpeg(16, 12), jpeg(320, 240));
+        let data = tiff_raw(&thumbnail, &preview);
+
+        assert_eq!(embedded_previews(&data), vec![&preview[..], &thumbnail[..]]);
+        // Offsets past the end of a truncated file are ignored
+        assert_eq!(embedded_previews(&data[..data.len() - 1]), vec![&thumbnail[..]]);
+    }
+
+    #[test]
+    fn finds_the_preview_of_raf_files() {
+        let preview = jpeg(64, 48);
+        let mut data = b"FUJIFILMCCD-RAW 0201FF383501".to_vec();
+        data.resize(84, 0);
+        data.extend(100u32.to_be_bytes());
+        data.extend((preview.len() as u32).to_be_bytes());
+        data.resize(100, 0);
+        data.extend(&preview);
+
+        assert_eq!(embedded_previews(&data), vec![&preview[..]]);
+    }
+
+    #[tokio::test]
+    async fn renders_the_largest_decodable_preview() {
+        let (thumbnail, preview) = (jpeg(16, 12), jpeg(320, 240));
+        let mut broken = vec![0; preview.len()];
+        broken[..2].copy_from_slice(&[0xFF, 0xD8]);
+        let path = Path::new("unused.nef");
+
+        assert_eq!(render(path, &tiff_raw(&thumbnail, &preview)).await.unwrap(), preview);
+        assert_eq!(render(path, &tiff_raw(&thumbnail, &broken)).await.unwrap(), thumbnail);
+        assert!(embedded_previews(b"not a raw file").is_empty());
+    }
+}
@@ -0,0 +1,408 @@
+use crate::domain::{DomainError, MediaItem, MediaRepository, MediaStorage};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::originals::local_original;
+use super::processor;
+
+/// Encodings a rendition can be stored in. JPEG is always generated, as the fallback
+/// for clients that accept nothing newer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThumbnailFormat {
+    Jpeg,
+    Avif,
+}
+
+impl ThumbnailFormat {
+    /// Formats by preference when a client accepts several: smallest files first.
+    const PREFERENCE: [ThumbnailFormat; 1] = [ThumbnailFormat::Avif];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "jpeg" | "jpg" => Some(Self::Jpeg),
+            "avif" => Some(Self::Avif),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Avif => "avif",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Avif => "image/avif",
+        }
+    }
+
+    /// Whether an `Accept` header lists this format explicitly. Wildcards do not count:
+    /// browsers name the image formats they decode, other clients get JPEG.
+    fn accepted_by(self, accept: &str) -> bool {
+        accept.split(',').any(|range| {
+            let mut params = range.split(';').map(str::trim);
+            let media_range = params.next().unwrap_or_default();
+            let refused = params.any(|param| {
+                param.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()) == Some(0.0)
+            });
+            media_range.eq_ignore_ascii_case(self.mime_type()) && !refused
+        })
+    }
+}
+
+/// Sizes and encodings of the images generated from every item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenditionSizes {
+    /// Edge of the square grid thumbnail.
+    pub thumbnail: u32,
+    /// Longest edge of each aspect-preserving preview, ascending.
+    pub previews: Vec<u32>,
+    /// Encodings every size is stored in, JPEG first.
+    pub formats: Vec<ThumbnailFormat>,
+    /// Whether videos and animated GIFs also get a short looping MP4 for hover-to-play.
+    /// Only generated while processing, never by the backfill.
+    pub animated: bool,
+}
+
+impl Default for RenditionSizes {
+    fn default() -> Self {
+        Self {
+            thumbnail: 224,
+            previews: vec![720, 2048],
+            formats: vec![ThumbnailFormat::Jpeg],
+            animated: false,
+        }
+    }
+}
+
+impl RenditionSizes {
+    pub fn new(thumbnail: u32, mut previews: Vec<u32>) -> Self {
+        previews.retain(|&size| size > 0);
+        previews.sort_unstable();
+        previews.dedup();
+        Self { thumbnail: thumbnail.max(1), previews, ..Self::default() }
+    }
+
+    /// Also store every rendition in these encodings, served to clients that accept them.
+    pub fn with_formats(mut self, formats: Vec<ThumbnailFormat>) -> Self {
+        for format in formats {
+            if !self.formats.contains(&format) {
+                self.formats.push(format);
+            }
+        }
+        self
+    }
+
+    /// Also generate animated previews of videos and animated GIFs.
+    pub fn with_animated_previews(mut self) -> Self {
+        self.animated = true;
+        self
+    }
+
+    /// The preview served for a request of `requested` pixels: the smallest one at
+    /// least that large, otherwise the largest.
+    pub fn preview_for(&self, requested: u32) -> Option<u32> {
+        self.previews
+            .iter()
+            .copied()
+            .find(|&size| size >= requested)
+            .or_else(|| self.previews.last().copied())
+    }
+
+    /// Extra encodings a client sending `accept` decodes, most preferred first.
+    pub fn negotiate(&self, accept: Option<&str>) -> Vec<ThumbnailFormat> {
+        let Some(accept) = accept else { return vec![] };
+        ThumbnailFormat::PREFERENCE
+            .into_iter()
+            .filter(|format| self.formats.contains(format) && format.accepted_by(accept))
+            .collect()
+    }
+
+    /// Names the renditions of an item are recorded under. A size or encoding that is
+    /// added or changed has a new name, so the backfill generates it for existing items.
+    pub fn names(&self) -> Vec<String> {
+        self.formats
+            .iter()
+            .flat_map(|&format| {
+                std::iter::once(thumbnail_name(self.thumbnail, format))
+                    .chain(self.previews.iter().map(move |&size| preview_name(size, format)))
+            })
+            .collect()
+    }
+}
+
+/// Rendition name of the square thumbnail: `square_224`, or `square_224.avif` for
+/// other encodings than JPEG.
+pub fn thumbnail_name(size: u32, format: ThumbnailFormat) -> String {
+    with_format(format!("square_{}", size), format)
+}
+
+/// Rendition name of a preview: `720`, or `720.avif` for other encodings than JPEG.
+pub fn preview_name(size: u32, format: ThumbnailFormat) -> String {
+    with_format(size.to_string(), format)
+}
+
+fn with_format(name: String, format: ThumbnailFormat) -> String {
+    match format {
+        ThumbnailFormat::Jpeg => name,
+        _ => format!("{}.{}", name, format.extension()),
+    }
+}
+
+/// Rendition name of the animated preview.
+pub const ANIMATION: &str = "animated";
+
+/// Encoded renditions of one item, by rendition name.
+pub struct Renditions {
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+/// Thumbnail storage key of an item's grid thumbnail: `ab/cd/<uuid>.jpg` for JPEG.
+pub fn thumbnail_key(id: Uuid, format: ThumbnailFormat) -> String {
+    let id_str = id.to_string();
+    format!("{}/{}/{}.{}", &id_str[0..2], &id_str[2..4], id, format.extension())
+}
+
+/// Thumbnail storage key of an item's preview: `ab/cd/<uuid>_<size>.jpg` for JPEG.
+pub fn preview_key(id: Uuid, size: u32, format: ThumbnailFormat) -> String {
+    let id_str = id.to_string();
+    format!("{}/{}/{}_{}.{}", &id_str[0..2], &id_str[2..4], id, size, format.extension())
+}
+
+/// Thumbnail storage key of an item's animated preview: `ab/cd/<uuid>_animated.mp4`.
+pub fn animation_key(id: Uuid) -> String {
+    let id_str = id.to_string();
+    format!("{}/{}/{}_{}.mp4", &id_str[0..2], &id_str[2..4], id, ANIMATION)
+}
+
+/// Thumbnail storage key of the rendition recorded as `name`.
+pub fn rendition_key(id: Uuid, name: &str) -> String {
+    if name == ANIMATION {
+        return animation_key(id);
+    }
+    let (base, format) = match name.split_once('.') {
+        Some((base, extension)) => (base, ThumbnailFormat::parse(extension).unwrap_or(ThumbnailFormat::Jpeg)),
+        None => (name, ThumbnailFormat::Jpeg),
+    };
+    match base.parse() {
+        Ok(size) => preview_key(id, size, format),
+        Err(_) => thumbnail_key(id, format),
+    }
+}
+
+/// Id of the item a thumbnail storage key belongs to. Every rendition's file name
+/// starts with the item's uuid.
+pub fn media_id_of_key(key: &str) -> Option<Uuid> {
+    let name = key.rsplit('/').next()?;
+    name.get(..36)?.parse().ok()
+}
+
+/// Write the renditions of item `id` and record them.
+pub async fn store_renditions(
+    repo: &dyn MediaRepository,
+    thumbnails: &dyn MediaStorage,
+    id: Uuid,
+    renditions: &Renditions,
+) -> Result<(), DomainError> {
+    let mut names = Vec::with_capacity(renditions.files.len());
+    for (name, bytes) in &renditions.files {
+        thumbnails.put(&rendition_key(id, name), bytes).await?;
+        names.push(name.clone());
+    }
+    repo.add_renditions(id, &names)
+}
+
+/// Serves previews, generating them on first request, and backfills the renditions of
+/// items stored before a size was configured.
+pub struct RenditionsUseCase {
+    repo: Arc<dyn MediaRepository>,
+    storage: Arc<dyn MediaStorage>,
+    thumbnails: Arc<dyn MediaStorage>,
+    sizes: RenditionSizes,
+}
+
+impl RenditionsUseCase {
+    pub fn new(
+        repo: Arc<dyn MediaRepository>,
+        storage: Arc<dyn MediaStorage>,
+        thumbnails: Arc<dyn MediaStorage>,
+        sizes: RenditionSizes,
+    ) -> Self {
+        Self { repo, storage, thumbnails, sizes }
+    }
+
+    /// Thumbnail storage key of the preview of `media` closest to `requested` pixels, in
+    /// the best encoding a client sending `accept` decodes. The item's renditions are
+    /// generated first if that preview does not exist yet.
+    pub async fn preview(&self, media: &MediaItem, requested: u32, accept: Option<&str>) -> Result<String, DomainError> {
+        let size = self.sizes.preview_for(requested).ok_or(DomainError::NotFound)?;
+        let key = preview_key(media.id, size, ThumbnailFormat::Jpeg);
+        if !self.thumbnails.exists(&key).await? {
+            self.generate(media).await?;
+        }
+        Ok(self.negotiate(&key, accept).await)
+    }
+
+    /// The thumbnail storage key to serve for a request of the JPEG rendition `key`.
+    /// Other encodings are only served once they were generated; until then, and for
+    /// keys of other images, that is `key` itself.
+    pub async fn negotiate(&self, key: &str, accept: Option<&str>) -> String {
+        if let Some(stem) = key.strip_suffix(".jpg") {
+            for format in self.sizes.negotiate(accept) {
+                let alternative = format!("{}.{}", stem, format.extension());
+                if self.thumbnails.exists(&alternative).await.unwrap_or(false) {
+                    return alternative;
+                }
+            }
+        }
+        key.to_string()
+    }
+
+    /// Generate the renditions of every processed item that lacks one of the
+    /// configured sizes. Returns how many items got them.
+    pub async fn backfill(&self) -> Result<usize, DomainError> {
+        let mut generated = 0;
+        for id in self.repo.find_media_missing_renditions(&self.sizes.names())? {
+            let Some(media) = self.repo.find_by_id(id)? else { continue };
+            match self.generate(&media).await {
+                Ok(()) => generated += 1,
+                Err(e) => println!("Failed to render {}: {}", id, e),
+            }
+        }
+        Ok(generated)
+    }
+
+    async fn generate(&self, media: &MediaItem) -> Result<(), DomainError> {
+        let original = local_original(self.storage.as_ref(), &media.filename).await?;
+        let img = processor::load_still(&media.original_filename, original.path()).await?;
+        drop(original);
+        let renditions = processor::encode_renditions(&img, &self.sizes)?;
+        store_renditions(self.repo.as_ref(), self.thumbnails.as_ref(), media.id, &renditions).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::{LocalStorage, SqliteRepository, TestDb};
+    use chrono::Utc;
+
+    #[test]
+    fn previews_are_picked_by_requested_size() {
+        let sizes = RenditionSizes::new(256, vec![2048, 0, 720, 720]);
+        assert_eq!(sizes.previews, vec![720, 2048]);
+        assert_eq!(sizes.preview_for(300), Some(720));
+        assert_eq!(sizes.preview_for(720), Some(720));
+        assert_eq!(sizes.preview_for(1080), Some(2048));
+        assert_eq!(sizes.preview_for(4096), Some(2048));
+        assert_eq!(RenditionSizes::new(224, vec![]).preview_for(720), None);
+        assert_eq!(sizes.names(), vec!["square_256", "720", "2048"]);
+        let id = Uuid::new_v4();
+        assert_eq!(rendition_key(id, "square_256"), thumbnail_key(id, ThumbnailFormat::Jpeg));
+        assert_eq!(rendition_key(id, "720"), preview_key(id, 720, ThumbnailFormat::Jpeg));
+    }
+
+    #[test]
+    fn formats_are_named_and_negotiated() {
+        let sizes = RenditionSizes::new(224, vec![720]).with_formats(vec![ThumbnailFormat::Avif, ThumbnailFormat::Jpeg]);
+        assert_eq!(sizes.formats, vec![ThumbnailFormat::Jpeg, ThumbnailFormat::Avif]);
+        assert_eq!(sizes.names(), vec!["square_224", "720", "square_224.avif", "720.avif"]);
+        let id = Uuid::new_v4();
+        assert_eq!(rendition_key(id, "square_224.avif"), thumbnail_key(id, ThumbnailFormat::Avif));
+        assert!(rendition_key(id, "720.avif").ends_with(&format!("{}_720.avif", id)));
+        // WebP is not an option: the image crate only encodes it losslessly
+        assert_eq!(ThumbnailFormat::parse("webp"), None);
+        assert_eq!(rendition_key(id, ANIMATION), animation_key(id));
+        // Animations are never backfilled, so they are not among the expected names
+        assert!(!sizes.clone().with_animated_previews().names().contains(&ANIMATION.to_string()));
+
+        let browser = "image/avif,image/webp,image/apng,image/*,*/*;q=0.8";
+        assert_eq!(sizes.negotiate(Some(browser)), vec![ThumbnailFormat::Avif]);
+        assert!(sizes.negotiate(Some("image/webp, image/avif;q=0")).is_empty());
+        // Wildcards and clients without an Accept header get JPEG
+        assert!(sizes.negotiate(Some("image/*,*/*")).is_empty());
+        assert!(sizes.negotiate(None).is_empty());
+        // Only configured encodings are offered
+        assert!(RenditionSizes::default().negotiate(Some(browser)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn missing_renditions_are_backfilled_and_served() {
+        let db = TestDb::new("renditions_backfill_test");
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let storage: Arc<dyn MediaStorage> = Arc::new(LocalStorage::new(dir.path().join("uploads")));
+        let thumbnails: Arc<dyn MediaStorage> = Arc::new(LocalStorage::new(dir.path().join("thumbnails")));
+
+        let mut png = Vec::new();
+        image::RgbImage::from_pixel(1000, 500, image::Rgb([40, 80, 120]))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let media = MediaItem {
+            id: Uuid::new_v4(),
+            filename: "ab/cd/wide.png".to_string(),
+            original_filename: "wide.png".to_string(),
+            media_type: "image".to_string(),
+            phash: "mock_phash".to_string(),
+            content_hash: None,
+            owner_id: None,
+            uploaded_at: Utc::now(),
+            original_date: Utc::now(),
+            width: Some(1000),
+            height: Some(500),
+            size_bytes: png.len() as i64,
+            exif_json: None,
+            is_favorite: false,
+            tags: vec![],
+            processing: false,
+        };
+        storage.put(&media.filename, &png).await.unwrap();
+        repo.save_metadata_and_vector(&media, None).unwrap();
+
+        let renditions = RenditionsUseCase::new(
+            repo.clone(),
+            storage.clone(),
+            thumbnails.clone(),
+            RenditionSizes::new(224, vec![720]),
+        );
+        assert_eq!(renditions.backfill().await.unwrap(), 1);
+        assert_eq!(renditions.backfill().await.unwrap(), 0);
+        let read = |key: &str| std::fs::read(dir.path().join("thumbnails").join(key)).unwrap();
+        let preview = image::load_from_memory(&read(&preview_key(media.id, 720, ThumbnailFormat::Jpeg))).unwrap();
+        assert_eq!((preview.width(), preview.height()), (720, 360));
+        assert!(thumbnails.exists(&thumbnail_key(media.id, ThumbnailFormat::Jpeg)).await.unwrap());
+
+        // A size added later is generated on first request
+        let renditions = RenditionsUseCase::new(
+            repo.clone(),
+            storage.clone(),
+            thumbnails.clone(),
+            RenditionSizes::new(224, vec![720, 2048]),
+        );
+        let key = renditions.preview(&media, 1500, None).await.unwrap();
+        assert_eq!(key, preview_key(media.id, 2048, ThumbnailFormat::Jpeg));
+        // Never scaled up
+        assert_eq!(image::load_from_memory(&read(&key)).unwrap().width(), 1000);
+        assert_eq!(renditions.backfill().await.unwrap(), 0);
+
+        // New encodings are served once the backfill generated them
+        let renditions = RenditionsUseCase::new(
+            repo.clone(),
+            storage.clone(),
+            thumbnails.clone(),
+            RenditionSizes::new(224, vec![720]).with_formats(vec![ThumbnailFormat::Avif]),
+        );
+        let jpeg = thumbnail_key(media.id, ThumbnailFormat::Jpeg);
+        assert_eq!(renditions.negotiate(&jpeg, Some("image/avif")).await, jpeg);
+        assert_eq!(renditions.backfill().await.unwrap(), 1);
+        let avif = renditions.negotiate(&jpeg, Some("image/avif")).await;
+        assert_eq!(avif, thumbnail_key(media.id, ThumbnailFormat::Avif));
+        assert!(!read(&avif).is_empty());
+        let key = renditions.preview(&media, 720, Some("image/avif,image/webp")).await.unwrap();
+        assert_eq!(key, preview_key(media.id, 720, ThumbnailFormat::Avif));
+        assert!(!read(&key).is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+use crate::domain::{DomainError, MediaRepository, ResumableUpload};
+use chrono::{Duration, Utc};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+use super::originals::hash_file;
+use super::upload::{is_allowed_extension, Accepted, UploadMediaUseCase};
+
+/// Uploads sent in chunks that survive dropped connections (the tus protocol). Chunks
+/// are appended to a part file in `dir`; once it is complete the file is handed to
+/// `UploadMediaUseCase::accept` like any other upload.
+pub struct ResumableUploadsUseCase {
+    repo: Arc<dyn MediaRepository>,
+    upload: Arc<UploadMediaUseCase>,
+    dir: PathBuf,
+    /// Largest upload that may be announced, in bytes.
+    max_size: u64,
+    /// How long an upload may go without a chunk before it is deleted.
+    expiry: Duration,
+    /// Uploads a request is writing to right now.
+    busy: Mutex<HashSet<Uuid>>,
+}
+
+/// Marks an upload as busy until dropped.
+struct BusyGuard<'a> {
+    busy: &'a Mutex<HashSet<Uuid>>,
+    id: Uuid,
+}
+
+impl Drop for BusyGuard<'_> {
+    fn drop(&mut self) {
+        self.busy.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl ResumableUploadsUseCase {
+    pub fn new(
+        repo: Arc<dyn MediaRepository>,
+        upload: Arc<UploadMediaUseCase>,
+        dir: PathBuf,
+        max_size: u64,
+        expiry_hours: u64,
+    ) -> Self {
+        Self {
+            repo,
+            upload,
+            dir,
+            max_size,
+            expiry: Duration::hours(expiry_hours.max(1) as i64),
+            busy: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    /// Announce an upload of `length` bytes. Unlisted file types are rejected before any
+    /// data is sent.
+    pub async fn create(
+        &self,
+        owner: Option<Uuid>,
+        filename: &str,
+        length: u64,
+    ) -> Result<ResumableUpload, DomainError> {
+        let filename = Path::new(filename)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let extension = Path::new(&filename)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("bin")
+            .to_lowercase();
+        if !is_allowed_extension(&extension) {
+            return Err(DomainError::Io(format!("File type not allowed: .{}", extension)));
+        }
+        if length > self.max_size {
+            return Err(DomainError::Io(format!("Upload too large (max {} bytes)", self.max_size)));
+        }
+
+        let now = Utc::now();
+        let upload = ResumableUpload {
+            id: Uuid::new_v4(),
+            filename,
+            length,
+            owner_id: owner,
+            created_at: now,
+            expires_at: now + self.expiry,
+        };
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| DomainError::Io(e.to_string()))?;
+        tokio::fs::File::create(self.part_path(upload.id))
+            .await
+            .map_err(|e| DomainError::Io(format!("Failed to create part file: {}", e)))?;
+        self.repo.create_resumable_upload(&upload)?;
+        Ok(upload)
+    }
+
+    /// An unexpired upload of `owner` and how many bytes of it were received.
+    pub async fn status(&self, id: Uuid, owner: Option<Uuid>) -> Result<(ResumableUpload, u64), DomainError> {
+        let upload = self.find(id, owner)?;
+        let offset = self.received(id).await;
+        Ok((upload, offset))
+    }
+
+    /// Append a chunk that starts at `offset`. Returns the upload with its new expiry and
+    /// the new offset. Whatever arrived before the connection dropped is kept, so the
+    /// client can resume from there.
+    pub async fn append<R: AsyncRead + Unpin>(
+        &self,
+        id: Uuid,
+        owner: Option<Uuid>,
+        offset: u64,
+        mut chunk: R,
+    ) -> Result<(ResumableUpload, u64), DomainError> {
+        let mut upload = self.find(id, owner)?;
+        let _busy = self.claim(id)?;
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.part_path(id))
+            .await
+            .map_err(|e| DomainError::Io(e.to_string()))?;
+        let received = file.metadata().await.map_err(|e| DomainError::Io(e.to_string()))?.len();
+        if received != offset {
+            return Err(DomainError::Io(format!("Upload offset is {}, not {}", received, offset)));
+        }
+
+        // One byte past the announced length is enough to tell that a chunk is too long
+        let remaining = upload.length - received;
+        let copied = tokio::io::copy(&mut (&mut chunk).take(remaining + 1), &mut file).await;
+        file.flush().await.map_err(|e| DomainError::Io(e.to_string()))?;
+        let new_offset = file.metadata().await.map_err(|e| DomainError::Io(e.to_string()))?.len();
+        if new_offset > upload.length {
+            file.set_len(received).await.map_err(|e| DomainError::Io(e.to_string()))?;
+            return Err(DomainError::Io("Upload exceeds its announced length".to_string()));
+        }
+
+        upload.expires_at = Utc::now() + self.expiry;
+        self.repo.touch_resumable_upload(id, upload.expires_at)?;
+        copied.map_err(|e| DomainError::Io(format!("Upload interrupted: {}", e)))?;
+        Ok((upload, new_offset))
+    }
+
+    /// Hand a fully received upload to `UploadMediaUseCase::accept`, which moves the part
+    /// file into storage. The part file is kept if that fails for a reason other than a duplicate, so completing can be
+    /// retried.
+    pub async fn complete(&self, id: Uuid, owner: Option<Uuid>) -> Result<Accepted, DomainError> {
+        let upload = self.find(id, owner)?;
+        let _busy = self.claim(id)?;
+        let path = self.part_path(id);
+        if self.received(id).await != upload.length {
+            return Err(DomainError::Io("Upload is not complete yet".to_string()));
+        }
+
+        let content_hash = hash_file(&path).await?;
+        let result = self.upload.accept(upload.owner_id, upload.filename, &path, content_hash).await;
+        if matches!(result, Ok(_) | Err(DomainError::DuplicateMedia(_))) {
+            self.discard(id).await?;
+        }
+        result
+    }
+
+    /// Abort an upload of `owner` and delete what was received.
+    pub async fn terminate(&self, id: Uuid, owner: Option<Uuid>) -> Result<(), DomainError> {
+        self.find(id, owner)?;
+        let _busy = self.claim(id)?;
+        self.discard(id).await
+    }
+
+    /// Delete uploads that went without a chunk for longer than the expiry.
+    /// Returns how many were deleted.
+    pub async fn purge_expired(&self) -> Result<usize, DomainError> {
+        let expired = self.repo.find_expired_resumable_uploads(Utc::now())?;
+        let mut purged = 0;
+        for id in expired {
+            // A chunk may be arriving right now; the next run gets it if it stalls
+            let Ok(_busy) = self.claim(id) else { continue };
+            self.discard(id).await?;
+            purged += 1;
+        }
+        Ok(purged)
+    }
+
+    fn find(&self, id: Uuid, owner: Option<Uuid>) -> Result<ResumableUpload, DomainError> {
+        match self.repo.find_resumable_upload(id)? {
+            Some(upload) if upload.owner_id == owner && upload.expires_at > Utc::now() => Ok(upload),
+            _ => Err(DomainError::NotFound),
+        }
+    }
+
+    fn claim(&self, id: Uuid) -> Result<BusyGuard<'_>, DomainError> {
+        if !self.busy.lock().unwrap().insert(id) {
+            return Err(DomainError::Io("Upload is busy with another request".to_string()));
+        }
+        Ok(BusyGuard { busy: &self.busy, id })
+    }
+
+    async fn received(&self, id: Uuid) -> u64 {
+        tokio::fs::metadata(self.part_path(id)).await.map(|m| m.len()).unwrap_or(0)
+    }
+
+    async fn discard(&self, id: Uuid) -> Result<(), DomainError> {
+        match tokio::fs::remove_file(self.part_path(id)).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(DomainError::Io(e.to_string())),
+        }
+        self.repo.delete_resumable_upload(id)
+    }
+
+    fn part_path(&self, id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.part", id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::MediaStorage;
+    use crate::infrastructure::{LocalStorage, OrtProcessor, PhashGenerator, SqliteRepository, TestDb};
+
+    fn resumable(db: &TestDb, dir: &Path) -> ResumableUploadsUseCase {
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let storage: Arc<dyn MediaStorage> = Arc::new(LocalStorage::new(dir.join("uploads")));
+        let upload = Arc::new(UploadMediaUseCase::new(
+            repo.clone(),
+            Arc::new(OrtProcessor::new_empty()),
+            Arc::new(PhashGenerator::new()),
+            storage.clone(),
+            storage,
+            0,
+        ));
+        ResumableUploadsUseCase::new(repo, upload, dir.join("partial"), 1024, 24)
+    }
+
+    #[tokio::test]
+    async fn chunks_are_appended_and_completed() {
+        let db = TestDb::new("resumable_upload_test");
+        let dir = tempfile::tempdir().unwrap();
+        let uploads = resumable(&db, dir.path());
+        let owner = Some(Uuid::new_v4());
+        let data: Vec<u8> = (0..100u8).collect();
+
+        assert!(uploads.create(owner, "notes.txt", 100).await.is_err());
+        assert!(uploads.create(owner, "huge.mp4", 2048).await.is_err());
+        let upload = uploads.create(owner, "../clips/clip.mp4", 100).await.unwrap();
+        assert_eq!(upload.filename, "clip.mp4");
+
+        assert_eq!(uploads.append(upload.id, owner, 0, &data[..40]).await.unwrap().1, 40);
+        // Resuming at the wrong offset, or as someone else, is refused
+        assert!(uploads.append(upload.id, owner, 0, &data[..40]).await.is_err());
+        assert!(matches!(uploads.status(upload.id, None).await, Err(DomainError::NotFound)));
+        assert_eq!(uploads.status(upload.id, owner).await.unwrap().1, 40);
+        // A chunk running past the announced length is dropped entirely
+        assert!(uploads.append(upload.id, owner, 40, &[0u8; 80][..]).await.is_err());
+        assert_eq!(uploads.status(upload.id, owner).await.unwrap().1, 40);
+        assert!(uploads.complete(upload.id, owner).await.is_err());
+
+        assert_eq!(uploads.append(upload.id, owner, 40, &data[40..]).await.unwrap().1, 100);
+        let media = uploads.complete(upload.id, owner).await.unwrap().into_media();
+        assert!(media.processing);
+        assert_eq!(media.original_filename, "clip.mp4");
+        assert_eq!(media.owner_id, owner);
+        assert_eq!(media.size_bytes, 100);
+        assert!(matches!(uploads.status(upload.id, owner).await, Err(DomainError::NotFound)));
+        assert!(!uploads.part_path(upload.id).exists());
+    }
+
+    #[tokio::test]
+    async fn abandoned_uploads_are_purged() {
+        let db = TestDb::new("resumable_purge_test");
+        let dir = tempfile::tempdir().unwrap();
+        let uploads = resumable(&db, dir.path());
+        let kept = uploads.create(None, "kept.jpg", 10).await.unwrap();
+        let abandoned = uploads.create(None, "abandoned.jpg", 10).await.unwrap();
+        uploads.repo.touch_resumable_upload(abandoned.id, Utc::now() - Duration::seconds(1)).unwrap();
+
+        assert!(matches!(uploads.status(abandoned.id, None).await, Err(DomainError::NotFound)));
+        assert_eq!(uploads.purge_expired().await.unwrap(), 1);
+        assert!(!uploads.part_path(abandoned.id).exists());
+        assert!(uploads.part_path(kept.id).exists());
+
+        uploads.terminate(kept.id, None).await.unwrap();
+        assert!(!uploads.part_path(kept.id).exists());
+        assert!(uploads.repo.find_resumable_upload(kept.id).unwrap().is_none());
+    }
+}
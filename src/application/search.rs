@@ -1,7 +1,10 @@
-use crate::domain::{MediaRepository, AiProcessor, MediaItem, DomainError};
+use crate::domain::{MediaRepository, AiProcessor, LibraryScope, MediaItem, DomainError};
 use std::sync::Arc;
 use uuid::Uuid;
 
+/// Most neighbours fetched from the vector index for one search.
+const MAX_CANDIDATES: usize = 4096;
+
 pub struct SearchSimilarUseCase {
     repo: Arc<dyn MediaRepository>,
     ai: Arc<dyn AiProcessor>,
@@ -12,31 +15,46 @@ impl SearchSimilarUseCase {
         Self { repo, ai }
     }
 
-    pub async fn execute(&self, image_bytes: &[u8], limit: usize, max_distance: f32) -> Result<Vec<MediaItem>, DomainError> {
+    pub async fn execute(&self, scope: &LibraryScope, image_bytes: &[u8], limit: usize, max_distance: f32) -> Result<Vec<MediaItem>, DomainError> {
         // 1. Extract features
         let vector = self.ai.extract_features(image_bytes)?;
 
         // 2. Find similar
-        let results = self.repo.find_similar(&vector, limit, max_distance)?;
-
-        Ok(results)
+        self.find_visible(scope, &vector, limit, max_distance, None)
     }
 
-    pub async fn execute_by_id(&self, id: Uuid, limit: usize, max_distance: f32) -> Result<Vec<MediaItem>, DomainError> {
+    pub async fn execute_by_id(&self, scope: &LibraryScope, id: Uuid, limit: usize, max_distance: f32) -> Result<Vec<MediaItem>, DomainError> {
         // 1. Get embedding for the existing item
         let vector = self.repo.get_embedding(id)?
             .ok_or(DomainError::NotFound)?;
 
-        // 2. Find similar (fetch limit + 1 to account for the item itself)
-        let results = self.repo.find_similar(&vector, limit + 1, max_distance)?;
-
-        // 3. Filter out the source item itself (distance 0)
-        let filtered: Vec<MediaItem> = results.into_iter()
-            .filter(|item| item.id != id)
-            .take(limit)
-            .collect();
+        // 2. Find similar, leaving out the source item itself (distance 0)
+        self.find_visible(scope, &vector, limit, max_distance, Some(id))
+    }
 
-        Ok(filtered)
+    /// The `limit` nearest items in `scope`. The vector index only knows the nearest
+    /// items overall, so more are fetched until enough of them are visible.
+    fn find_visible(
+        &self,
+        scope: &LibraryScope,
+        vector: &[f32],
+        limit: usize,
+        max_distance: f32,
+        exclude: Option<Uuid>,
+    ) -> Result<Vec<MediaItem>, DomainError> {
+        let mut candidates = (limit + 1).min(MAX_CANDIDATES);
+        loop {
+            let results = self.repo.find_similar(vector, candidates, max_distance)?;
+            let exhausted = results.len() < candidates || candidates == MAX_CANDIDATES;
+            let visible: Vec<MediaItem> = results
+                .into_iter()
+                .filter(|item| Some(item.id) != exclude && scope.can_see(item.owner_id))
+                .take(limit)
+                .collect();
+            if visible.len() == limit || exhausted {
+                return Ok(visible);
+            }
+            candidates = (candidates * 4).min(MAX_CANDIDATES);
+        }
     }
 }
-
@@ -0,0 +1,133 @@
+use crate::domain::{DomainError, MediaRepository, Session, User};
+use chrono::{Duration, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// `last_seen_at` is only written when it is older than this, so browsing the gallery
+/// does not turn every request into a database write.
+const TOUCH_INTERVAL_SECS: i64 = 60;
+
+/// Longest user agent kept for the session list.
+const MAX_USER_AGENT_LEN: usize = 256;
+
+/// 256 random bits, hex encoded. Shared by session cookies and API tokens.
+pub(crate) fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Tokens are random, so a plain SHA-256 is enough to keep them out of the database.
+pub(crate) fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Login sessions stored in the database, so they survive restarts and can be
+/// revoked one device at a time.
+pub struct SessionsUseCase {
+    repo: Arc<dyn MediaRepository>,
+    ttl: Duration,
+}
+
+impl SessionsUseCase {
+    pub fn new(repo: Arc<dyn MediaRepository>, ttl_days: u64) -> Self {
+        Self { repo, ttl: Duration::days(ttl_days.max(1) as i64) }
+    }
+
+    /// Lifetime of a new session, also used as the cookie's Max-Age.
+    pub fn ttl_secs(&self) -> i64 {
+        self.ttl.num_seconds()
+    }
+
+    /// Start a session for `user_id`. Returns the session and its token; the token is
+    /// only ever handed to the client, the database keeps its SHA-256.
+    pub fn create(
+        &self,
+        user_id: Uuid,
+        user_agent: Option<&str>,
+        ip: Option<String>,
+    ) -> Result<(Session, String), DomainError> {
+        let token = new_token();
+        let now = Utc::now();
+        let session = Session {
+            id: Uuid::new_v4(),
+            user_id,
+            created_at: now,
+            last_seen_at: now,
+            expires_at: now + self.ttl,
+            user_agent: user_agent
+                .map(|ua| ua.chars().take(MAX_USER_AGENT_LEN).collect::<String>())
+                .filter(|ua| !ua.is_empty()),
+            ip,
+        };
+        self.repo.create_session(&session, &hash_token(&token))?;
+        Ok((session, token))
+    }
+
+    /// Resolve a token to its unexpired session and user.
+    pub fn authenticate(&self, token: &str) -> Result<Option<(Session, User)>, DomainError> {
+        let now = Utc::now();
+        let Some(mut session) = self.repo.find_session(&hash_token(token), now)? else {
+            return Ok(None);
+        };
+        let Some(user) = self.repo.find_user_by_id(session.user_id)? else {
+            return Ok(None);
+        };
+        if now - session.last_seen_at >= Duration::seconds(TOUCH_INTERVAL_SECS) {
+            self.repo.touch_session(session.id, now)?;
+            session.last_seen_at = now;
+        }
+        Ok(Some((session, user)))
+    }
+
+    pub fn list(&self, user_id: Uuid) -> Result<Vec<Session>, DomainError> {
+        self.repo.list_sessions(user_id, Utc::now())
+    }
+
+    /// Revoke one of the user's own sessions.
+    pub fn revoke(&self, user_id: Uuid, session_id: Uuid) -> Result<(), DomainError> {
+        if !self.repo.delete_session(user_id, session_id)? {
+            return Err(DomainError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Drop expired sessions. Called by the scheduled task.
+    pub fn purge_expired(&self) -> Result<usize, DomainError> {
+        self.repo.delete_expired_sessions(Utc::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Role;
+    use crate::infrastructure::{SqliteRepository, TestDb};
+
+    #[test]
+    fn sessions_survive_reopen_and_revoke_individually() {
+        let db = TestDb::new("sessions_use_case_test");
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let user = User { id: Uuid::new_v4(), username: "kim".to_string(), role: Role::Member, created_at: Utc::now() };
+        repo.create_user(&user, "hash").unwrap();
+
+        let sessions = SessionsUseCase::new(repo.clone(), 30);
+        let (phone, phone_token) = sessions.create(user.id, Some("Phone"), None).unwrap();
+        let (_, laptop_token) = sessions.create(user.id, Some("Laptop"), Some("10.0.0.5".to_string())).unwrap();
+        assert_ne!(phone_token, laptop_token);
+        assert!(sessions.authenticate("bogus").unwrap().is_none());
+
+        // A fresh use case over the same database (a restart) still knows both tokens
+        let reopened = SessionsUseCase::new(Arc::new(SqliteRepository::new(&db.path).unwrap()), 30);
+        assert_eq!(reopened.authenticate(&phone_token).unwrap().unwrap().1.id, user.id);
+        assert_eq!(reopened.list(user.id).unwrap().len(), 2);
+
+        // Revoking one device leaves the other signed in
+        assert!(matches!(reopened.revoke(Uuid::new_v4(), phone.id), Err(DomainError::NotFound)));
+        reopened.revoke(user.id, phone.id).unwrap();
+        assert!(reopened.authenticate(&phone_token).unwrap().is_none());
+        assert!(reopened.authenticate(&laptop_token).unwrap().is_some());
+    }
+}
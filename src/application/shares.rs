@@ -0,0 +1,257 @@
+use crate::domain::{
+    DomainError, LibraryScope, MediaRepository, MediaSummary, ShareLink, ShareTarget,
+};
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::sessions::hash_token;
+use super::users::{hash_password, verify_password};
+
+/// Length of the random slug in a share URL (~130 bits).
+const SLUG_LEN: usize = 22;
+
+/// Proof that a visitor entered the share's password, kept in a cookie. It is tied to
+/// the stored hash, so it needs no server-side state and stops working when the share
+/// is deleted.
+fn unlock_key(slug: &str, password_hash: &str) -> String {
+    hash_token(&format!("{}:{}", slug, password_hash))
+}
+
+fn keys_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Public, read-only links to a folder or a single item.
+pub struct SharesUseCase {
+    repo: Arc<dyn MediaRepository>,
+}
+
+impl SharesUseCase {
+    pub fn new(repo: Arc<dyn MediaRepository>) -> Self {
+        Self { repo }
+    }
+
+    /// Create a link. Callers check that the creator may see `target`.
+    pub fn create(
+        &self,
+        created_by: Option<Uuid>,
+        target: ShareTarget,
+        password: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+        allow_download: bool,
+    ) -> Result<ShareLink, DomainError> {
+        if expires_at.is_some_and(|at| at <= Utc::now()) {
+            return Err(DomainError::Io("Share expiry must be in the future".to_string()));
+        }
+        let password_hash = password
+            .filter(|pw| !pw.is_empty())
+            .map(hash_password)
+            .transpose()?;
+
+        let share = ShareLink {
+            id: Uuid::new_v4(),
+            slug: rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(SLUG_LEN)
+                .map(char::from)
+                .collect(),
+            created_by,
+            target,
+            has_password: password_hash.is_some(),
+            allow_download,
+            expires_at,
+            created_at: Utc::now(),
+        };
+        self.repo.create_share(&share, password_hash.as_deref())?;
+        Ok(share)
+    }
+
+    /// Links created by `created_by`, or every link for `None`.
+    pub fn list(&self, created_by: Option<Uuid>) -> Result<Vec<ShareLink>, DomainError> {
+        self.repo.list_shares(created_by)
+    }
+
+    pub fn find(&self, id: Uuid) -> Result<ShareLink, DomainError> {
+        self.repo.find_share(id)?.ok_or(DomainError::NotFound)
+    }
+
+    pub fn delete(&self, id: Uuid) -> Result<(), DomainError> {
+        self.repo.delete_share(id)
+    }
+
+    /// Open a link for a visitor. Missing and expired links are `NotFound`; links with
+    /// a password are `Forbidden` until `key` (from `unlock`) matches.
+    pub fn open(&self, slug: &str, key: Option<&str>) -> Result<ShareLink, DomainError> {
+        let (share, password_hash) = self.live_share(slug)?;
+        match password_hash {
+            Some(hash) if !key.is_some_and(|key| keys_match(key, &unlock_key(slug, &hash))) => {
+                Err(DomainError::Forbidden)
+            }
+            _ => Ok(share),
+        }
+    }
+
+    /// Check a visitor's password. Returns the key that unlocks the link, or `None` if
+    /// the password is wrong.
+    pub fn unlock(&self, slug: &str, password: &str) -> Result<Option<String>, DomainError> {
+        let (_, password_hash) = self.live_share(slug)?;
+        Ok(match password_hash {
+            Some(hash) => verify_password(&hash, password).then(|| unlock_key(slug, &hash)),
+            None => Some(String::new()),
+        })
+    }
+
+    /// Display name of the shared folder or item.
+    pub fn title(&self, share: &ShareLink) -> Result<String, DomainError> {
+        match share.target {
+            ShareTarget::Folder(id) => Ok(self.repo.get_folder(id)?.ok_or(DomainError::NotFound)?.name),
+            ShareTarget::Media(id) => {
+                Ok(self.repo.find_by_id(id)?.ok_or(DomainError::NotFound)?.original_filename)
+            }
+        }
+    }
+
+    /// Whether a visitor of `share` may see `media_id`.
+    pub fn contains(&self, share: &ShareLink, media_id: Uuid) -> Result<bool, DomainError> {
+        self.repo.share_contains_media(share, &self.scope(share)?, media_id)
+    }
+
+    /// One page of the shared items, newest first.
+    pub fn items(
+        &self,
+        share: &ShareLink,
+        page: usize,
+        limit: usize,
+    ) -> Result<Vec<MediaSummary>, DomainError> {
+        let offset = (page.max(1) - 1) * limit;
+        let mut items = match share.target {
+            ShareTarget::Folder(folder_id) => self.repo.find_all_in_folder(
+                &self.scope(share)?,
+                folder_id,
+                limit,
+                offset,
+                None,
+                false,
+                None,
+                false,
+                "date",
+            )?,
+            ShareTarget::Media(_) if offset > 0 => Vec::new(),
+            ShareTarget::Media(_) => self.files(share)?,
+        };
+        // Favorites are the creator's business
+        for item in &mut items {
+            item.is_favorite = false;
+        }
+        Ok(items)
+    }
+
+    /// Every shared item, for zip downloads.
+    pub fn files(&self, share: &ShareLink) -> Result<Vec<MediaSummary>, DomainError> {
+        match share.target {
+            ShareTarget::Folder(folder_id) => {
+                let scope = self.scope(share)?;
+                let mut items = self.repo.get_folder_media_files(folder_id)?;
+                items.retain(|item| scope.can_see(item.owner_id));
+                Ok(items)
+            }
+            ShareTarget::Media(id) => {
+                if !self.contains(share, id)? {
+                    return Ok(Vec::new());
+                }
+                Ok(self.repo.find_by_id(id)?.map(MediaSummary::from).into_iter().collect())
+            }
+        }
+    }
+
+    fn live_share(&self, slug: &str) -> Result<(ShareLink, Option<String>), DomainError> {
+        self.repo
+            .find_share_by_slug(slug)?
+            .filter(|(share, _)| share.expires_at.is_none_or(|at| at > Utc::now()))
+            .ok_or(DomainError::NotFound)
+    }
+
+    /// Visitors see what the link's creator sees.
+    fn scope(&self, share: &ShareLink) -> Result<LibraryScope, DomainError> {
+        match share.created_by {
+            Some(id) => self
+                .repo
+                .find_user_by_id(id)?
+                .map(|user| LibraryScope::for_user(&user))
+                .ok_or(DomainError::NotFound),
+            None => Ok(LibraryScope::ALL),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::MediaItem;
+    use crate::infrastructure::{SqliteRepository, TestDb};
+    use chrono::Duration;
+
+    fn item() -> MediaItem {
+        MediaItem {
+            id: Uuid::new_v4(),
+            filename: "ab/cd/x.jpg".to_string(),
+            original_filename: "beach.jpg".to_string(),
+            media_type: "image".to_string(),
+            phash: "no_hash".to_string(),
+            content_hash: None,
+            owner_id: None,
+            uploaded_at: Utc::now(),
+            original_date: Utc::now(),
+            width: None,
+            height: None,
+            size_bytes: 1,
+            exif_json: None,
+            is_favorite: false,
+            tags: vec![],
+            processing: false,
+        }
+    }
+
+    #[test]
+    fn password_expiry_and_contents() {
+        let db = TestDb::new("shares_use_case_test");
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let shares = SharesUseCase::new(repo.clone());
+        let photo = item();
+        let other = item();
+        repo.save_metadata_and_vector(&photo, None).unwrap();
+        repo.save_metadata_and_vector(&other, None).unwrap();
+
+        let open = shares.create(None, ShareTarget::Media(photo.id), None, None, false).unwrap();
+        assert_eq!(open.slug.len(), SLUG_LEN);
+        assert_eq!(shares.open(&open.slug, None).unwrap().id, open.id);
+        assert_eq!(shares.title(&open).unwrap(), "beach.jpg");
+        assert_eq!(shares.items(&open, 1, 20).unwrap().len(), 1);
+        assert!(shares.items(&open, 2, 20).unwrap().is_empty());
+        assert!(shares.contains(&open, photo.id).unwrap());
+        assert!(!shares.contains(&open, other.id).unwrap());
+
+        let locked = shares.create(None, ShareTarget::Media(photo.id), Some("pw"), None, true).unwrap();
+        assert!(matches!(shares.open(&locked.slug, None), Err(DomainError::Forbidden)));
+        assert!(shares.unlock(&locked.slug, "wrong").unwrap().is_none());
+        let key = shares.unlock(&locked.slug, "pw").unwrap().unwrap();
+        assert!(shares.open(&locked.slug, Some(&key)).is_ok());
+        // A key only opens the link it was issued for
+        assert!(matches!(shares.open(&locked.slug, Some("forged")), Err(DomainError::Forbidden)));
+
+        assert!(shares
+            .create(None, ShareTarget::Media(photo.id), None, Some(Utc::now() - Duration::hours(1)), false)
+            .is_err());
+        let expiring = shares
+            .create(None, ShareTarget::Media(photo.id), None, Some(Utc::now() + Duration::hours(1)), false)
+            .unwrap();
+        assert!(shares.open(&expiring.slug, None).is_ok());
+        assert!(matches!(shares.open("nope", None), Err(DomainError::NotFound)));
+
+        shares.delete(open.id).unwrap();
+        assert!(matches!(shares.open(&open.slug, None), Err(DomainError::NotFound)));
+    }
+}
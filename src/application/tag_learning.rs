@@ -1,4 +1,4 @@
-use crate::domain::{DomainError, MediaRepository, MediaSummary, TrainedTagModel};
+use crate::domain::{DomainError, LibraryScope, MediaRepository, MediaSummary, TrainedTagModel};
 use linfa::composing::platt_scaling::{platt_newton_method, PlattParams};
 use linfa::prelude::*;
 use linfa_svm::Svm;
@@ -245,8 +245,18 @@ impl TagLearningUseCase {
             .collect())
     }
 
-    pub fn run_auto_tagging(&self, folder_id: Option<Uuid>) -> Result<AutoTagResult, DomainError> {
-        let before = self.repo.count_auto_tags(folder_id)?;
+    /// Retrain changed models and apply all of them, reporting `(models done, models
+    /// total)` before each model. Stops early, keeping the tags applied so far, when
+    /// `on_progress` returns false.
+    pub fn run_auto_tagging<F>(
+        &self,
+        folder_id: Option<Uuid>,
+        mut on_progress: F,
+    ) -> Result<AutoTagResult, DomainError>
+    where
+        F: FnMut(usize, usize) -> bool,
+    {
+        let before = self.repo.count_auto_tags(&LibraryScope::ALL, folder_id)?;
         let existing_auto = self.repo.get_tags_with_auto_counts()?;
         let trainable = self
             .repo
@@ -280,7 +290,11 @@ impl TagLearningUseCase {
             }
         }
 
-        for (tag_id, name, manual_count) in &trainable {
+        for (done, (tag_id, name, manual_count)) in trainable.iter().enumerate() {
+            if !on_progress(done, trainable.len()) {
+                info!(done, "Auto-tagging stopped");
+                break;
+            }
             let last_trained = self.repo.get_last_trained_count(*tag_id)?;
             let needs_retrain = *manual_count != last_trained;
             if needs_retrain {
@@ -316,7 +330,7 @@ impl TagLearningUseCase {
                     .update_auto_tags(*tag_id, &predictions, Some(&scope_ids))?;
             }
         }
-        let after = self.repo.count_auto_tags(folder_id)?;
+        let after = self.repo.count_auto_tags(&LibraryScope::ALL, folder_id)?;
         info!(before, after, change = (after as i64 - before as i64), "Auto-tagging complete");
         Ok(AutoTagResult {
             before,
@@ -559,11 +573,11 @@ mod tests {
             for i in 0..15 { conn.execute("INSERT INTO media_tags (media_id, tag_id, is_auto) VALUES (?1, 1, 0)", params![ids[i].as_bytes()]).unwrap(); }
             Ok(())
         }).unwrap();
-        let result = use_case.run_auto_tagging(None).unwrap();
+        let result = use_case.run_auto_tagging(None, |_, _| true).unwrap();
         assert!(result.after > 0, "Before: {}, After: {}, Models: {}", result.before, result.after, result.models_processed);
 
         // Run again without changes — should use cached model and produce same result
-        let result2 = use_case.run_auto_tagging(None).unwrap();
+        let result2 = use_case.run_auto_tagging(None, |_, _| true).unwrap();
         assert_eq!(result.after, result2.after, "Second run should produce identical results (cached model)");
     }
 
@@ -1,23 +1,63 @@
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::broadcast;
-use tracing::{info, error};
-use crate::application::FixThumbnailsUseCase;
-use crate::presentation::WsMessage;
+use tracing::{info, error, warn};
+use crate::application::{AuditUseCase, CheckExternalMediaUseCase, FixThumbnailsUseCase, JobEvent, JobEventSink, JobsUseCase, RenditionsUseCase, ResumableUploadsUseCase, SessionsUseCase, TrashUseCase, VerifyIntegrityUseCase, WatchFolderUseCase};
+use crate::domain::{JobKind, JobState};
+use crate::presentation::{WsBroadcast, WsMessage};
 use serde_json;
 
 pub struct TaskRunner {
     fix_thumbnails_use_case: Arc<FixThumbnailsUseCase>,
-    tx: broadcast::Sender<Arc<str>>,
+    renditions_use_case: Arc<RenditionsUseCase>,
+    check_external_use_case: Arc<CheckExternalMediaUseCase>,
+    watch_folder_use_case: Option<Arc<WatchFolderUseCase>>,
+    watch_interval_secs: u64,
+    verify_integrity_use_case: Arc<VerifyIntegrityUseCase>,
+    /// 0 disables the scheduled integrity scan.
+    integrity_interval_hours: u64,
+    trash_use_case: Arc<TrashUseCase>,
+    sessions_use_case: Arc<SessionsUseCase>,
+    audit_use_case: Arc<AuditUseCase>,
+    jobs_use_case: Arc<JobsUseCase>,
+    resumable_uploads_use_case: Arc<ResumableUploadsUseCase>,
+    /// Number of concurrent job workers.
+    job_workers: usize,
+    tx: broadcast::Sender<WsBroadcast>,
 }
 
 impl TaskRunner {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         fix_thumbnails_use_case: Arc<FixThumbnailsUseCase>,
-        tx: broadcast::Sender<Arc<str>>,
+        renditions_use_case: Arc<RenditionsUseCase>,
+        check_external_use_case: Arc<CheckExternalMediaUseCase>,
+        watch_folder_use_case: Option<Arc<WatchFolderUseCase>>,
+        watch_interval_secs: u64,
+        verify_integrity_use_case: Arc<VerifyIntegrityUseCase>,
+        integrity_interval_hours: u64,
+        trash_use_case: Arc<TrashUseCase>,
+        sessions_use_case: Arc<SessionsUseCase>,
+        audit_use_case: Arc<AuditUseCase>,
+        jobs_use_case: Arc<JobsUseCase>,
+        resumable_uploads_use_case: Arc<ResumableUploadsUseCase>,
+        job_workers: usize,
+        tx: broadcast::Sender<WsBroadcast>,
     ) -> Self {
         Self {
             fix_thumbnails_use_case,
+            renditions_use_case,
+            check_external_use_case,
+            watch_folder_use_case,
+            watch_interval_secs,
+            verify_integrity_use_case,
+            integrity_interval_hours,
+            trash_use_case,
+            sessions_use_case,
+            audit_use_case,
+            jobs_use_case,
+            resumable_uploads_use_case,
+            job_workers,
             tx,
         }
     }
@@ -59,18 +99,217 @@ impl TaskRunner {
                         error!("Scheduled thumbnail fix failed: {}", e);
                     }
                 }
+
+                // Render previews of items stored before their size was configured
+                match r.renditions_use_case.backfill().await {
+                    Ok(generated) if generated > 0 => {
+                        info!("Generated missing previews for {} items.", generated);
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Scheduled preview backfill failed: {}", e);
+                    }
+                }
+
+                // Detect originals referenced in place that were moved or deleted
+                match r.check_external_use_case.execute().await {
+                    Ok(missing) if !missing.is_empty() => {
+                        warn!("{} external originals are missing.", missing.len());
+                        r.broadcast(WsMessage::ExternalMediaMissing { ids: missing });
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Scheduled external media check failed: {}", e);
+                    }
+                }
+
+                // Permanently delete items that outlived the trash retention period
+                match r.trash_use_case.purge_expired().await {
+                    Ok(purged) if !purged.is_empty() => {
+                        info!("Purged {} expired items from the trash.", purged.len());
+                        r.audit_use_case.record(
+                            None,
+                            None,
+                            "trash.expire",
+                            purged.iter().map(|id| id.to_string()).collect(),
+                            None,
+                        );
+                        r.broadcast(WsMessage::MediaBatchDeleted { ids: purged });
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Scheduled trash purge failed: {}", e);
+                    }
+                }
+
+                match r.sessions_use_case.purge_expired() {
+                    Ok(removed) if removed > 0 => {
+                        info!("Removed {} expired login sessions.", removed);
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Scheduled session cleanup failed: {}", e);
+                    }
+                }
+
+                match r.jobs_use_case.prune() {
+                    Ok(removed) if removed > 0 => {
+                        info!("Removed {} finished jobs.", removed);
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Scheduled job cleanup failed: {}", e);
+                    }
+                }
+
+                match r.resumable_uploads_use_case.purge_expired().await {
+                    Ok(removed) if removed > 0 => {
+                        info!("Removed {} abandoned resumable uploads.", removed);
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Scheduled resumable upload cleanup failed: {}", e);
+                    }
+                }
+
+                match r.audit_use_case.prune() {
+                    Ok(removed) if removed > 0 => {
+                        info!("Removed {} audit log entries past retention.", removed);
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Scheduled audit log pruning failed: {}", e);
+                    }
+                }
                 
                 // Run once every 24 hours
                 tokio::time::sleep(Duration::from_secs(86400)).await;
             }
         });
         
+        // Start watch folder task
+        if let Some(watcher) = runner.watch_folder_use_case.clone() {
+            let r = runner.clone();
+            tokio::spawn(async move {
+                loop {
+                    match watcher.scan().await {
+                        Ok(created) => {
+                            if !created.is_empty() {
+                                info!("Watch folder ingested {} new items.", created.len());
+                            }
+                            for item in created {
+                                if let Ok(json_item) = serde_json::to_value(&item) {
+                                    r.broadcast(WsMessage::MediaCreated { item: json_item });
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            error!("Watch folder scan failed: {}", e);
+                        }
+                    }
+
+                    tokio::time::sleep(Duration::from_secs(r.watch_interval_secs)).await;
+                }
+            });
+        }
+
+        // Start integrity scan task (re-hashes every stored original, so it runs rarely)
+        if runner.integrity_interval_hours > 0 {
+            let r = runner.clone();
+            tokio::spawn(async move {
+                // Let the thumbnail fix run first
+                tokio::time::sleep(Duration::from_secs(600)).await;
+
+                loop {
+                    info!("Starting scheduled integrity scan...");
+                    match r.verify_integrity_use_case.execute().await {
+                        Ok(report) => {
+                            info!(
+                                "Integrity scan completed. Checked {} originals ({} newly hashed), {} corrupted, {} missing.",
+                                report.checked,
+                                report.backfilled,
+                                report.corrupted.len(),
+                                report.missing.len()
+                            );
+                            if !report.corrupted.is_empty() || !report.missing.is_empty() {
+                                warn!("Integrity scan found damaged or missing originals.");
+                                r.broadcast(WsMessage::IntegrityIssues {
+                                    corrupted: report.corrupted,
+                                    missing: report.missing,
+                                });
+                            }
+                        }
+                        Err(e) => {
+                            error!("Scheduled integrity scan failed: {}", e);
+                        }
+                    }
+
+                    tokio::time::sleep(Duration::from_secs(r.integrity_interval_hours * 3600)).await;
+                }
+            });
+        }
+
+        // Start job workers, after returning jobs interrupted by a restart to the queue
+        match runner.jobs_use_case.recover() {
+            Ok(requeued) if requeued > 0 => info!("Requeued {} interrupted jobs.", requeued),
+            Ok(_) => {}
+            Err(e) => error!("Failed to requeue interrupted jobs: {}", e),
+        }
+        for _ in 0..runner.job_workers {
+            let r = runner.clone();
+            tokio::spawn(async move {
+                let sink: JobEventSink = {
+                    let r = r.clone();
+                    Arc::new(move |event| r.on_job_event(event))
+                };
+                loop {
+                    match r.jobs_use_case.run_next(&sink).await {
+                        Ok(true) => {}
+                        // Idle: wait for a new job, or for a retry to become due
+                        Ok(false) => r.jobs_use_case.wait_for_work(Duration::from_secs(5)).await,
+                        Err(e) => {
+                            error!("Job worker failed: {}", e);
+                            tokio::time::sleep(Duration::from_secs(5)).await;
+                        }
+                    }
+                }
+            });
+        }
+
         // Add more background tasks here as needed
     }
 
+    /// Broadcast a job's progress, plus the messages clients knew before jobs existed.
+    fn on_job_event(&self, event: JobEvent) {
+        match &event {
+            JobEvent::Started(job) if job.kind == JobKind::FixThumbnails => {
+                self.broadcast(WsMessage::ThumbnailFixStarted);
+            }
+            JobEvent::Finished(job) => match &job.kind {
+                JobKind::FixThumbnails => {
+                    let count = job
+                        .result
+                        .as_ref()
+                        .and_then(|r| r["fixed_count"].as_u64())
+                        .unwrap_or(0) as usize;
+                    self.broadcast(WsMessage::ThumbnailFixCompleted { count });
+                }
+                JobKind::LearnTag { tag_name } if job.state == JobState::Done => {
+                    self.broadcast(WsMessage::TagLearningComplete { tag_name: tag_name.clone() });
+                }
+                JobKind::AutoTag { .. } if job.state == JobState::Done => {
+                    self.broadcast(WsMessage::UploadComplete);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        self.broadcast(event.into());
+    }
+
     fn broadcast(&self, msg: WsMessage) {
-        if let Ok(json) = serde_json::to_string(&msg) {
-            let _ = self.tx.send(Arc::from(json));
+        if let Some(message) = msg.to_broadcast() {
+            let _ = self.tx.send(message);
         }
     }
 }
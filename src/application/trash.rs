@@ -0,0 +1,152 @@
+use crate::domain::{DomainError, LibraryScope, MediaRepository, TrashedMedia};
+use chrono::{Duration, Utc};
+use std::collections::HashSet;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::delete::DeleteMediaUseCase;
+
+/// Browses, restores and purges soft-deleted media. Items older than the retention
+/// period are purged by the scheduled task.
+pub struct TrashUseCase {
+    repo: Arc<dyn MediaRepository>,
+    delete_use_case: Arc<DeleteMediaUseCase>,
+    /// 0 keeps trashed items until they are purged by hand.
+    retention_days: u64,
+}
+
+impl TrashUseCase {
+    pub fn new(
+        repo: Arc<dyn MediaRepository>,
+        delete_use_case: Arc<DeleteMediaUseCase>,
+        retention_days: u64,
+    ) -> Self {
+        Self { repo, delete_use_case, retention_days }
+    }
+
+    pub fn list(
+        &self,
+        scope: &LibraryScope,
+        page: usize,
+        limit: usize,
+    ) -> Result<Vec<TrashedMedia>, DomainError> {
+        let offset = (page.max(1) - 1) * limit;
+        self.repo.find_trashed(scope, limit, offset)
+    }
+
+    /// Returns the ids that were actually restored.
+    pub fn restore(&self, ids: &[Uuid]) -> Result<Vec<Uuid>, DomainError> {
+        let trashed = self.trashed_ids()?;
+        let ids: Vec<Uuid> = ids.iter().copied().filter(|id| trashed.contains(id)).collect();
+        self.repo.restore_media(&ids)?;
+        Ok(ids)
+    }
+
+    /// Permanently delete one trashed item. Live items are reported as not found.
+    pub async fn purge_one(&self, id: Uuid) -> Result<(), DomainError> {
+        if !self.trashed_ids()?.contains(&id) {
+            return Err(DomainError::NotFound);
+        }
+        self.delete_use_case.purge_one(id).await
+    }
+
+    /// Permanently delete the given items. Only items already in the trash are touched.
+    /// Returns the ids that were purged.
+    pub async fn purge(&self, ids: &[Uuid]) -> Result<Vec<Uuid>, DomainError> {
+        let trashed = self.trashed_ids()?;
+        let ids: Vec<Uuid> = ids.iter().copied().filter(|id| trashed.contains(id)).collect();
+        self.delete_use_case.purge(&ids).await?;
+        Ok(ids)
+    }
+
+    /// Permanently delete everything in the trash. Returns the ids that were purged.
+    pub async fn empty(&self) -> Result<Vec<Uuid>, DomainError> {
+        let ids = self.repo.find_trashed_before(Utc::now())?;
+        self.delete_use_case.purge(&ids).await?;
+        Ok(ids)
+    }
+
+    /// Purge items that have been in the trash longer than the retention period.
+    pub async fn purge_expired(&self) -> Result<Vec<Uuid>, DomainError> {
+        if self.retention_days == 0 {
+            return Ok(Vec::new());
+        }
+        let cutoff = Utc::now() - Duration::days(self.retention_days as i64);
+        let ids = self.repo.find_trashed_before(cutoff)?;
+        self.delete_use_case.purge(&ids).await?;
+        Ok(ids)
+    }
+
+    fn trashed_ids(&self) -> Result<HashSet<Uuid>, DomainError> {
+        Ok(self.repo.find_trashed_before(Utc::now())?.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{MediaItem, MediaStorage};
+    use crate::infrastructure::{LocalStorage, SqliteRepository, TestDb};
+
+    fn item(filename: &str) -> MediaItem {
+        MediaItem {
+            id: Uuid::new_v4(),
+            filename: filename.to_string(),
+            original_filename: "x.jpg".to_string(),
+            media_type: "image".to_string(),
+            phash: "no_hash".to_string(),
+            content_hash: None,
+            owner_id: None,
+            uploaded_at: Utc::now(),
+            original_date: Utc::now(),
+            width: None,
+            height: None,
+            size_bytes: 1,
+            exif_json: None,
+            is_favorite: false,
+            tags: vec![],
+            processing: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn delete_restore_and_purge() {
+        let db = TestDb::new("trash_use_case_test");
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(LocalStorage::new(dir.path().join("uploads")));
+        let delete_use_case = Arc::new(DeleteMediaUseCase::new(
+            repo.clone(),
+            storage.clone(),
+            Arc::new(LocalStorage::new(dir.path().join("thumbnails"))),
+        ));
+        let trash = TrashUseCase::new(repo.clone(), delete_use_case.clone(), 30);
+
+        let a = item("ab/cd/a.jpg");
+        let b = item("ab/cd/b.jpg");
+        for m in [&a, &b] {
+            repo.save_metadata_and_vector(m, None).unwrap();
+            storage.put(&m.filename, b"data").await.unwrap();
+        }
+
+        delete_use_case.execute(a.id).await.unwrap();
+        assert!(matches!(delete_use_case.execute(Uuid::new_v4()).await, Err(DomainError::NotFound)));
+        assert_eq!(delete_use_case.execute_batch(&[b.id]).await.unwrap(), 1);
+        assert_eq!(trash.list(&LibraryScope::ALL, 1, 10).unwrap().len(), 2);
+        // Files stay until purge
+        assert!(storage.exists(&a.filename).await.unwrap());
+
+        assert_eq!(trash.restore(&[b.id, Uuid::new_v4()]).unwrap(), vec![b.id]);
+
+        // Recently trashed items are not expired yet
+        assert!(trash.purge_expired().await.unwrap().is_empty());
+
+        // Live items are never purged
+        assert!(trash.purge(&[b.id]).await.unwrap().is_empty());
+        assert!(matches!(trash.purge_one(b.id).await, Err(DomainError::NotFound)));
+        assert_eq!(trash.purge(&[a.id]).await.unwrap(), vec![a.id]);
+        assert!(repo.find_by_id(a.id).unwrap().is_none());
+        assert!(!storage.exists(&a.filename).await.unwrap());
+        assert!(repo.find_by_id(b.id).unwrap().is_some());
+    }
+}
@@ -1,24 +1,87 @@
-use crate::domain::{MediaRepository, AiProcessor, HashGenerator, MediaItem, DomainError};
+use crate::domain::{MediaRepository, AiProcessor, HashGenerator, LibraryScope, MediaCompanion, MediaItem, MediaStorage, DomainError};
 use std::sync::Arc;
-use std::path::PathBuf;
+use std::path::Path;
+use serde::Serialize;
 use uuid::Uuid;
 use chrono::{Datelike, DateTime, NaiveDateTime, Utc};
-use tokio::fs;
 
+use super::originals::{hash_file, local_original};
 use super::processor;
+use super::raw;
+use super::renditions::{store_renditions, RenditionSizes, Renditions};
 
-/// Allowed file extensions for upload (images + videos).
+/// Allowed file extensions for upload (images, camera RAW + videos).
 const ALLOWED_EXTENSIONS: &[&str] = &[
     "jpg", "jpeg", "png", "gif", "webp", "bmp", "tiff", "tif", "heic", "heif", "avif",
+    "dng", "cr2", "nef", "arw", "raf",
     "mp4", "mov", "avi", "mkv", "webm",
 ];
 
+/// A RAW and a JPEG of the same name are only paired when uploaded this close together,
+/// since camera file names repeat once the counter wraps around.
+const PAIRING_WINDOW_MINUTES: i64 = 30;
+
+/// Whether a (lowercase) file extension is accepted for ingestion.
+pub fn is_allowed_extension(extension: &str) -> bool {
+    ALLOWED_EXTENSIONS.contains(&extension)
+}
+
+/// How far the server got with one uploaded file, reported to the client that sent it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum UploadStage {
+    /// Stored, waiting for a worker to process it.
+    Received,
+    /// Decoding an image for its thumbnail and perceptual hash.
+    Hashing,
+    /// Letting ffmpeg pick a video's representative frames.
+    ExtractingFrames,
+    /// Computing the embedding used by visual search.
+    Embedding,
+    /// Processed and complete in the library.
+    Saved,
+    /// Rejected as a copy of `existing`.
+    Duplicate { existing: Uuid },
+    Failed { error: String },
+}
+
+/// Receives the stages `UploadMediaUseCase::process` goes through.
+pub type UploadStageSink<'a> = &'a (dyn Fn(UploadStage) + Send + Sync);
+
+/// What `UploadMediaUseCase::accept` did with a received file.
+#[derive(Debug, Clone)]
+pub enum Accepted {
+    /// Stored as a new item, still to be processed.
+    Created(MediaItem),
+    /// Stored as the companion of this existing item (RAW+JPEG pairing).
+    Paired(MediaItem),
+}
+
+impl Accepted {
+    pub fn media(&self) -> &MediaItem {
+        match self {
+            Accepted::Created(media) | Accepted::Paired(media) => media,
+        }
+    }
+
+    pub fn into_media(self) -> MediaItem {
+        match self {
+            Accepted::Created(media) | Accepted::Paired(media) => media,
+        }
+    }
+}
+
 pub struct UploadMediaUseCase {
     repo: Arc<dyn MediaRepository>,
     ai: Arc<dyn AiProcessor>,
     hasher: Arc<dyn HashGenerator>,
-    storage_path: PathBuf,
-    thumbnail_path: PathBuf,
+    storage: Arc<dyn MediaStorage>,
+    thumbnails: Arc<dyn MediaStorage>,
+    /// Maximum Hamming distance (in bits) at which a perceptual hash counts as a duplicate.
+    duplicate_threshold: u32,
+    /// Store an uploaded RAW and its sibling JPEG as one item.
+    pair_raw_with_jpeg: bool,
+    renditions: RenditionSizes,
 }
 
 impl UploadMediaUseCase {
@@ -26,66 +89,224 @@ impl UploadMediaUseCase {
         repo: Arc<dyn MediaRepository>,
         ai: Arc<dyn AiProcessor>,
         hasher: Arc<dyn HashGenerator>,
-        storage_path: PathBuf,
-        thumbnail_path: PathBuf,
+        storage: Arc<dyn MediaStorage>,
+        thumbnails: Arc<dyn MediaStorage>,
+        duplicate_threshold: u32,
     ) -> Self {
-        Self { repo, ai, hasher, storage_path, thumbnail_path }
+        Self {
+            repo,
+            ai,
+            hasher,
+            storage,
+            thumbnails,
+            duplicate_threshold,
+            pair_raw_with_jpeg: false,
+            renditions: RenditionSizes::default(),
+        }
     }
 
-    pub async fn execute(&self, filename: String, data: &[u8]) -> Result<MediaItem, DomainError> {
-        let extension = std::path::Path::new(&filename)
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .unwrap_or("bin")
-            .to_lowercase();
+    /// Generate these thumbnail and preview sizes instead of the default ones.
+    pub fn with_renditions(mut self, renditions: RenditionSizes) -> Self {
+        self.renditions = renditions;
+        self
+    }
 
-        // Reject files with unlisted extensions
-        if !ALLOWED_EXTENSIONS.contains(&extension.as_str()) {
-            return Err(DomainError::Io(format!("File type not allowed: .{}", extension)));
-        }
+    /// Attach an uploaded RAW to the item of the JPEG with the same name uploaded shortly
+    /// before, or the other way round, instead of storing them as two items.
+    pub fn with_raw_jpeg_pairing(mut self) -> Self {
+        self.pair_raw_with_jpeg = true;
+        self
+    }
 
-        let is_video = matches!(extension.as_str(), "mp4" | "mov" | "avi" | "mkv" | "webm");
-        let size_bytes = data.len() as i64;
+    /// Ingest the file at `path`, copying it into storage.
+    pub async fn execute(&self, filename: String, path: &Path) -> Result<MediaItem, DomainError> {
+        self.ingest(filename, path, false).await
+    }
 
-        // Process media using the extracted processor logic
-        let processed = processor::process_media(&filename, data, self.hasher.as_ref()).await?;
+    /// Like `execute`, but references the original at `source` (an absolute path) instead
+    /// of copying it into storage. Only the thumbnail is written.
+    pub async fn execute_in_place(&self, source: &Path) -> Result<MediaItem, DomainError> {
+        if !source.is_absolute() {
+            return Err(DomainError::Io("In-place originals need an absolute path".to_string()));
+        }
+        let filename = source
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.ingest(filename, source, true).await
+    }
 
-        // Check for duplicates
-        if processed.phash != "no_hash" && self.repo.exists_by_phash(&processed.phash)? {
-            return Err(DomainError::DuplicateMedia);
+    /// Move the received file at `path` into storage for `owner` and record it as
+    /// `processing`, leaving thumbnail, hashes and embedding to `process`. `content_hash`
+    /// was computed while the file was received. Only exact duplicates are rejected here,
+    /// in which case the file stays where it is. With RAW+JPEG pairing, the other half
+    /// of an existing item is stored as its companion instead.
+    pub async fn accept(
+        &self,
+        owner: Option<Uuid>,
+        filename: String,
+        path: &Path,
+        content_hash: String,
+    ) -> Result<Accepted, DomainError> {
+        let (extension, media_type) = classify(&filename)?;
+        if let Some(existing) = self.repo.find_by_content_hash(&LibraryScope::for_owner(owner), &content_hash)? {
+            return Err(DomainError::DuplicateMedia(existing));
         }
+        let size_bytes = file_size(path).await?;
 
-        // Extract features
-        let features = processed.feature_input.and_then(|bytes: Vec<u8>| self.ai.extract_features(&bytes).ok());
+        if let Some(partner) = self.find_partner(owner, &filename, &extension)? {
+            let companion = MediaCompanion {
+                media_id: partner.id,
+                filename: storage_key(Uuid::new_v4(), &extension),
+                original_filename: filename,
+                size_bytes,
+                content_hash,
+            };
+            self.storage.move_file(&companion.filename, path).await?;
+            if let Err(e) = self.repo.add_companion(&companion) {
+                let _ = self.storage.delete(&companion.filename).await;
+                return Err(e);
+            }
+            return Ok(Accepted::Paired(partner));
+        }
 
-        // Save to disk
         let id = Uuid::new_v4();
-        let id_str = id.to_string();
-        let (p1, p2) = (&id_str[0..2], &id_str[2..4]);
+        let key = storage_key(id, &extension);
+        self.storage.move_file(&key, path).await?;
 
-        let sub_path = self.storage_path.join(p1).join(p2);
-        fs::create_dir_all(&sub_path).await
-            .map_err(|e: std::io::Error| DomainError::Io(e.to_string()))?;
+        let now = Utc::now();
+        let media = MediaItem {
+            id,
+            filename: key,
+            original_date: parse_date_from_filename(&filename).unwrap_or(now),
+            original_filename: filename,
+            media_type,
+            phash: "no_hash".to_string(),
+            content_hash: Some(content_hash),
+            owner_id: owner,
+            uploaded_at: now,
+            width: None,
+            height: None,
+            size_bytes,
+            exif_json: None,
+            is_favorite: false,
+            tags: vec![],
+            processing: true,
+        };
 
-        let thumb_sub_path = self.thumbnail_path.join(p1).join(p2);
-        fs::create_dir_all(&thumb_sub_path).await
-            .map_err(|e: std::io::Error| DomainError::Io(e.to_string()))?;
+        if let Err(e) = self.repo.save_metadata_and_vector(&media, None) {
+            let _ = self.storage.delete(&media.filename).await;
+            return Err(e);
+        }
+        Ok(Accepted::Created(media))
+    }
 
-        let file_name = format!("{}.{}", id, extension);
-        let file_path = sub_path.join(&file_name);
+    /// The item an upload named `filename` pairs with: the JPEG of a RAW file or the RAW
+    /// file of a JPEG, of the same owner and with the same name otherwise.
+    fn find_partner(
+        &self,
+        owner: Option<Uuid>,
+        filename: &str,
+        extension: &str,
+    ) -> Result<Option<MediaItem>, DomainError> {
+        if !self.pair_raw_with_jpeg {
+            return Ok(None);
+        }
+        let partner_extensions: &[&str] = match extension {
+            "jpg" | "jpeg" => raw::RAW_EXTENSIONS,
+            ext if raw::is_raw_extension(ext) => &["jpg", "jpeg"],
+            _ => return Ok(None),
+        };
+        let Some(stem) = Path::new(filename).file_stem().and_then(|s| s.to_str()) else {
+            return Ok(None);
+        };
+        let since = Utc::now() - chrono::Duration::minutes(PAIRING_WINDOW_MINUTES);
+        match self.repo.find_companion_partner(owner, stem, partner_extensions, since)? {
+            Some(id) => self.repo.find_by_id(id),
+            None => Ok(None),
+        }
+    }
+
+    /// Finish an item stored by `accept`, reporting each step to `on_stage`. Returns
+    /// `None` if it was processed already or deleted in the meantime. A near-duplicate of
+    /// an existing item is removed again, original included, and reported as
+    /// `DuplicateMedia`.
+    pub async fn process(&self, id: Uuid, on_stage: UploadStageSink<'_>) -> Result<Option<MediaItem>, DomainError> {
+        let Some(mut media) = self.repo.find_by_id(id)? else {
+            return Ok(None);
+        };
+        if !media.processing {
+            return Ok(None);
+        }
 
-        fs::write(&file_path, data).await
-            .map_err(|e: std::io::Error| DomainError::Io(e.to_string()))?;
+        let original = local_original(self.storage.as_ref(), &media.filename).await?;
+        on_stage(if media.media_type == "video" { UploadStage::ExtractingFrames } else { UploadStage::Hashing });
+        let analyzed = self
+            .analyze(media.owner_id, &media.original_filename, original.path(), &media.media_type, on_stage)
+            .await;
+        drop(original);
+        let (processed, features) = match analyzed {
+            Err(DomainError::DuplicateMedia(existing)) => {
+                // A companion may have been paired with it in the meantime
+                let companion = self.repo.find_companion(id)?;
+                self.repo.delete(id)?;
+                self.storage.delete(&media.filename).await?;
+                if let Some(companion) = companion {
+                    self.storage.delete(&companion.filename).await?;
+                }
+                return Err(DomainError::DuplicateMedia(existing));
+            }
+            result => result?,
+        };
+        self.store_renditions(id, processed.renditions.as_ref()).await?;
 
-        if !processed.thumbnail_bytes.is_empty() {
-            let thumb_name = format!("{}.jpg", id);
-            fs::write(thumb_sub_path.join(thumb_name), &processed.thumbnail_bytes).await
-                .map_err(|e: std::io::Error| DomainError::Io(e.to_string()))?;
+        media.phash = processed.phash;
+        media.width = processed.width;
+        media.height = processed.height;
+        media.exif_json = processed.exif_json;
+        // The provisional date already fell back to the filename, then the upload time
+        if let Some(date) = processed.original_date {
+            media.original_date = date;
         }
+        media.processing = false;
+
+        self.repo.update_media_and_vector(&media, features.as_deref())?;
+        on_stage(UploadStage::Saved);
+        Ok(Some(media))
+    }
 
-        let saved_filename = format!("{}/{}/{}", p1, p2, file_name);
+    /// Give up on processing an item whose job failed for good: it is listed like any
+    /// other item without a hash, so the thumbnail repair can take it from there.
+    /// Returns the item if it was still being processed.
+    pub fn abandon(&self, id: Uuid) -> Result<Option<MediaItem>, DomainError> {
+        let Some(mut media) = self.repo.find_by_id(id)?.filter(|media| media.processing) else {
+            return Ok(None);
+        };
+        media.processing = false;
+        self.repo.update_media_and_vector(&media, None)?;
+        Ok(Some(media))
+    }
 
-        let media_type = if is_video { "video" } else { "image" }.to_string();
+    async fn ingest(
+        &self,
+        filename: String,
+        path: &Path,
+        in_place: bool,
+    ) -> Result<MediaItem, DomainError> {
+        let (extension, media_type) = classify(&filename)?;
+        let size_bytes = file_size(path).await?;
+        let content_hash = self.check_content_hash(None, path).await?;
+        let (processed, features) = self.analyze(None, &filename, path, &media_type, &|_| {}).await?;
+
+        // Save to storage
+        let id = Uuid::new_v4();
+        let saved_filename = if in_place {
+            path.to_string_lossy().to_string()
+        } else {
+            let key = storage_key(id, &extension);
+            self.storage.put_file(&key, path).await?;
+            key
+        };
 
         let now = Utc::now();
 
@@ -100,6 +321,8 @@ impl UploadMediaUseCase {
             original_filename: filename,
             media_type,
             phash: processed.phash,
+            content_hash: Some(content_hash),
+            owner_id: None,
             uploaded_at: now,
             original_date,
             width: processed.width,
@@ -108,12 +331,90 @@ impl UploadMediaUseCase {
             exif_json: processed.exif_json,
             is_favorite: false,
             tags: vec![],
+            processing: false,
         };
 
         self.repo.save_metadata_and_vector(&media, features.as_deref())?;
+        // Recorded against the saved item
+        self.store_renditions(id, processed.renditions.as_ref()).await?;
 
         Ok(media)
     }
+
+    /// SHA-256 of the file at `path`, unless the exact same bytes are stored already
+    /// among what `owner` can see. This is cheap and done before any other processing.
+    async fn check_content_hash(&self, owner: Option<Uuid>, path: &Path) -> Result<String, DomainError> {
+        let content_hash = hash_file(path).await?;
+        if let Some(existing) = self.repo.find_by_content_hash(&LibraryScope::for_owner(owner), &content_hash)? {
+            return Err(DomainError::DuplicateMedia(existing));
+        }
+        Ok(content_hash)
+    }
+
+    /// Thumbnail, perceptual hash and EXIF, plus the embedding. Fails with
+    /// `DuplicateMedia` if an item of the same type that `owner` can see looks the same.
+    async fn analyze(
+        &self,
+        owner: Option<Uuid>,
+        filename: &str,
+        path: &Path,
+        media_type: &str,
+        on_stage: UploadStageSink<'_>,
+    ) -> Result<(processor::ProcessedMedia, Option<Vec<f32>>), DomainError> {
+        let mut processed = processor::process_media(filename, path, self.hasher.as_ref(), &self.renditions).await?;
+
+        if processed.phash != "no_hash" {
+            let scope = LibraryScope::for_owner(owner);
+            if let Some(existing) = self.repo.find_near_duplicate(&scope, &processed.phash, media_type, self.duplicate_threshold)? {
+                return Err(DomainError::DuplicateMedia(existing));
+            }
+        }
+
+        let features = processed.feature_input.take().and_then(|bytes| {
+            on_stage(UploadStage::Embedding);
+            self.ai.extract_features(&bytes).ok()
+        });
+        Ok((processed, features))
+    }
+
+    async fn store_renditions(&self, id: Uuid, renditions: Option<&Renditions>) -> Result<(), DomainError> {
+        match renditions {
+            Some(renditions) => {
+                store_renditions(self.repo.as_ref(), self.thumbnails.as_ref(), id, renditions).await
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Lowercase extension and media type of an upload, rejecting unlisted extensions.
+fn classify(filename: &str) -> Result<(String, String), DomainError> {
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("bin")
+        .to_lowercase();
+
+    if !is_allowed_extension(&extension) {
+        return Err(DomainError::Io(format!("File type not allowed: .{}", extension)));
+    }
+
+    let is_video = matches!(extension.as_str(), "mp4" | "mov" | "avi" | "mkv" | "webm");
+    let media_type = if is_video { "video" } else { "image" }.to_string();
+    Ok((extension, media_type))
+}
+
+async fn file_size(path: &Path) -> Result<i64, DomainError> {
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| DomainError::Io(format!("Failed to read {}: {}", path.display(), e)))?;
+    Ok(metadata.len() as i64)
+}
+
+/// Storage key of an item's file: `ab/cd/<uuid>.<extension>`.
+fn storage_key(id: Uuid, extension: &str) -> String {
+    let id_str = id.to_string();
+    format!("{}/{}/{}.{}", &id_str[0..2], &id_str[2..4], id, extension)
 }
 
 /// Try to extract a date from the filename using common patterns:
@@ -163,6 +464,151 @@ fn parse_date_from_filename(filename: &str) -> Option<DateTime<Utc>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::application::renditions::{preview_key, ThumbnailFormat};
+    use crate::infrastructure::{LocalStorage, SqliteRepository, TestDb};
+    use std::sync::Mutex;
+
+    struct FixedAi;
+
+    impl AiProcessor for FixedAi {
+        fn extract_features(&self, _image_bytes: &[u8]) -> Result<Vec<f32>, DomainError> {
+            Ok(vec![0.1; 1280])
+        }
+    }
+
+    /// Every image looks the same, so any two of them are near-duplicates.
+    struct FixedHash;
+
+    impl HashGenerator for FixedHash {
+        fn generate_phash(&self, _image_bytes: &[u8]) -> Result<String, DomainError> {
+            Ok("mock_phash".to_string())
+        }
+    }
+
+    /// A received upload: a PNG file in `dir` and its content hash.
+    fn png(dir: &Path, shade: u8) -> (std::path::PathBuf, String) {
+        let mut bytes = Vec::new();
+        image::RgbImage::from_pixel(8, 6, image::Rgb([shade, shade, shade]))
+            .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .unwrap();
+        let path = dir.join(format!("upload_{}.tmp", Uuid::new_v4()));
+        std::fs::write(&path, &bytes).unwrap();
+        (path, crate::application::content_hash(&bytes))
+    }
+
+    #[tokio::test]
+    async fn accepts_uploads_and_processes_them_later() {
+        let db = TestDb::new("upload_accept_test");
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let storage: Arc<dyn MediaStorage> = Arc::new(LocalStorage::new(dir.path().join("uploads")));
+        let thumbnails: Arc<dyn MediaStorage> = Arc::new(LocalStorage::new(dir.path().join("thumbnails")));
+        let upload = UploadMediaUseCase::new(
+            repo.clone(),
+            Arc::new(FixedAi),
+            Arc::new(FixedHash),
+            storage.clone(),
+            thumbnails.clone(),
+            0,
+        );
+
+        let (path, hash) = png(dir.path(), 10);
+        let first = upload.accept(None, "IMG_20240115_134530.png".to_string(), &path, hash).await.unwrap().into_media();
+        // The received file is moved into storage
+        assert!(!path.exists());
+        assert!(storage.exists(&first.filename).await.unwrap());
+        assert!(first.processing);
+        assert_eq!(first.width, None);
+        assert_eq!(first.original_date.to_rfc3339(), "2024-01-15T13:45:30+00:00");
+        assert!(repo.find_by_id(first.id).unwrap().unwrap().processing);
+        // The same bytes again are rejected straight away
+        let (path, hash) = png(dir.path(), 10);
+        assert!(matches!(
+            upload.accept(None, "copy.png".to_string(), &path, hash).await,
+            Err(DomainError::DuplicateMedia(id)) if id == first.id
+        ));
+        assert!(path.exists());
+
+        let stages = Mutex::new(Vec::new());
+        let record = |stage| stages.lock().unwrap().push(stage);
+        let done = upload.process(first.id, &record).await.unwrap().unwrap();
+        assert_eq!(
+            *stages.lock().unwrap(),
+            vec![UploadStage::Hashing, UploadStage::Embedding, UploadStage::Saved]
+        );
+        assert!(!done.processing);
+        assert_eq!((done.width, done.height), (Some(8), Some(6)));
+        assert!(thumbnails.exists(&storage_key(first.id, "jpg")).await.unwrap());
+        assert!(thumbnails.exists(&preview_key(first.id, 720, ThumbnailFormat::Jpeg)).await.unwrap());
+        assert!(repo.get_embedding(first.id).unwrap().is_some());
+        assert!(upload.process(first.id, &|_| {}).await.unwrap().is_none());
+
+        // Different bytes that look the same are only caught once processed
+        let (path, hash) = png(dir.path(), 200);
+        let second = upload.accept(None, "other.png".to_string(), &path, hash).await.unwrap().into_media();
+        assert!(matches!(
+            upload.process(second.id, &|_| {}).await,
+            Err(DomainError::DuplicateMedia(id)) if id == first.id
+        ));
+        assert!(repo.find_by_id(second.id).unwrap().is_none());
+        assert!(!storage.exists(&second.filename).await.unwrap());
+
+        // Someone else's private copy is neither reported nor rejected
+        let (path, hash) = png(dir.path(), 60);
+        let private = upload.accept(Some(Uuid::new_v4()), "mine.png".to_string(), &path, hash).await.unwrap();
+        let (path, hash) = png(dir.path(), 60);
+        let other = upload.accept(Some(Uuid::new_v4()), "mine.png".to_string(), &path, hash).await.unwrap();
+        assert_ne!(other.into_media().id, private.into_media().id);
+    }
+
+    #[tokio::test]
+    async fn pairs_raw_files_with_their_jpeg() {
+        let db = TestDb::new("upload_raw_pairing_test");
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let storage: Arc<dyn MediaStorage> = Arc::new(LocalStorage::new(dir.path().join("uploads")));
+        let upload = UploadMediaUseCase::new(
+            repo.clone(),
+            Arc::new(FixedAi),
+            Arc::new(FixedHash),
+            storage.clone(),
+            storage.clone(),
+            0,
+        )
+        .with_raw_jpeg_pairing();
+        let owner = Some(Uuid::new_v4());
+        let raw_file = |contents: &[u8]| {
+            let path = dir.path().join(format!("upload_{}.tmp", Uuid::new_v4()));
+            std::fs::write(&path, contents).unwrap();
+            (path, crate::application::content_hash(contents))
+        };
+
+        let (path, hash) = png(dir.path(), 10);
+        let Accepted::Created(jpeg) = upload.accept(owner, "DSC_0042.JPG".to_string(), &path, hash).await.unwrap() else {
+            panic!("the first half becomes an item");
+        };
+        // Someone else's RAW file of the same name is an item of its own
+        let (path, hash) = raw_file(b"other raw");
+        assert!(matches!(
+            upload.accept(None, "DSC_0042.NEF".to_string(), &path, hash).await.unwrap(),
+            Accepted::Created(_)
+        ));
+
+        let (path, hash) = raw_file(b"raw sensor data");
+        let paired = upload.accept(owner, "DSC_0042.nef".to_string(), &path, hash).await.unwrap();
+        assert!(matches!(&paired, Accepted::Paired(media) if media.id == jpeg.id));
+        let companion = repo.find_companion(jpeg.id).unwrap().unwrap();
+        assert_eq!(companion.original_filename, "DSC_0042.nef");
+        assert_eq!(companion.size_bytes, 15);
+        assert!(storage.exists(&companion.filename).await.unwrap());
+        assert!(!path.exists());
+        // The same RAW again is a duplicate of the pair
+        let (path, hash) = raw_file(b"raw sensor data");
+        assert!(matches!(
+            upload.accept(owner, "DSC_0042.NEF".to_string(), &path, hash).await,
+            Err(DomainError::DuplicateMedia(id)) if id == jpeg.id
+        ));
+    }
 
     #[test]
     fn allowed_extensions_accepted() {
@@ -175,6 +621,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn raw_extensions_accepted() {
+        for ext in raw::RAW_EXTENSIONS {
+            assert!(is_allowed_extension(ext), "RAW extension {} should be allowed", ext);
+        }
+    }
+
     #[test]
     fn dangerous_extensions_rejected() {
         let dangerous = ["html", "htm", "svg", "exe", "js", "php", "sh", "bat", "cmd"];
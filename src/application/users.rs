@@ -0,0 +1,196 @@
+use crate::domain::{DomainError, MediaRepository, Role, User};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::Utc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Hash a password with Argon2id and a random salt (PHC string format).
+pub fn hash_password(password: &str) -> Result<String, DomainError> {
+    let salt = SaltString::generate(&mut rand::rngs::OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| DomainError::Hashing(e.to_string()))
+}
+
+/// Check a password against a stored PHC hash. Malformed hashes never match.
+pub fn verify_password(hash: &str, candidate: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(candidate.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// Manages user accounts. Every change keeps at least one admin around.
+pub struct UsersUseCase {
+    repo: Arc<dyn MediaRepository>,
+}
+
+impl UsersUseCase {
+    pub fn new(repo: Arc<dyn MediaRepository>) -> Self {
+        Self { repo }
+    }
+
+    /// Returns the user if the username exists and the password matches.
+    pub fn authenticate(&self, username: &str, password: &str) -> Result<Option<User>, DomainError> {
+        Ok(self
+            .repo
+            .find_user_by_username(username.trim())?
+            .filter(|(_, hash)| verify_password(hash, password))
+            .map(|(user, _)| user))
+    }
+
+    pub fn list(&self) -> Result<Vec<User>, DomainError> {
+        self.repo.list_users()
+    }
+
+    /// Account for a username vouched for by an authenticating reverse proxy. Unknown
+    /// names get a new account with `role` and no password.
+    pub fn find_or_create_external(&self, username: &str, role: Role) -> Result<User, DomainError> {
+        let username = username.trim();
+        if username.is_empty() {
+            return Err(DomainError::Io("Username cannot be empty".to_string()));
+        }
+        if let Some((user, _)) = self.repo.find_user_by_username(username)? {
+            return Ok(user);
+        }
+        let user = User {
+            id: Uuid::new_v4(),
+            username: username.to_string(),
+            role,
+            created_at: Utc::now(),
+        };
+        // An empty hash never verifies, so password login stays impossible
+        if let Err(e) = self.repo.create_user(&user, "") {
+            // A concurrent request may have created the account a moment ago
+            return self.repo.find_user_by_username(username)?.map(|(user, _)| user).ok_or(e);
+        }
+        Ok(user)
+    }
+
+    pub fn create(&self, username: &str, password: &str, role: Role) -> Result<User, DomainError> {
+        let username = username.trim();
+        if username.is_empty() {
+            return Err(DomainError::Io("Username cannot be empty".to_string()));
+        }
+        if password.is_empty() {
+            return Err(DomainError::Io("Password cannot be empty".to_string()));
+        }
+        if self.repo.find_user_by_username(username)?.is_some() {
+            return Err(DomainError::Io("Username already taken".to_string()));
+        }
+
+        let user = User {
+            id: Uuid::new_v4(),
+            username: username.to_string(),
+            role,
+            created_at: Utc::now(),
+        };
+        self.repo.create_user(&user, &hash_password(password)?)?;
+        Ok(user)
+    }
+
+    pub fn update(
+        &self,
+        id: Uuid,
+        role: Option<Role>,
+        password: Option<&str>,
+    ) -> Result<User, DomainError> {
+        let user = self.repo.find_user_by_id(id)?.ok_or(DomainError::NotFound)?;
+        if let Some(role) = role {
+            if user.role == Role::Admin && role != Role::Admin {
+                self.ensure_other_admin(id)?;
+            }
+            self.repo.update_user_role(id, role)?;
+        }
+        if let Some(password) = password {
+            if password.is_empty() {
+                return Err(DomainError::Io("Password cannot be empty".to_string()));
+            }
+            self.repo.update_user_password(id, &hash_password(password)?)?;
+            // A new password signs the user out on every device
+            self.repo.delete_user_sessions(id)?;
+        }
+        self.repo.find_user_by_id(id)?.ok_or(DomainError::NotFound)
+    }
+
+    /// Delete a user. Their media and folders stay, in the shared library.
+    pub fn delete(&self, id: Uuid) -> Result<(), DomainError> {
+        let user = self.repo.find_user_by_id(id)?.ok_or(DomainError::NotFound)?;
+        if user.role == Role::Admin {
+            self.ensure_other_admin(id)?;
+        }
+        self.repo.delete_user(id)
+    }
+
+    /// Create the first admin from the legacy single-password setup. Existing media
+    /// stays shared and the anonymous favorites become the admin's. Does nothing once
+    /// any user exists.
+    pub fn bootstrap_admin(&self, username: &str, password: &str) -> Result<Option<User>, DomainError> {
+        if self.repo.count_users()? > 0 {
+            return Ok(None);
+        }
+        let admin = self.create(username, password, Role::Admin)?;
+        self.repo.claim_anonymous_favorites(admin.id)?;
+        Ok(Some(admin))
+    }
+
+    fn ensure_other_admin(&self, id: Uuid) -> Result<(), DomainError> {
+        let has_other = self
+            .repo
+            .list_users()?
+            .iter()
+            .any(|u| u.role == Role::Admin && u.id != id);
+        if !has_other {
+            return Err(DomainError::Io("Users need at least one admin".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::{SqliteRepository, TestDb};
+
+    #[test]
+    fn password_hash_roundtrip() {
+        let hash = hash_password("hunter2").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password(&hash, "hunter2"));
+        assert!(!verify_password(&hash, "hunter3"));
+        assert!(!verify_password("not a hash", "hunter2"));
+    }
+
+    #[test]
+    fn bootstrap_and_last_admin() {
+        let db = TestDb::new("users_use_case_test");
+        let users = UsersUseCase::new(Arc::new(SqliteRepository::new(&db.path).unwrap()));
+
+        let admin = users.bootstrap_admin("admin", "secret").unwrap().unwrap();
+        assert!(users.bootstrap_admin("other", "secret").unwrap().is_none());
+        assert_eq!(users.authenticate("Admin", "secret").unwrap().unwrap().id, admin.id);
+        assert!(users.authenticate("admin", "wrong").unwrap().is_none());
+        assert!(users.authenticate("nobody", "secret").unwrap().is_none());
+
+        // The only admin can be neither demoted nor deleted
+        assert!(users.update(admin.id, Some(Role::Member), None).is_err());
+        assert!(users.delete(admin.id).is_err());
+        assert!(users.create("ADMIN", "x", Role::Viewer).is_err());
+
+        let member = users.create("kim", "pw", Role::Member).unwrap();
+        let sessions = super::super::SessionsUseCase::new(users.repo.clone(), 30);
+        let (_, token) = sessions.create(member.id, None, None).unwrap();
+        users.update(member.id, Some(Role::Admin), Some("new")).unwrap();
+        // Changing the password signs the user out
+        assert!(sessions.authenticate(&token).unwrap().is_none());
+        assert!(users.authenticate("kim", "new").unwrap().is_some());
+        users.update(admin.id, Some(Role::Viewer), None).unwrap();
+        users.delete(admin.id).unwrap();
+        assert_eq!(users.list().unwrap().len(), 1);
+    }
+}
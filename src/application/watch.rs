@@ -0,0 +1,200 @@
+use crate::domain::{DomainError, MediaItem};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::fs;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use super::import::collect_files;
+use super::upload::UploadMediaUseCase;
+
+/// Size and modification time of a file as seen by the previous scan.
+type FileStamp = (u64, Option<SystemTime>);
+
+/// Watches an inbox directory and ingests files dropped into it (e.g. by a phone sync app).
+///
+/// A file is only picked up once its size and modification time are unchanged between two
+/// scans, so partially written files are left alone. Ingested files are removed from the
+/// inbox; duplicates and files that cannot be processed are moved to the reject directory.
+pub struct WatchFolderUseCase {
+    upload_use_case: Arc<UploadMediaUseCase>,
+    watch_dir: PathBuf,
+    reject_dir: PathBuf,
+    seen: Mutex<HashMap<PathBuf, FileStamp>>,
+}
+
+impl WatchFolderUseCase {
+    pub fn new(upload_use_case: Arc<UploadMediaUseCase>, watch_dir: PathBuf, reject_dir: PathBuf) -> Self {
+        Self {
+            upload_use_case,
+            watch_dir,
+            reject_dir,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Scan the inbox once and return the media items that were created.
+    pub async fn scan(&self) -> Result<Vec<MediaItem>, DomainError> {
+        let root = self.watch_dir.clone();
+        let files = tokio::task::spawn_blocking(move || collect_files(&root))
+            .await
+            .map_err(|e| DomainError::Io(e.to_string()))?;
+
+        let mut seen = self.seen.lock().await;
+        let mut current = HashMap::with_capacity(files.len());
+        let mut ready = Vec::new();
+
+        for path in files {
+            let Ok(meta) = fs::metadata(&path).await else {
+                continue;
+            };
+            let stamp = (meta.len(), meta.modified().ok());
+            if seen.get(&path) == Some(&stamp) {
+                ready.push(path);
+            } else {
+                current.insert(path, stamp);
+            }
+        }
+        // Forget files that disappeared or were picked up; keep the rest for the next scan
+        *seen = current;
+        drop(seen);
+
+        let mut created = Vec::new();
+        for path in ready {
+            if let Some(media) = self.ingest(&path).await {
+                created.push(media);
+            }
+        }
+        Ok(created)
+    }
+
+    async fn ingest(&self, path: &Path) -> Option<MediaItem> {
+        let filename = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        match self.upload_use_case.execute(filename, path).await {
+            Ok(media) => {
+                info!("Watch: ingested {}", path.display());
+                if let Err(e) = fs::remove_file(path).await {
+                    warn!("Watch: failed to remove {}: {}", path.display(), e);
+                }
+                Some(media)
+            }
+            Err(DomainError::DuplicateMedia(existing)) => {
+                info!("Watch: {} duplicates {}, rejecting", path.display(), existing);
+                self.reject(path).await;
+                None
+            }
+            Err(e) => {
+                warn!("Watch: failed to ingest {}: {}", path.display(), e);
+                self.reject(path).await;
+                None
+            }
+        }
+    }
+
+    /// Move a file into the reject directory, keeping its path relative to the inbox.
+    async fn reject(&self, path: &Path) {
+        let relative = path.strip_prefix(&self.watch_dir).unwrap_or(path);
+        let mut target = self.reject_dir.join(relative);
+        if let Some(parent) = target.parent() {
+            if let Err(e) = fs::create_dir_all(parent).await {
+                warn!("Watch: cannot create {}: {}", parent.display(), e);
+                return;
+            }
+        }
+        if fs::try_exists(&target).await.unwrap_or(false) {
+            let name = target
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            target.set_file_name(format!("{}_{}", chrono::Utc::now().timestamp_millis(), name));
+        }
+
+        if fs::rename(path, &target).await.is_ok() {
+            return;
+        }
+        // Rename fails across filesystems; fall back to copy + delete
+        match fs::copy(path, &target).await {
+            Ok(_) => {
+                if let Err(e) = fs::remove_file(path).await {
+                    warn!("Watch: failed to remove {}: {}", path.display(), e);
+                }
+            }
+            Err(e) => warn!("Watch: failed to move {} to {}: {}", path.display(), target.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{AiProcessor, HashGenerator};
+    use crate::infrastructure::{LocalStorage, SqliteRepository, TestDb};
+
+    struct MockAiProcessor;
+    impl AiProcessor for MockAiProcessor {
+        fn extract_features(&self, _image_data: &[u8]) -> Result<Vec<f32>, DomainError> {
+            Ok(vec![0.1; 1280])
+        }
+    }
+
+    /// Hashes by content so identical files collide and different ones don't.
+    struct ContentHashGenerator;
+    impl HashGenerator for ContentHashGenerator {
+        fn generate_phash(&self, image_data: &[u8]) -> Result<String, DomainError> {
+            Ok(format!("{:x}", image_data.iter().map(|b| *b as u64).sum::<u64>()))
+        }
+    }
+
+    fn png_bytes(r: u8) -> Vec<u8> {
+        let img = image::RgbImage::from_pixel(4, 4, image::Rgb([r, 0, 0]));
+        let mut buf = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut buf, image::ImageFormat::Png).unwrap();
+        buf.into_inner()
+    }
+
+    #[tokio::test]
+    async fn scan_ingests_settled_files_and_rejects_duplicates() {
+        let db = TestDb::new("watch_folder_test");
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let temp = tempfile::tempdir().unwrap();
+        let inbox = temp.path().join("inbox");
+        let rejects = inbox.join(".rejected");
+        std::fs::create_dir_all(inbox.join("camera")).unwrap();
+
+        let upload = Arc::new(UploadMediaUseCase::new(
+            repo,
+            Arc::new(MockAiProcessor),
+            Arc::new(ContentHashGenerator),
+            Arc::new(LocalStorage::new(temp.path().join("uploads"))),
+            Arc::new(LocalStorage::new(temp.path().join("thumbs"))),
+            0,
+        ));
+        let watcher = WatchFolderUseCase::new(upload, inbox.clone(), rejects.clone());
+
+        std::fs::write(inbox.join("a.png"), png_bytes(10)).unwrap();
+        std::fs::write(inbox.join("notes.txt"), b"ignored").unwrap();
+
+        // First sighting only records the file
+        assert!(watcher.scan().await.unwrap().is_empty());
+        assert!(inbox.join("a.png").exists());
+
+        let created = watcher.scan().await.unwrap();
+        assert_eq!(created.len(), 1);
+        assert_eq!(created[0].original_filename, "a.png");
+        assert!(!inbox.join("a.png").exists());
+        assert!(inbox.join("notes.txt").exists());
+
+        // Same content again is a duplicate and lands in the reject dir
+        std::fs::write(inbox.join("camera/copy.png"), png_bytes(10)).unwrap();
+        watcher.scan().await.unwrap();
+        assert!(watcher.scan().await.unwrap().is_empty());
+        assert!(!inbox.join("camera/copy.png").exists());
+        assert!(rejects.join("camera/copy.png").exists());
+    }
+}
@@ -1,5 +1,7 @@
 pub mod models;
+pub mod phash;
 pub mod ports;
 
 pub use models::*;
+pub use phash::*;
 pub use ports::*;
@@ -1,5 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +17,13 @@ pub struct MediaItem {
     pub original_filename: String,
     pub media_type: String,
     pub phash: String,
+    /// Hex SHA-256 of the original bytes. `None` for items stored before it was tracked
+    /// until the integrity scan backfills it.
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    /// Uploading user. `None` for the shared library (uploads without auth, imports).
+    #[serde(default)]
+    pub owner_id: Option<Uuid>,
     pub uploaded_at: DateTime<Utc>,
     pub original_date: DateTime<Utc>,
     pub width: Option<u32>,
@@ -26,6 +34,16 @@ pub struct MediaItem {
     pub is_favorite: bool,
     #[serde(default)]
     pub tags: Vec<TagDetail>,
+    /// Uploaded and stored, but thumbnail, hashes, EXIF and embedding are still being
+    /// computed in the background. Width, height and `original_date` are provisional.
+    #[serde(default)]
+    pub processing: bool,
+}
+
+/// Stored filenames are relative to the upload directory (`ab/cd/<uuid>.jpg`), except for
+/// originals referenced in place (library mode), which are stored as absolute paths.
+pub fn is_external_filename(filename: &str) -> bool {
+    Path::new(filename).is_absolute()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +52,8 @@ pub struct MediaSummary {
     pub filename: String,
     pub original_filename: String,
     pub media_type: String,
+    #[serde(default)]
+    pub owner_id: Option<Uuid>,
     pub uploaded_at: DateTime<Utc>,
     pub original_date: DateTime<Utc>,
     pub size_bytes: i64,
@@ -41,6 +61,26 @@ pub struct MediaSummary {
     pub is_favorite: bool,
     #[serde(default)]
     pub tags: Vec<TagDetail>,
+    #[serde(default)]
+    pub processing: bool,
+}
+
+impl From<MediaItem> for MediaSummary {
+    fn from(item: MediaItem) -> Self {
+        Self {
+            id: item.id,
+            filename: item.filename,
+            original_filename: item.original_filename,
+            media_type: item.media_type,
+            owner_id: item.owner_id,
+            uploaded_at: item.uploaded_at,
+            original_date: item.original_date,
+            size_bytes: item.size_bytes,
+            is_favorite: item.is_favorite,
+            tags: item.tags,
+            processing: item.processing,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,6 +95,9 @@ pub struct MediaCounts {
 pub struct Folder {
     pub id: Uuid,
     pub name: String,
+    /// Creating user. `None` for shared folders.
+    #[serde(default)]
+    pub owner_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub item_count: i64,
     pub sort_order: i64,
@@ -66,6 +109,333 @@ pub struct MediaGroup {
     pub items: Vec<MediaSummary>,
 }
 
+/// A media item in the trash, with the time it was deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedMedia {
+    #[serde(flatten)]
+    pub media: MediaSummary,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// A set of near-duplicate items. `items` is ranked best copy first and `best` is its id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateCluster {
+    pub id: usize,
+    pub best: Uuid,
+    pub items: Vec<MediaItem>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Everything, including user management and library-wide maintenance.
+    Admin,
+    /// Uploads and edits their own media and folders.
+    Member,
+    /// Read-only access.
+    Viewer,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Member => "member",
+            Role::Viewer => "viewer",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Role> {
+        match value {
+            "admin" => Some(Role::Admin),
+            "member" => Some(Role::Member),
+            "viewer" => Some(Role::Viewer),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    pub id: Uuid,
+    pub username: String,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Who an external identity provider says signed in, taken from a verified ID token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdentityClaims {
+    pub issuer: String,
+    /// Stable user id at the provider (`sub`).
+    pub subject: String,
+    /// `preferred_username`, if the provider sent one.
+    pub username: Option<String>,
+    pub email: Option<String>,
+    /// Group names from the `groups` claim.
+    pub groups: Vec<String>,
+}
+
+/// A signed-in browser or device. Only a hash of its token is ever stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+/// What an API token may do, on top of the limits of its owner's role.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenScope {
+    /// Browse, search and download.
+    Read,
+    /// Read access plus uploading new media.
+    Upload,
+    /// Everything the owner's role allows.
+    Admin,
+}
+
+impl TokenScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::Upload => "upload",
+            TokenScope::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<TokenScope> {
+        match value {
+            "read" => Some(TokenScope::Read),
+            "upload" => Some(TokenScope::Upload),
+            "admin" => Some(TokenScope::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// A long-lived bearer token for scripts. Only a hash of the token is stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub scope: TokenScope,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// What a share link exposes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "lowercase")]
+pub enum ShareTarget {
+    Folder(Uuid),
+    Media(Uuid),
+}
+
+/// A public link to a folder or a single item, opened without signing in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub id: Uuid,
+    /// Random, unguessable part of the public URL (`/s/<slug>`).
+    pub slug: String,
+    /// User who created the link. `None` when authentication is disabled.
+    pub created_by: Option<Uuid>,
+    pub target: ShareTarget,
+    pub has_password: bool,
+    /// Whether visitors may download the shared items as a zip.
+    pub allow_download: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One destructive or administrative action, as recorded in the append-only audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Assigned by the database; increases with every entry.
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    /// Acting user. `None` without authentication, for failed logins and for
+    /// scheduled tasks.
+    pub user_id: Option<Uuid>,
+    /// Username at the time, kept after the account is deleted.
+    pub username: Option<String>,
+    pub ip: Option<String>,
+    /// Dotted action name, e.g. `media.delete` or `auth.login`.
+    pub action: String,
+    /// Ids of the media, folders, users, ... the action touched.
+    pub target_ids: Vec<String>,
+    /// Extra context, such as the tags of a batch update.
+    pub detail: Option<String>,
+}
+
+/// Work a background job does, with its parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobKind {
+    /// Thumbnail, hash and embed an accepted upload. Progress goes to the client's
+    /// `upload_session`, if it gave one.
+    ProcessUpload {
+        media_id: Uuid,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        upload_session: Option<Uuid>,
+    },
+    /// Re-process items that have no thumbnail or perceptual hash.
+    FixThumbnails,
+    /// Retrain changed tag models and apply all of them.
+    AutoTag { folder_id: Option<Uuid> },
+    /// Train one tag's model and apply it to the whole library.
+    LearnTag { tag_name: String },
+    /// Cluster visually similar items (cosine distance up to `threshold`).
+    GroupMedia { folder_id: Option<Uuid>, threshold: f32 },
+}
+
+impl JobKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobKind::ProcessUpload { .. } => "process_upload",
+            JobKind::FixThumbnails => "fix_thumbnails",
+            JobKind::AutoTag { .. } => "auto_tag",
+            JobKind::LearnTag { .. } => "learn_tag",
+            JobKind::GroupMedia { .. } => "group_media",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    /// Waiting for a worker, possibly until `run_after` for a retry.
+    Queued,
+    Running,
+    Done,
+    /// Gave up after the last attempt.
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Done => "done",
+            JobState::Failed => "failed",
+            JobState::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "queued" => Some(JobState::Queued),
+            "running" => Some(JobState::Running),
+            "done" => Some(JobState::Done),
+            "failed" => Some(JobState::Failed),
+            "cancelled" => Some(JobState::Cancelled),
+            _ => None,
+        }
+    }
+
+    /// Whether the job will not run again unless retried by hand.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobState::Done | JobState::Failed | JobState::Cancelled)
+    }
+}
+
+/// A long operation run by the background workers, kept in the database so it
+/// survives restarts and its progress can be followed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub kind: JobKind,
+    pub state: JobState,
+    /// Units of work finished so far, out of `total` (0 while unknown).
+    pub done: usize,
+    pub total: usize,
+    /// Runs started so far, including the current one.
+    pub attempts: u32,
+    pub max_attempts: u32,
+    /// Error of the last failed attempt.
+    pub error: Option<String>,
+    /// What the job produced, e.g. the groups of a `group_media` job.
+    pub result: Option<serde_json::Value>,
+    /// User who queued the job. `None` when authentication is disabled.
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Earliest time a queued job may start; later than `created_at` for retries.
+    pub run_after: DateTime<Utc>,
+}
+
+/// A file being uploaded in chunks (tus). The bytes received so far live in a part
+/// file; its length is the upload offset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumableUpload {
+    pub id: Uuid,
+    pub filename: String,
+    /// Total size announced when the upload was created.
+    pub length: u64,
+    /// Uploading user. `None` when authentication is disabled.
+    pub owner_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    /// Abandoned uploads are deleted after this. Pushed back by every chunk.
+    pub expires_at: DateTime<Utc>,
+}
+
+/// The other half of a RAW+JPEG pair, stored with the item it belongs to instead of
+/// becoming an item of its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaCompanion {
+    pub media_id: Uuid,
+    /// Storage key, like `MediaItem::filename`.
+    pub filename: String,
+    pub original_filename: String,
+    pub size_bytes: i64,
+    pub content_hash: String,
+}
+
+/// Whose view of the library a query runs for. Decides which items are visible and
+/// whose favorites are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LibraryScope {
+    /// `None` when authentication is disabled; favorites then belong to a shared
+    /// anonymous user.
+    pub user_id: Option<Uuid>,
+    /// Admins see every user's media. Everyone else sees their own and the shared library.
+    pub all_owners: bool,
+}
+
+impl LibraryScope {
+    /// The whole library, as seen without authentication.
+    pub const ALL: LibraryScope = LibraryScope { user_id: None, all_owners: true };
+
+    pub fn for_user(user: &User) -> Self {
+        Self { user_id: Some(user.id), all_owners: user.role == Role::Admin }
+    }
+
+    /// What an item with this owner is compared against: the owner's own media and the
+    /// shared library. Shared items only see the shared library.
+    pub fn for_owner(owner_id: Option<Uuid>) -> Self {
+        Self { user_id: owner_id, all_owners: false }
+    }
+
+    /// Key under which favorites are stored for this scope.
+    pub fn favorites_key(&self) -> Uuid {
+        self.user_id.unwrap_or(Uuid::nil())
+    }
+
+    /// Whether an item or folder with this owner is visible in the scope.
+    pub fn can_see(&self, owner_id: Option<Uuid>) -> bool {
+        self.all_owners || owner_id.is_none() || owner_id == self.user_id
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TagCount {
     pub name: String,
@@ -0,0 +1,96 @@
+use base64::{engine::general_purpose, Engine as _};
+
+/// Default number of differing bits up to which two perceptual hashes count as duplicates.
+pub const DEFAULT_DUPLICATE_THRESHOLD: u32 = 4;
+
+/// Decode a stored phash string into one bit vector per frame. Images have a single frame;
+/// videos store one hash per sampled frame, joined with `|`. Returns an empty list for
+/// `no_hash` or anything that is not a base64-encoded hash.
+pub fn decode_phash_frames(phash: &str) -> Vec<Vec<u8>> {
+    if phash.is_empty() || phash == "no_hash" {
+        return Vec::new();
+    }
+    phash
+        .split('|')
+        .map(|frame| general_purpose::STANDARD.decode(frame).ok().filter(|b| !b.is_empty()))
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default()
+}
+
+/// Number of differing bits, or `None` if the hashes have different lengths.
+pub fn hamming_distance(a: &[u8], b: &[u8]) -> Option<u32> {
+    if a.len() != b.len() {
+        return None;
+    }
+    Some(a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum())
+}
+
+/// Whether `query` is a near-duplicate of `candidate`: at least half of the query frames
+/// (and at least one) must be within `max_distance` bits of some candidate frame, so a
+/// re-encoded video still matches when a few sampled frames differ.
+pub fn frames_match(query: &[Vec<u8>], candidate: &[Vec<u8>], max_distance: u32) -> bool {
+    if query.is_empty() || candidate.is_empty() {
+        return false;
+    }
+    let matched = query
+        .iter()
+        .filter(|q| {
+            candidate
+                .iter()
+                .any(|c| hamming_distance(q, c).is_some_and(|d| d <= max_distance))
+        })
+        .count();
+    matched > 0 && matched * 2 >= query.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(bytes: &[u8]) -> String {
+        general_purpose::STANDARD.encode(bytes)
+    }
+
+    #[test]
+    fn decode_single_and_multi_frame() {
+        assert_eq!(decode_phash_frames(&encode(&[1, 2, 3])), vec![vec![1, 2, 3]]);
+        let video = format!("{}|{}", encode(&[1]), encode(&[2]));
+        assert_eq!(decode_phash_frames(&video), vec![vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn decode_rejects_non_hashes() {
+        assert!(decode_phash_frames("no_hash").is_empty());
+        assert!(decode_phash_frames("").is_empty());
+        assert!(decode_phash_frames("mock_phash").is_empty());
+        // One bad frame invalidates the whole value
+        assert!(decode_phash_frames(&format!("{}|not base64!", encode(&[1]))).is_empty());
+    }
+
+    #[test]
+    fn hamming_distance_counts_bits() {
+        assert_eq!(hamming_distance(&[0b1010], &[0b1010]), Some(0));
+        assert_eq!(hamming_distance(&[0b1111, 0], &[0b0000, 1]), Some(5));
+        assert_eq!(hamming_distance(&[0], &[0, 0]), None);
+    }
+
+    #[test]
+    fn single_frame_matches_within_threshold() {
+        let a = vec![vec![0b0000_0000, 0xff]];
+        let b = vec![vec![0b0000_0011, 0xff]];
+        assert!(frames_match(&a, &b, 2));
+        assert!(!frames_match(&a, &b, 1));
+        assert!(!frames_match(&a, &[], 8));
+    }
+
+    #[test]
+    fn video_needs_half_of_frames() {
+        let query = vec![vec![0x00], vec![0x0f], vec![0xf0], vec![0xff]];
+        // Two of four frames have a counterpart
+        let half = vec![vec![0x00], vec![0x0f], vec![0xaa]];
+        assert!(frames_match(&query, &half, 0));
+        // Only one of four frames has a counterpart
+        let one = vec![vec![0x00], vec![0x55]];
+        assert!(!frames_match(&query, &one, 0));
+    }
+}
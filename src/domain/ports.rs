@@ -1,4 +1,11 @@
-use super::models::{Folder, MediaCounts, MediaItem, MediaSummary};
+use super::models::{
+    ApiToken, AuditEntry, Folder, IdentityClaims, Job, JobState, LibraryScope, MediaCompanion, MediaCounts, MediaItem, MediaSummary, ResumableUpload, Role, Session,
+    ShareLink, TrashedMedia, User,
+};
+use bytes::Bytes;
+use futures_util::Stream;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,10 +18,13 @@ pub enum DomainError {
     Hashing(String),
     #[error("IO error: {0}")]
     Io(String),
+    /// Carries the id of the existing item the new media collided with.
     #[error("Media already exists")]
-    DuplicateMedia,
+    DuplicateMedia(uuid::Uuid),
     #[error("Media not found")]
     NotFound,
+    #[error("Forbidden")]
+    Forbidden,
     #[error("Model loading error: {0}")]
     ModelLoad(String),
 }
@@ -36,7 +46,16 @@ pub trait MediaRepository: Send + Sync {
         media: &MediaItem,
         vector: Option<&[f32]>,
     ) -> Result<(), DomainError>;
-    fn exists_by_phash(&self, phash: &str) -> Result<bool, DomainError>;
+    /// Find an existing item in `scope` of the same media type whose perceptual hash is
+    /// within `max_distance` bits of `phash` (per frame for videos). Hashes that cannot be
+    /// decoded fall back to exact string comparison.
+    fn find_near_duplicate(
+        &self,
+        scope: &LibraryScope,
+        phash: &str,
+        media_type: &str,
+        max_distance: u32,
+    ) -> Result<Option<uuid::Uuid>, DomainError>;
     fn find_similar(
         &self,
         vector: &[f32],
@@ -49,6 +68,7 @@ pub trait MediaRepository: Send + Sync {
     fn delete_many(&self, ids: &[uuid::Uuid]) -> Result<usize, DomainError>;
     fn find_all(
         &self,
+        scope: &LibraryScope,
         limit: usize,
         offset: usize,
         media_type: Option<&str>,
@@ -57,11 +77,19 @@ pub trait MediaRepository: Send + Sync {
         sort_asc: bool,
         sort_by: &str,
     ) -> Result<Vec<MediaSummary>, DomainError>;
-    fn media_counts(&self) -> Result<MediaCounts, DomainError>;
+    fn media_counts(&self, scope: &LibraryScope) -> Result<MediaCounts, DomainError>;
 
-    fn set_favorite(&self, id: uuid::Uuid, favorite: bool) -> Result<(), DomainError>;
+    /// Favorites are per user; `find_by_id` reports the shared anonymous favorites.
+    fn set_favorite(
+        &self,
+        scope: &LibraryScope,
+        id: uuid::Uuid,
+        favorite: bool,
+    ) -> Result<(), DomainError>;
+    fn is_favorite(&self, scope: &LibraryScope, id: uuid::Uuid) -> Result<bool, DomainError>;
 
-    fn get_all_tags(&self) -> Result<Vec<super::models::TagCount>, DomainError>;
+    /// Tags in use on items in `scope`, counting only those items.
+    fn get_all_tags(&self, scope: &LibraryScope) -> Result<Vec<super::models::TagCount>, DomainError>;
     fn update_media_tags(&self, id: uuid::Uuid, tags: Vec<String>) -> Result<(), DomainError>;
     fn update_media_tags_batch(
         &self,
@@ -70,9 +98,14 @@ pub trait MediaRepository: Send + Sync {
     ) -> Result<(), DomainError>;
 
     // --- Folder operations ---
-    fn create_folder(&self, id: uuid::Uuid, name: &str) -> Result<Folder, DomainError>;
+    fn create_folder(
+        &self,
+        id: uuid::Uuid,
+        name: &str,
+        owner_id: Option<uuid::Uuid>,
+    ) -> Result<Folder, DomainError>;
     fn get_folder(&self, id: uuid::Uuid) -> Result<Option<Folder>, DomainError>;
-    fn list_folders(&self) -> Result<Vec<Folder>, DomainError>;
+    fn list_folders(&self, scope: &LibraryScope) -> Result<Vec<Folder>, DomainError>;
     fn delete_folder(&self, id: uuid::Uuid) -> Result<(), DomainError>;
     fn rename_folder(&self, id: uuid::Uuid, name: &str) -> Result<(), DomainError>;
     /// Update the sort_order for each folder. The vec contains (folder_id, new_sort_order) pairs.
@@ -89,6 +122,7 @@ pub trait MediaRepository: Send + Sync {
     ) -> Result<usize, DomainError>;
     fn find_all_in_folder(
         &self,
+        scope: &LibraryScope,
         folder_id: uuid::Uuid,
         limit: usize,
         offset: usize,
@@ -127,7 +161,7 @@ pub trait MediaRepository: Send + Sync {
     fn get_last_trained_count(&self, tag_id: i64) -> Result<usize, DomainError>;
     fn get_tags_with_manual_counts(&self) -> Result<Vec<(i64, String, usize)>, DomainError>;
     fn get_tags_with_auto_counts(&self) -> Result<Vec<(i64, String, usize)>, DomainError>;
-    fn count_auto_tags(&self, folder_id: Option<uuid::Uuid>) -> Result<usize, DomainError>;
+    fn count_auto_tags(&self, scope: &LibraryScope, folder_id: Option<uuid::Uuid>) -> Result<usize, DomainError>;
     fn update_auto_tags(
         &self,
         tag_id: i64,
@@ -150,6 +184,243 @@ pub trait MediaRepository: Send + Sync {
     fn get_manual_positives(&self, tag_id: i64) -> Result<Vec<uuid::Uuid>, DomainError>;
     fn get_all_ids_with_tag(&self, tag_id: i64) -> Result<Vec<uuid::Uuid>, DomainError>;
     fn find_media_without_phash(&self) -> Result<Vec<MediaItem>, DomainError>;
+
+    // --- Server-side import ---
+    fn is_path_imported(&self, path: &str) -> Result<bool, DomainError>;
+    /// Remember that `path` was handled by the importer. `media_id` is `None`
+    /// when the file was skipped as a duplicate.
+    fn mark_path_imported(
+        &self,
+        path: &str,
+        media_id: Option<uuid::Uuid>,
+    ) -> Result<(), DomainError>;
+
+    // --- Library mode (originals referenced in place) ---
+    fn exists_by_filename(&self, filename: &str) -> Result<bool, DomainError>;
+    /// Owner of the media item whose original or companion is stored as `filename`.
+    /// `None` when no item references the file.
+    fn find_file_owner(&self, filename: &str) -> Result<Option<Option<uuid::Uuid>>, DomainError>;
+    /// Id and absolute path of every media item whose original lives outside the upload directory.
+    fn find_external_media(&self) -> Result<Vec<(uuid::Uuid, String)>, DomainError>;
+
+    // --- Content hashes (exact dedup and integrity checks) ---
+    fn find_by_content_hash(&self, scope: &LibraryScope, content_hash: &str) -> Result<Option<uuid::Uuid>, DomainError>;
+    fn set_content_hash(&self, id: uuid::Uuid, content_hash: &str) -> Result<(), DomainError>;
+    /// Id, stored filename and content hash of every original kept in media storage
+    /// (originals referenced in place are excluded).
+    fn find_stored_originals(&self) -> Result<Vec<(uuid::Uuid, String, Option<String>)>, DomainError>;
+
+    // --- Trash (soft delete) ---
+    /// Move live items to the trash. Returns how many were trashed.
+    fn trash_media(&self, ids: &[uuid::Uuid]) -> Result<usize, DomainError>;
+    /// Bring trashed items back. Returns how many were restored.
+    fn restore_media(&self, ids: &[uuid::Uuid]) -> Result<usize, DomainError>;
+    /// Trashed items, most recently trashed first.
+    fn find_trashed(
+        &self,
+        scope: &LibraryScope,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<TrashedMedia>, DomainError>;
+    /// Ids of items trashed before `cutoff`.
+    fn find_trashed_before(
+        &self,
+        cutoff: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<uuid::Uuid>, DomainError>;
+
+    // --- Users ---
+    fn count_users(&self) -> Result<usize, DomainError>;
+    fn create_user(&self, user: &User, password_hash: &str) -> Result<(), DomainError>;
+    fn find_user_by_id(&self, id: uuid::Uuid) -> Result<Option<User>, DomainError>;
+    /// Case-insensitive lookup. Returns the user together with their password hash.
+    fn find_user_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<(User, String)>, DomainError>;
+    fn list_users(&self) -> Result<Vec<User>, DomainError>;
+    fn update_user_role(&self, id: uuid::Uuid, role: Role) -> Result<(), DomainError>;
+    fn update_user_password(&self, id: uuid::Uuid, password_hash: &str) -> Result<(), DomainError>;
+    /// Delete a user. Their media and folders move to the shared library.
+    fn delete_user(&self, id: uuid::Uuid) -> Result<(), DomainError>;
+    /// Give the shared anonymous favorites to `id`. Used when the first admin is created.
+    fn claim_anonymous_favorites(&self, id: uuid::Uuid) -> Result<(), DomainError>;
+    /// User linked to the external identity `subject` at `issuer`.
+    fn find_user_by_identity(&self, issuer: &str, subject: &str) -> Result<Option<User>, DomainError>;
+    /// Link an external identity to a user. Returns false if the user is already linked
+    /// to another identity at the same issuer.
+    fn link_identity(
+        &self,
+        user_id: uuid::Uuid,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<bool, DomainError>;
+
+    // --- Sessions ---
+    fn create_session(&self, session: &Session, token_hash: &str) -> Result<(), DomainError>;
+    /// Session whose token hashes to `token_hash`, if it has not expired by `now`.
+    fn find_session(
+        &self,
+        token_hash: &str,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<Session>, DomainError>;
+    fn touch_session(
+        &self,
+        id: uuid::Uuid,
+        last_seen_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), DomainError>;
+    /// Unexpired sessions of a user, most recently used first.
+    fn list_sessions(
+        &self,
+        user_id: uuid::Uuid,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Session>, DomainError>;
+    /// Delete one session of a user. Returns false if the user has no such session.
+    fn delete_session(&self, user_id: uuid::Uuid, id: uuid::Uuid) -> Result<bool, DomainError>;
+    fn delete_user_sessions(&self, user_id: uuid::Uuid) -> Result<usize, DomainError>;
+    fn delete_expired_sessions(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize, DomainError>;
+
+    // --- API tokens ---
+    fn create_api_token(&self, token: &ApiToken, token_hash: &str) -> Result<(), DomainError>;
+    fn find_api_token(&self, token_hash: &str) -> Result<Option<ApiToken>, DomainError>;
+    fn touch_api_token(
+        &self,
+        id: uuid::Uuid,
+        last_used_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), DomainError>;
+    /// Tokens of a user, newest first.
+    fn list_api_tokens(&self, user_id: uuid::Uuid) -> Result<Vec<ApiToken>, DomainError>;
+    /// Delete one token of a user. Returns false if the user has no such token.
+    fn delete_api_token(&self, user_id: uuid::Uuid, id: uuid::Uuid) -> Result<bool, DomainError>;
+
+    // --- Share links ---
+    fn create_share(&self, share: &ShareLink, password_hash: Option<&str>) -> Result<(), DomainError>;
+    fn find_share(&self, id: uuid::Uuid) -> Result<Option<ShareLink>, DomainError>;
+    /// Returns the share together with its password hash, if it has one.
+    fn find_share_by_slug(
+        &self,
+        slug: &str,
+    ) -> Result<Option<(ShareLink, Option<String>)>, DomainError>;
+    /// Shares created by `created_by`, or all shares for `None`. Newest first.
+    fn list_shares(&self, created_by: Option<uuid::Uuid>) -> Result<Vec<ShareLink>, DomainError>;
+    fn delete_share(&self, id: uuid::Uuid) -> Result<(), DomainError>;
+    /// Whether `media_id` is a live item exposed by `share` and visible in `scope`.
+    fn share_contains_media(
+        &self,
+        share: &ShareLink,
+        scope: &LibraryScope,
+        media_id: uuid::Uuid,
+    ) -> Result<bool, DomainError>;
+
+    // --- Audit log ---
+    /// Append an entry. `entry.id` is ignored; the database assigns it.
+    fn append_audit(&self, entry: &AuditEntry) -> Result<(), DomainError>;
+    /// Entries newest first, optionally only those of one action.
+    fn list_audit(
+        &self,
+        action: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<AuditEntry>, DomainError>;
+    /// Delete entries older than `before`. The only way entries ever go away.
+    fn delete_audit_before(
+        &self,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize, DomainError>;
+
+    // --- Background jobs ---
+    fn create_job(&self, job: &Job) -> Result<(), DomainError>;
+    fn find_job(&self, id: uuid::Uuid) -> Result<Option<Job>, DomainError>;
+    /// Jobs queued by `created_by`, or all jobs for `None`. Newest first.
+    fn list_jobs(
+        &self,
+        created_by: Option<uuid::Uuid>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<Job>, DomainError>;
+    /// Move the oldest queued job that is due at `now` to running and count the
+    /// attempt. Atomic, so concurrent workers never claim the same job.
+    fn claim_next_job(&self, now: chrono::DateTime<chrono::Utc>) -> Result<Option<Job>, DomainError>;
+    fn update_job_progress(&self, id: uuid::Uuid, done: usize, total: usize) -> Result<(), DomainError>;
+    /// Record the outcome of a running job.
+    fn finish_job(
+        &self,
+        id: uuid::Uuid,
+        state: JobState,
+        error: Option<&str>,
+        result: Option<&serde_json::Value>,
+    ) -> Result<(), DomainError>;
+    /// Put a failed running job back in the queue until `run_after`.
+    fn reschedule_job(
+        &self,
+        id: uuid::Uuid,
+        run_after: chrono::DateTime<chrono::Utc>,
+        error: &str,
+    ) -> Result<(), DomainError>;
+    /// Cancel a job that has not started. Returns false if it is no longer queued.
+    fn cancel_queued_job(&self, id: uuid::Uuid) -> Result<bool, DomainError>;
+    /// Queue a failed or cancelled job again with fresh attempts. Returns false for
+    /// jobs in any other state.
+    fn retry_job(&self, id: uuid::Uuid, now: chrono::DateTime<chrono::Utc>) -> Result<bool, DomainError>;
+    /// Return jobs left running by a previous process to the queue.
+    fn requeue_running_jobs(&self) -> Result<usize, DomainError>;
+    /// Delete finished jobs last updated before `before`.
+    fn delete_finished_jobs_before(
+        &self,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize, DomainError>;
+
+    // --- Resumable uploads ---
+    fn create_resumable_upload(&self, upload: &ResumableUpload) -> Result<(), DomainError>;
+    fn find_resumable_upload(&self, id: uuid::Uuid) -> Result<Option<ResumableUpload>, DomainError>;
+    fn touch_resumable_upload(
+        &self,
+        id: uuid::Uuid,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), DomainError>;
+    fn delete_resumable_upload(&self, id: uuid::Uuid) -> Result<(), DomainError>;
+    /// Ids of uploads that expired by `now`.
+    fn find_expired_resumable_uploads(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<uuid::Uuid>, DomainError>;
+
+    // --- RAW+JPEG pairs ---
+    fn add_companion(&self, companion: &MediaCompanion) -> Result<(), DomainError>;
+    fn find_companion(&self, media_id: uuid::Uuid) -> Result<Option<MediaCompanion>, DomainError>;
+    /// A non-trashed item of `owner` without a companion, named `<stem>.<extension>` for
+    /// one of `extensions` (ignoring case) and uploaded at or after `since`.
+    fn find_companion_partner(
+        &self,
+        owner: Option<uuid::Uuid>,
+        stem: &str,
+        extensions: &[&str],
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<uuid::Uuid>, DomainError>;
+
+    // --- Renditions (thumbnail and previews) ---
+    /// Record that the renditions `names` of an item exist.
+    fn add_renditions(&self, media_id: uuid::Uuid, names: &[String]) -> Result<(), DomainError>;
+    fn find_renditions(&self, media_id: uuid::Uuid) -> Result<Vec<String>, DomainError>;
+    /// Processed, non-trashed items that lack one of the renditions `names`.
+    fn find_media_missing_renditions(&self, names: &[String]) -> Result<Vec<uuid::Uuid>, DomainError>;
+
+    // --- Duplicate review ---
+    /// Id, media type and phash of every hashed item in `scope` (optionally limited to
+    /// a folder).
+    fn get_all_phashes(
+        &self,
+        scope: &LibraryScope,
+        folder_id: Option<uuid::Uuid>,
+    ) -> Result<Vec<(uuid::Uuid, String, String)>, DomainError>;
+    /// Copy manual tags, folder memberships and favorite status of `sources` onto `target`.
+    fn merge_media_metadata(
+        &self,
+        target: uuid::Uuid,
+        sources: &[uuid::Uuid],
+    ) -> Result<(), DomainError>;
 }
 
 pub trait AiProcessor: Send + Sync {
@@ -159,3 +430,58 @@ pub trait AiProcessor: Send + Sync {
 pub trait HashGenerator: Send + Sync {
     fn generate_phash(&self, image_bytes: &[u8]) -> Result<String, DomainError>;
 }
+
+/// Byte stream of a stored object, as returned by `MediaStorage::stream`.
+pub type ByteStream = Pin<Box<dyn Stream<Item = std::io::Result<Bytes>> + Send>>;
+
+/// Blob storage for originals and thumbnails. Keys are relative, `/`-separated paths
+/// such as `ab/cd/<uuid>.jpg`; each instance is rooted at its own directory or prefix.
+#[async_trait::async_trait]
+pub trait MediaStorage: Send + Sync {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), DomainError>;
+    /// Copy a local file into storage without reading it into memory.
+    async fn put_file(&self, key: &str, source: &Path) -> Result<(), DomainError>;
+    /// Move a local file into storage; `source` is gone afterwards. Storage on the same
+    /// filesystem renames it instead of copying.
+    async fn move_file(&self, key: &str, source: &Path) -> Result<(), DomainError> {
+        self.put_file(key, source).await?;
+        tokio::fs::remove_file(source)
+            .await
+            .map_err(|e| DomainError::Io(e.to_string()))
+    }
+    /// Open an object for streaming, returning its size and contents. Returns
+    /// `DomainError::NotFound` if it does not exist.
+    async fn stream(&self, key: &str) -> Result<(u64, ByteStream), DomainError>;
+    /// Size of an object in bytes. Returns `DomainError::NotFound` if it does not exist.
+    async fn size(&self, key: &str) -> Result<u64, DomainError>;
+    /// Stream the bytes `range` of an object, which must lie within its size.
+    async fn stream_range(&self, key: &str, range: std::ops::Range<u64>) -> Result<ByteStream, DomainError>;
+    /// Delete an object. Deleting a missing object is not an error.
+    async fn delete(&self, key: &str) -> Result<(), DomainError>;
+    async fn exists(&self, key: &str) -> Result<bool, DomainError>;
+    /// Where an object lives on the local filesystem, for storage that keeps it there.
+    /// Lets ffmpeg and the decoders read originals without a temporary copy.
+    fn local_path(&self, _key: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// External login provider (OpenID Connect authorization-code flow with PKCE).
+#[async_trait::async_trait]
+pub trait IdentityProvider: Send + Sync {
+    /// Where to send the browser to sign in. `code_challenge` is the S256 PKCE challenge.
+    async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_challenge: &str,
+    ) -> Result<String, DomainError>;
+    /// Redeem an authorization code and return the claims of the verified ID token,
+    /// whose nonce must equal `nonce`.
+    async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<IdentityClaims, DomainError>;
+}
@@ -0,0 +1,204 @@
+use crate::domain::{ByteStream, DomainError, MediaStorage};
+use std::io::{ErrorKind, SeekFrom};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+/// Stores objects as files below a root directory (`UPLOAD_DIR` / `THUMBNAIL_DIR`).
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Map a key to a path below the root, rejecting anything that could escape it.
+    fn path_for(&self, key: &str) -> Result<PathBuf, DomainError> {
+        let relative = Path::new(key);
+        if key.is_empty()
+            || !relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(DomainError::Io(format!("Invalid storage key: {}", key)));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+fn map_io_error(e: std::io::Error) -> DomainError {
+    if e.kind() == ErrorKind::NotFound {
+        DomainError::NotFound
+    } else {
+        DomainError::Io(e.to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl MediaStorage for LocalStorage {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), DomainError> {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|e| DomainError::Io(e.to_string()))?;
+        }
+        fs::write(&path, data)
+            .await
+            .map_err(|e| DomainError::Io(e.to_string()))
+    }
+
+    async fn put_file(&self, key: &str, source: &Path) -> Result<(), DomainError> {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|e| DomainError::Io(e.to_string()))?;
+        }
+        fs::copy(source, &path)
+            .await
+            .map(|_| ())
+            .map_err(|e| DomainError::Io(e.to_string()))
+    }
+
+    async fn move_file(&self, key: &str, source: &Path) -> Result<(), DomainError> {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|e| DomainError::Io(e.to_string()))?;
+        }
+        if fs::rename(source, &path).await.is_ok() {
+            return Ok(());
+        }
+        // Renaming fails across filesystems, e.g. from /tmp into a mounted volume
+        fs::copy(source, &path)
+            .await
+            .map_err(|e| DomainError::Io(e.to_string()))?;
+        fs::remove_file(source)
+            .await
+            .map_err(|e| DomainError::Io(e.to_string()))
+    }
+
+    async fn stream(&self, key: &str) -> Result<(u64, ByteStream), DomainError> {
+        let file = fs::File::open(self.path_for(key)?)
+            .await
+            .map_err(map_io_error)?;
+        let size = file
+            .metadata()
+            .await
+            .map_err(|e| DomainError::Io(e.to_string()))?
+            .len();
+        let stream = ReaderStream::with_capacity(file, 128 * 1024);
+        Ok((size, Box::pin(stream)))
+    }
+
+    async fn size(&self, key: &str) -> Result<u64, DomainError> {
+        Ok(fs::metadata(self.path_for(key)?).await.map_err(map_io_error)?.len())
+    }
+
+    async fn stream_range(&self, key: &str, range: Range<u64>) -> Result<ByteStream, DomainError> {
+        let mut file = fs::File::open(self.path_for(key)?)
+            .await
+            .map_err(map_io_error)?;
+        file.seek(SeekFrom::Start(range.start))
+            .await
+            .map_err(|e| DomainError::Io(e.to_string()))?;
+        let stream = ReaderStream::with_capacity(file.take(range.end - range.start), 128 * 1024);
+        Ok(Box::pin(stream))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), DomainError> {
+        match fs::remove_file(self.path_for(key)?).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(DomainError::Io(e.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, DomainError> {
+        fs::try_exists(self.path_for(key)?)
+            .await
+            .map_err(|e| DomainError::Io(e.to_string()))
+    }
+
+    fn local_path(&self, key: &str) -> Option<PathBuf> {
+        self.path_for(key).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    async fn read(storage: &LocalStorage, key: &str) -> Result<Vec<u8>, DomainError> {
+        let (_, mut stream) = storage.stream(key).await?;
+        let mut body = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            body.extend_from_slice(&chunk.unwrap());
+        }
+        Ok(body)
+    }
+
+    #[tokio::test]
+    async fn test_put_get_stream_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path().to_path_buf());
+
+        assert!(!storage.exists("ab/cd/file.jpg").await.unwrap());
+        assert!(matches!(read(&storage, "ab/cd/file.jpg").await, Err(DomainError::NotFound)));
+
+        storage.put("ab/cd/file.jpg", b"hello").await.unwrap();
+        assert!(dir.path().join("ab/cd/file.jpg").exists());
+        assert!(storage.exists("ab/cd/file.jpg").await.unwrap());
+        assert_eq!(read(&storage, "ab/cd/file.jpg").await.unwrap(), b"hello");
+
+        let (size, mut stream) = storage.stream("ab/cd/file.jpg").await.unwrap();
+        assert_eq!(size, 5);
+        let mut body = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            body.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(body, b"hello");
+
+        storage.delete("ab/cd/file.jpg").await.unwrap();
+        assert!(!storage.exists("ab/cd/file.jpg").await.unwrap());
+        // Deleting again is fine
+        storage.delete("ab/cd/file.jpg").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_put_and_move_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path().join("store"));
+        let source = dir.path().join("upload.tmp");
+        std::fs::write(&source, b"video").unwrap();
+
+        storage.put_file("ab/cd/copy.mp4", &source).await.unwrap();
+        assert!(source.exists());
+        storage.move_file("ab/cd/moved.mp4", &source).await.unwrap();
+        assert!(!source.exists());
+
+        assert_eq!(read(&storage, "ab/cd/copy.mp4").await.unwrap(), b"video");
+        assert_eq!(read(&storage, "ab/cd/moved.mp4").await.unwrap(), b"video");
+        assert_eq!(
+            storage.local_path("ab/cd/moved.mp4"),
+            Some(dir.path().join("store/ab/cd/moved.mp4"))
+        );
+        assert!(storage.move_file("ab/cd/again.mp4", &source).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_escaping_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path().to_path_buf());
+
+        assert!(read(&storage, "../secret").await.is_err());
+        assert!(read(&storage, "/etc/passwd").await.is_err());
+        assert!(storage.put("", b"x").await.is_err());
+    }
+}
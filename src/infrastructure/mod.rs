@@ -1,7 +1,13 @@
 pub mod sqlite_repo;
 pub mod ort_processor;
 pub mod phash_generator;
+pub mod local_storage;
+pub mod s3_storage;
+pub mod oidc_client;
 
 pub use sqlite_repo::*;
 pub use ort_processor::*;
 pub use phash_generator::*;
+pub use local_storage::*;
+pub use s3_storage::*;
+pub use oidc_client::*;
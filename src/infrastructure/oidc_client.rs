@@ -0,0 +1,476 @@
+use crate::domain::{DomainError, IdentityClaims, IdentityProvider};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use ring::signature;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::{OnceCell, RwLock};
+
+/// Clock skew tolerated when checking an ID token's expiry.
+const LEEWAY_SECS: i64 = 60;
+
+/// Settings for signing in through an OpenID Connect provider (Keycloak, Authentik,
+/// Authelia, Dex, ...).
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    /// Issuer URL; `<issuer>/.well-known/openid-configuration` must exist.
+    pub issuer: String,
+    pub client_id: String,
+    /// Secret of a confidential client. Public clients rely on PKCE alone.
+    pub client_secret: Option<String>,
+    /// Callback registered at the provider, e.g. `https://photos.example.com/api/oidc/callback`.
+    pub redirect_url: String,
+    /// Space-separated scopes; must include `openid`.
+    pub scopes: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    // RSA
+    n: Option<String>,
+    e: Option<String>,
+    // EC
+    crv: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    aud: Audience,
+    exp: i64,
+    nonce: Option<String>,
+    preferred_username: Option<String>,
+    email: Option<String>,
+    #[serde(default)]
+    groups: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+fn oidc_error(context: &str, e: impl std::fmt::Display) -> DomainError {
+    DomainError::Io(format!("OIDC {}: {}", context, e))
+}
+
+/// Authorization-code flow against a discovered OIDC provider. Only the ID token is
+/// used; its signature (RS256 or ES256) is checked against the provider's JWKS.
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    metadata: OnceCell<ProviderMetadata>,
+    keys: RwLock<Vec<Jwk>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Result<Self, DomainError> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| oidc_error("HTTP client", e))?;
+        Ok(Self {
+            config,
+            http,
+            metadata: OnceCell::new(),
+            keys: RwLock::new(Vec::new()),
+        })
+    }
+
+    /// The discovery document, fetched on first use so the gallery still starts while
+    /// the provider is down.
+    async fn metadata(&self) -> Result<&ProviderMetadata, DomainError> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let issuer = self.config.issuer.trim_end_matches('/');
+                let url = format!("{}/.well-known/openid-configuration", issuer);
+                let metadata: ProviderMetadata = self.get_json(&url).await?;
+                if metadata.issuer.trim_end_matches('/') != issuer {
+                    return Err(oidc_error("discovery", format!("unexpected issuer {}", metadata.issuer)));
+                }
+                Ok(metadata)
+            })
+            .await
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, DomainError> {
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| oidc_error(url, e))?
+            .json()
+            .await
+            .map_err(|e| oidc_error(url, e))
+    }
+
+    /// The provider's signing keys. Fetched again when a token names a key we have not
+    /// seen yet, which is how key rotation shows up.
+    async fn keys_for(&self, kid: Option<&str>) -> Result<Vec<Jwk>, DomainError> {
+        {
+            let keys = self.keys.read().await;
+            let known = kid.is_none_or(|kid| keys.iter().any(|k| k.kid.as_deref() == Some(kid)));
+            if !keys.is_empty() && known {
+                return Ok(keys.clone());
+            }
+        }
+        let jwks_uri = self.metadata().await?.jwks_uri.clone();
+        let set: JwkSet = self.get_json(&jwks_uri).await?;
+        *self.keys.write().await = set.keys.clone();
+        Ok(set.keys)
+    }
+}
+
+#[async_trait::async_trait]
+impl IdentityProvider for OidcClient {
+    async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_challenge: &str,
+    ) -> Result<String, DomainError> {
+        let metadata = self.metadata().await?;
+        let url = reqwest::Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("scope", self.config.scopes.as_str()),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", code_challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| oidc_error("authorization endpoint", e))?;
+        Ok(url.into())
+    }
+
+    async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<IdentityClaims, DomainError> {
+        let metadata = self.metadata().await?;
+        let mut request = self.http.post(&metadata.token_endpoint).form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ]);
+        if let Some(secret) = &self.config.client_secret {
+            request = request.basic_auth(&self.config.client_id, Some(secret));
+        }
+        let response: TokenResponse = request
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| oidc_error("token endpoint", e))?
+            .json()
+            .await
+            .map_err(|e| oidc_error("token endpoint", e))?;
+
+        let kid = response
+            .id_token
+            .split('.')
+            .next()
+            .and_then(decode_part::<JwtHeader>)
+            .and_then(|header| header.kid);
+        let keys = self.keys_for(kid.as_deref()).await?;
+        verify_id_token(
+            &response.id_token,
+            &keys,
+            &metadata.issuer,
+            &self.config.client_id,
+            nonce,
+            Utc::now().timestamp(),
+        )
+    }
+}
+
+fn decode_part<T: DeserializeOwned>(part: &str) -> Option<T> {
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(part).ok()?).ok()
+}
+
+/// Check the signature and the claims of an ID token issued for `client_id`.
+fn verify_id_token(
+    token: &str,
+    keys: &[Jwk],
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: i64,
+) -> Result<IdentityClaims, DomainError> {
+    let invalid = |reason: &str| DomainError::Io(format!("Invalid ID token: {}", reason));
+
+    let mut parts = token.split('.');
+    let (Some(header_part), Some(payload_part), Some(signature_part), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid("malformed"));
+    };
+    let header: JwtHeader = decode_part(header_part).ok_or_else(|| invalid("bad header"))?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature_part)
+        .map_err(|_| invalid("bad signature encoding"))?;
+    let message = &token[..header_part.len() + 1 + payload_part.len()];
+    let signed = keys
+        .iter()
+        .filter(|key| header.kid.is_none() || key.kid == header.kid)
+        .any(|key| verify_signature(&header.alg, key, message.as_bytes(), &signature));
+    if !signed {
+        return Err(invalid("signature"));
+    }
+
+    let claims: IdTokenClaims = decode_part(payload_part).ok_or_else(|| invalid("bad claims"))?;
+    if claims.iss.trim_end_matches('/') != issuer.trim_end_matches('/') {
+        return Err(invalid("issuer"));
+    }
+    let audience_ok = match &claims.aud {
+        Audience::One(aud) => aud == client_id,
+        Audience::Many(auds) => auds.iter().any(|aud| aud == client_id),
+    };
+    if !audience_ok {
+        return Err(invalid("audience"));
+    }
+    if claims.exp + LEEWAY_SECS < now {
+        return Err(invalid("expired"));
+    }
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err(invalid("nonce"));
+    }
+
+    Ok(IdentityClaims {
+        issuer: claims.iss,
+        subject: claims.sub,
+        username: claims.preferred_username,
+        email: claims.email,
+        groups: claims.groups,
+    })
+}
+
+/// Verify a JWS signature. Only RS256 and ES256 are accepted, never `none` or HMAC.
+fn verify_signature(alg: &str, key: &Jwk, message: &[u8], sig: &[u8]) -> bool {
+    let decode = |value: &Option<String>| value.as_deref().and_then(|v| URL_SAFE_NO_PAD.decode(v).ok());
+    match (alg, key.kty.as_str()) {
+        ("RS256", "RSA") => {
+            let (Some(n), Some(e)) = (decode(&key.n), decode(&key.e)) else {
+                return false;
+            };
+            signature::RsaPublicKeyComponents { n, e }
+                .verify(&signature::RSA_PKCS1_2048_8192_SHA256, message, sig)
+                .is_ok()
+        }
+        ("ES256", "EC") if key.crv.as_deref() == Some("P-256") => {
+            let (Some(x), Some(y)) = (decode(&key.x), decode(&key.y)) else {
+                return false;
+            };
+            // Uncompressed SEC1 point
+            let point = [&[0x04][..], &x, &y].concat();
+            signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point)
+                .verify(message, sig)
+                .is_ok()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::routing::{get, post};
+    use axum::{Form, Json, Router};
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+    use serde_json::{json, Value};
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    fn key_pair() -> EcdsaKeyPair {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng).unwrap()
+    }
+
+    fn jwk(key: &EcdsaKeyPair) -> Value {
+        let point = key.public_key().as_ref();
+        json!({
+            "kty": "EC",
+            "kid": "k1",
+            "crv": "P-256",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+        })
+    }
+
+    fn sign(key: &EcdsaKeyPair, claims: &Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(json!({ "alg": "ES256", "kid": "k1" }).to_string());
+        let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let message = format!("{}.{}", header, payload);
+        let sig = key.sign(&SystemRandom::new(), message.as_bytes()).unwrap();
+        format!("{}.{}", message, URL_SAFE_NO_PAD.encode(sig.as_ref()))
+    }
+
+    fn claims(issuer: &str, nonce: &str) -> Value {
+        json!({
+            "iss": issuer,
+            "sub": "user-42",
+            "aud": ["gallery", "other"],
+            "exp": Utc::now().timestamp() + 300,
+            "nonce": nonce,
+            "preferred_username": "kim",
+            "groups": ["family", "gallery-admins"],
+        })
+    }
+
+    struct MockIdp {
+        issuer: String,
+        jwks: Value,
+        /// PKCE challenge the next code was issued for.
+        challenge: Mutex<String>,
+        /// ID token handed out for the next code.
+        id_token: Mutex<String>,
+    }
+
+    /// A minimal OIDC provider: discovery, JWKS and a token endpoint that enforces PKCE.
+    async fn mock_idp(key: &EcdsaKeyPair) -> Arc<MockIdp> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let idp = Arc::new(MockIdp {
+            issuer: issuer.clone(),
+            jwks: json!({ "keys": [jwk(key)] }),
+            challenge: Mutex::new(String::new()),
+            id_token: Mutex::new(String::new()),
+        });
+
+        let app = Router::new()
+            .route(
+                "/.well-known/openid-configuration",
+                get(|State(idp): State<Arc<MockIdp>>| async move {
+                    Json(json!({
+                        "issuer": idp.issuer,
+                        "authorization_endpoint": format!("{}/authorize", idp.issuer),
+                        "token_endpoint": format!("{}/token", idp.issuer),
+                        "jwks_uri": format!("{}/jwks", idp.issuer),
+                    }))
+                }),
+            )
+            .route("/jwks", get(|State(idp): State<Arc<MockIdp>>| async move { Json(idp.jwks.clone()) }))
+            .route(
+                "/token",
+                post(
+                    |State(idp): State<Arc<MockIdp>>, Form(form): Form<HashMap<String, String>>| async move {
+                        let verifier = form.get("code_verifier").cloned().unwrap_or_default();
+                        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+                        if form.get("code").map(String::as_str) != Some("good-code")
+                            || challenge != *idp.challenge.lock().unwrap()
+                        {
+                            return Err(axum::http::StatusCode::BAD_REQUEST);
+                        }
+                        Ok(Json(json!({ "id_token": *idp.id_token.lock().unwrap() })))
+                    },
+                ),
+            )
+            .with_state(idp.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        idp
+    }
+
+    #[tokio::test]
+    async fn code_flow_against_mock_idp() {
+        let key = key_pair();
+        let idp = mock_idp(&key).await;
+        let client = OidcClient::new(OidcConfig {
+            issuer: idp.issuer.clone(),
+            client_id: "gallery".to_string(),
+            client_secret: None,
+            redirect_url: "http://gallery.test/api/oidc/callback".to_string(),
+            scopes: "openid profile".to_string(),
+        })
+        .unwrap();
+
+        let verifier = "v".repeat(64);
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+        let url = client.authorization_url("st4te", "n0nce", &challenge).await.unwrap();
+        assert!(url.starts_with(&format!("{}/authorize?", idp.issuer)));
+        assert!(url.contains("state=st4te") && url.contains("code_challenge_method=S256"));
+        assert!(url.contains(&format!("code_challenge={}", challenge)));
+
+        *idp.challenge.lock().unwrap() = challenge;
+        *idp.id_token.lock().unwrap() = sign(&key, &claims(&idp.issuer, "n0nce"));
+        let identity = client.exchange_code("good-code", &verifier, "n0nce").await.unwrap();
+        assert_eq!(identity.subject, "user-42");
+        assert_eq!(identity.username.as_deref(), Some("kim"));
+        assert_eq!(identity.groups, vec!["family", "gallery-admins"]);
+
+        // The provider refuses a code redeemed without the matching verifier
+        assert!(client.exchange_code("good-code", "wrong", "n0nce").await.is_err());
+        // A replayed token from another login fails the nonce check
+        assert!(client.exchange_code("good-code", &verifier, "other").await.is_err());
+    }
+
+    #[test]
+    fn id_token_checks() {
+        let key = key_pair();
+        let keys: Vec<Jwk> = serde_json::from_value(json!([jwk(&key)])).unwrap();
+        let issuer = "https://id.example.com";
+        let now = Utc::now().timestamp();
+        let verify = |token: &str| verify_id_token(token, &keys, issuer, "gallery", "n", now);
+
+        assert!(verify(&sign(&key, &claims(issuer, "n"))).is_ok());
+        // Signed by a key the provider does not publish
+        assert!(verify(&sign(&key_pair(), &claims(issuer, "n"))).is_err());
+        assert!(verify(&sign(&key, &claims("https://evil.example.com", "n"))).is_err());
+
+        let mut wrong_audience = claims(issuer, "n");
+        wrong_audience["aud"] = json!("someone-else");
+        assert!(verify(&sign(&key, &wrong_audience)).is_err());
+        let mut expired = claims(issuer, "n");
+        expired["exp"] = json!(now - 3600);
+        assert!(verify(&sign(&key, &expired)).is_err());
+
+        // Unsigned tokens are never accepted
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(claims(issuer, "n").to_string());
+        assert!(verify(&format!("{}.{}.", header, payload)).is_err());
+    }
+}
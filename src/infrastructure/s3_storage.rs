@@ -0,0 +1,250 @@
+use crate::domain::{ByteStream, DomainError, MediaStorage};
+use bytes::Bytes;
+use futures_util::TryStreamExt;
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path as ObjectPath;
+use object_store::{GetOptions, GetRange, ObjectStore, PutPayload, WriteMultipart};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+
+/// Parts of a file upload that may be in flight at once.
+const UPLOAD_CONCURRENCY: usize = 4;
+
+/// Connection settings for an S3-compatible object store (AWS S3, MinIO, Garage, ...).
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    pub bucket: String,
+    pub region: String,
+    /// Custom endpoint for S3-compatible servers, e.g. `http://minio:9000`.
+    pub endpoint: Option<String>,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+/// Stores objects in an S3 bucket below a key prefix (e.g. `originals/`).
+pub struct S3Storage {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+}
+
+impl S3Storage {
+    pub fn new(config: &S3Config, prefix: &str) -> Result<Self, DomainError> {
+        let mut builder = AmazonS3Builder::new()
+            .with_bucket_name(&config.bucket)
+            .with_region(&config.region)
+            .with_access_key_id(&config.access_key_id)
+            .with_secret_access_key(&config.secret_access_key);
+        if let Some(endpoint) = &config.endpoint {
+            // Self-hosted servers usually need path-style requests and often run without TLS
+            builder = builder
+                .with_endpoint(endpoint)
+                .with_virtual_hosted_style_request(false)
+                .with_allow_http(endpoint.starts_with("http://"));
+        }
+        let store = builder
+            .build()
+            .map_err(|e| DomainError::Io(format!("Failed to configure S3 storage: {}", e)))?;
+        Ok(Self::with_store(Arc::new(store), prefix))
+    }
+
+    pub(crate) fn with_store(store: Arc<dyn ObjectStore>, prefix: &str) -> Self {
+        Self {
+            store,
+            prefix: prefix.trim_matches('/').to_string(),
+        }
+    }
+
+    fn path_for(&self, key: &str) -> Result<ObjectPath, DomainError> {
+        let full = if self.prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}/{}", self.prefix, key)
+        };
+        // `parse` rejects empty segments and `.`/`..`, so keys cannot escape the prefix
+        ObjectPath::parse(&full)
+            .ok()
+            .filter(|_| !key.is_empty())
+            .ok_or_else(|| DomainError::Io(format!("Invalid storage key: {}", key)))
+    }
+}
+
+fn map_store_error(e: object_store::Error) -> DomainError {
+    match e {
+        object_store::Error::NotFound { .. } => DomainError::NotFound,
+        e => DomainError::Io(e.to_string()),
+    }
+}
+
+#[async_trait::async_trait]
+impl MediaStorage for S3Storage {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<(), DomainError> {
+        let payload = PutPayload::from(Bytes::copy_from_slice(data));
+        self.store
+            .put(&self.path_for(key)?, payload)
+            .await
+            .map_err(map_store_error)?;
+        Ok(())
+    }
+
+    async fn put_file(&self, key: &str, source: &Path) -> Result<(), DomainError> {
+        let path = self.path_for(key)?;
+        let mut file = tokio::fs::File::open(source)
+            .await
+            .map_err(|e| DomainError::Io(e.to_string()))?;
+        let upload = self.store.put_multipart(&path).await.map_err(map_store_error)?;
+        let mut writer = WriteMultipart::new(upload);
+        let mut buf = vec![0u8; 1024 * 1024];
+        loop {
+            let read = match file.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) => {
+                    let _ = writer.abort().await;
+                    return Err(DomainError::Io(e.to_string()));
+                }
+            };
+            writer
+                .wait_for_capacity(UPLOAD_CONCURRENCY)
+                .await
+                .map_err(map_store_error)?;
+            writer.write(&buf[..read]);
+        }
+        writer.finish().await.map_err(map_store_error)?;
+        Ok(())
+    }
+
+    async fn stream(&self, key: &str) -> Result<(u64, ByteStream), DomainError> {
+        let result = self.store.get(&self.path_for(key)?).await.map_err(map_store_error)?;
+        let size = result.meta.size;
+        let stream = result.into_stream().map_err(std::io::Error::other);
+        Ok((size, Box::pin(stream)))
+    }
+
+    async fn size(&self, key: &str) -> Result<u64, DomainError> {
+        let meta = self.store.head(&self.path_for(key)?).await.map_err(map_store_error)?;
+        Ok(meta.size)
+    }
+
+    async fn stream_range(&self, key: &str, range: Range<u64>) -> Result<ByteStream, DomainError> {
+        let options = GetOptions { range: Some(GetRange::Bounded(range)), ..Default::default() };
+        let result = self
+            .store
+            .get_opts(&self.path_for(key)?, options)
+            .await
+            .map_err(map_store_error)?;
+        Ok(Box::pin(result.into_stream().map_err(std::io::Error::other)))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), DomainError> {
+        match self.store.delete(&self.path_for(key)?).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(DomainError::Io(e.to_string())),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, DomainError> {
+        match self.store.head(&self.path_for(key)?).await {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(e) => Err(DomainError::Io(e.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use object_store::memory::InMemory;
+
+    async fn read(storage: &S3Storage, key: &str) -> Result<Vec<u8>, DomainError> {
+        let (_, mut stream) = storage.stream(key).await?;
+        let mut body = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            body.extend_from_slice(&chunk.unwrap());
+        }
+        Ok(body)
+    }
+
+    async fn exercise(storage: &S3Storage) {
+        let key = format!("ab/cd/{}.jpg", uuid::Uuid::new_v4());
+
+        assert!(!storage.exists(&key).await.unwrap());
+        assert!(matches!(read(storage, &key).await, Err(DomainError::NotFound)));
+
+        storage.put(&key, b"hello").await.unwrap();
+        assert!(storage.exists(&key).await.unwrap());
+        assert_eq!(read(storage, &key).await.unwrap(), b"hello");
+
+        let (size, mut stream) = storage.stream(&key).await.unwrap();
+        assert_eq!(size, 5);
+        let mut body = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            body.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(body, b"hello");
+
+        assert_eq!(storage.size(&key).await.unwrap(), 5);
+        let mut stream = storage.stream_range(&key, 1..4).await.unwrap();
+        let mut body = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            body.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(body, b"ell");
+
+        storage.delete(&key).await.unwrap();
+        assert!(!storage.exists(&key).await.unwrap());
+        storage.delete(&key).await.unwrap();
+
+        // Files are sent in parts, larger than one read buffer here
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("upload.tmp");
+        let data: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&source, &data).unwrap();
+        storage.move_file(&key, &source).await.unwrap();
+        assert!(!source.exists());
+        assert_eq!(read(storage, &key).await.unwrap(), data);
+        assert_eq!(storage.local_path(&key), None);
+        storage.delete(&key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store() {
+        let store = Arc::new(InMemory::new());
+        let storage = S3Storage::with_store(store.clone(), "originals/");
+        exercise(&storage).await;
+
+        // Keys live below the prefix
+        storage.put("ab/cd/x.jpg", b"x").await.unwrap();
+        assert!(store.head(&ObjectPath::from("originals/ab/cd/x.jpg")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_escaping_keys() {
+        let storage = S3Storage::with_store(Arc::new(InMemory::new()), "originals");
+        assert!(read(&storage, "../secret").await.is_err());
+        assert!(read(&storage, "a//b").await.is_err());
+        assert!(storage.put("", b"x").await.is_err());
+    }
+
+    /// Runs against a real S3-compatible server when `S3_TEST_ENDPOINT` is set, e.g.
+    /// `docker run -p 9000:9000 minio/minio server /data` with a `gallerynet-test` bucket.
+    #[tokio::test]
+    async fn test_s3_compatible_server() {
+        let Ok(endpoint) = std::env::var("S3_TEST_ENDPOINT") else {
+            return;
+        };
+        let config = S3Config {
+            bucket: std::env::var("S3_TEST_BUCKET").unwrap_or_else(|_| "gallerynet-test".to_string()),
+            region: "us-east-1".to_string(),
+            endpoint: Some(endpoint),
+            access_key_id: std::env::var("S3_TEST_ACCESS_KEY_ID").unwrap_or_else(|_| "minioadmin".to_string()),
+            secret_access_key: std::env::var("S3_TEST_SECRET_ACCESS_KEY")
+                .unwrap_or_else(|_| "minioadmin".to_string()),
+        };
+        let storage = S3Storage::new(&config, "test").unwrap();
+        exercise(&storage).await;
+    }
+}
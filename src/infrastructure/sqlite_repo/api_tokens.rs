@@ -0,0 +1,164 @@
+use crate::domain::{ApiToken, DomainError, TokenScope};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension, Row};
+use uuid::Uuid;
+
+use super::SqliteRepository;
+
+const TOKEN_COLUMNS: &str = "id, user_id, name, scope, created_at, last_used_at";
+
+fn api_token_from_row(row: &Row) -> rusqlite::Result<ApiToken> {
+    let uuid_at = |idx: usize| -> rusqlite::Result<Uuid> {
+        let bytes: Vec<u8> = row.get(idx)?;
+        Uuid::from_slice(&bytes).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Blob, Box::new(e))
+        })
+    };
+    let parse_date = |idx: usize, value: String| -> rusqlite::Result<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&value)
+            .map(|d| d.with_timezone(&Utc))
+            .map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+            })
+    };
+    let scope_str: String = row.get(3)?;
+    let last_used_at: Option<String> = row.get(5)?;
+
+    Ok(ApiToken {
+        id: uuid_at(0)?,
+        user_id: uuid_at(1)?,
+        name: row.get(2)?,
+        // Unknown scopes get the least privilege rather than failing the whole query
+        scope: TokenScope::parse(&scope_str).unwrap_or(TokenScope::Read),
+        created_at: parse_date(4, row.get(4)?)?,
+        last_used_at: last_used_at.map(|v| parse_date(5, v)).transpose()?,
+    })
+}
+
+impl SqliteRepository {
+    pub(crate) fn create_api_token_impl(
+        &self,
+        token: &ApiToken,
+        token_hash: &str,
+    ) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO api_tokens (id, token_hash, user_id, name, scope, created_at, last_used_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    token.id.as_bytes(),
+                    token_hash,
+                    token.user_id.as_bytes(),
+                    token.name,
+                    token.scope.as_str(),
+                    token.created_at.to_rfc3339(),
+                    token.last_used_at.map(|d| d.to_rfc3339())
+                ],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn find_api_token_impl(&self, token_hash: &str) -> Result<Option<ApiToken>, DomainError> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM api_tokens WHERE token_hash = ?1", TOKEN_COLUMNS),
+                params![token_hash],
+                api_token_from_row,
+            )
+            .optional()
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn touch_api_token_impl(
+        &self,
+        id: Uuid,
+        last_used_at: DateTime<Utc>,
+    ) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE api_tokens SET last_used_at = ?2 WHERE id = ?1",
+                params![id.as_bytes(), last_used_at.to_rfc3339()],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn list_api_tokens_impl(&self, user_id: Uuid) -> Result<Vec<ApiToken>, DomainError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT {} FROM api_tokens WHERE user_id = ?1 ORDER BY created_at DESC",
+                    TOKEN_COLUMNS
+                ))
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            let rows = stmt
+                .query_map(params![user_id.as_bytes()], api_token_from_row)
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn delete_api_token_impl(&self, user_id: Uuid, id: Uuid) -> Result<bool, DomainError> {
+        self.with_conn(|conn| {
+            let deleted = conn
+                .execute(
+                    "DELETE FROM api_tokens WHERE id = ?1 AND user_id = ?2",
+                    params![id.as_bytes(), user_id.as_bytes()],
+                )
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(deleted > 0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::domain::{ApiToken, TokenScope};
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    fn token(user_id: Uuid, name: &str, scope: TokenScope) -> ApiToken {
+        ApiToken {
+            id: Uuid::new_v4(),
+            user_id,
+            name: name.to_string(),
+            scope,
+            created_at: Utc::now(),
+            last_used_at: None,
+        }
+    }
+
+    #[test]
+    fn test_api_token_lifecycle() {
+        let db = TestDb::new("test_api_token_lifecycle");
+        let user = Uuid::new_v4();
+        let backup = token(user, "backup", TokenScope::Read);
+        let mut hooks = token(user, "hooks", TokenScope::Upload);
+        hooks.created_at = backup.created_at + Duration::seconds(1);
+        db.repo.create_api_token_impl(&backup, "h1").unwrap();
+        db.repo.create_api_token_impl(&hooks, "h2").unwrap();
+
+        let found = db.repo.find_api_token_impl("h2").unwrap().unwrap();
+        assert_eq!(found.id, hooks.id);
+        assert_eq!(found.scope, TokenScope::Upload);
+        assert!(found.last_used_at.is_none());
+        assert!(db.repo.find_api_token_impl("nope").unwrap().is_none());
+
+        let used = Utc::now();
+        db.repo.touch_api_token_impl(backup.id, used).unwrap();
+        let listed = db.repo.list_api_tokens_impl(user).unwrap();
+        assert_eq!(listed.iter().map(|t| t.id).collect::<Vec<_>>(), vec![hooks.id, backup.id]);
+        assert_eq!(listed[1].last_used_at.unwrap().timestamp(), used.timestamp());
+
+        // Tokens can only be revoked by their owner
+        assert!(!db.repo.delete_api_token_impl(Uuid::new_v4(), backup.id).unwrap());
+        assert!(db.repo.delete_api_token_impl(user, backup.id).unwrap());
+        assert!(db.repo.find_api_token_impl("h1").unwrap().is_none());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::domain::{AuditEntry, DomainError};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Row};
+use uuid::Uuid;
+
+use super::SqliteRepository;
+
+const AUDIT_COLUMNS: &str = "id, created_at, user_id, username, ip, action, target_ids, detail";
+
+fn audit_entry_from_row(row: &Row) -> rusqlite::Result<AuditEntry> {
+    let created_at: String = row.get(1)?;
+    let user_id: Option<Vec<u8>> = row.get(2)?;
+    let target_ids: String = row.get(6)?;
+
+    Ok(AuditEntry {
+        id: row.get(0)?,
+        created_at: DateTime::parse_from_rfc3339(&created_at)
+            .map(|d| d.with_timezone(&Utc))
+            .map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e))
+            })?,
+        user_id: user_id
+            .map(|bytes| Uuid::from_slice(&bytes))
+            .transpose()
+            .map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Blob, Box::new(e))
+            })?,
+        username: row.get(3)?,
+        ip: row.get(4)?,
+        action: row.get(5)?,
+        target_ids: serde_json::from_str(&target_ids).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(6, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        detail: row.get(7)?,
+    })
+}
+
+impl SqliteRepository {
+    pub(crate) fn append_audit_impl(&self, entry: &AuditEntry) -> Result<(), DomainError> {
+        let target_ids = serde_json::to_string(&entry.target_ids)
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO audit_log (created_at, user_id, username, ip, action, target_ids, detail)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    entry.created_at.to_rfc3339(),
+                    entry.user_id.as_ref().map(|id| id.as_bytes().to_vec()),
+                    entry.username,
+                    entry.ip,
+                    entry.action,
+                    target_ids,
+                    entry.detail
+                ],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn list_audit_impl(
+        &self,
+        action: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<AuditEntry>, DomainError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT {} FROM audit_log WHERE ?1 IS NULL OR action = ?1
+                     ORDER BY id DESC LIMIT ?2 OFFSET ?3",
+                    AUDIT_COLUMNS
+                ))
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            let rows = stmt
+                .query_map(params![action, limit as i64, offset as i64], audit_entry_from_row)
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn delete_audit_before_impl(&self, before: DateTime<Utc>) -> Result<usize, DomainError> {
+        self.with_conn(|conn| {
+            conn.execute("DELETE FROM audit_log WHERE created_at < ?1", params![before.to_rfc3339()])
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::domain::AuditEntry;
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    fn entry(action: &str, age_days: i64) -> AuditEntry {
+        AuditEntry {
+            id: 0,
+            created_at: Utc::now() - Duration::days(age_days),
+            user_id: Some(Uuid::new_v4()),
+            username: Some("kim".to_string()),
+            ip: Some("198.51.100.7".to_string()),
+            action: action.to_string(),
+            target_ids: vec![Uuid::new_v4().to_string()],
+            detail: None,
+        }
+    }
+
+    #[test]
+    fn test_audit_log_is_append_only() {
+        let db = TestDb::new("test_audit_log_is_append_only");
+        let old = entry("media.delete", 400);
+        db.repo.append_audit_impl(&old).unwrap();
+        db.repo.append_audit_impl(&entry("auth.login", 1)).unwrap();
+        db.repo.append_audit_impl(&entry("media.delete", 0)).unwrap();
+
+        let all = db.repo.list_audit_impl(None, 10, 0).unwrap();
+        assert_eq!(all.iter().map(|e| e.action.as_str()).collect::<Vec<_>>(), ["media.delete", "auth.login", "media.delete"]);
+        assert!(all[0].id > all[2].id);
+        assert_eq!(all[2].target_ids, old.target_ids);
+        assert_eq!(all[2].user_id, old.user_id);
+        assert_eq!(db.repo.list_audit_impl(Some("media.delete"), 10, 0).unwrap().len(), 2);
+        assert_eq!(db.repo.list_audit_impl(None, 10, 2).unwrap().len(), 1);
+
+        // Entries cannot be rewritten, only pruned by age
+        let tampered = db.repo.with_conn(|conn| {
+            conn.execute("UPDATE audit_log SET action = 'nothing'", [])
+                .map_err(|e| crate::domain::DomainError::Database(e.to_string()))
+        });
+        assert!(tampered.is_err());
+        assert_eq!(db.repo.delete_audit_before_impl(Utc::now() - Duration::days(365)).unwrap(), 1);
+        assert_eq!(db.repo.list_audit_impl(None, 10, 0).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::domain::{DomainError, MediaCompanion};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension};
+use uuid::Uuid;
+
+use super::SqliteRepository;
+
+impl SqliteRepository {
+    pub(crate) fn add_companion_impl(&self, companion: &MediaCompanion) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO media_companions (media_id, filename, original_filename, size_bytes, content_hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    companion.media_id.as_bytes(),
+                    companion.filename,
+                    companion.original_filename,
+                    companion.size_bytes,
+                    companion.content_hash
+                ],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn find_companion_impl(&self, media_id: Uuid) -> Result<Option<MediaCompanion>, DomainError> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                "SELECT filename, original_filename, size_bytes, content_hash
+                 FROM media_companions WHERE media_id = ?1",
+                params![media_id.as_bytes()],
+                |row| {
+                    Ok(MediaCompanion {
+                        media_id,
+                        filename: row.get(0)?,
+                        original_filename: row.get(1)?,
+                        size_bytes: row.get(2)?,
+                        content_hash: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn find_companion_partner_impl(
+        &self,
+        owner: Option<Uuid>,
+        stem: &str,
+        extensions: &[&str],
+        since: DateTime<Utc>,
+    ) -> Result<Option<Uuid>, DomainError> {
+        let owner = owner.map(|id| id.as_bytes().to_vec());
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id FROM media
+                     WHERE owner_id IS ?1 AND original_filename = ?2 COLLATE NOCASE
+                       AND uploaded_at >= ?3 AND deleted_at IS NULL
+                       AND id NOT IN (SELECT media_id FROM media_companions)
+                     ORDER BY uploaded_at DESC LIMIT 1",
+                )
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            for extension in extensions {
+                let id: Option<Vec<u8>> = stmt
+                    .query_row(params![owner, format!("{}.{}", stem, extension), since.to_rfc3339()], |row| row.get(0))
+                    .optional()
+                    .map_err(|e| DomainError::Database(e.to_string()))?;
+                if let Some(id) = id {
+                    return Ok(Uuid::from_slice(&id).ok());
+                }
+            }
+            Ok(None)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::domain::{LibraryScope, MediaCompanion, MediaItem};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn media(original_filename: &str, owner_id: Option<Uuid>) -> MediaItem {
+        MediaItem {
+            id: Uuid::new_v4(),
+            filename: format!("{}.bin", Uuid::new_v4()),
+            original_filename: original_filename.to_string(),
+            media_type: "image".to_string(),
+            phash: "no_hash".to_string(),
+            content_hash: None,
+            owner_id,
+            uploaded_at: Utc::now(),
+            original_date: Utc::now(),
+            width: None,
+            height: None,
+            size_bytes: 0,
+            exif_json: None,
+            is_favorite: false,
+            tags: vec![],
+            processing: false,
+        }
+    }
+
+    #[test]
+    fn test_companion_pairing() {
+        let db = TestDb::new("test_companion_pairing");
+        let owner = Some(Uuid::new_v4());
+        let jpeg = media("DSC_0042.JPG", owner);
+        db.repo.save_metadata_and_vector_impl(&jpeg, None).unwrap();
+        db.repo.save_metadata_and_vector_impl(&media("DSC_0043.JPG", owner), None).unwrap();
+
+        let mut old = media("DSC_0044.JPG", owner);
+        old.uploaded_at = Utc::now() - chrono::Duration::days(365);
+        db.repo.save_metadata_and_vector_impl(&old, None).unwrap();
+
+        let since = Utc::now() - chrono::Duration::minutes(30);
+        let find = |owner, stem| db.repo.find_companion_partner_impl(owner, stem, &["jpg", "jpeg"], since).unwrap();
+        assert_eq!(find(owner, "DSC_0042"), Some(jpeg.id));
+        assert_eq!(find(None, "DSC_0042"), None);
+        assert_eq!(find(owner, "DSC_004"), None);
+        // A file of the same name uploaded long ago is another shot
+        assert_eq!(find(owner, "DSC_0044"), None);
+
+        let companion = MediaCompanion {
+            media_id: jpeg.id,
+            filename: "ab/cd/raw.nef".to_string(),
+            original_filename: "DSC_0042.NEF".to_string(),
+            size_bytes: 25_000_000,
+            content_hash: "raw_hash".to_string(),
+        };
+        db.repo.add_companion_impl(&companion).unwrap();
+        let found = db.repo.find_companion_impl(jpeg.id).unwrap().unwrap();
+        assert_eq!(found.filename, companion.filename);
+        assert_eq!(found.size_bytes, companion.size_bytes);
+        // An item is paired once, and the companion's bytes count as stored
+        assert_eq!(find(owner, "DSC_0042"), None);
+        assert_eq!(db.repo.find_by_content_hash_impl(&LibraryScope::ALL, "raw_hash").unwrap(), Some(jpeg.id));
+
+        db.repo.delete_impl(jpeg.id).unwrap();
+        assert!(db.repo.find_companion_impl(jpeg.id).unwrap().is_none());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::domain::{DomainError, LibraryScope};
+use rusqlite::params;
+use uuid::Uuid;
+
+use super::{scope_condition, SqliteRepository};
+
+impl SqliteRepository {
+    pub(crate) fn get_all_phashes_impl(
+        &self,
+        scope: &LibraryScope,
+        folder_id: Option<Uuid>,
+    ) -> Result<Vec<(Uuid, String, String)>, DomainError> {
+        self.with_conn(|conn| {
+            let mut sql = "SELECT m.id, m.media_type, m.phash FROM media m".to_string();
+            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+            if let Some(fid) = folder_id {
+                sql.push_str(" JOIN folder_media fm ON fm.media_id = m.id AND fm.folder_id = ?");
+                params_vec.push(Box::new(fid.as_bytes().to_vec()));
+            }
+            sql.push_str(" WHERE m.phash != 'no_hash' AND m.deleted_at IS NULL");
+            // Hidden items are left out so they cannot link two visible ones
+            if let Some((cond, param)) = scope_condition(scope, "m") {
+                sql.push_str(&format!(" AND {}", cond));
+                params_vec.push(param);
+            }
+
+            let mut stmt = conn
+                .prepare(&sql)
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            let rows = stmt
+                .query_map(rusqlite::params_from_iter(params_vec.iter()), |row| {
+                    let id_bytes: Vec<u8> = row.get(0)?;
+                    let id = Uuid::from_slice(&id_bytes).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            0,
+                            rusqlite::types::Type::Blob,
+                            Box::new(e),
+                        )
+                    })?;
+                    Ok((id, row.get(1)?, row.get(2)?))
+                })
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn merge_media_metadata_impl(
+        &self,
+        target: Uuid,
+        sources: &[Uuid],
+    ) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute("BEGIN", [])
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            for source in sources {
+                if *source == target {
+                    continue;
+                }
+                let statements = [
+                    // Manual tags win over an auto tag already on the target
+                    "INSERT INTO media_tags (media_id, tag_id, is_auto, confidence)
+                     SELECT ?1, tag_id, 0, NULL FROM media_tags WHERE media_id = ?2 AND is_auto = 0
+                     ON CONFLICT(media_id, tag_id) DO UPDATE SET is_auto = 0, confidence = NULL",
+                    "INSERT OR IGNORE INTO folder_media (folder_id, media_id, added_at)
+                     SELECT folder_id, ?1, added_at FROM folder_media WHERE media_id = ?2",
+                    "INSERT OR IGNORE INTO favorites (user_id, media_id, created_at)
+                     SELECT user_id, ?1, created_at FROM favorites WHERE media_id = ?2",
+                ];
+                for sql in statements {
+                    conn.execute(sql, params![target.as_bytes(), source.as_bytes()])
+                        .map_err(|e| {
+                            let _ = conn.execute("ROLLBACK", []);
+                            DomainError::Database(e.to_string())
+                        })?;
+                }
+            }
+
+            conn.execute("COMMIT", [])
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::domain::{LibraryScope, Role, User};
+    use crate::infrastructure::SqliteRepository;
+    use chrono::Utc;
+    use rusqlite::params;
+    use uuid::Uuid;
+
+    fn insert_media(repo: &SqliteRepository, id: Uuid, phash: &str) {
+        repo.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO media (id, filename, original_filename, size_bytes, phash, uploaded_at, original_date)
+                 VALUES (?1, 'f.jpg', 'f.jpg', 1, ?2, '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')",
+                params![id.as_bytes(), phash],
+            )
+            .unwrap();
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_get_all_phashes_skips_unhashed() {
+        let db = TestDb::new("test_all_phashes");
+        let hashed = Uuid::new_v4();
+        insert_media(&db.repo, hashed, "abc");
+        insert_media(&db.repo, Uuid::new_v4(), "no_hash");
+
+        let all = db.repo.get_all_phashes_impl(&LibraryScope::ALL, None).unwrap();
+        assert_eq!(all, vec![(hashed, "image".to_string(), "abc".to_string())]);
+
+        let folder = Uuid::new_v4();
+        db.repo.create_folder_impl(folder, "F", None).unwrap();
+        assert!(db.repo.get_all_phashes_impl(&LibraryScope::ALL, Some(folder)).unwrap().is_empty());
+        db.repo.add_media_to_folder_impl(folder, &[hashed]).unwrap();
+        assert_eq!(db.repo.get_all_phashes_impl(&LibraryScope::ALL, Some(folder)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_get_all_phashes_is_scoped() {
+        let db = TestDb::new("test_all_phashes_scoped");
+        let owner = User { id: Uuid::new_v4(), username: "kim".to_string(), role: Role::Member, created_at: Utc::now() };
+        db.repo.create_user_impl(&owner, "hash").unwrap();
+        let private = Uuid::new_v4();
+        insert_media(&db.repo, private, "abc");
+        db.repo
+            .with_conn(|conn| {
+                conn.execute(
+                    "UPDATE media SET owner_id = ?1 WHERE id = ?2",
+                    params![owner.id.as_bytes(), private.as_bytes()],
+                )
+                .unwrap();
+                Ok(())
+            })
+            .unwrap();
+
+        let scope = |id| LibraryScope { user_id: Some(id), all_owners: false };
+        assert_eq!(db.repo.get_all_phashes_impl(&scope(owner.id), None).unwrap().len(), 1);
+        assert!(db.repo.get_all_phashes_impl(&scope(Uuid::new_v4()), None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_merge_media_metadata() {
+        let db = TestDb::new("test_merge_metadata");
+        let keep = Uuid::new_v4();
+        let dup = Uuid::new_v4();
+        insert_media(&db.repo, keep, "a");
+        insert_media(&db.repo, dup, "b");
+
+        db.repo.update_media_tags_impl(keep, vec!["beach".to_string()]).unwrap();
+        db.repo
+            .update_media_tags_impl(dup, vec!["beach".to_string(), "sunset".to_string()])
+            .unwrap();
+        db.repo.set_favorite_impl(&LibraryScope::ALL, dup, true).unwrap();
+        let folder = Uuid::new_v4();
+        db.repo.create_folder_impl(folder, "Trip", None).unwrap();
+        db.repo.add_media_to_folder_impl(folder, &[dup]).unwrap();
+
+        db.repo.merge_media_metadata_impl(keep, &[dup]).unwrap();
+
+        let item = db.repo.find_by_id_impl(keep).unwrap().unwrap();
+        let tags: Vec<_> = item.tags.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(tags, vec!["beach", "sunset"]);
+        assert!(item.is_favorite);
+        let in_folder = db.repo.get_folder_media_files_impl(folder).unwrap();
+        assert!(in_folder.iter().any(|m| m.id == keep));
+
+        // Merging twice is harmless
+        db.repo.merge_media_metadata_impl(keep, &[dup]).unwrap();
+    }
+}
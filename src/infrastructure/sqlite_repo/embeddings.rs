@@ -3,7 +3,7 @@ use chrono::{DateTime, Utc};
 use rusqlite::params;
 use uuid::Uuid;
 
-use super::SqliteRepository;
+use super::{owner_from_row, SqliteRepository};
 
 impl SqliteRepository {
     pub(crate) fn get_all_embeddings_impl(
@@ -14,20 +14,21 @@ impl SqliteRepository {
             let (sql, params_vec): (String, Vec<Box<dyn rusqlite::types::ToSql>>) =
                 match folder_id {
                     Some(fid) => (
-                        "SELECT m.id, m.filename, m.original_filename, m.media_type, m.uploaded_at, m.original_date, v.embedding, m.size_bytes
+                        "SELECT m.id, m.filename, m.original_filename, m.media_type, m.uploaded_at, m.original_date, v.embedding, m.size_bytes, m.owner_id
                      FROM media m
                      JOIN folder_media fm ON fm.media_id = m.id
                      JOIN vec_media v ON v.rowid = m.rowid
-                     WHERE fm.folder_id = ?1"
+                     WHERE fm.folder_id = ?1 AND m.deleted_at IS NULL"
                             .to_string(),
                         vec![
                             Box::new(fid.as_bytes().to_vec()) as Box<dyn rusqlite::types::ToSql>
                         ],
                     ),
                     None => (
-                        "SELECT m.id, m.filename, m.original_filename, m.media_type, m.uploaded_at, m.original_date, v.embedding, m.size_bytes
+                        "SELECT m.id, m.filename, m.original_filename, m.media_type, m.uploaded_at, m.original_date, v.embedding, m.size_bytes, m.owner_id
                      FROM media m
-                     JOIN vec_media v ON v.rowid = m.rowid"
+                     JOIN vec_media v ON v.rowid = m.rowid
+                     WHERE m.deleted_at IS NULL"
                             .to_string(),
                         vec![],
                     ),
@@ -49,6 +50,7 @@ impl SqliteRepository {
                     let original_date_str: String = row.get(5)?;
                     let embedding_bytes: Vec<u8> = row.get(6)?;
                     let size_bytes: i64 = row.get(7)?;
+                    let owner_id = owner_from_row(row.get(8)?);
 
                     let id = Uuid::from_slice(&id_bytes).map_err(|e| {
                         rusqlite::Error::FromSqlConversionFailure(
@@ -81,11 +83,13 @@ impl SqliteRepository {
                         filename,
                         original_filename,
                         media_type,
+                        owner_id,
                         uploaded_at,
                         original_date,
                         size_bytes,
                         is_favorite: false,
                         tags: vec![],
+                        processing: false,
                     };
 
                     // Parse embedding bytes into f32 vec
@@ -129,6 +133,7 @@ impl SqliteRepository {
                 .prepare(
                     "SELECT m.id, v.embedding FROM media m
                  JOIN vec_media v ON v.rowid = m.rowid
+                 WHERE m.deleted_at IS NULL
                  ORDER BY RANDOM() LIMIT ?1",
                 )
                 .map_err(|e| DomainError::Database(e.to_string()))?;
@@ -187,7 +192,8 @@ impl SqliteRepository {
                          LIMIT ?2
                      ) v
                      JOIN media m ON m.rowid = v.rowid
-                     JOIN vec_media v2 ON v2.rowid = v.rowid",
+                     JOIN vec_media v2 ON v2.rowid = v.rowid
+                     WHERE m.deleted_at IS NULL",
                 )
                 .map_err(|e| DomainError::Database(e.to_string()))?;
 
@@ -1,12 +1,17 @@
-use crate::domain::{DomainError, Folder, MediaSummary};
+use crate::domain::{DomainError, Folder, LibraryScope, MediaSummary};
 use chrono::{DateTime, Utc};
 use rusqlite::params;
 use uuid::Uuid;
 
-use super::{load_tags_bulk, SqliteRepository};
+use super::{load_tags_bulk, owner_from_row, scope_condition, SqliteRepository};
 
 impl SqliteRepository {
-    pub(crate) fn create_folder_impl(&self, id: Uuid, name: &str) -> Result<Folder, DomainError> {
+    pub(crate) fn create_folder_impl(
+        &self,
+        id: Uuid,
+        name: &str,
+        owner_id: Option<Uuid>,
+    ) -> Result<Folder, DomainError> {
         let now = Utc::now();
         self.with_conn(|conn| {
             let max_order: i64 = conn
@@ -19,14 +24,21 @@ impl SqliteRepository {
             let sort_order = max_order + 1;
 
             conn.execute(
-                "INSERT INTO folders (id, name, created_at, sort_order) VALUES (?1, ?2, ?3, ?4)",
-                params![id.as_bytes(), name, now.to_rfc3339(), sort_order],
+                "INSERT INTO folders (id, name, created_at, sort_order, owner_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    id.as_bytes(),
+                    name,
+                    now.to_rfc3339(),
+                    sort_order,
+                    owner_id.map(|o| o.as_bytes().to_vec())
+                ],
             )
             .map_err(|e| DomainError::Database(e.to_string()))?;
 
             Ok(Folder {
                 id,
                 name: name.to_string(),
+                owner_id,
                 created_at: now,
                 item_count: 0,
                 sort_order,
@@ -38,9 +50,11 @@ impl SqliteRepository {
         self.with_conn(|conn| {
             let mut stmt = conn
                 .prepare(
-                    "SELECT f.id, f.name, f.created_at, COALESCE(c.cnt, 0), f.sort_order
+                    "SELECT f.id, f.name, f.created_at, COALESCE(c.cnt, 0), f.sort_order, f.owner_id
                  FROM folders f
-                 LEFT JOIN (SELECT folder_id, COUNT(*) as cnt FROM folder_media WHERE folder_id = ?1) c
+                 LEFT JOIN (SELECT fm.folder_id, COUNT(*) as cnt FROM folder_media fm
+                            JOIN media m ON m.id = fm.media_id
+                            WHERE fm.folder_id = ?1 AND m.deleted_at IS NULL) c
                    ON c.folder_id = f.id
                  WHERE f.id = ?1",
                 )
@@ -52,6 +66,7 @@ impl SqliteRepository {
                 let created_at_str: String = row.get(2)?;
                 let item_count: i64 = row.get(3)?;
                 let sort_order: i64 = row.get(4)?;
+                let owner_id = owner_from_row(row.get(5)?);
 
                 let id = Uuid::from_slice(&id_bytes).map_err(|e| {
                     rusqlite::Error::FromSqlConversionFailure(
@@ -73,6 +88,7 @@ impl SqliteRepository {
                 Ok(Folder {
                     id,
                     name,
+                    owner_id,
                     created_at,
                     item_count,
                     sort_order,
@@ -87,25 +103,39 @@ impl SqliteRepository {
         })
     }
 
-    pub(crate) fn list_folders_impl(&self) -> Result<Vec<Folder>, DomainError> {
+    pub(crate) fn list_folders_impl(
+        &self,
+        scope: &LibraryScope,
+    ) -> Result<Vec<Folder>, DomainError> {
         self.with_conn(|conn| {
-            let mut stmt = conn
-                .prepare(
-                    "SELECT f.id, f.name, f.created_at, COALESCE(c.cnt, 0), f.sort_order
+            let mut sql = "SELECT f.id, f.name, f.created_at, COALESCE(c.cnt, 0), f.sort_order, f.owner_id
                  FROM folders f
-                 LEFT JOIN (SELECT folder_id, COUNT(*) as cnt FROM folder_media GROUP BY folder_id) c
-                   ON c.folder_id = f.id
-                 ORDER BY f.sort_order ASC, f.name COLLATE NOCASE",
-                )
+                 LEFT JOIN (SELECT fm.folder_id, COUNT(*) as cnt FROM folder_media fm
+                            JOIN media m ON m.id = fm.media_id
+                            WHERE m.deleted_at IS NULL
+                            GROUP BY fm.folder_id) c
+                   ON c.folder_id = f.id"
+                .to_string();
+            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+            if let Some((cond, param)) = scope_condition(scope, "f") {
+                sql.push_str(" WHERE ");
+                sql.push_str(&cond);
+                params_vec.push(param);
+            }
+            sql.push_str(" ORDER BY f.sort_order ASC, f.name COLLATE NOCASE");
+
+            let mut stmt = conn
+                .prepare(&sql)
                 .map_err(|e| DomainError::Database(e.to_string()))?;
 
             let rows = stmt
-                .query_map([], |row| {
+                .query_map(rusqlite::params_from_iter(params_vec.iter()), |row| {
                     let id_bytes: Vec<u8> = row.get(0)?;
                     let name: String = row.get(1)?;
                     let created_at_str: String = row.get(2)?;
                     let item_count: i64 = row.get(3)?;
                     let sort_order: i64 = row.get(4)?;
+                    let owner_id = owner_from_row(row.get(5)?);
 
                     let id = Uuid::from_slice(&id_bytes).map_err(|e| {
                         rusqlite::Error::FromSqlConversionFailure(
@@ -127,6 +157,7 @@ impl SqliteRepository {
                     Ok(Folder {
                         id,
                         name,
+                        owner_id,
                         created_at,
                         item_count,
                         sort_order,
@@ -149,6 +180,8 @@ impl SqliteRepository {
                 params![id.as_bytes()],
             )
             .map_err(|e| DomainError::Database(e.to_string()))?;
+            conn.execute("DELETE FROM shares WHERE folder_id = ?1", params![id.as_bytes()])
+                .map_err(|e| DomainError::Database(e.to_string()))?;
             let deleted = conn
                 .execute("DELETE FROM folders WHERE id = ?1", params![id.as_bytes()])
                 .map_err(|e| DomainError::Database(e.to_string()))?;
@@ -231,6 +264,7 @@ impl SqliteRepository {
 
     pub(crate) fn find_all_in_folder_impl(
         &self,
+        scope: &LibraryScope,
         folder_id: Uuid,
         limit: usize,
         offset: usize,
@@ -247,15 +281,23 @@ impl SqliteRepository {
                 _ => "m.original_date",
             };
 
-            let mut sql = "SELECT m.id, m.filename, m.original_filename, m.media_type, m.uploaded_at, m.original_date, (f.media_id IS NOT NULL) as is_favorite, m.size_bytes
+            let mut sql = "SELECT m.id, m.filename, m.original_filename, m.media_type, m.uploaded_at, m.original_date, (f.media_id IS NOT NULL) as is_favorite, m.size_bytes, m.owner_id, m.processing
                            FROM media m
                            JOIN folder_media fm ON fm.media_id = m.id
-                           LEFT JOIN favorites f ON f.media_id = m.id
-                           WHERE fm.folder_id = ?"
+                           LEFT JOIN favorites f ON f.media_id = m.id AND f.user_id = ?
+                           WHERE fm.folder_id = ? AND m.deleted_at IS NULL"
                 .to_string();
 
-            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> =
-                vec![Box::new(folder_id.as_bytes().to_vec())];
+            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = vec![
+                Box::new(scope.favorites_key().as_bytes().to_vec()),
+                Box::new(folder_id.as_bytes().to_vec()),
+            ];
+
+            if let Some((cond, param)) = scope_condition(scope, "m") {
+                sql.push_str(" AND ");
+                sql.push_str(&cond);
+                params_vec.push(param);
+            }
 
             if let Some(mt) = media_type {
                 sql.push_str(" AND m.media_type = ?");
@@ -303,6 +345,8 @@ impl SqliteRepository {
                     let original_date_str: String = row.get(5)?;
                     let is_favorite: bool = row.get(6)?;
                     let size_bytes: i64 = row.get(7)?;
+                    let owner_id = owner_from_row(row.get(8)?);
+                    let processing: bool = row.get(9)?;
 
                     let id = Uuid::from_slice(&id_bytes).map_err(|e| {
                         rusqlite::Error::FromSqlConversionFailure(
@@ -337,11 +381,13 @@ impl SqliteRepository {
                             filename,
                             original_filename,
                             media_type,
+                            owner_id,
                             uploaded_at,
                             original_date,
                             size_bytes,
                             is_favorite,
                             tags: vec![],
+                            processing,
                         },
                     ))
                 })
@@ -379,10 +425,10 @@ impl SqliteRepository {
         self.with_conn(|conn| {
             let mut stmt = conn
                 .prepare(
-                    "SELECT m.id, m.filename, m.original_filename, m.media_type, m.uploaded_at, m.original_date, m.size_bytes
+                    "SELECT m.id, m.filename, m.original_filename, m.media_type, m.uploaded_at, m.original_date, m.size_bytes, m.owner_id
                  FROM media m
                  JOIN folder_media fm ON fm.media_id = m.id
-                 WHERE fm.folder_id = ?1",
+                 WHERE fm.folder_id = ?1 AND m.deleted_at IS NULL",
                 )
                 .map_err(|e| DomainError::Database(e.to_string()))?;
 
@@ -395,6 +441,7 @@ impl SqliteRepository {
                     let timestamp_str: String = row.get(4)?;
                     let original_date_str: String = row.get(5)?;
                     let size_bytes: i64 = row.get(6)?;
+                    let owner_id = owner_from_row(row.get(7)?);
 
                     let id = Uuid::from_slice(&id_bytes).map_err(|e| {
                         rusqlite::Error::FromSqlConversionFailure(
@@ -427,11 +474,13 @@ impl SqliteRepository {
                         filename,
                         original_filename,
                         media_type,
+                        owner_id,
                         uploaded_at,
                         original_date,
                         size_bytes,
                         is_favorite: false,
                         tags: vec![],
+                        processing: false,
                     })
                 })
                 .map_err(|e| DomainError::Database(e.to_string()))?;
@@ -448,6 +497,7 @@ impl SqliteRepository {
 #[cfg(test)]
 mod tests {
     use super::super::TestDb;
+    use crate::domain::LibraryScope;
     use rusqlite::params;
     use uuid::Uuid;
 
@@ -479,7 +529,7 @@ mod tests {
         let db = TestDb::new("test_create_folder");
         let id = Uuid::new_v4();
 
-        let folder = db.repo.create_folder_impl(id, "My Folder").unwrap();
+        let folder = db.repo.create_folder_impl(id, "My Folder", None).unwrap();
         assert_eq!(folder.id, id);
         assert_eq!(folder.name, "My Folder");
         assert_eq!(folder.item_count, 0);
@@ -503,11 +553,11 @@ mod tests {
         let id2 = Uuid::new_v4();
         let id3 = Uuid::new_v4();
 
-        db.repo.create_folder_impl(id1, "Zebras").unwrap();
-        db.repo.create_folder_impl(id2, "Apples").unwrap();
-        db.repo.create_folder_impl(id3, "Mango").unwrap();
+        db.repo.create_folder_impl(id1, "Zebras", None).unwrap();
+        db.repo.create_folder_impl(id2, "Apples", None).unwrap();
+        db.repo.create_folder_impl(id3, "Mango", None).unwrap();
 
-        let folders = db.repo.list_folders_impl().unwrap();
+        let folders = db.repo.list_folders_impl(&LibraryScope::ALL).unwrap();
         assert_eq!(folders.len(), 3);
         // Ordered by sort_order (creation order), not alphabetically
         assert_eq!(folders[0].id, id1);
@@ -519,7 +569,7 @@ mod tests {
     fn test_rename_folder() {
         let db = TestDb::new("test_rename_folder");
         let id = Uuid::new_v4();
-        db.repo.create_folder_impl(id, "Old Name").unwrap();
+        db.repo.create_folder_impl(id, "Old Name", None).unwrap();
 
         db.repo.rename_folder_impl(id, "New Name").unwrap();
         let found = db.repo.get_folder_impl(id).unwrap().unwrap();
@@ -537,7 +587,7 @@ mod tests {
     fn test_delete_folder() {
         let db = TestDb::new("test_delete_folder");
         let id = Uuid::new_v4();
-        db.repo.create_folder_impl(id, "To Delete").unwrap();
+        db.repo.create_folder_impl(id, "To Delete", None).unwrap();
 
         db.repo.delete_folder_impl(id).unwrap();
         assert!(db.repo.get_folder_impl(id).unwrap().is_none());
@@ -557,16 +607,16 @@ mod tests {
         let id2 = Uuid::new_v4();
         let id3 = Uuid::new_v4();
 
-        db.repo.create_folder_impl(id1, "First").unwrap();
-        db.repo.create_folder_impl(id2, "Second").unwrap();
-        db.repo.create_folder_impl(id3, "Third").unwrap();
+        db.repo.create_folder_impl(id1, "First", None).unwrap();
+        db.repo.create_folder_impl(id2, "Second", None).unwrap();
+        db.repo.create_folder_impl(id3, "Third", None).unwrap();
 
         // Reverse the order
         db.repo
             .reorder_folders_impl(&[(id3, 0), (id2, 1), (id1, 2)])
             .unwrap();
 
-        let folders = db.repo.list_folders_impl().unwrap();
+        let folders = db.repo.list_folders_impl(&LibraryScope::ALL).unwrap();
         assert_eq!(folders[0].id, id3);
         assert_eq!(folders[1].id, id2);
         assert_eq!(folders[2].id, id1);
@@ -582,7 +632,7 @@ mod tests {
         let id2 = Uuid::new_v4();
         let id3 = Uuid::new_v4();
 
-        db.repo.create_folder_impl(folder_id, "Test").unwrap();
+        db.repo.create_folder_impl(folder_id, "Test", None).unwrap();
         insert_media(&db, id1, "2024-01-01T00:00:00Z", 100, "image");
         insert_media(&db, id2, "2024-02-01T00:00:00Z", 200, "image");
         insert_media(&db, id3, "2024-03-01T00:00:00Z", 300, "image");
@@ -601,7 +651,7 @@ mod tests {
         // Verify listing
         let items = db
             .repo
-            .find_all_in_folder_impl(folder_id, 10, 0, None, false, None, false, "date")
+            .find_all_in_folder_impl(&LibraryScope::ALL, folder_id, 10, 0, None, false, None, false, "date")
             .unwrap();
         assert_eq!(items.len(), 3);
 
@@ -614,7 +664,7 @@ mod tests {
 
         let items = db
             .repo
-            .find_all_in_folder_impl(folder_id, 10, 0, None, false, None, false, "date")
+            .find_all_in_folder_impl(&LibraryScope::ALL, folder_id, 10, 0, None, false, None, false, "date")
             .unwrap();
         assert_eq!(items.len(), 2);
         assert!(!items.iter().any(|m| m.id == id2));
@@ -626,7 +676,7 @@ mod tests {
         let folder_id = Uuid::new_v4();
         let id1 = Uuid::new_v4();
 
-        db.repo.create_folder_impl(folder_id, "Test").unwrap();
+        db.repo.create_folder_impl(folder_id, "Test", None).unwrap();
         insert_media(&db, id1, "2024-01-01T00:00:00Z", 100, "image");
 
         // Add once
@@ -640,7 +690,7 @@ mod tests {
         // Still only 1 item
         let items = db
             .repo
-            .find_all_in_folder_impl(folder_id, 10, 0, None, false, None, false, "date")
+            .find_all_in_folder_impl(&LibraryScope::ALL, folder_id, 10, 0, None, false, None, false, "date")
             .unwrap();
         assert_eq!(items.len(), 1);
     }
@@ -651,7 +701,7 @@ mod tests {
         let folder_id = Uuid::new_v4();
         let id1 = Uuid::new_v4();
 
-        db.repo.create_folder_impl(folder_id, "Test").unwrap();
+        db.repo.create_folder_impl(folder_id, "Test", None).unwrap();
         insert_media(&db, id1, "2024-01-01T00:00:00Z", 100, "image");
         db.repo.add_media_to_folder_impl(folder_id, &[id1]).unwrap();
 
@@ -687,7 +737,7 @@ mod tests {
         let img = Uuid::new_v4();
         let vid = Uuid::new_v4();
 
-        db.repo.create_folder_impl(folder_id, "Mixed").unwrap();
+        db.repo.create_folder_impl(folder_id, "Mixed", None).unwrap();
         insert_media(&db, img, "2024-01-01T00:00:00Z", 100, "image");
         insert_media(&db, vid, "2024-02-01T00:00:00Z", 200, "video");
         db.repo
@@ -696,14 +746,14 @@ mod tests {
 
         let images = db
             .repo
-            .find_all_in_folder_impl(folder_id, 10, 0, Some("image"), false, None, false, "date")
+            .find_all_in_folder_impl(&LibraryScope::ALL, folder_id, 10, 0, Some("image"), false, None, false, "date")
             .unwrap();
         assert_eq!(images.len(), 1);
         assert_eq!(images[0].id, img);
 
         let videos = db
             .repo
-            .find_all_in_folder_impl(folder_id, 10, 0, Some("video"), false, None, false, "date")
+            .find_all_in_folder_impl(&LibraryScope::ALL, folder_id, 10, 0, Some("video"), false, None, false, "date")
             .unwrap();
         assert_eq!(videos.len(), 1);
         assert_eq!(videos[0].id, vid);
@@ -716,17 +766,17 @@ mod tests {
         let id1 = Uuid::new_v4();
         let id2 = Uuid::new_v4();
 
-        db.repo.create_folder_impl(folder_id, "Favs").unwrap();
+        db.repo.create_folder_impl(folder_id, "Favs", None).unwrap();
         insert_media(&db, id1, "2024-01-01T00:00:00Z", 100, "image");
         insert_media(&db, id2, "2024-02-01T00:00:00Z", 200, "image");
         db.repo
             .add_media_to_folder_impl(folder_id, &[id1, id2])
             .unwrap();
-        db.repo.set_favorite_impl(id1, true).unwrap();
+        db.repo.set_favorite_impl(&LibraryScope::ALL, id1, true).unwrap();
 
         let favs = db
             .repo
-            .find_all_in_folder_impl(folder_id, 10, 0, None, true, None, false, "date")
+            .find_all_in_folder_impl(&LibraryScope::ALL, folder_id, 10, 0, None, true, None, false, "date")
             .unwrap();
         assert_eq!(favs.len(), 1);
         assert_eq!(favs[0].id, id1);
@@ -739,7 +789,7 @@ mod tests {
         let id1 = Uuid::new_v4();
         let id2 = Uuid::new_v4();
 
-        db.repo.create_folder_impl(folder_id, "Tagged").unwrap();
+        db.repo.create_folder_impl(folder_id, "Tagged", None).unwrap();
         insert_media(&db, id1, "2024-01-01T00:00:00Z", 100, "image");
         insert_media(&db, id2, "2024-02-01T00:00:00Z", 200, "image");
         db.repo
@@ -752,6 +802,7 @@ mod tests {
         let tagged = db
             .repo
             .find_all_in_folder_impl(
+                &LibraryScope::ALL,
                 folder_id,
                 10,
                 0,
@@ -770,7 +821,7 @@ mod tests {
     fn test_folder_pagination() {
         let db = TestDb::new("test_folder_pagination");
         let folder_id = Uuid::new_v4();
-        db.repo.create_folder_impl(folder_id, "Big").unwrap();
+        db.repo.create_folder_impl(folder_id, "Big", None).unwrap();
 
         let ids: Vec<Uuid> = (0..7).map(|_| Uuid::new_v4()).collect();
         for (i, id) in ids.iter().enumerate() {
@@ -787,14 +838,14 @@ mod tests {
         // Page 1
         let p1 = db
             .repo
-            .find_all_in_folder_impl(folder_id, 3, 0, None, false, None, false, "date")
+            .find_all_in_folder_impl(&LibraryScope::ALL, folder_id, 3, 0, None, false, None, false, "date")
             .unwrap();
         assert_eq!(p1.len(), 3);
 
         // Page 2
         let p2 = db
             .repo
-            .find_all_in_folder_impl(folder_id, 3, 3, None, false, None, false, "date")
+            .find_all_in_folder_impl(&LibraryScope::ALL, folder_id, 3, 3, None, false, None, false, "date")
             .unwrap();
         assert_eq!(p2.len(), 3);
 
@@ -807,7 +858,7 @@ mod tests {
         // Page 3 — only 1 left
         let p3 = db
             .repo
-            .find_all_in_folder_impl(folder_id, 3, 6, None, false, None, false, "date")
+            .find_all_in_folder_impl(&LibraryScope::ALL, folder_id, 3, 6, None, false, None, false, "date")
             .unwrap();
         assert_eq!(p3.len(), 1);
     }
@@ -819,7 +870,7 @@ mod tests {
         let id1 = Uuid::new_v4();
         let id2 = Uuid::new_v4();
 
-        db.repo.create_folder_impl(folder_id, "Download").unwrap();
+        db.repo.create_folder_impl(folder_id, "Download", None).unwrap();
         insert_media(&db, id1, "2024-01-01T00:00:00Z", 1000, "image");
         insert_media(&db, id2, "2024-02-01T00:00:00Z", 2000, "video");
         db.repo
@@ -0,0 +1,67 @@
+use crate::domain::DomainError;
+use chrono::Utc;
+use rusqlite::params;
+use uuid::Uuid;
+
+use super::SqliteRepository;
+
+impl SqliteRepository {
+    pub(crate) fn is_path_imported_impl(&self, path: &str) -> Result<bool, DomainError> {
+        self.with_conn(|conn| {
+            let count: i64 = conn
+                .query_row(
+                    "SELECT count(*) FROM imported_paths WHERE path = ?1",
+                    params![path],
+                    |row| row.get(0),
+                )
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(count > 0)
+        })
+    }
+
+    pub(crate) fn mark_path_imported_impl(
+        &self,
+        path: &str,
+        media_id: Option<Uuid>,
+    ) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO imported_paths (path, media_id, imported_at) VALUES (?1, ?2, ?3)",
+                params![
+                    path,
+                    media_id.map(|id| id.as_bytes().to_vec()),
+                    Utc::now().to_rfc3339()
+                ],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_mark_and_check_imported_path() {
+        let db = TestDb::new("test_imported_paths");
+
+        assert!(!db.repo.is_path_imported_impl("/photos/a.jpg").unwrap());
+
+        db.repo
+            .mark_path_imported_impl("/photos/a.jpg", Some(Uuid::new_v4()))
+            .unwrap();
+        // Duplicates are recorded without a media id
+        db.repo.mark_path_imported_impl("/photos/b.jpg", None).unwrap();
+
+        assert!(db.repo.is_path_imported_impl("/photos/a.jpg").unwrap());
+        assert!(db.repo.is_path_imported_impl("/photos/b.jpg").unwrap());
+        assert!(!db.repo.is_path_imported_impl("/photos/c.jpg").unwrap());
+
+        // Re-marking the same path is idempotent
+        db.repo.mark_path_imported_impl("/photos/a.jpg", None).unwrap();
+        assert!(db.repo.is_path_imported_impl("/photos/a.jpg").unwrap());
+    }
+}
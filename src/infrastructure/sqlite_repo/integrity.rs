@@ -0,0 +1,134 @@
+use crate::domain::{DomainError, LibraryScope};
+use rusqlite::params;
+use uuid::Uuid;
+
+use super::{scope_condition, SqliteRepository};
+
+impl SqliteRepository {
+    pub(crate) fn find_by_content_hash_impl(
+        &self,
+        scope: &LibraryScope,
+        content_hash: &str,
+    ) -> Result<Option<Uuid>, DomainError> {
+        self.with_conn(|conn| {
+            // The other half of a RAW+JPEG pair counts as part of its item
+            let mut sql = "SELECT id FROM (
+                     SELECT id, owner_id FROM media WHERE content_hash = ?1 AND deleted_at IS NULL
+                     UNION ALL
+                     SELECT m.id, m.owner_id FROM media_companions c JOIN media m ON m.id = c.media_id
+                     WHERE c.content_hash = ?1 AND m.deleted_at IS NULL
+                 ) m"
+                .to_string();
+            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = vec![Box::new(content_hash.to_string())];
+            if let Some((cond, param)) = scope_condition(scope, "m") {
+                sql.push_str(" WHERE ");
+                sql.push_str(&cond);
+                params_vec.push(param);
+            }
+            sql.push_str(" LIMIT 1");
+            let result = conn.query_row(
+                &sql,
+                rusqlite::params_from_iter(params_vec.iter()),
+                |row| row.get::<_, Vec<u8>>(0),
+            );
+            match result {
+                Ok(id) => Ok(Uuid::from_slice(&id).ok()),
+                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(DomainError::Database(e.to_string())),
+            }
+        })
+    }
+
+    pub(crate) fn set_content_hash_impl(
+        &self,
+        id: Uuid,
+        content_hash: &str,
+    ) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            let updated = conn
+                .execute(
+                    "UPDATE media SET content_hash = ?2 WHERE id = ?1",
+                    params![id.as_bytes(), content_hash],
+                )
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            if updated == 0 {
+                return Err(DomainError::NotFound);
+            }
+            Ok(())
+        })
+    }
+
+    pub(crate) fn find_stored_originals_impl(
+        &self,
+    ) -> Result<Vec<(Uuid, String, Option<String>)>, DomainError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id, filename, content_hash FROM media
+                     WHERE filename NOT LIKE '/%'
+                     ORDER BY uploaded_at",
+                )
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            let rows = stmt
+                .query_map([], |row| {
+                    let id_bytes: Vec<u8> = row.get(0)?;
+                    let id = Uuid::from_slice(&id_bytes).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            0,
+                            rusqlite::types::Type::Blob,
+                            Box::new(e),
+                        )
+                    })?;
+                    Ok((id, row.get(1)?, row.get(2)?))
+                })
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::domain::LibraryScope;
+    use crate::infrastructure::SqliteRepository;
+    use rusqlite::params;
+    use uuid::Uuid;
+
+    fn insert_media(repo: &SqliteRepository, id: Uuid, filename: &str) {
+        repo.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO media (id, filename, original_filename, size_bytes, phash, uploaded_at, original_date)
+                 VALUES (?1, ?2, 'a.jpg', 1, 'ph', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')",
+                params![id.as_bytes(), filename],
+            )
+            .unwrap();
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_content_hash_lookup_and_backfill() {
+        let db = TestDb::new("test_content_hash");
+        let stored = Uuid::new_v4();
+        let external = Uuid::new_v4();
+        insert_media(&db.repo, stored, "ab/cd/a.jpg");
+        insert_media(&db.repo, external, "/archive/a.jpg");
+
+        assert_eq!(db.repo.find_by_content_hash_impl(&LibraryScope::ALL, "abc").unwrap(), None);
+        assert_eq!(
+            db.repo.find_stored_originals_impl().unwrap(),
+            vec![(stored, "ab/cd/a.jpg".to_string(), None)]
+        );
+
+        db.repo.set_content_hash_impl(stored, "abc").unwrap();
+        assert_eq!(db.repo.find_by_content_hash_impl(&LibraryScope::ALL, "abc").unwrap(), Some(stored));
+        assert_eq!(db.repo.find_by_id_impl(stored).unwrap().unwrap().content_hash.as_deref(), Some("abc"));
+
+        assert!(db.repo.set_content_hash_impl(Uuid::new_v4(), "x").is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use crate::domain::{DomainError, Job, JobKind, JobState};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension, Row};
+use uuid::Uuid;
+
+use super::SqliteRepository;
+
+const JOB_COLUMNS: &str = "id, spec, state, done, total, attempts, max_attempts, error, result, \
+                           created_by, created_at, updated_at, run_after";
+
+fn job_from_row(row: &Row) -> rusqlite::Result<Job> {
+    let conversion = |idx: usize, kind: rusqlite::types::Type, e: Box<dyn std::error::Error + Send + Sync>| {
+        rusqlite::Error::FromSqlConversionFailure(idx, kind, e)
+    };
+    let uuid_at = |idx: usize, bytes: Vec<u8>| -> rusqlite::Result<Uuid> {
+        Uuid::from_slice(&bytes).map_err(|e| conversion(idx, rusqlite::types::Type::Blob, Box::new(e)))
+    };
+    let parse_date = |idx: usize| -> rusqlite::Result<DateTime<Utc>> {
+        let value: String = row.get(idx)?;
+        DateTime::parse_from_rfc3339(&value)
+            .map(|d| d.with_timezone(&Utc))
+            .map_err(|e| conversion(idx, rusqlite::types::Type::Text, Box::new(e)))
+    };
+    let spec: String = row.get(1)?;
+    let state: String = row.get(2)?;
+    let result: Option<String> = row.get(8)?;
+    let created_by: Option<Vec<u8>> = row.get(9)?;
+
+    Ok(Job {
+        id: uuid_at(0, row.get(0)?)?,
+        kind: serde_json::from_str::<JobKind>(&spec)
+            .map_err(|e| conversion(1, rusqlite::types::Type::Text, Box::new(e)))?,
+        state: JobState::parse(&state).ok_or_else(|| {
+            conversion(2, rusqlite::types::Type::Text, format!("unknown job state '{}'", state).into())
+        })?,
+        done: row.get::<_, i64>(3)? as usize,
+        total: row.get::<_, i64>(4)? as usize,
+        attempts: row.get(5)?,
+        max_attempts: row.get(6)?,
+        error: row.get(7)?,
+        result: result
+            .map(|r| serde_json::from_str(&r))
+            .transpose()
+            .map_err(|e| conversion(8, rusqlite::types::Type::Text, Box::new(e)))?,
+        created_by: created_by.map(|b| uuid_at(9, b)).transpose()?,
+        created_at: parse_date(10)?,
+        updated_at: parse_date(11)?,
+        run_after: parse_date(12)?,
+    })
+}
+
+impl SqliteRepository {
+    pub(crate) fn create_job_impl(&self, job: &Job) -> Result<(), DomainError> {
+        let spec = serde_json::to_string(&job.kind).map_err(|e| DomainError::Database(e.to_string()))?;
+        let result = job
+            .result
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO jobs (id, kind, spec, state, done, total, attempts, max_attempts, error,
+                                   result, created_by, created_at, updated_at, run_after)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                params![
+                    job.id.as_bytes(),
+                    job.kind.name(),
+                    spec,
+                    job.state.as_str(),
+                    job.done as i64,
+                    job.total as i64,
+                    job.attempts,
+                    job.max_attempts,
+                    job.error,
+                    result,
+                    job.created_by.as_ref().map(|id| id.as_bytes().to_vec()),
+                    job.created_at.to_rfc3339(),
+                    job.updated_at.to_rfc3339(),
+                    job.run_after.to_rfc3339()
+                ],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn find_job_impl(&self, id: Uuid) -> Result<Option<Job>, DomainError> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM jobs WHERE id = ?1", JOB_COLUMNS),
+                params![id.as_bytes()],
+                job_from_row,
+            )
+            .optional()
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn list_jobs_impl(
+        &self,
+        created_by: Option<Uuid>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<Job>, DomainError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT {} FROM jobs WHERE ?1 IS NULL OR created_by = ?1
+                     ORDER BY created_at DESC LIMIT ?2 OFFSET ?3",
+                    JOB_COLUMNS
+                ))
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            let rows = stmt
+                .query_map(
+                    params![created_by.as_ref().map(|id| id.as_bytes().to_vec()), limit as i64, offset as i64],
+                    job_from_row,
+                )
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn claim_next_job_impl(&self, now: DateTime<Utc>) -> Result<Option<Job>, DomainError> {
+        self.with_conn(|conn| {
+            // One statement, so two workers cannot both see the job as queued
+            conn.query_row(
+                &format!(
+                    "UPDATE jobs SET state = 'running', attempts = attempts + 1, updated_at = ?1
+                     WHERE id = (
+                         SELECT id FROM jobs WHERE state = 'queued' AND run_after <= ?1
+                         ORDER BY run_after, created_at LIMIT 1
+                     )
+                     RETURNING {}",
+                    JOB_COLUMNS
+                ),
+                params![now.to_rfc3339()],
+                job_from_row,
+            )
+            .optional()
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn update_job_progress_impl(
+        &self,
+        id: Uuid,
+        done: usize,
+        total: usize,
+    ) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE jobs SET done = ?2, total = ?3, updated_at = ?4 WHERE id = ?1",
+                params![id.as_bytes(), done as i64, total as i64, Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn finish_job_impl(
+        &self,
+        id: Uuid,
+        state: JobState,
+        error: Option<&str>,
+        result: Option<&serde_json::Value>,
+    ) -> Result<(), DomainError> {
+        let result = result
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE jobs SET state = ?2, error = ?3, result = ?4, updated_at = ?5 WHERE id = ?1",
+                params![id.as_bytes(), state.as_str(), error, result, Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn reschedule_job_impl(
+        &self,
+        id: Uuid,
+        run_after: DateTime<Utc>,
+        error: &str,
+    ) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE jobs SET state = 'queued', error = ?2, run_after = ?3, updated_at = ?4
+                 WHERE id = ?1 AND state = 'running'",
+                params![id.as_bytes(), error, run_after.to_rfc3339(), Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn cancel_queued_job_impl(&self, id: Uuid) -> Result<bool, DomainError> {
+        self.with_conn(|conn| {
+            let updated = conn
+                .execute(
+                    "UPDATE jobs SET state = 'cancelled', updated_at = ?2 WHERE id = ?1 AND state = 'queued'",
+                    params![id.as_bytes(), Utc::now().to_rfc3339()],
+                )
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(updated > 0)
+        })
+    }
+
+    pub(crate) fn retry_job_impl(&self, id: Uuid, now: DateTime<Utc>) -> Result<bool, DomainError> {
+        self.with_conn(|conn| {
+            let updated = conn
+                .execute(
+                    "UPDATE jobs SET state = 'queued', attempts = 0, done = 0, total = 0, error = NULL,
+                                     result = NULL, run_after = ?2, updated_at = ?2
+                     WHERE id = ?1 AND state IN ('failed', 'cancelled')",
+                    params![id.as_bytes(), now.to_rfc3339()],
+                )
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(updated > 0)
+        })
+    }
+
+    pub(crate) fn requeue_running_jobs_impl(&self) -> Result<usize, DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE jobs SET state = 'queued', updated_at = ?1 WHERE state = 'running'",
+                params![Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn delete_finished_jobs_before_impl(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<usize, DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "DELETE FROM jobs WHERE state IN ('done', 'failed', 'cancelled') AND updated_at < ?1",
+                params![before.to_rfc3339()],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::domain::{Job, JobKind, JobState};
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    fn job(kind: JobKind, run_after_secs: i64) -> Job {
+        let now = Utc::now();
+        Job {
+            id: Uuid::new_v4(),
+            kind,
+            state: JobState::Queued,
+            done: 0,
+            total: 0,
+            attempts: 0,
+            max_attempts: 3,
+            error: None,
+            result: None,
+            created_by: None,
+            created_at: now,
+            updated_at: now,
+            run_after: now + Duration::seconds(run_after_secs),
+        }
+    }
+
+    #[test]
+    fn test_job_queue_lifecycle() {
+        let db = TestDb::new("test_job_queue_lifecycle");
+        let later = job(JobKind::FixThumbnails, 3600);
+        let group = job(JobKind::GroupMedia { folder_id: Some(Uuid::new_v4()), threshold: 0.2 }, 0);
+        db.repo.create_job_impl(&later).unwrap();
+        db.repo.create_job_impl(&group).unwrap();
+
+        // Only due jobs are claimed, each exactly once
+        let claimed = db.repo.claim_next_job_impl(Utc::now()).unwrap().unwrap();
+        assert_eq!((claimed.id, claimed.state, claimed.attempts), (group.id, JobState::Running, 1));
+        assert_eq!(claimed.kind, group.kind);
+        assert!(db.repo.claim_next_job_impl(Utc::now()).unwrap().is_none());
+
+        db.repo.update_job_progress_impl(group.id, 2, 5).unwrap();
+        db.repo.reschedule_job_impl(group.id, Utc::now(), "disk full").unwrap();
+        let retried = db.repo.claim_next_job_impl(Utc::now()).unwrap().unwrap();
+        assert_eq!((retried.attempts, retried.done, retried.total), (2, 2, 5));
+        assert_eq!(retried.error.as_deref(), Some("disk full"));
+
+        let result = serde_json::json!([{ "id": 0 }]);
+        db.repo.finish_job_impl(group.id, JobState::Done, None, Some(&result)).unwrap();
+        let done = db.repo.find_job_impl(group.id).unwrap().unwrap();
+        assert_eq!((done.state, done.result), (JobState::Done, Some(result)));
+        assert!(!db.repo.retry_job_impl(group.id, Utc::now()).unwrap());
+
+        assert!(db.repo.cancel_queued_job_impl(later.id).unwrap());
+        assert!(!db.repo.cancel_queued_job_impl(later.id).unwrap());
+        assert!(db.repo.retry_job_impl(later.id, Utc::now()).unwrap());
+        assert_eq!(db.repo.claim_next_job_impl(Utc::now()).unwrap().unwrap().id, later.id);
+
+        // A restart puts interrupted jobs back in the queue
+        assert_eq!(db.repo.requeue_running_jobs_impl().unwrap(), 1);
+        assert_eq!(db.repo.list_jobs_impl(None, 10, 0).unwrap().len(), 2);
+        assert!(db.repo.list_jobs_impl(Some(Uuid::new_v4()), 10, 0).unwrap().is_empty());
+
+        assert_eq!(db.repo.delete_finished_jobs_before_impl(Utc::now() + Duration::seconds(1)).unwrap(), 1);
+        assert!(db.repo.find_job_impl(group.id).unwrap().is_none());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::domain::DomainError;
+use rusqlite::{params, OptionalExtension};
+use uuid::Uuid;
+
+use super::SqliteRepository;
+
+impl SqliteRepository {
+    pub(crate) fn exists_by_filename_impl(&self, filename: &str) -> Result<bool, DomainError> {
+        self.with_conn(|conn| {
+            let count: i64 = conn
+                .query_row(
+                    "SELECT count(*) FROM media WHERE filename = ?1",
+                    params![filename],
+                    |row| row.get(0),
+                )
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(count > 0)
+        })
+    }
+
+    pub(crate) fn find_file_owner_impl(&self, filename: &str) -> Result<Option<Option<Uuid>>, DomainError> {
+        self.with_conn(|conn| {
+            let owner: Option<Option<Vec<u8>>> = conn
+                .query_row(
+                    "SELECT owner_id FROM media WHERE filename = ?1
+                     UNION ALL
+                     SELECT m.owner_id FROM media_companions c JOIN media m ON m.id = c.media_id
+                     WHERE c.filename = ?1
+                     LIMIT 1",
+                    params![filename],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(owner.map(|bytes| bytes.and_then(|b| Uuid::from_slice(&b).ok())))
+        })
+    }
+
+    /// Media whose original is referenced in place (stored as an absolute path).
+    pub(crate) fn find_external_media_impl(&self) -> Result<Vec<(Uuid, String)>, DomainError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT id, filename FROM media WHERE filename LIKE '/%'")
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            let rows = stmt
+                .query_map([], |row| {
+                    let id_bytes: Vec<u8> = row.get(0)?;
+                    let filename: String = row.get(1)?;
+                    let id = Uuid::from_slice(&id_bytes).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            0,
+                            rusqlite::types::Type::Blob,
+                            Box::new(e),
+                        )
+                    })?;
+                    Ok((id, filename))
+                })
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::infrastructure::SqliteRepository;
+    use rusqlite::params;
+    use uuid::Uuid;
+
+    fn insert_media(repo: &SqliteRepository, id: Uuid, filename: &str) {
+        repo.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO media (id, filename, original_filename, size_bytes, phash, uploaded_at, original_date)
+                 VALUES (?1, ?2, 'a.jpg', 1, 'ph', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')",
+                params![id.as_bytes(), filename],
+            )
+            .unwrap();
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_find_external_media() {
+        let db = TestDb::new("test_external_media");
+        let internal = Uuid::new_v4();
+        let external = Uuid::new_v4();
+        insert_media(&db.repo, internal, "ab/cd/internal.jpg");
+        insert_media(&db.repo, external, "/archive/2020/a.jpg");
+
+        let found = db.repo.find_external_media_impl().unwrap();
+        assert_eq!(found, vec![(external, "/archive/2020/a.jpg".to_string())]);
+
+        assert!(db.repo.exists_by_filename_impl("/archive/2020/a.jpg").unwrap());
+        assert!(!db.repo.exists_by_filename_impl("/archive/2020/b.jpg").unwrap());
+    }
+}
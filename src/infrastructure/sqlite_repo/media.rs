@@ -1,9 +1,10 @@
-use crate::domain::{DomainError, MediaCounts, MediaItem, MediaSummary};
+use crate::domain::{DomainError, LibraryScope, MediaCounts, MediaItem, MediaSummary};
 use chrono::{DateTime, Utc};
 use rusqlite::params;
 use uuid::Uuid;
 
-use super::{load_tags_bulk, load_tags_for_media, SqliteRepository};
+use super::phash::write_phash_frames;
+use super::{load_tags_bulk, load_tags_for_media, owner_from_row, scope_condition, SqliteRepository};
 
 impl SqliteRepository {
     pub(crate) fn save_metadata_and_vector_impl(
@@ -20,8 +21,8 @@ impl SqliteRepository {
             let original_date_str = media.original_date.to_rfc3339();
 
             let res = conn.execute(
-                "INSERT INTO media (id, filename, original_filename, media_type, phash, uploaded_at, original_date, width, height, size_bytes, exif_json)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                "INSERT INTO media (id, filename, original_filename, media_type, phash, uploaded_at, original_date, width, height, size_bytes, exif_json, content_hash, owner_id, processing)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
                 params![
                     uuid_bytes,
                     media.filename,
@@ -33,7 +34,10 @@ impl SqliteRepository {
                     media.width,
                     media.height,
                     media.size_bytes,
-                    media.exif_json
+                    media.exif_json,
+                    media.content_hash,
+                    media.owner_id.map(|o| o.as_bytes().to_vec()),
+                    media.processing
                 ],
             );
 
@@ -42,6 +46,13 @@ impl SqliteRepository {
                 return Err(DomainError::Database(e.to_string()));
             }
 
+            let media_rowid = conn.last_insert_rowid();
+
+            if let Err(e) = write_phash_frames(conn, uuid_bytes, &media.phash) {
+                let _ = conn.execute("ROLLBACK", []);
+                return Err(DomainError::Database(e.to_string()));
+            }
+
             if let Some(v) = vector {
                 let vector_bytes: &[u8] = unsafe {
                     std::slice::from_raw_parts(
@@ -50,8 +61,6 @@ impl SqliteRepository {
                     )
                 };
 
-                let media_rowid = conn.last_insert_rowid();
-
                 let res = conn.execute(
                     "INSERT INTO vec_media (rowid, embedding) VALUES (?1, ?2)",
                     params![media_rowid, vector_bytes],
@@ -94,7 +103,9 @@ impl SqliteRepository {
                     width = ?8,
                     height = ?9,
                     size_bytes = ?10,
-                    exif_json = ?11
+                    exif_json = ?11,
+                    content_hash = ?12,
+                    processing = ?13
                  WHERE id = ?1",
                 params![
                     uuid_bytes,
@@ -107,7 +118,9 @@ impl SqliteRepository {
                     media.width,
                     media.height,
                     media.size_bytes,
-                    media.exif_json
+                    media.exif_json,
+                    media.content_hash,
+                    media.processing
                 ],
             );
 
@@ -116,6 +129,11 @@ impl SqliteRepository {
                 return Err(DomainError::Database(e.to_string()));
             }
 
+            if let Err(e) = write_phash_frames(conn, uuid_bytes, &media.phash) {
+                let _ = conn.execute("ROLLBACK", []);
+                return Err(DomainError::Database(e.to_string()));
+            }
+
             // Update vector: get rowid, delete from vec_media, re-insert
             let rowid: Option<i64> = conn
                 .prepare("SELECT rowid FROM media WHERE id = ?1")
@@ -151,20 +169,6 @@ impl SqliteRepository {
         })
     }
 
-    pub(crate) fn exists_by_phash_impl(&self, phash: &str) -> Result<bool, DomainError> {
-        self.with_conn(|conn| {
-            let mut stmt = conn
-                .prepare("SELECT count(*) FROM media WHERE phash = ?1")
-                .map_err(|e| DomainError::Database(e.to_string()))?;
-
-            let count: i64 = stmt
-                .query_row([phash], |row| row.get(0))
-                .map_err(|e| DomainError::Database(e.to_string()))?;
-
-            Ok(count > 0)
-        })
-    }
-
     pub(crate) fn find_similar_impl(
         &self,
         vector: &[f32],
@@ -180,7 +184,7 @@ impl SqliteRepository {
             };
 
             let mut stmt = conn.prepare(
-                "SELECT m.id, m.filename, m.original_filename, m.media_type, m.phash, m.uploaded_at, m.original_date, m.width, m.height, m.size_bytes, m.exif_json, v.distance, (f.media_id IS NOT NULL) as is_favorite
+                "SELECT m.id, m.filename, m.original_filename, m.media_type, m.phash, m.uploaded_at, m.original_date, m.width, m.height, m.size_bytes, m.exif_json, v.distance, (f.media_id IS NOT NULL) as is_favorite, m.content_hash, m.owner_id, m.processing
                  FROM (
                     SELECT rowid, distance
                     FROM vec_media
@@ -189,8 +193,8 @@ impl SqliteRepository {
                     LIMIT ?2
                  ) v
                  JOIN media m ON m.rowid = v.rowid
-                 LEFT JOIN favorites f ON f.media_id = m.id
-                 WHERE v.distance <= ?3
+                 LEFT JOIN favorites f ON f.media_id = m.id AND f.user_id = zeroblob(16)
+                 WHERE v.distance <= ?3 AND m.deleted_at IS NULL
                  ORDER BY v.distance"
             ).map_err(|e| DomainError::Database(e.to_string()))?;
 
@@ -210,6 +214,9 @@ impl SqliteRepository {
                         let size_bytes: i64 = row.get(9)?;
                         let exif_json: Option<String> = row.get(10)?;
                         let is_favorite: bool = row.get(12)?;
+                        let content_hash: Option<String> = row.get(13)?;
+                        let owner_id = owner_from_row(row.get(14)?);
+                        let processing: bool = row.get(15)?;
 
                         let id = Uuid::from_slice(&id_bytes).map_err(|e| {
                             rusqlite::Error::FromSqlConversionFailure(
@@ -245,6 +252,8 @@ impl SqliteRepository {
                                 original_filename,
                                 media_type,
                                 phash,
+                                content_hash,
+                                owner_id,
                                 uploaded_at,
                                 original_date,
                                 width,
@@ -253,6 +262,7 @@ impl SqliteRepository {
                                 exif_json,
                                 is_favorite,
                                 tags: vec![],
+                                processing,
                             },
                         ))
                     },
@@ -287,9 +297,9 @@ impl SqliteRepository {
     pub(crate) fn find_by_id_impl(&self, id: Uuid) -> Result<Option<MediaItem>, DomainError> {
         self.with_conn(|conn| {
             let mut stmt = conn.prepare(
-                "SELECT m.id, m.filename, m.original_filename, m.media_type, m.phash, m.uploaded_at, m.original_date, m.width, m.height, m.size_bytes, m.exif_json, (f.media_id IS NOT NULL) as is_favorite
+                "SELECT m.id, m.filename, m.original_filename, m.media_type, m.phash, m.uploaded_at, m.original_date, m.width, m.height, m.size_bytes, m.exif_json, (f.media_id IS NOT NULL) as is_favorite, m.content_hash, m.owner_id, m.processing
                  FROM media m
-                 LEFT JOIN favorites f ON f.media_id = m.id
+                 LEFT JOIN favorites f ON f.media_id = m.id AND f.user_id = zeroblob(16)
                  WHERE m.id = ?1"
             ).map_err(|e| DomainError::Database(e.to_string()))?;
 
@@ -306,6 +316,9 @@ impl SqliteRepository {
                 let size_bytes: i64 = row.get(9)?;
                 let exif_json: Option<String> = row.get(10)?;
                 let is_favorite: bool = row.get(11)?;
+                let content_hash: Option<String> = row.get(12)?;
+                let owner_id = owner_from_row(row.get(13)?);
+                let processing: bool = row.get(14)?;
 
                 let id = Uuid::from_slice(&id_bytes).map_err(|e| {
                     rusqlite::Error::FromSqlConversionFailure(
@@ -339,6 +352,8 @@ impl SqliteRepository {
                     original_filename,
                     media_type,
                     phash,
+                    content_hash,
+                    owner_id,
                     uploaded_at,
                     original_date,
                     width,
@@ -347,6 +362,7 @@ impl SqliteRepository {
                     exif_json,
                     is_favorite,
                     tags: vec![],
+                    processing,
                 })
             });
 
@@ -364,10 +380,10 @@ impl SqliteRepository {
     pub(crate) fn find_media_without_phash_impl(&self) -> Result<Vec<MediaItem>, DomainError> {
         self.with_conn(|conn| {
             let mut stmt = conn.prepare(
-                "SELECT m.id, m.filename, m.original_filename, m.media_type, m.phash, m.uploaded_at, m.original_date, m.width, m.height, m.size_bytes, m.exif_json, (f.media_id IS NOT NULL) as is_favorite
+                "SELECT m.id, m.filename, m.original_filename, m.media_type, m.phash, m.uploaded_at, m.original_date, m.width, m.height, m.size_bytes, m.exif_json, (f.media_id IS NOT NULL) as is_favorite, m.content_hash, m.owner_id, m.processing
                  FROM media m
-                 LEFT JOIN favorites f ON f.media_id = m.id
-                 WHERE m.phash = 'no_hash'"
+                 LEFT JOIN favorites f ON f.media_id = m.id AND f.user_id = zeroblob(16)
+                 WHERE m.phash = 'no_hash' AND m.processing = 0 AND m.deleted_at IS NULL"
             ).map_err(|e| DomainError::Database(e.to_string()))?;
 
             let rows = stmt
@@ -384,6 +400,9 @@ impl SqliteRepository {
                     let size_bytes: i64 = row.get(9)?;
                     let exif_json: Option<String> = row.get(10)?;
                     let is_favorite: bool = row.get(11)?;
+                    let content_hash: Option<String> = row.get(12)?;
+                    let owner_id = owner_from_row(row.get(13)?);
+                    let processing: bool = row.get(14)?;
 
                     let id = Uuid::from_slice(&id_bytes).map_err(|e| {
                         rusqlite::Error::FromSqlConversionFailure(
@@ -417,6 +436,8 @@ impl SqliteRepository {
                         original_filename,
                         media_type,
                         phash,
+                        content_hash,
+                        owner_id,
                         uploaded_at,
                         original_date,
                         width,
@@ -425,6 +446,7 @@ impl SqliteRepository {
                         exif_json,
                         is_favorite,
                         tags: vec![],
+                        processing,
                     })
                 })
                 .map_err(|e| DomainError::Database(e.to_string()))?;
@@ -459,6 +481,16 @@ impl SqliteRepository {
                 params![id.as_bytes()],
             );
 
+            let _ = conn.execute(
+                "DELETE FROM media_phash WHERE media_id = ?1",
+                params![id.as_bytes()],
+            );
+            let _ = conn.execute("DELETE FROM media_phash_bands WHERE media_id = ?1", params![id.as_bytes()]);
+
+            let _ = conn.execute("DELETE FROM shares WHERE media_id = ?1", params![id.as_bytes()]);
+            let _ = conn.execute("DELETE FROM media_companions WHERE media_id = ?1", params![id.as_bytes()]);
+            let _ = conn.execute("DELETE FROM media_renditions WHERE media_id = ?1", params![id.as_bytes()]);
+
             let deleted = conn
                 .execute("DELETE FROM media WHERE id = ?1", params![id.as_bytes()])
                 .map_err(|e| {
@@ -547,6 +579,16 @@ impl SqliteRepository {
                     params![id.as_bytes()],
                 );
 
+                let _ = conn.execute(
+                    "DELETE FROM media_phash WHERE media_id = ?1",
+                    params![id.as_bytes()],
+                );
+                let _ = conn.execute("DELETE FROM media_phash_bands WHERE media_id = ?1", params![id.as_bytes()]);
+
+                let _ = conn.execute("DELETE FROM shares WHERE media_id = ?1", params![id.as_bytes()]);
+                let _ = conn.execute("DELETE FROM media_companions WHERE media_id = ?1", params![id.as_bytes()]);
+                let _ = conn.execute("DELETE FROM media_renditions WHERE media_id = ?1", params![id.as_bytes()]);
+
                 let count = conn
                     .execute("DELETE FROM media WHERE id = ?1", params![id.as_bytes()])
                     .map_err(|e| {
@@ -569,6 +611,7 @@ impl SqliteRepository {
 
     pub(crate) fn find_all_impl(
         &self,
+        scope: &LibraryScope,
         limit: usize,
         offset: usize,
         media_type: Option<&str>,
@@ -584,12 +627,19 @@ impl SqliteRepository {
                 _ => "m.original_date",
             };
 
-            let mut sql = "SELECT m.id, m.filename, m.original_filename, m.media_type, m.uploaded_at, m.original_date, (f.media_id IS NOT NULL) as is_favorite, m.size_bytes
+            let mut sql = "SELECT m.id, m.filename, m.original_filename, m.media_type, m.uploaded_at, m.original_date, (f.media_id IS NOT NULL) as is_favorite, m.size_bytes, m.owner_id, m.processing
                          FROM media m
-                         LEFT JOIN favorites f ON f.media_id = m.id".to_string();
+                         LEFT JOIN favorites f ON f.media_id = m.id AND f.user_id = ?".to_string();
 
-            let mut conditions = Vec::new();
-            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+            // Trashed items only show up in the trash listing
+            let mut conditions = vec!["m.deleted_at IS NULL".to_string()];
+            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> =
+                vec![Box::new(scope.favorites_key().as_bytes().to_vec())];
+
+            if let Some((condition, owner)) = scope_condition(scope, "m") {
+                conditions.push(condition);
+                params_vec.push(owner);
+            }
 
             if let Some(mt) = media_type {
                 conditions.push("m.media_type = ?".to_string());
@@ -643,6 +693,8 @@ impl SqliteRepository {
                     let original_date_str: String = row.get(5)?;
                     let is_favorite: bool = row.get(6)?;
                     let size_bytes: i64 = row.get(7)?;
+                    let owner_id = owner_from_row(row.get(8)?);
+                    let processing: bool = row.get(9)?;
 
                     let id = Uuid::from_slice(&id_bytes).map_err(|e| {
                         rusqlite::Error::FromSqlConversionFailure(
@@ -677,11 +729,13 @@ impl SqliteRepository {
                             filename,
                             original_filename,
                             media_type,
+                            owner_id,
                             uploaded_at,
                             original_date,
                             size_bytes,
                             is_favorite,
                             tags: vec![],
+                            processing,
                         },
                     ))
                 })
@@ -712,20 +766,28 @@ impl SqliteRepository {
         })
     }
 
-    pub(crate) fn media_counts_impl(&self) -> Result<MediaCounts, DomainError> {
+    pub(crate) fn media_counts_impl(&self, scope: &LibraryScope) -> Result<MediaCounts, DomainError> {
         self.with_conn(|conn| {
-            let mut stmt = conn
-                .prepare(
-                    "SELECT
+            let mut sql = "SELECT
                     COUNT(*) AS total,
-                    COALESCE(SUM(CASE WHEN media_type = 'image' THEN 1 ELSE 0 END), 0) AS images,
-                    COALESCE(SUM(CASE WHEN media_type = 'video' THEN 1 ELSE 0 END), 0) AS videos,
-                    COALESCE(SUM(size_bytes), 0) AS total_size_bytes
-                 FROM media",
-                )
+                    COALESCE(SUM(CASE WHEN m.media_type = 'image' THEN 1 ELSE 0 END), 0) AS images,
+                    COALESCE(SUM(CASE WHEN m.media_type = 'video' THEN 1 ELSE 0 END), 0) AS videos,
+                    COALESCE(SUM(m.size_bytes), 0) AS total_size_bytes
+                 FROM media m
+                 WHERE m.deleted_at IS NULL"
+                .to_string();
+            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+            if let Some((condition, owner)) = scope_condition(scope, "m") {
+                sql.push_str(" AND ");
+                sql.push_str(&condition);
+                params_vec.push(owner);
+            }
+
+            let mut stmt = conn
+                .prepare(&sql)
                 .map_err(|e| DomainError::Database(e.to_string()))?;
 
-            stmt.query_row([], |row| {
+            stmt.query_row(rusqlite::params_from_iter(params_vec), |row| {
                 Ok(MediaCounts {
                     total: row.get(0)?,
                     images: row.get(1)?,
@@ -737,28 +799,46 @@ impl SqliteRepository {
         })
     }
 
-    pub(crate) fn set_favorite_impl(&self, id: Uuid, favorite: bool) -> Result<(), DomainError> {
+    pub(crate) fn set_favorite_impl(
+        &self,
+        scope: &LibraryScope,
+        id: Uuid,
+        favorite: bool,
+    ) -> Result<(), DomainError> {
+        let user = scope.favorites_key();
         self.with_conn(|conn| {
             if favorite {
                 conn.execute(
-                    "INSERT OR IGNORE INTO favorites (media_id, created_at) VALUES (?1, ?2)",
-                    params![id.as_bytes(), Utc::now().to_rfc3339()],
+                    "INSERT OR IGNORE INTO favorites (user_id, media_id, created_at) VALUES (?1, ?2, ?3)",
+                    params![user.as_bytes(), id.as_bytes(), Utc::now().to_rfc3339()],
                 )
             } else {
                 conn.execute(
-                    "DELETE FROM favorites WHERE media_id = ?1",
-                    params![id.as_bytes()],
+                    "DELETE FROM favorites WHERE user_id = ?1 AND media_id = ?2",
+                    params![user.as_bytes(), id.as_bytes()],
                 )
             }
             .map_err(|e| DomainError::Database(e.to_string()))?;
             Ok(())
         })
     }
+
+    pub(crate) fn is_favorite_impl(&self, scope: &LibraryScope, id: Uuid) -> Result<bool, DomainError> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM favorites WHERE user_id = ?1 AND media_id = ?2)",
+                params![scope.favorites_key().as_bytes(), id.as_bytes()],
+                |row| row.get(0),
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::super::TestDb;
+    use crate::domain::LibraryScope;
     use crate::infrastructure::SqliteRepository;
     use rusqlite::params;
     use uuid::Uuid;
@@ -796,7 +876,7 @@ mod tests {
 
         let results = db
             .repo
-            .find_all_impl(10, 0, None, false, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 10, 0, None, false, None, false, "date")
             .unwrap();
         assert_eq!(results.len(), 3);
         // DESC by date: Jun, Mar, Jan
@@ -818,7 +898,7 @@ mod tests {
 
         let results = db
             .repo
-            .find_all_impl(10, 0, None, false, None, true, "date")
+            .find_all_impl(&LibraryScope::ALL, 10, 0, None, false, None, true, "date")
             .unwrap();
         assert_eq!(results.len(), 3);
         // ASC by date: Jan, Mar, Jun
@@ -840,7 +920,7 @@ mod tests {
 
         let results = db
             .repo
-            .find_all_impl(10, 0, None, false, None, false, "size")
+            .find_all_impl(&LibraryScope::ALL, 10, 0, None, false, None, false, "size")
             .unwrap();
         assert_eq!(results.len(), 3);
         // DESC by size: 9999, 500, 100
@@ -862,7 +942,7 @@ mod tests {
 
         let results = db
             .repo
-            .find_all_impl(10, 0, None, false, None, true, "size")
+            .find_all_impl(&LibraryScope::ALL, 10, 0, None, false, None, true, "size")
             .unwrap();
         assert_eq!(results.len(), 3);
         // ASC by size: 100, 500, 9999
@@ -884,6 +964,7 @@ mod tests {
         let results = db
             .repo
             .find_all_impl(
+                &LibraryScope::ALL,
                 10,
                 0,
                 None,
@@ -913,7 +994,7 @@ mod tests {
 
         // Create folder and add media
         db.repo
-            .create_folder_impl(folder_id, "Test Folder")
+            .create_folder_impl(folder_id, "Test Folder", None)
             .unwrap();
         db.repo
             .add_media_to_folder_impl(folder_id, &[id1, id2, id3])
@@ -922,7 +1003,7 @@ mod tests {
         // Sort folder by size descending
         let results = db
             .repo
-            .find_all_in_folder_impl(folder_id, 10, 0, None, false, None, false, "size")
+            .find_all_in_folder_impl(&LibraryScope::ALL, folder_id, 10, 0, None, false, None, false, "size")
             .unwrap();
         assert_eq!(results.len(), 3);
         assert_eq!(results[0].id, id3); // 9999
@@ -932,7 +1013,7 @@ mod tests {
         // Sort folder by size ascending
         let results = db
             .repo
-            .find_all_in_folder_impl(folder_id, 10, 0, None, false, None, true, "size")
+            .find_all_in_folder_impl(&LibraryScope::ALL, folder_id, 10, 0, None, false, None, true, "size")
             .unwrap();
         assert_eq!(results.len(), 3);
         assert_eq!(results[0].id, id2); // 100
@@ -968,7 +1049,7 @@ mod tests {
         // Filter images only
         let images = db
             .repo
-            .find_all_impl(10, 0, Some("image"), false, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 10, 0, Some("image"), false, None, false, "date")
             .unwrap();
         assert_eq!(images.len(), 2);
         assert!(images.iter().all(|m| m.media_type == "image"));
@@ -976,7 +1057,7 @@ mod tests {
         // Filter videos only
         let videos = db
             .repo
-            .find_all_impl(10, 0, Some("video"), false, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 10, 0, Some("video"), false, None, false, "date")
             .unwrap();
         assert_eq!(videos.len(), 1);
         assert_eq!(videos[0].id, vid1);
@@ -984,7 +1065,7 @@ mod tests {
         // No filter — returns all
         let all = db
             .repo
-            .find_all_impl(10, 0, None, false, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 10, 0, None, false, None, false, "date")
             .unwrap();
         assert_eq!(all.len(), 3);
     }
@@ -1001,22 +1082,22 @@ mod tests {
         insert_media(&db.repo, id3, "2024-03-01T00:00:00Z", 300);
 
         // Favorite id1 and id3
-        db.repo.set_favorite_impl(id1, true).unwrap();
-        db.repo.set_favorite_impl(id3, true).unwrap();
+        db.repo.set_favorite_impl(&LibraryScope::ALL, id1, true).unwrap();
+        db.repo.set_favorite_impl(&LibraryScope::ALL, id3, true).unwrap();
 
         // Filter favorites
         let favs = db
             .repo
-            .find_all_impl(10, 0, None, true, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 10, 0, None, true, None, false, "date")
             .unwrap();
         assert_eq!(favs.len(), 2);
         assert!(favs.iter().all(|m| m.is_favorite));
 
         // Unfavorite id1
-        db.repo.set_favorite_impl(id1, false).unwrap();
+        db.repo.set_favorite_impl(&LibraryScope::ALL, id1, false).unwrap();
         let favs = db
             .repo
-            .find_all_impl(10, 0, None, true, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 10, 0, None, true, None, false, "date")
             .unwrap();
         assert_eq!(favs.len(), 1);
         assert_eq!(favs[0].id, id3);
@@ -1024,7 +1105,7 @@ mod tests {
         // No favorite filter — all returned, with correct is_favorite flag
         let all = db
             .repo
-            .find_all_impl(10, 0, None, false, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 10, 0, None, false, None, false, "date")
             .unwrap();
         assert_eq!(all.len(), 3);
         // id3 (Mar) is first in desc order and is favorited
@@ -1058,6 +1139,7 @@ mod tests {
         let nature = db
             .repo
             .find_all_impl(
+                &LibraryScope::ALL,
                 10,
                 0,
                 None,
@@ -1076,6 +1158,7 @@ mod tests {
         let city = db
             .repo
             .find_all_impl(
+                &LibraryScope::ALL,
                 10,
                 0,
                 None,
@@ -1091,6 +1174,7 @@ mod tests {
         let both = db
             .repo
             .find_all_impl(
+                &LibraryScope::ALL,
                 10,
                 0,
                 None,
@@ -1106,6 +1190,7 @@ mod tests {
         let none = db
             .repo
             .find_all_impl(
+                &LibraryScope::ALL,
                 10,
                 0,
                 None,
@@ -1141,9 +1226,9 @@ mod tests {
                 Ok(())
             })
             .unwrap();
-        db.repo.set_favorite_impl(id1, true).unwrap();
-        db.repo.set_favorite_impl(id2, true).unwrap();
-        db.repo.set_favorite_impl(id4, true).unwrap();
+        db.repo.set_favorite_impl(&LibraryScope::ALL, id1, true).unwrap();
+        db.repo.set_favorite_impl(&LibraryScope::ALL, id2, true).unwrap();
+        db.repo.set_favorite_impl(&LibraryScope::ALL, id4, true).unwrap();
         db.repo
             .update_media_tags_impl(id1, vec!["Nature".to_string()])
             .unwrap();
@@ -1160,7 +1245,7 @@ mod tests {
         // Favorite images only
         let fav_images = db
             .repo
-            .find_all_impl(10, 0, Some("image"), true, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 10, 0, Some("image"), true, None, false, "date")
             .unwrap();
         assert_eq!(fav_images.len(), 2); // id1, id4
         assert!(fav_images
@@ -1171,6 +1256,7 @@ mod tests {
         let fav_nature = db
             .repo
             .find_all_impl(
+                &LibraryScope::ALL,
                 10,
                 0,
                 None,
@@ -1186,6 +1272,7 @@ mod tests {
         let img_nat_fav = db
             .repo
             .find_all_impl(
+                &LibraryScope::ALL,
                 10,
                 0,
                 Some("image"),
@@ -1219,7 +1306,7 @@ mod tests {
         // Page 1: limit 3, offset 0 (DESC: Oct, Sep, Aug)
         let page1 = db
             .repo
-            .find_all_impl(3, 0, None, false, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 3, 0, None, false, None, false, "date")
             .unwrap();
         assert_eq!(page1.len(), 3);
         assert_eq!(page1[0].id, ids[9]); // Oct (month 10)
@@ -1229,7 +1316,7 @@ mod tests {
         // Page 2: limit 3, offset 3 (DESC: Jul, Jun, May)
         let page2 = db
             .repo
-            .find_all_impl(3, 3, None, false, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 3, 3, None, false, None, false, "date")
             .unwrap();
         assert_eq!(page2.len(), 3);
         assert_eq!(page2[0].id, ids[6]); // Jul
@@ -1237,7 +1324,7 @@ mod tests {
         // Page 4: limit 3, offset 9 (only 1 item left)
         let page4 = db
             .repo
-            .find_all_impl(3, 9, None, false, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 3, 9, None, false, None, false, "date")
             .unwrap();
         assert_eq!(page4.len(), 1);
         assert_eq!(page4[0].id, ids[0]); // Jan
@@ -1245,7 +1332,7 @@ mod tests {
         // Beyond all: offset 10
         let empty = db
             .repo
-            .find_all_impl(3, 10, None, false, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 3, 10, None, false, None, false, "date")
             .unwrap();
         assert_eq!(empty.len(), 0);
     }
@@ -1269,7 +1356,7 @@ mod tests {
         for offset in (0..10).step_by(3) {
             let page = db
                 .repo
-                .find_all_impl(3, offset, None, false, None, false, "date")
+                .find_all_impl(&LibraryScope::ALL, 3, offset, None, false, None, false, "date")
                 .unwrap();
             for item in &page {
                 assert!(!all_ids.contains(&item.id), "Duplicate item across pages");
@@ -1292,6 +1379,8 @@ mod tests {
             original_filename: "photo.jpg".to_string(),
             media_type: "image".to_string(),
             phash: "abc123".to_string(),
+            content_hash: None,
+            owner_id: None,
             uploaded_at: chrono::Utc::now(),
             original_date: chrono::DateTime::parse_from_rfc3339("2024-06-15T12:00:00Z")
                 .unwrap()
@@ -1302,6 +1391,7 @@ mod tests {
             exif_json: Some(r#"{"Make":"Canon"}"#.to_string()),
             is_favorite: false,
             tags: vec![],
+            processing: false,
         };
 
         db.repo.save_metadata_and_vector_impl(&media, None).unwrap();
@@ -1326,14 +1416,15 @@ mod tests {
     }
 
     #[test]
-    fn test_exists_by_phash() {
+    fn test_find_duplicate_by_exact_phash() {
         let db = TestDb::new("test_exists_phash");
 
         let id = Uuid::new_v4();
         insert_media(&db.repo, id, "2024-01-01T00:00:00Z", 100);
 
-        assert!(db.repo.exists_by_phash_impl("ph").unwrap());
-        assert!(!db.repo.exists_by_phash_impl("nonexistent").unwrap());
+        // "ph" is not a decodable hash, so only an exact match counts
+        assert_eq!(db.repo.find_near_duplicate_impl(&LibraryScope::ALL, "ph", "image", 4).unwrap(), Some(id));
+        assert_eq!(db.repo.find_near_duplicate_impl(&LibraryScope::ALL, "nonexistent", "image", 4).unwrap(), None);
     }
 
     #[test]
@@ -1371,7 +1462,7 @@ mod tests {
         // Verify remaining
         let all = db
             .repo
-            .find_all_impl(10, 0, None, false, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 10, 0, None, false, None, false, "date")
             .unwrap();
         assert_eq!(all.len(), 2);
     }
@@ -1383,7 +1474,7 @@ mod tests {
         let db = TestDb::new("test_media_counts");
 
         // Empty DB
-        let counts = db.repo.media_counts_impl().unwrap();
+        let counts = db.repo.media_counts_impl(&LibraryScope::ALL).unwrap();
         assert_eq!(counts.total, 0);
         assert_eq!(counts.images, 0);
         assert_eq!(counts.videos, 0);
@@ -1408,7 +1499,7 @@ mod tests {
             })
             .unwrap();
 
-        let counts = db.repo.media_counts_impl().unwrap();
+        let counts = db.repo.media_counts_impl(&LibraryScope::ALL).unwrap();
         assert_eq!(counts.total, 3);
         assert_eq!(counts.images, 2);
         assert_eq!(counts.videos, 1);
@@ -1442,7 +1533,7 @@ mod tests {
         for payload in &payloads {
             let results = db
                 .repo
-                .find_all_impl(10, 0, None, false, None, false, payload)
+                .find_all_impl(&LibraryScope::ALL, 10, 0, None, false, None, false, payload)
                 .unwrap();
             assert_eq!(
                 results.len(),
@@ -1477,7 +1568,7 @@ mod tests {
         for payload in &payloads {
             let results = db
                 .repo
-                .find_all_impl(10, 0, Some(payload), false, None, false, "date")
+                .find_all_impl(&LibraryScope::ALL, 10, 0, Some(payload), false, None, false, "date")
                 .unwrap();
             assert_eq!(
                 results.len(),
@@ -1490,7 +1581,7 @@ mod tests {
         // Normal filter still works
         let results = db
             .repo
-            .find_all_impl(10, 0, Some("image"), false, None, false, "date")
+            .find_all_impl(&LibraryScope::ALL, 10, 0, Some("image"), false, None, false, "date")
             .unwrap();
         assert_eq!(results.len(), 1);
     }
@@ -1516,6 +1607,7 @@ mod tests {
             let results = db
                 .repo
                 .find_all_impl(
+                    &LibraryScope::ALL,
                     10,
                     0,
                     None,
@@ -1537,6 +1629,7 @@ mod tests {
         let results = db
             .repo
             .find_all_impl(
+                &LibraryScope::ALL,
                 10,
                 0,
                 None,
@@ -1556,7 +1649,7 @@ mod tests {
         let folder_id = Uuid::new_v4();
         let id1 = Uuid::new_v4();
         let id2 = Uuid::new_v4();
-        db.repo.create_folder_impl(folder_id, "Test").unwrap();
+        db.repo.create_folder_impl(folder_id, "Test", None).unwrap();
         insert_media(&db.repo, id1, "2024-01-01T00:00:00Z", 100);
         insert_media(&db.repo, id2, "2024-06-15T00:00:00Z", 200);
         db.repo
@@ -1568,7 +1661,7 @@ mod tests {
         for payload in &payloads {
             let results = db
                 .repo
-                .find_all_in_folder_impl(folder_id, 10, 0, None, false, None, false, payload)
+                .find_all_in_folder_impl(&LibraryScope::ALL, folder_id, 10, 0, None, false, None, false, payload)
                 .unwrap();
             assert_eq!(
                 results.len(),
@@ -1598,17 +1691,17 @@ mod tests {
         assert!(!item.is_favorite);
 
         // Favorite it
-        db.repo.set_favorite_impl(id, true).unwrap();
+        db.repo.set_favorite_impl(&LibraryScope::ALL, id, true).unwrap();
         let item = db.repo.find_by_id_impl(id).unwrap().unwrap();
         assert!(item.is_favorite);
 
         // Double-favorite is idempotent (INSERT OR IGNORE)
-        db.repo.set_favorite_impl(id, true).unwrap();
+        db.repo.set_favorite_impl(&LibraryScope::ALL, id, true).unwrap();
         let item = db.repo.find_by_id_impl(id).unwrap().unwrap();
         assert!(item.is_favorite);
 
         // Unfavorite
-        db.repo.set_favorite_impl(id, false).unwrap();
+        db.repo.set_favorite_impl(&LibraryScope::ALL, id, false).unwrap();
         let item = db.repo.find_by_id_impl(id).unwrap().unwrap();
         assert!(!item.is_favorite);
     }
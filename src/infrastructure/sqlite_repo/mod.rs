@@ -1,7 +1,22 @@
+mod api_tokens;
+mod audit;
+mod companions;
+mod jobs;
+mod duplicates;
 mod embeddings;
 mod folders;
+mod imports;
+mod integrity;
+mod library;
 mod media;
+mod phash;
+mod renditions;
+mod resumable;
+mod sessions;
+mod shares;
 mod tags;
+mod trash;
+mod users;
 
 use crate::domain::DomainError;
 use rusqlite::{params, Connection};
@@ -93,16 +108,46 @@ impl SqliteRepository {
         })?;
 
         println!("Ensuring favorites table exists...");
-        // Favorites
+        // Favorites, per user. The nil UUID is the shared anonymous user (auth disabled).
         conn.execute(
             "CREATE TABLE IF NOT EXISTS favorites (
-                media_id BLOB PRIMARY KEY REFERENCES media(id) ON DELETE CASCADE,
-                created_at TEXT NOT NULL
+                user_id BLOB NOT NULL,
+                media_id BLOB NOT NULL REFERENCES media(id) ON DELETE CASCADE,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (user_id, media_id)
             )",
             [],
         )
         .map_err(|e| DomainError::Database(format!("Failed to create favorites table: {}", e)))?;
 
+        let has_favorites_user: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('favorites') WHERE name='user_id'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
+
+        if has_favorites_user == 0 {
+            println!("Migrating favorites to per-user favorites...");
+            // The primary key changes, so the table has to be rebuilt
+            conn.execute_batch(
+                "BEGIN;
+                 ALTER TABLE favorites RENAME TO favorites_old;
+                 CREATE TABLE favorites (
+                     user_id BLOB NOT NULL,
+                     media_id BLOB NOT NULL REFERENCES media(id) ON DELETE CASCADE,
+                     created_at TEXT NOT NULL,
+                     PRIMARY KEY (user_id, media_id)
+                 );
+                 INSERT INTO favorites (user_id, media_id, created_at)
+                     SELECT zeroblob(16), media_id, created_at FROM favorites_old;
+                 DROP TABLE favorites_old;
+                 COMMIT;",
+            )
+            .map_err(|e| DomainError::Database(format!("Failed to migrate favorites: {}", e)))?;
+        }
+
         println!("Ensuring tags table exists...");
         // Tags
         conn.execute(
@@ -218,6 +263,338 @@ impl SqliteRepository {
         )
         .map_err(|e| DomainError::Database(format!("Failed to create index: {}", e)))?;
 
+        println!("Ensuring imported_paths table exists...");
+        // Source paths already handled by the server-side importer (no FK: deleting
+        // an imported item must not cause it to be re-imported on the next run)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS imported_paths (
+                path TEXT PRIMARY KEY,
+                media_id BLOB,
+                imported_at TEXT NOT NULL
+            )",
+            [],
+        )
+        .map_err(|e| {
+            DomainError::Database(format!("Failed to create imported_paths table: {}", e))
+        })?;
+
+        println!("Ensuring idx_media_filename index exists...");
+        // Used to look up originals referenced in place when serving /uploads
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_media_filename ON media(filename)",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create index: {}", e)))?;
+
+        let has_content_hash: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('media') WHERE name='content_hash'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
+
+        if has_content_hash == 0 {
+            println!("Adding content_hash column to media...");
+            let _ = conn.execute("ALTER TABLE media ADD COLUMN content_hash TEXT", []);
+        }
+
+        println!("Ensuring idx_media_content_hash index exists...");
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_media_content_hash ON media(content_hash)",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create index: {}", e)))?;
+
+        let has_deleted_at: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('media') WHERE name='deleted_at'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
+
+        if has_deleted_at == 0 {
+            println!("Adding deleted_at column to media...");
+            // Soft delete: NULL = live, otherwise the time the item was moved to the trash
+            let _ = conn.execute("ALTER TABLE media ADD COLUMN deleted_at TEXT", []);
+        }
+
+        let has_processing: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('media') WHERE name='processing'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
+
+        if has_processing == 0 {
+            println!("Adding processing column to media...");
+            // Set while an accepted upload waits for its thumbnail, hashes and embedding
+            let _ = conn.execute("ALTER TABLE media ADD COLUMN processing BOOLEAN NOT NULL DEFAULT 0", []);
+        }
+
+        println!("Ensuring idx_media_deleted_at index exists...");
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_media_deleted_at ON media(deleted_at)",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create index: {}", e)))?;
+
+        println!("Ensuring users table exists...");
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS users (
+                id BLOB PRIMARY KEY,
+                username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                password_hash TEXT NOT NULL,
+                role TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create users table: {}", e)))?;
+
+        println!("Ensuring user_identities table exists...");
+        // Accounts signed in through an OIDC provider; one identity per user and issuer
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS user_identities (
+                issuer TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                user_id BLOB NOT NULL,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (issuer, subject),
+                UNIQUE (issuer, user_id)
+            )",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create user_identities table: {}", e)))?;
+
+        println!("Ensuring sessions table exists...");
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                id BLOB PRIMARY KEY,
+                token_hash TEXT NOT NULL UNIQUE,
+                user_id BLOB NOT NULL,
+                created_at TEXT NOT NULL,
+                last_seen_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL,
+                user_agent TEXT,
+                ip TEXT
+            )",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create sessions table: {}", e)))?;
+
+        println!("Ensuring idx_sessions_user_id index exists...");
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_sessions_user_id ON sessions(user_id)",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create index: {}", e)))?;
+
+        println!("Ensuring api_tokens table exists...");
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS api_tokens (
+                id BLOB PRIMARY KEY,
+                token_hash TEXT NOT NULL UNIQUE,
+                user_id BLOB NOT NULL,
+                name TEXT NOT NULL,
+                scope TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                last_used_at TEXT
+            )",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create api_tokens table: {}", e)))?;
+
+        println!("Ensuring idx_api_tokens_user_id index exists...");
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_api_tokens_user_id ON api_tokens(user_id)",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create index: {}", e)))?;
+
+        println!("Ensuring shares table exists...");
+        // Exactly one of folder_id / media_id is set
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS shares (
+                id BLOB PRIMARY KEY,
+                slug TEXT NOT NULL UNIQUE,
+                created_by BLOB,
+                folder_id BLOB,
+                media_id BLOB,
+                password_hash TEXT,
+                allow_download INTEGER NOT NULL DEFAULT 0,
+                expires_at TEXT,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create shares table: {}", e)))?;
+
+        println!("Ensuring jobs table exists...");
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                id BLOB PRIMARY KEY,
+                kind TEXT NOT NULL,
+                spec TEXT NOT NULL,
+                state TEXT NOT NULL,
+                done INTEGER NOT NULL DEFAULT 0,
+                total INTEGER NOT NULL DEFAULT 0,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                max_attempts INTEGER NOT NULL,
+                error TEXT,
+                result TEXT,
+                created_by BLOB,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                run_after TEXT NOT NULL
+            )",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create jobs table: {}", e)))?;
+
+        println!("Ensuring idx_jobs_state_run_after index exists...");
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_jobs_state_run_after ON jobs(state, run_after)",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create index: {}", e)))?;
+
+        println!("Ensuring resumable_uploads table exists...");
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS resumable_uploads (
+                id BLOB PRIMARY KEY,
+                filename TEXT NOT NULL,
+                length INTEGER NOT NULL,
+                owner_id BLOB,
+                created_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL
+            )",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create resumable_uploads table: {}", e)))?;
+
+        println!("Ensuring media_companions table exists...");
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS media_companions (
+                media_id BLOB PRIMARY KEY REFERENCES media(id) ON DELETE CASCADE,
+                filename TEXT NOT NULL,
+                original_filename TEXT NOT NULL,
+                size_bytes INTEGER NOT NULL,
+                content_hash TEXT NOT NULL
+            )",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create media_companions table: {}", e)))?;
+
+        println!("Ensuring idx_media_companions_content_hash index exists...");
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_media_companions_content_hash ON media_companions(content_hash)",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create index: {}", e)))?;
+
+        println!("Ensuring media_renditions table exists...");
+        // Thumbnails and previews generated for an item, by name (see `RenditionSizes`)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS media_renditions (
+                media_id BLOB NOT NULL REFERENCES media(id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                PRIMARY KEY (media_id, name)
+            )",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create media_renditions table: {}", e)))?;
+
+        println!("Ensuring audit_log table exists...");
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                created_at TEXT NOT NULL,
+                user_id BLOB,
+                username TEXT,
+                ip TEXT,
+                action TEXT NOT NULL,
+                target_ids TEXT NOT NULL,
+                detail TEXT
+            )",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create audit_log table: {}", e)))?;
+
+        println!("Ensuring idx_audit_log_created_at index exists...");
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log(created_at)",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create index: {}", e)))?;
+
+        // Entries are append-only; retention pruning is the only delete
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS audit_log_append_only
+             BEFORE UPDATE ON audit_log
+             BEGIN SELECT RAISE(ABORT, 'audit_log is append-only'); END",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create audit_log trigger: {}", e)))?;
+
+        // Ownership: NULL = shared library, visible to every user
+        for table in ["media", "folders"] {
+            let has_owner: i64 = conn
+                .query_row(
+                    &format!("SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name='owner_id'", table),
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap_or(0);
+
+            if has_owner == 0 {
+                println!("Adding owner_id column to {}...", table);
+                let _ = conn.execute(&format!("ALTER TABLE {} ADD COLUMN owner_id BLOB", table), []);
+            }
+
+            conn.execute(
+                &format!("CREATE INDEX IF NOT EXISTS idx_{0}_owner_id ON {0}(owner_id)", table),
+                [],
+            )
+            .map_err(|e| DomainError::Database(format!("Failed to create index: {}", e)))?;
+        }
+
+        println!("Ensuring media_phash table exists...");
+        // Perceptual hash bits per frame (one row for images, one per sampled frame for
+        // videos), used for Hamming-distance duplicate detection
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS media_phash (
+                media_id BLOB NOT NULL REFERENCES media(id) ON DELETE CASCADE,
+                frame INTEGER NOT NULL,
+                bits BLOB NOT NULL,
+                PRIMARY KEY (media_id, frame)
+            )",
+            [],
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create media_phash table: {}", e)))?;
+
+        println!("Ensuring media_phash_bands table exists...");
+        // 16-bit bands of each frame, so near-duplicate lookups only compare items
+        // that share a nearby band with the query instead of every frame
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS media_phash_bands (
+                media_id BLOB NOT NULL REFERENCES media(id) ON DELETE CASCADE,
+                frame INTEGER NOT NULL,
+                band INTEGER NOT NULL,
+                value INTEGER NOT NULL,
+                PRIMARY KEY (media_id, frame, band)
+            );
+            CREATE INDEX IF NOT EXISTS idx_media_phash_bands_value ON media_phash_bands(band, value);",
+        )
+        .map_err(|e| DomainError::Database(format!("Failed to create media_phash_bands table: {}", e)))?;
+
+        let backfilled = phash::backfill_phash_frames(&conn)?;
+        if backfilled > 0 {
+            println!("Backfilled perceptual hash frames for {} media items.", backfilled);
+        }
+
         println!("Opening connection pool...");
         let mut connections = vec![conn];
         for _ in 1..POOL_SIZE {
@@ -274,7 +651,8 @@ impl SqliteRepository {
 // ---- MediaRepository trait implementation (delegates to submodule _impl methods) ----
 
 use crate::domain::{
-    Folder, MediaCounts, MediaItem, MediaRepository, MediaSummary, TagCount, TagDetail,
+    ApiToken, AuditEntry, Folder, Job, JobState, LibraryScope, MediaCompanion, MediaCounts, MediaItem, MediaRepository, MediaSummary, ResumableUpload, Role,
+    Session, ShareLink, TagCount, TagDetail, TrashedMedia, User,
 };
 
 impl MediaRepository for SqliteRepository {
@@ -294,8 +672,14 @@ impl MediaRepository for SqliteRepository {
         self.update_media_and_vector_impl(media, vector)
     }
 
-    fn exists_by_phash(&self, phash: &str) -> Result<bool, DomainError> {
-        self.exists_by_phash_impl(phash)
+    fn find_near_duplicate(
+        &self,
+        scope: &LibraryScope,
+        phash: &str,
+        media_type: &str,
+        max_distance: u32,
+    ) -> Result<Option<uuid::Uuid>, DomainError> {
+        self.find_near_duplicate_impl(scope, phash, media_type, max_distance)
     }
 
     fn find_similar(
@@ -325,6 +709,7 @@ impl MediaRepository for SqliteRepository {
 
     fn find_all(
         &self,
+        scope: &LibraryScope,
         limit: usize,
         offset: usize,
         media_type: Option<&str>,
@@ -333,19 +718,28 @@ impl MediaRepository for SqliteRepository {
         sort_asc: bool,
         sort_by: &str,
     ) -> Result<Vec<MediaSummary>, DomainError> {
-        self.find_all_impl(limit, offset, media_type, favorite, tags, sort_asc, sort_by)
+        self.find_all_impl(scope, limit, offset, media_type, favorite, tags, sort_asc, sort_by)
     }
 
-    fn media_counts(&self) -> Result<MediaCounts, DomainError> {
-        self.media_counts_impl()
+    fn media_counts(&self, scope: &LibraryScope) -> Result<MediaCounts, DomainError> {
+        self.media_counts_impl(scope)
+    }
+
+    fn set_favorite(
+        &self,
+        scope: &LibraryScope,
+        id: uuid::Uuid,
+        favorite: bool,
+    ) -> Result<(), DomainError> {
+        self.set_favorite_impl(scope, id, favorite)
     }
 
-    fn set_favorite(&self, id: uuid::Uuid, favorite: bool) -> Result<(), DomainError> {
-        self.set_favorite_impl(id, favorite)
+    fn is_favorite(&self, scope: &LibraryScope, id: uuid::Uuid) -> Result<bool, DomainError> {
+        self.is_favorite_impl(scope, id)
     }
 
-    fn get_all_tags(&self) -> Result<Vec<TagCount>, DomainError> {
-        self.get_all_tags_impl()
+    fn get_all_tags(&self, scope: &LibraryScope) -> Result<Vec<TagCount>, DomainError> {
+        self.get_all_tags_impl(scope)
     }
 
     fn update_media_tags(&self, id: uuid::Uuid, tags: Vec<String>) -> Result<(), DomainError> {
@@ -360,16 +754,21 @@ impl MediaRepository for SqliteRepository {
         self.update_media_tags_batch_impl(ids, tags)
     }
 
-    fn create_folder(&self, id: uuid::Uuid, name: &str) -> Result<Folder, DomainError> {
-        self.create_folder_impl(id, name)
+    fn create_folder(
+        &self,
+        id: uuid::Uuid,
+        name: &str,
+        owner_id: Option<uuid::Uuid>,
+    ) -> Result<Folder, DomainError> {
+        self.create_folder_impl(id, name, owner_id)
     }
 
     fn get_folder(&self, id: uuid::Uuid) -> Result<Option<Folder>, DomainError> {
         self.get_folder_impl(id)
     }
 
-    fn list_folders(&self) -> Result<Vec<Folder>, DomainError> {
-        self.list_folders_impl()
+    fn list_folders(&self, scope: &LibraryScope) -> Result<Vec<Folder>, DomainError> {
+        self.list_folders_impl(scope)
     }
 
     fn delete_folder(&self, id: uuid::Uuid) -> Result<(), DomainError> {
@@ -402,6 +801,7 @@ impl MediaRepository for SqliteRepository {
 
     fn find_all_in_folder(
         &self,
+        scope: &LibraryScope,
         folder_id: uuid::Uuid,
         limit: usize,
         offset: usize,
@@ -412,7 +812,7 @@ impl MediaRepository for SqliteRepository {
         sort_by: &str,
     ) -> Result<Vec<MediaSummary>, DomainError> {
         self.find_all_in_folder_impl(
-            folder_id, limit, offset, media_type, favorite, tags, sort_asc, sort_by,
+            scope, folder_id, limit, offset, media_type, favorite, tags, sort_asc, sort_by,
         )
     }
 
@@ -462,8 +862,8 @@ impl MediaRepository for SqliteRepository {
         self.get_tags_with_auto_counts_impl()
     }
 
-    fn count_auto_tags(&self, folder_id: Option<uuid::Uuid>) -> Result<usize, DomainError> {
-        self.count_auto_tags_impl(folder_id)
+    fn count_auto_tags(&self, scope: &LibraryScope, folder_id: Option<uuid::Uuid>) -> Result<usize, DomainError> {
+        self.count_auto_tags_impl(scope, folder_id)
     }
 
     fn update_auto_tags(
@@ -511,6 +911,375 @@ impl MediaRepository for SqliteRepository {
     fn find_media_without_phash(&self) -> Result<Vec<MediaItem>, DomainError> {
         self.find_media_without_phash_impl()
     }
+
+    fn is_path_imported(&self, path: &str) -> Result<bool, DomainError> {
+        self.is_path_imported_impl(path)
+    }
+
+    fn mark_path_imported(
+        &self,
+        path: &str,
+        media_id: Option<uuid::Uuid>,
+    ) -> Result<(), DomainError> {
+        self.mark_path_imported_impl(path, media_id)
+    }
+
+    fn exists_by_filename(&self, filename: &str) -> Result<bool, DomainError> {
+        self.exists_by_filename_impl(filename)
+    }
+
+    fn find_file_owner(&self, filename: &str) -> Result<Option<Option<uuid::Uuid>>, DomainError> {
+        self.find_file_owner_impl(filename)
+    }
+
+    fn find_external_media(&self) -> Result<Vec<(uuid::Uuid, String)>, DomainError> {
+        self.find_external_media_impl()
+    }
+
+    fn find_by_content_hash(&self, scope: &LibraryScope, content_hash: &str) -> Result<Option<uuid::Uuid>, DomainError> {
+        self.find_by_content_hash_impl(scope, content_hash)
+    }
+
+    fn set_content_hash(&self, id: uuid::Uuid, content_hash: &str) -> Result<(), DomainError> {
+        self.set_content_hash_impl(id, content_hash)
+    }
+
+    fn find_stored_originals(
+        &self,
+    ) -> Result<Vec<(uuid::Uuid, String, Option<String>)>, DomainError> {
+        self.find_stored_originals_impl()
+    }
+
+    fn trash_media(&self, ids: &[uuid::Uuid]) -> Result<usize, DomainError> {
+        self.trash_media_impl(ids)
+    }
+
+    fn restore_media(&self, ids: &[uuid::Uuid]) -> Result<usize, DomainError> {
+        self.restore_media_impl(ids)
+    }
+
+    fn find_trashed(
+        &self,
+        scope: &LibraryScope,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<TrashedMedia>, DomainError> {
+        self.find_trashed_impl(scope, limit, offset)
+    }
+
+    fn find_trashed_before(
+        &self,
+        cutoff: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<uuid::Uuid>, DomainError> {
+        self.find_trashed_before_impl(cutoff)
+    }
+
+    fn count_users(&self) -> Result<usize, DomainError> {
+        self.count_users_impl()
+    }
+
+    fn create_user(&self, user: &User, password_hash: &str) -> Result<(), DomainError> {
+        self.create_user_impl(user, password_hash)
+    }
+
+    fn find_user_by_id(&self, id: uuid::Uuid) -> Result<Option<User>, DomainError> {
+        self.find_user_by_id_impl(id)
+    }
+
+    fn find_user_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<(User, String)>, DomainError> {
+        self.find_user_by_username_impl(username)
+    }
+
+    fn list_users(&self) -> Result<Vec<User>, DomainError> {
+        self.list_users_impl()
+    }
+
+    fn update_user_role(&self, id: uuid::Uuid, role: Role) -> Result<(), DomainError> {
+        self.update_user_role_impl(id, role)
+    }
+
+    fn update_user_password(&self, id: uuid::Uuid, password_hash: &str) -> Result<(), DomainError> {
+        self.update_user_password_impl(id, password_hash)
+    }
+
+    fn delete_user(&self, id: uuid::Uuid) -> Result<(), DomainError> {
+        self.delete_user_impl(id)
+    }
+
+    fn claim_anonymous_favorites(&self, id: uuid::Uuid) -> Result<(), DomainError> {
+        self.claim_anonymous_favorites_impl(id)
+    }
+
+    fn find_user_by_identity(&self, issuer: &str, subject: &str) -> Result<Option<User>, DomainError> {
+        self.find_user_by_identity_impl(issuer, subject)
+    }
+
+    fn link_identity(
+        &self,
+        user_id: uuid::Uuid,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<bool, DomainError> {
+        self.link_identity_impl(user_id, issuer, subject)
+    }
+
+    fn create_session(&self, session: &Session, token_hash: &str) -> Result<(), DomainError> {
+        self.create_session_impl(session, token_hash)
+    }
+
+    fn find_session(
+        &self,
+        token_hash: &str,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<Session>, DomainError> {
+        self.find_session_impl(token_hash, now)
+    }
+
+    fn touch_session(
+        &self,
+        id: uuid::Uuid,
+        last_seen_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), DomainError> {
+        self.touch_session_impl(id, last_seen_at)
+    }
+
+    fn list_sessions(
+        &self,
+        user_id: uuid::Uuid,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Session>, DomainError> {
+        self.list_sessions_impl(user_id, now)
+    }
+
+    fn delete_session(&self, user_id: uuid::Uuid, id: uuid::Uuid) -> Result<bool, DomainError> {
+        self.delete_session_impl(user_id, id)
+    }
+
+    fn delete_user_sessions(&self, user_id: uuid::Uuid) -> Result<usize, DomainError> {
+        self.delete_user_sessions_impl(user_id)
+    }
+
+    fn delete_expired_sessions(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize, DomainError> {
+        self.delete_expired_sessions_impl(now)
+    }
+
+    fn create_api_token(&self, token: &ApiToken, token_hash: &str) -> Result<(), DomainError> {
+        self.create_api_token_impl(token, token_hash)
+    }
+
+    fn find_api_token(&self, token_hash: &str) -> Result<Option<ApiToken>, DomainError> {
+        self.find_api_token_impl(token_hash)
+    }
+
+    fn touch_api_token(
+        &self,
+        id: uuid::Uuid,
+        last_used_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), DomainError> {
+        self.touch_api_token_impl(id, last_used_at)
+    }
+
+    fn list_api_tokens(&self, user_id: uuid::Uuid) -> Result<Vec<ApiToken>, DomainError> {
+        self.list_api_tokens_impl(user_id)
+    }
+
+    fn delete_api_token(&self, user_id: uuid::Uuid, id: uuid::Uuid) -> Result<bool, DomainError> {
+        self.delete_api_token_impl(user_id, id)
+    }
+
+    fn create_share(&self, share: &ShareLink, password_hash: Option<&str>) -> Result<(), DomainError> {
+        self.create_share_impl(share, password_hash)
+    }
+
+    fn find_share(&self, id: uuid::Uuid) -> Result<Option<ShareLink>, DomainError> {
+        self.find_share_impl(id)
+    }
+
+    fn find_share_by_slug(
+        &self,
+        slug: &str,
+    ) -> Result<Option<(ShareLink, Option<String>)>, DomainError> {
+        self.find_share_by_slug_impl(slug)
+    }
+
+    fn list_shares(&self, created_by: Option<uuid::Uuid>) -> Result<Vec<ShareLink>, DomainError> {
+        self.list_shares_impl(created_by)
+    }
+
+    fn delete_share(&self, id: uuid::Uuid) -> Result<(), DomainError> {
+        self.delete_share_impl(id)
+    }
+
+    fn share_contains_media(
+        &self,
+        share: &ShareLink,
+        scope: &LibraryScope,
+        media_id: uuid::Uuid,
+    ) -> Result<bool, DomainError> {
+        self.share_contains_media_impl(share, scope, media_id)
+    }
+
+    fn append_audit(&self, entry: &AuditEntry) -> Result<(), DomainError> {
+        self.append_audit_impl(entry)
+    }
+
+    fn list_audit(
+        &self,
+        action: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<AuditEntry>, DomainError> {
+        self.list_audit_impl(action, limit, offset)
+    }
+
+    fn delete_audit_before(
+        &self,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize, DomainError> {
+        self.delete_audit_before_impl(before)
+    }
+
+    fn create_job(&self, job: &Job) -> Result<(), DomainError> {
+        self.create_job_impl(job)
+    }
+
+    fn find_job(&self, id: uuid::Uuid) -> Result<Option<Job>, DomainError> {
+        self.find_job_impl(id)
+    }
+
+    fn list_jobs(
+        &self,
+        created_by: Option<uuid::Uuid>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<Job>, DomainError> {
+        self.list_jobs_impl(created_by, limit, offset)
+    }
+
+    fn claim_next_job(&self, now: chrono::DateTime<chrono::Utc>) -> Result<Option<Job>, DomainError> {
+        self.claim_next_job_impl(now)
+    }
+
+    fn update_job_progress(&self, id: uuid::Uuid, done: usize, total: usize) -> Result<(), DomainError> {
+        self.update_job_progress_impl(id, done, total)
+    }
+
+    fn finish_job(
+        &self,
+        id: uuid::Uuid,
+        state: JobState,
+        error: Option<&str>,
+        result: Option<&serde_json::Value>,
+    ) -> Result<(), DomainError> {
+        self.finish_job_impl(id, state, error, result)
+    }
+
+    fn reschedule_job(
+        &self,
+        id: uuid::Uuid,
+        run_after: chrono::DateTime<chrono::Utc>,
+        error: &str,
+    ) -> Result<(), DomainError> {
+        self.reschedule_job_impl(id, run_after, error)
+    }
+
+    fn cancel_queued_job(&self, id: uuid::Uuid) -> Result<bool, DomainError> {
+        self.cancel_queued_job_impl(id)
+    }
+
+    fn retry_job(&self, id: uuid::Uuid, now: chrono::DateTime<chrono::Utc>) -> Result<bool, DomainError> {
+        self.retry_job_impl(id, now)
+    }
+
+    fn requeue_running_jobs(&self) -> Result<usize, DomainError> {
+        self.requeue_running_jobs_impl()
+    }
+
+    fn delete_finished_jobs_before(
+        &self,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize, DomainError> {
+        self.delete_finished_jobs_before_impl(before)
+    }
+
+    fn create_resumable_upload(&self, upload: &ResumableUpload) -> Result<(), DomainError> {
+        self.create_resumable_upload_impl(upload)
+    }
+
+    fn find_resumable_upload(&self, id: uuid::Uuid) -> Result<Option<ResumableUpload>, DomainError> {
+        self.find_resumable_upload_impl(id)
+    }
+
+    fn touch_resumable_upload(
+        &self,
+        id: uuid::Uuid,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), DomainError> {
+        self.touch_resumable_upload_impl(id, expires_at)
+    }
+
+    fn delete_resumable_upload(&self, id: uuid::Uuid) -> Result<(), DomainError> {
+        self.delete_resumable_upload_impl(id)
+    }
+
+    fn find_expired_resumable_uploads(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<uuid::Uuid>, DomainError> {
+        self.find_expired_resumable_uploads_impl(now)
+    }
+
+    fn add_companion(&self, companion: &MediaCompanion) -> Result<(), DomainError> {
+        self.add_companion_impl(companion)
+    }
+
+    fn find_companion(&self, media_id: uuid::Uuid) -> Result<Option<MediaCompanion>, DomainError> {
+        self.find_companion_impl(media_id)
+    }
+
+    fn find_companion_partner(
+        &self,
+        owner: Option<uuid::Uuid>,
+        stem: &str,
+        extensions: &[&str],
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<uuid::Uuid>, DomainError> {
+        self.find_companion_partner_impl(owner, stem, extensions, since)
+    }
+
+    fn add_renditions(&self, media_id: uuid::Uuid, names: &[String]) -> Result<(), DomainError> {
+        self.add_renditions_impl(media_id, names)
+    }
+
+    fn find_renditions(&self, media_id: uuid::Uuid) -> Result<Vec<String>, DomainError> {
+        self.find_renditions_impl(media_id)
+    }
+
+    fn find_media_missing_renditions(&self, names: &[String]) -> Result<Vec<uuid::Uuid>, DomainError> {
+        self.find_media_missing_renditions_impl(names)
+    }
+
+    fn get_all_phashes(
+        &self,
+        scope: &LibraryScope,
+        folder_id: Option<uuid::Uuid>,
+    ) -> Result<Vec<(uuid::Uuid, String, String)>, DomainError> {
+        self.get_all_phashes_impl(scope, folder_id)
+    }
+
+    fn merge_media_metadata(
+        &self,
+        target: uuid::Uuid,
+        sources: &[uuid::Uuid],
+    ) -> Result<(), DomainError> {
+        self.merge_media_metadata_impl(target, sources)
+    }
 }
 
 // ---- Tag helpers shared across submodules ----
@@ -559,6 +1328,27 @@ pub(crate) fn load_tags_bulk(
     map
 }
 
+/// Decode a nullable `owner_id` column.
+pub(crate) fn owner_from_row(bytes: Option<Vec<u8>>) -> Option<uuid::Uuid> {
+    bytes.and_then(|b| uuid::Uuid::from_slice(&b).ok())
+}
+
+/// SQL condition (plus its parameter) restricting `alias` rows to what `scope` may see,
+/// or `None` when the scope sees everything.
+pub(crate) fn scope_condition(
+    scope: &LibraryScope,
+    alias: &str,
+) -> Option<(String, Box<dyn rusqlite::types::ToSql>)> {
+    if scope.all_owners {
+        return None;
+    }
+    let owner = scope.user_id.map(|id| id.as_bytes().to_vec());
+    Some((
+        format!("({0}.owner_id IS NULL OR {0}.owner_id = ?)", alias),
+        Box::new(owner),
+    ))
+}
+
 pub(crate) fn normalize_vector(vector: &mut [f32]) {
     let norm: f32 = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
     if norm > 0.0 {
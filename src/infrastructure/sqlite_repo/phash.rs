@@ -0,0 +1,328 @@
+use crate::domain::{decode_phash_frames, frames_match, DomainError, LibraryScope};
+use rusqlite::{params, Connection};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use uuid::Uuid;
+
+use super::{scope_condition, SqliteRepository};
+
+/// Bits per band of the `media_phash_bands` index.
+const BAND_BITS: u32 = 16;
+
+/// Largest per-band distance looked up in `media_phash_bands`. A looser threshold
+/// would expand to thousands of band values, so those lookups scan every frame instead.
+const MAX_BAND_RADIUS: u32 = 2;
+
+/// Split frame bits into 16-bit bands (the last one shorter for odd lengths), as
+/// `(width in bits, value)`.
+fn bands(bits: &[u8]) -> Vec<(u32, u16)> {
+    bits.chunks(BAND_BITS as usize / 8)
+        .map(|chunk| {
+            let value = chunk.iter().fold(0u16, |value, &byte| value << 8 | byte as u16);
+            (chunk.len() as u32 * 8, value)
+        })
+        .collect()
+}
+
+/// Every `width`-bit value within `radius` differing bits of `value`.
+fn band_neighbors(value: u16, width: u32, radius: u32, lowest_bit: u32, out: &mut BTreeSet<u16>) {
+    out.insert(value);
+    if radius == 0 {
+        return;
+    }
+    for bit in lowest_bit..width {
+        band_neighbors(value ^ (1 << bit), width, radius - 1, bit + 1, out);
+    }
+}
+
+/// SQL condition on `media_phash_bands` rows that holds for every frame within
+/// `max_distance` bits of a query frame: frames that close differ by at most
+/// `max_distance / bands` bits in at least one band. `None` when that radius is too
+/// large to look up.
+fn band_filter(query: &[Vec<u8>], max_distance: u32) -> Option<String> {
+    let mut values: BTreeMap<usize, BTreeSet<u16>> = BTreeMap::new();
+    for frame in query {
+        let frame_bands = bands(frame);
+        let radius = max_distance / frame_bands.len() as u32;
+        if radius > MAX_BAND_RADIUS {
+            return None;
+        }
+        for (band, (width, value)) in frame_bands.into_iter().enumerate() {
+            band_neighbors(value, width, radius, 0, values.entry(band).or_default());
+        }
+    }
+    let conditions: Vec<String> = values
+        .into_iter()
+        .map(|(band, values)| {
+            let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+            format!("(band = {} AND value IN ({}))", band, values.join(","))
+        })
+        .collect();
+    Some(conditions.join(" OR "))
+}
+
+/// Replace the stored per-frame hash bits of a media item and their band index.
+/// Called inside the save/update transactions so `media.phash` and `media_phash`
+/// never disagree.
+pub(crate) fn write_phash_frames(
+    conn: &Connection,
+    media_id: &[u8],
+    phash: &str,
+) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM media_phash WHERE media_id = ?1", params![media_id])?;
+    conn.execute("DELETE FROM media_phash_bands WHERE media_id = ?1", params![media_id])?;
+    let mut stmt =
+        conn.prepare("INSERT INTO media_phash (media_id, frame, bits) VALUES (?1, ?2, ?3)")?;
+    let mut band_stmt = conn.prepare(
+        "INSERT INTO media_phash_bands (media_id, frame, band, value) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    for (frame, bits) in decode_phash_frames(phash).iter().enumerate() {
+        stmt.execute(params![media_id, frame as i64, bits])?;
+        for (band, (_, value)) in bands(bits).into_iter().enumerate() {
+            band_stmt.execute(params![media_id, frame as i64, band as i64, value])?;
+        }
+    }
+    Ok(())
+}
+
+/// Decode frames for rows stored before `media_phash` or its band index existed.
+/// Returns the number of media items that were backfilled.
+pub(crate) fn backfill_phash_frames(conn: &Connection) -> Result<usize, DomainError> {
+    let rows: Vec<(Vec<u8>, String)> = {
+        let mut stmt = conn
+            .prepare(
+                "SELECT m.id, m.phash FROM media m
+                 WHERE m.phash != 'no_hash'
+                   AND NOT EXISTS (SELECT 1 FROM media_phash_bands b WHERE b.media_id = m.id)",
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| DomainError::Database(e.to_string()))?
+    };
+
+    let mut count = 0;
+    for (id, phash) in rows {
+        if decode_phash_frames(&phash).is_empty() {
+            continue;
+        }
+        write_phash_frames(conn, &id, &phash).map_err(|e| DomainError::Database(e.to_string()))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+impl SqliteRepository {
+    pub(crate) fn find_near_duplicate_impl(
+        &self,
+        scope: &LibraryScope,
+        phash: &str,
+        media_type: &str,
+        max_distance: u32,
+    ) -> Result<Option<Uuid>, DomainError> {
+        let query = decode_phash_frames(phash);
+
+        self.with_conn(|conn| {
+            let (scope_sql, scope_param) = match scope_condition(scope, "m") {
+                Some((cond, param)) => (format!(" AND {}", cond), Some(param)),
+                None => (String::new(), None),
+            };
+            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+
+            if query.is_empty() {
+                // Not a decodable hash: only an identical string counts as a duplicate
+                params_vec.push(Box::new(phash.to_string()));
+                params_vec.extend(scope_param);
+                let id: Option<Vec<u8>> = conn
+                    .query_row(
+                        &format!(
+                            "SELECT id FROM media m WHERE phash = ? AND deleted_at IS NULL{} LIMIT 1",
+                            scope_sql
+                        ),
+                        rusqlite::params_from_iter(params_vec.iter()),
+                        |row| row.get(0),
+                    )
+                    .ok();
+                return Ok(id.and_then(|b| Uuid::from_slice(&b).ok()));
+            }
+
+            // Only compare items sharing a nearby band with the query
+            let band_sql = band_filter(&query, max_distance)
+                .map(|filter| {
+                    format!(" AND p.media_id IN (SELECT media_id FROM media_phash_bands WHERE {})", filter)
+                })
+                .unwrap_or_default();
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT p.media_id, p.bits FROM media_phash p
+                     JOIN media m ON m.id = p.media_id
+                     WHERE m.media_type = ? AND m.deleted_at IS NULL{}{}
+                     ORDER BY m.uploaded_at, p.media_id, p.frame",
+                    scope_sql, band_sql
+                ))
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            params_vec.push(Box::new(media_type.to_string()));
+            params_vec.extend(scope_param);
+            let rows = stmt
+                .query_map(rusqlite::params_from_iter(params_vec.iter()), |row| {
+                    Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?))
+                })
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            // Group frames per media item, keeping the oldest item first so the
+            // reported collision is the original rather than a later copy
+            let mut order: Vec<Vec<u8>> = Vec::new();
+            let mut frames: HashMap<Vec<u8>, Vec<Vec<u8>>> = HashMap::new();
+            for row in rows {
+                let (id, bits) = row.map_err(|e| DomainError::Database(e.to_string()))?;
+                if !frames.contains_key(&id) {
+                    order.push(id.clone());
+                }
+                frames.entry(id).or_default().push(bits);
+            }
+
+            Ok(order
+                .into_iter()
+                .find(|id| frames_match(&query, &frames[id], max_distance))
+                .and_then(|id| Uuid::from_slice(&id).ok()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::domain::{LibraryScope, MediaItem};
+    use base64::{engine::general_purpose, Engine as _};
+    use chrono::Utc;
+    use rusqlite::params;
+    use uuid::Uuid;
+
+    fn hash(frames: &[&[u8]]) -> String {
+        frames
+            .iter()
+            .map(|f| general_purpose::STANDARD.encode(f))
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+
+    fn save(db: &TestDb, media_type: &str, phash: &str) -> Uuid {
+        let item = MediaItem {
+            id: Uuid::new_v4(),
+            filename: "ab/cd/x.jpg".to_string(),
+            original_filename: "x.jpg".to_string(),
+            media_type: media_type.to_string(),
+            phash: phash.to_string(),
+            content_hash: None,
+            owner_id: None,
+            uploaded_at: Utc::now(),
+            original_date: Utc::now(),
+            width: None,
+            height: None,
+            size_bytes: 1,
+            exif_json: None,
+            is_favorite: false,
+            tags: vec![],
+            processing: false,
+        };
+        db.repo.save_metadata_and_vector_impl(&item, None).unwrap();
+        item.id
+    }
+
+    #[test]
+    fn test_near_duplicate_image_within_threshold() {
+        let db = TestDb::new("test_near_dup_image");
+        let id = save(&db, "image", &hash(&[&[0b1111_0000, 0xaa]]));
+
+        let close = hash(&[&[0b1111_0011, 0xaa]]);
+        assert_eq!(db.repo.find_near_duplicate_impl(&LibraryScope::ALL, &close, "image", 2).unwrap(), Some(id));
+        assert_eq!(db.repo.find_near_duplicate_impl(&LibraryScope::ALL, &close, "image", 1).unwrap(), None);
+        // Only items of the same media type are compared
+        assert_eq!(db.repo.find_near_duplicate_impl(&LibraryScope::ALL, &close, "video", 8).unwrap(), None);
+    }
+
+    #[test]
+    fn test_near_duplicate_video_frames() {
+        let db = TestDb::new("test_near_dup_video");
+        let id = save(&db, "video", &hash(&[&[0x00], &[0x0f], &[0xf0], &[0xff]]));
+
+        let reencoded = hash(&[&[0x01], &[0x0f], &[0xf0], &[0x7e]]);
+        assert_eq!(db.repo.find_near_duplicate_impl(&LibraryScope::ALL, &reencoded, "video", 1).unwrap(), Some(id));
+
+        let different = hash(&[&[0x55], &[0xaa], &[0x33], &[0xff]]);
+        assert_eq!(db.repo.find_near_duplicate_impl(&LibraryScope::ALL, &different, "video", 1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_band_filter_bounds_candidates() {
+        // Four bands: a 4-bit threshold leaves one band within one bit
+        let query = vec![vec![0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0]];
+        let filter = super::band_filter(&query, 4).unwrap();
+        assert!(filter.contains("(band = 0 AND value IN ("));
+        assert_eq!(filter.matches(',').count(), 4 * 16);
+        // Too loose to look up per band
+        assert!(super::band_filter(&query, 12).is_none());
+    }
+
+    #[test]
+    fn test_near_duplicate_through_band_index() {
+        let db = TestDb::new("test_near_dup_bands");
+        let id = save(&db, "image", &hash(&[&[0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0]]));
+        save(&db, "image", &hash(&[&[0xed, 0xcb, 0xa9, 0x87, 0x65, 0x43, 0x21, 0x0f]]));
+
+        // One bit flipped in every band is still found through the index
+        let close = hash(&[&[0x13, 0x34, 0x57, 0x78, 0x9b, 0xbc, 0xdf, 0xf0]]);
+        assert_eq!(db.repo.find_near_duplicate_impl(&LibraryScope::ALL, &close, "image", 4).unwrap(), Some(id));
+        assert_eq!(db.repo.find_near_duplicate_impl(&LibraryScope::ALL, &close, "image", 3).unwrap(), None);
+        // Thresholds beyond the band radius fall back to comparing every frame
+        let far = hash(&[&[0x1f, 0x34, 0x5f, 0x78, 0x9a, 0xbc, 0xde, 0xf0]]);
+        assert_eq!(db.repo.find_near_duplicate_impl(&LibraryScope::ALL, &far, "image", 12).unwrap(), Some(id));
+    }
+
+    #[test]
+    fn test_frames_follow_media_lifecycle() {
+        let db = TestDb::new("test_phash_frames_lifecycle");
+        let id = save(&db, "image", &hash(&[&[1, 2]]));
+        let frames = |db: &TestDb| -> i64 {
+            db.repo
+                .with_conn(|conn| {
+                    Ok(conn
+                        .query_row(
+                            "SELECT count(*) FROM media_phash WHERE media_id = ?1",
+                            params![id.as_bytes()],
+                            |r| r.get(0),
+                        )
+                        .unwrap())
+                })
+                .unwrap()
+        };
+        assert_eq!(frames(&db), 1);
+
+        db.repo.delete_impl(id).unwrap();
+        assert_eq!(frames(&db), 0);
+    }
+
+    #[test]
+    fn test_backfill_existing_rows() {
+        let db = TestDb::new("test_phash_backfill");
+        let id = Uuid::new_v4();
+        let phash = hash(&[&[9, 9]]);
+        db.repo
+            .with_conn(|conn| {
+                conn.execute(
+                    "INSERT INTO media (id, filename, original_filename, size_bytes, phash, uploaded_at, original_date)
+                     VALUES (?1, 'f.jpg', 'f.jpg', 1, ?2, '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')",
+                    params![id.as_bytes(), phash],
+                )
+                .unwrap();
+                assert_eq!(super::backfill_phash_frames(conn).unwrap(), 1);
+                assert_eq!(super::backfill_phash_frames(conn).unwrap(), 0);
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(db.repo.find_near_duplicate_impl(&LibraryScope::ALL, &phash, "image", 0).unwrap(), Some(id));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::domain::DomainError;
+use rusqlite::params;
+use uuid::Uuid;
+
+use super::SqliteRepository;
+
+impl SqliteRepository {
+    pub(crate) fn add_renditions_impl(&self, media_id: Uuid, names: &[String]) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction().map_err(|e| DomainError::Database(e.to_string()))?;
+            {
+                let mut stmt = tx
+                    .prepare("INSERT OR IGNORE INTO media_renditions (media_id, name) VALUES (?1, ?2)")
+                    .map_err(|e| DomainError::Database(e.to_string()))?;
+                for name in names {
+                    stmt.execute(params![media_id.as_bytes(), name])
+                        .map_err(|e| DomainError::Database(e.to_string()))?;
+                }
+            }
+            tx.commit().map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn find_renditions_impl(&self, media_id: Uuid) -> Result<Vec<String>, DomainError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT name FROM media_renditions WHERE media_id = ?1 ORDER BY name")
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            let rows = stmt
+                .query_map(params![media_id.as_bytes()], |row| row.get(0))
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            rows.collect::<Result<Vec<String>, _>>()
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn find_media_missing_renditions_impl(&self, names: &[String]) -> Result<Vec<Uuid>, DomainError> {
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+        self.with_conn(|conn| {
+            let placeholders = vec!["?"; names.len()].join(", ");
+            // Items without a decodable image are left to the thumbnail fix
+            let sql = format!(
+                "SELECT m.id FROM media m
+                 WHERE m.processing = 0 AND m.deleted_at IS NULL AND m.phash != 'no_hash'
+                   AND (SELECT COUNT(*) FROM media_renditions r
+                        WHERE r.media_id = m.id AND r.name IN ({})) < {}
+                 ORDER BY m.uploaded_at DESC",
+                placeholders,
+                names.len()
+            );
+            let mut stmt = conn.prepare(&sql).map_err(|e| DomainError::Database(e.to_string()))?;
+            let rows = stmt
+                .query_map(rusqlite::params_from_iter(names.iter()), |row| {
+                    let bytes: Vec<u8> = row.get(0)?;
+                    Uuid::from_slice(&bytes).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Blob, Box::new(e))
+                    })
+                })
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::domain::MediaItem;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn media(phash: &str) -> MediaItem {
+        MediaItem {
+            id: Uuid::new_v4(),
+            filename: format!("{}.jpg", Uuid::new_v4()),
+            original_filename: "photo.jpg".to_string(),
+            media_type: "image".to_string(),
+            phash: phash.to_string(),
+            content_hash: None,
+            owner_id: None,
+            uploaded_at: Utc::now(),
+            original_date: Utc::now(),
+            width: None,
+            height: None,
+            size_bytes: 0,
+            exif_json: None,
+            is_favorite: false,
+            tags: vec![],
+            processing: false,
+        }
+    }
+
+    #[test]
+    fn test_media_missing_renditions() {
+        let db = TestDb::new("test_media_missing_renditions");
+        let names = vec!["square_224".to_string(), "720".to_string()];
+        let done = media("abc");
+        let partial = media("def");
+        db.repo.save_metadata_and_vector_impl(&done, None).unwrap();
+        db.repo.save_metadata_and_vector_impl(&partial, None).unwrap();
+        // Not decodable, so nothing to render from
+        db.repo.save_metadata_and_vector_impl(&media("no_hash"), None).unwrap();
+
+        db.repo.add_renditions_impl(done.id, &names).unwrap();
+        db.repo.add_renditions_impl(done.id, &names[..1]).unwrap();
+        db.repo.add_renditions_impl(partial.id, &names[..1]).unwrap();
+        assert_eq!(db.repo.find_renditions_impl(done.id).unwrap(), vec!["720", "square_224"]);
+        assert_eq!(db.repo.find_media_missing_renditions_impl(&names).unwrap(), vec![partial.id]);
+        // A newly configured size is missing everywhere
+        let mut more = names.clone();
+        more.push("2048".to_string());
+        assert_eq!(db.repo.find_media_missing_renditions_impl(&more).unwrap().len(), 2);
+
+        db.repo.delete_impl(done.id).unwrap();
+        assert!(db.repo.find_renditions_impl(done.id).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+use crate::domain::{DomainError, ResumableUpload};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension, Row};
+use uuid::Uuid;
+
+use super::{owner_from_row, SqliteRepository};
+
+const UPLOAD_COLUMNS: &str = "id, filename, length, owner_id, created_at, expires_at";
+
+fn upload_from_row(row: &Row) -> rusqlite::Result<ResumableUpload> {
+    let date_at = |idx: usize| -> rusqlite::Result<DateTime<Utc>> {
+        let value: String = row.get(idx)?;
+        DateTime::parse_from_rfc3339(&value)
+            .map(|d| d.with_timezone(&Utc))
+            .map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+            })
+    };
+    let id_bytes: Vec<u8> = row.get(0)?;
+
+    Ok(ResumableUpload {
+        id: Uuid::from_slice(&id_bytes).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Blob, Box::new(e))
+        })?,
+        filename: row.get(1)?,
+        length: row.get::<_, i64>(2)? as u64,
+        owner_id: owner_from_row(row.get(3)?),
+        created_at: date_at(4)?,
+        expires_at: date_at(5)?,
+    })
+}
+
+impl SqliteRepository {
+    pub(crate) fn create_resumable_upload_impl(&self, upload: &ResumableUpload) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO resumable_uploads (id, filename, length, owner_id, created_at, expires_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    upload.id.as_bytes(),
+                    upload.filename,
+                    upload.length as i64,
+                    upload.owner_id.as_ref().map(|id| id.as_bytes().to_vec()),
+                    upload.created_at.to_rfc3339(),
+                    upload.expires_at.to_rfc3339()
+                ],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn find_resumable_upload_impl(&self, id: Uuid) -> Result<Option<ResumableUpload>, DomainError> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM resumable_uploads WHERE id = ?1", UPLOAD_COLUMNS),
+                params![id.as_bytes()],
+                upload_from_row,
+            )
+            .optional()
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn touch_resumable_upload_impl(
+        &self,
+        id: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE resumable_uploads SET expires_at = ?2 WHERE id = ?1",
+                params![id.as_bytes(), expires_at.to_rfc3339()],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn delete_resumable_upload_impl(&self, id: Uuid) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute("DELETE FROM resumable_uploads WHERE id = ?1", params![id.as_bytes()])
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn find_expired_resumable_uploads_impl(&self, now: DateTime<Utc>) -> Result<Vec<Uuid>, DomainError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT id FROM resumable_uploads WHERE expires_at <= ?1")
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            let rows = stmt
+                .query_map(params![now.to_rfc3339()], |row| {
+                    let bytes: Vec<u8> = row.get(0)?;
+                    Uuid::from_slice(&bytes).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Blob, Box::new(e))
+                    })
+                })
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::domain::ResumableUpload;
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    fn upload(expires_in: Duration) -> ResumableUpload {
+        let now = Utc::now();
+        ResumableUpload {
+            id: Uuid::new_v4(),
+            filename: "holiday.mp4".to_string(),
+            length: 4 * 1024 * 1024 * 1024,
+            owner_id: Some(Uuid::new_v4()),
+            created_at: now,
+            expires_at: now + expires_in,
+        }
+    }
+
+    #[test]
+    fn test_resumable_upload_lifecycle() {
+        let db = TestDb::new("test_resumable_upload_lifecycle");
+        let active = upload(Duration::hours(1));
+        let abandoned = upload(Duration::seconds(-1));
+        db.repo.create_resumable_upload_impl(&active).unwrap();
+        db.repo.create_resumable_upload_impl(&abandoned).unwrap();
+
+        let found = db.repo.find_resumable_upload_impl(active.id).unwrap().unwrap();
+        assert_eq!(found.filename, "holiday.mp4");
+        assert_eq!(found.length, active.length);
+        assert_eq!(found.owner_id, active.owner_id);
+
+        let now = Utc::now();
+        assert_eq!(db.repo.find_expired_resumable_uploads_impl(now).unwrap(), vec![abandoned.id]);
+        // A chunk pushes the expiry back
+        db.repo.touch_resumable_upload_impl(abandoned.id, now + Duration::hours(1)).unwrap();
+        assert!(db.repo.find_expired_resumable_uploads_impl(now).unwrap().is_empty());
+
+        db.repo.delete_resumable_upload_impl(active.id).unwrap();
+        assert!(db.repo.find_resumable_upload_impl(active.id).unwrap().is_none());
+    }
+}
@@ -0,0 +1,197 @@
+use crate::domain::{DomainError, Session};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension, Row};
+use uuid::Uuid;
+
+use super::SqliteRepository;
+
+const SESSION_COLUMNS: &str = "id, user_id, created_at, last_seen_at, expires_at, user_agent, ip";
+
+fn session_from_row(row: &Row) -> rusqlite::Result<Session> {
+    let uuid_at = |idx: usize| -> rusqlite::Result<Uuid> {
+        let bytes: Vec<u8> = row.get(idx)?;
+        Uuid::from_slice(&bytes).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Blob, Box::new(e))
+        })
+    };
+    let date_at = |idx: usize| -> rusqlite::Result<DateTime<Utc>> {
+        let value: String = row.get(idx)?;
+        DateTime::parse_from_rfc3339(&value)
+            .map(|d| d.with_timezone(&Utc))
+            .map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+            })
+    };
+
+    Ok(Session {
+        id: uuid_at(0)?,
+        user_id: uuid_at(1)?,
+        created_at: date_at(2)?,
+        last_seen_at: date_at(3)?,
+        expires_at: date_at(4)?,
+        user_agent: row.get(5)?,
+        ip: row.get(6)?,
+    })
+}
+
+impl SqliteRepository {
+    pub(crate) fn create_session_impl(
+        &self,
+        session: &Session,
+        token_hash: &str,
+    ) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO sessions (id, token_hash, user_id, created_at, last_seen_at, expires_at, user_agent, ip)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    session.id.as_bytes(),
+                    token_hash,
+                    session.user_id.as_bytes(),
+                    session.created_at.to_rfc3339(),
+                    session.last_seen_at.to_rfc3339(),
+                    session.expires_at.to_rfc3339(),
+                    session.user_agent,
+                    session.ip
+                ],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn find_session_impl(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Session>, DomainError> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                &format!(
+                    "SELECT {} FROM sessions WHERE token_hash = ?1 AND expires_at > ?2",
+                    SESSION_COLUMNS
+                ),
+                params![token_hash, now.to_rfc3339()],
+                session_from_row,
+            )
+            .optional()
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn touch_session_impl(
+        &self,
+        id: Uuid,
+        last_seen_at: DateTime<Utc>,
+    ) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE sessions SET last_seen_at = ?2 WHERE id = ?1",
+                params![id.as_bytes(), last_seen_at.to_rfc3339()],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn list_sessions_impl(
+        &self,
+        user_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, DomainError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT {} FROM sessions WHERE user_id = ?1 AND expires_at > ?2
+                     ORDER BY last_seen_at DESC",
+                    SESSION_COLUMNS
+                ))
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            let rows = stmt
+                .query_map(params![user_id.as_bytes(), now.to_rfc3339()], session_from_row)
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn delete_session_impl(&self, user_id: Uuid, id: Uuid) -> Result<bool, DomainError> {
+        self.with_conn(|conn| {
+            let deleted = conn
+                .execute(
+                    "DELETE FROM sessions WHERE id = ?1 AND user_id = ?2",
+                    params![id.as_bytes(), user_id.as_bytes()],
+                )
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(deleted > 0)
+        })
+    }
+
+    pub(crate) fn delete_user_sessions_impl(&self, user_id: Uuid) -> Result<usize, DomainError> {
+        self.with_conn(|conn| {
+            conn.execute("DELETE FROM sessions WHERE user_id = ?1", params![user_id.as_bytes()])
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn delete_expired_sessions_impl(&self, now: DateTime<Utc>) -> Result<usize, DomainError> {
+        self.with_conn(|conn| {
+            conn.execute("DELETE FROM sessions WHERE expires_at <= ?1", params![now.to_rfc3339()])
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::domain::Session;
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    fn session(user_id: Uuid, expires_in: Duration) -> Session {
+        let now = Utc::now();
+        Session {
+            id: Uuid::new_v4(),
+            user_id,
+            created_at: now,
+            last_seen_at: now,
+            expires_at: now + expires_in,
+            user_agent: Some("Firefox".to_string()),
+            ip: Some("10.0.0.2".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_session_lifecycle() {
+        let db = TestDb::new("test_session_lifecycle");
+        let user = Uuid::new_v4();
+        let phone = session(user, Duration::days(1));
+        let laptop = session(user, Duration::days(1));
+        let stale = session(user, Duration::seconds(-1));
+        db.repo.create_session_impl(&phone, "phone").unwrap();
+        db.repo.create_session_impl(&laptop, "laptop").unwrap();
+        db.repo.create_session_impl(&stale, "stale").unwrap();
+
+        let now = Utc::now();
+        let found = db.repo.find_session_impl("phone", now).unwrap().unwrap();
+        assert_eq!(found.id, phone.id);
+        assert_eq!(found.user_agent.as_deref(), Some("Firefox"));
+        // Expired sessions are never returned
+        assert!(db.repo.find_session_impl("stale", now).unwrap().is_none());
+
+        db.repo.touch_session_impl(laptop.id, now + Duration::minutes(5)).unwrap();
+        let listed = db.repo.list_sessions_impl(user, now).unwrap();
+        assert_eq!(listed.iter().map(|s| s.id).collect::<Vec<_>>(), vec![laptop.id, phone.id]);
+
+        // Sessions can only be revoked by their owner
+        assert!(!db.repo.delete_session_impl(Uuid::new_v4(), phone.id).unwrap());
+        assert!(db.repo.delete_session_impl(user, phone.id).unwrap());
+        assert!(db.repo.find_session_impl("phone", now).unwrap().is_none());
+        assert!(db.repo.find_session_impl("laptop", now).unwrap().is_some());
+
+        assert_eq!(db.repo.delete_expired_sessions_impl(now).unwrap(), 1);
+        assert_eq!(db.repo.delete_user_sessions_impl(user).unwrap(), 1);
+        assert!(db.repo.list_sessions_impl(user, now).unwrap().is_empty());
+    }
+}
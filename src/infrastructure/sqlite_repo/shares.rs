@@ -0,0 +1,271 @@
+use crate::domain::{DomainError, LibraryScope, ShareLink, ShareTarget};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension, Row};
+use uuid::Uuid;
+
+use super::{owner_from_row, scope_condition, SqliteRepository};
+
+const SHARE_COLUMNS: &str =
+    "id, slug, created_by, folder_id, media_id, password_hash IS NOT NULL, allow_download, expires_at, created_at";
+
+fn share_from_row(row: &Row) -> rusqlite::Result<ShareLink> {
+    let conversion_error = |idx: usize, ty, e: Box<dyn std::error::Error + Send + Sync>| {
+        rusqlite::Error::FromSqlConversionFailure(idx, ty, e)
+    };
+    let id_bytes: Vec<u8> = row.get(0)?;
+    let id = Uuid::from_slice(&id_bytes)
+        .map_err(|e| conversion_error(0, rusqlite::types::Type::Blob, Box::new(e)))?;
+    let target = match (owner_from_row(row.get(3)?), owner_from_row(row.get(4)?)) {
+        (Some(folder_id), None) => ShareTarget::Folder(folder_id),
+        (None, Some(media_id)) => ShareTarget::Media(media_id),
+        _ => {
+            return Err(conversion_error(
+                3,
+                rusqlite::types::Type::Blob,
+                "share must target exactly one folder or item".into(),
+            ))
+        }
+    };
+    let parse_date = |idx: usize, value: String| {
+        DateTime::parse_from_rfc3339(&value)
+            .map(|d| d.with_timezone(&Utc))
+            .map_err(|e| conversion_error(idx, rusqlite::types::Type::Text, Box::new(e)))
+    };
+    let expires_at: Option<String> = row.get(7)?;
+
+    Ok(ShareLink {
+        id,
+        slug: row.get(1)?,
+        created_by: owner_from_row(row.get(2)?),
+        target,
+        has_password: row.get(5)?,
+        allow_download: row.get(6)?,
+        expires_at: expires_at.map(|v| parse_date(7, v)).transpose()?,
+        created_at: parse_date(8, row.get(8)?)?,
+    })
+}
+
+impl SqliteRepository {
+    pub(crate) fn create_share_impl(
+        &self,
+        share: &ShareLink,
+        password_hash: Option<&str>,
+    ) -> Result<(), DomainError> {
+        let (folder_id, media_id) = match share.target {
+            ShareTarget::Folder(id) => (Some(id), None),
+            ShareTarget::Media(id) => (None, Some(id)),
+        };
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO shares (id, slug, created_by, folder_id, media_id, password_hash, allow_download, expires_at, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    share.id.as_bytes(),
+                    share.slug,
+                    share.created_by.map(|id| id.as_bytes().to_vec()),
+                    folder_id.map(|id| id.as_bytes().to_vec()),
+                    media_id.map(|id| id.as_bytes().to_vec()),
+                    password_hash,
+                    share.allow_download,
+                    share.expires_at.map(|d| d.to_rfc3339()),
+                    share.created_at.to_rfc3339()
+                ],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn find_share_impl(&self, id: Uuid) -> Result<Option<ShareLink>, DomainError> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM shares WHERE id = ?1", SHARE_COLUMNS),
+                params![id.as_bytes()],
+                share_from_row,
+            )
+            .optional()
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn find_share_by_slug_impl(
+        &self,
+        slug: &str,
+    ) -> Result<Option<(ShareLink, Option<String>)>, DomainError> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                &format!("SELECT {}, password_hash FROM shares WHERE slug = ?1", SHARE_COLUMNS),
+                params![slug],
+                |row| Ok((share_from_row(row)?, row.get(9)?)),
+            )
+            .optional()
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn list_shares_impl(
+        &self,
+        created_by: Option<Uuid>,
+    ) -> Result<Vec<ShareLink>, DomainError> {
+        self.with_conn(|conn| {
+            let mut sql = format!("SELECT {} FROM shares", SHARE_COLUMNS);
+            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+            if let Some(user_id) = created_by {
+                sql.push_str(" WHERE created_by = ?");
+                params_vec.push(Box::new(user_id.as_bytes().to_vec()));
+            }
+            sql.push_str(" ORDER BY created_at DESC");
+
+            let mut stmt = conn
+                .prepare(&sql)
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            let rows = stmt
+                .query_map(rusqlite::params_from_iter(params_vec.iter()), share_from_row)
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn delete_share_impl(&self, id: Uuid) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            let deleted = conn
+                .execute("DELETE FROM shares WHERE id = ?1", params![id.as_bytes()])
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            if deleted == 0 {
+                return Err(DomainError::NotFound);
+            }
+            Ok(())
+        })
+    }
+
+    pub(crate) fn share_contains_media_impl(
+        &self,
+        share: &ShareLink,
+        scope: &LibraryScope,
+        media_id: Uuid,
+    ) -> Result<bool, DomainError> {
+        self.with_conn(|conn| {
+            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> =
+                vec![Box::new(media_id.as_bytes().to_vec())];
+            let mut sql = match share.target {
+                ShareTarget::Folder(folder_id) => {
+                    params_vec.push(Box::new(folder_id.as_bytes().to_vec()));
+                    "SELECT COUNT(*) FROM media m
+                     JOIN folder_media fm ON fm.media_id = m.id
+                     WHERE m.id = ? AND fm.folder_id = ? AND m.deleted_at IS NULL"
+                        .to_string()
+                }
+                ShareTarget::Media(shared_id) => {
+                    params_vec.push(Box::new(shared_id.as_bytes().to_vec()));
+                    "SELECT COUNT(*) FROM media m WHERE m.id = ? AND m.id = ? AND m.deleted_at IS NULL"
+                        .to_string()
+                }
+            };
+            if let Some((cond, param)) = scope_condition(scope, "m") {
+                sql.push_str(" AND ");
+                sql.push_str(&cond);
+                params_vec.push(param);
+            }
+
+            let count: i64 = conn
+                .query_row(&sql, rusqlite::params_from_iter(params_vec.iter()), |row| row.get(0))
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(count > 0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::domain::{DomainError, LibraryScope, ShareLink, ShareTarget};
+    use chrono::Utc;
+    use rusqlite::params;
+    use uuid::Uuid;
+
+    fn insert_media(db: &TestDb, id: Uuid, owner: Option<Uuid>) {
+        db.repo
+            .with_conn(|conn| {
+                conn.execute(
+                    "INSERT INTO media (id, filename, original_filename, size_bytes, phash, uploaded_at, original_date, owner_id)
+                     VALUES (?1, 'f.jpg', 'f.jpg', 1, 'ph', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z', ?2)",
+                    params![id.as_bytes(), owner.map(|o| o.as_bytes().to_vec())],
+                )
+                .unwrap();
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    fn share(target: ShareTarget) -> ShareLink {
+        ShareLink {
+            id: Uuid::new_v4(),
+            slug: Uuid::new_v4().simple().to_string(),
+            created_by: None,
+            target,
+            has_password: false,
+            allow_download: true,
+            expires_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_share_crud() {
+        let db = TestDb::new("test_share_crud");
+        let creator = Uuid::new_v4();
+        let mut link = share(ShareTarget::Media(Uuid::new_v4()));
+        link.created_by = Some(creator);
+        db.repo.create_share_impl(&link, Some("hash")).unwrap();
+        db.repo.create_share_impl(&share(ShareTarget::Folder(Uuid::new_v4())), None).unwrap();
+
+        let (found, hash) = db.repo.find_share_by_slug_impl(&link.slug).unwrap().unwrap();
+        assert_eq!(found.target, link.target);
+        assert!(found.has_password);
+        assert_eq!(hash.as_deref(), Some("hash"));
+        assert!(db.repo.find_share_by_slug_impl("missing").unwrap().is_none());
+
+        assert_eq!(db.repo.list_shares_impl(None).unwrap().len(), 2);
+        assert_eq!(db.repo.list_shares_impl(Some(creator)).unwrap().len(), 1);
+
+        db.repo.delete_share_impl(link.id).unwrap();
+        assert!(db.repo.find_share_impl(link.id).unwrap().is_none());
+        assert!(matches!(db.repo.delete_share_impl(link.id), Err(DomainError::NotFound)));
+    }
+
+    #[test]
+    fn test_share_contains_only_live_visible_items() {
+        let db = TestDb::new("test_share_contains");
+        let member = Uuid::new_v4();
+        let shared = Uuid::new_v4();
+        let private = Uuid::new_v4();
+        let trashed = Uuid::new_v4();
+        let outside = Uuid::new_v4();
+        insert_media(&db, shared, None);
+        insert_media(&db, private, Some(Uuid::new_v4()));
+        insert_media(&db, trashed, None);
+        insert_media(&db, outside, None);
+
+        let folder = db.repo.create_folder_impl(Uuid::new_v4(), "Trip", None).unwrap();
+        db.repo.add_media_to_folder_impl(folder.id, &[shared, private, trashed]).unwrap();
+        db.repo.trash_media_impl(&[trashed]).unwrap();
+
+        let link = share(ShareTarget::Folder(folder.id));
+        let scope = LibraryScope { user_id: Some(member), all_owners: false };
+        assert!(db.repo.share_contains_media_impl(&link, &scope, shared).unwrap());
+        // Another member's private item stays hidden, as do trashed and unrelated items
+        assert!(!db.repo.share_contains_media_impl(&link, &scope, private).unwrap());
+        assert!(!db.repo.share_contains_media_impl(&link, &scope, trashed).unwrap());
+        assert!(!db.repo.share_contains_media_impl(&link, &scope, outside).unwrap());
+
+        let single = share(ShareTarget::Media(shared));
+        assert!(db.repo.share_contains_media_impl(&single, &LibraryScope::ALL, shared).unwrap());
+        assert!(!db.repo.share_contains_media_impl(&single, &LibraryScope::ALL, outside).unwrap());
+
+        // Deleting the folder removes its links
+        db.repo.create_share_impl(&link, None).unwrap();
+        db.repo.delete_folder_impl(folder.id).unwrap();
+        assert!(db.repo.find_share_impl(link.id).unwrap().is_none());
+    }
+}
@@ -1,26 +1,35 @@
-use crate::domain::DomainError;
+use crate::domain::{DomainError, LibraryScope};
 use rusqlite::params;
 use uuid::Uuid;
 
-use super::SqliteRepository;
+use super::{scope_condition, SqliteRepository};
 
 impl SqliteRepository {
     pub(crate) fn get_all_tags_impl(
         &self,
+        scope: &LibraryScope,
     ) -> Result<Vec<crate::domain::models::TagCount>, DomainError> {
         self.with_conn(|conn| {
-            let mut stmt = conn
-                .prepare(
-                    "SELECT t.name, COUNT(mt.media_id)
+            let mut sql = "SELECT t.name, COUNT(mt.media_id)
                      FROM tags t
                      JOIN media_tags mt ON mt.tag_id = t.id
-                     GROUP BY t.id, t.name
-                     ORDER BY t.name",
-                )
+                     JOIN media m ON m.id = mt.media_id
+                     WHERE m.deleted_at IS NULL"
+                .to_string();
+            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+            if let Some((cond, param)) = scope_condition(scope, "m") {
+                sql.push_str(" AND ");
+                sql.push_str(&cond);
+                params_vec.push(param);
+            }
+            sql.push_str(" GROUP BY t.id, t.name ORDER BY t.name");
+
+            let mut stmt = conn
+                .prepare(&sql)
                 .map_err(|e| DomainError::Database(e.to_string()))?;
 
             let rows = stmt
-                .query_map([], |row| {
+                .query_map(rusqlite::params_from_iter(params_vec.iter()), |row| {
                     Ok(crate::domain::models::TagCount {
                         name: row.get(0)?,
                         count: row.get(1)?,
@@ -180,31 +189,27 @@ impl SqliteRepository {
 
     pub(crate) fn count_auto_tags_impl(
         &self,
+        scope: &LibraryScope,
         folder_id: Option<Uuid>,
     ) -> Result<usize, DomainError> {
         self.with_conn(|conn| {
-            let sql = match folder_id {
-                Some(_) => {
-                    "SELECT COUNT(*) FROM media_tags mt 
-                            JOIN folder_media fm ON fm.media_id = mt.media_id 
-                            WHERE mt.is_auto = 1 AND fm.folder_id = ?1"
-                }
-                None => "SELECT COUNT(*) FROM media_tags WHERE is_auto = 1",
-            };
+            let mut sql = "SELECT COUNT(*) FROM media_tags mt JOIN media m ON m.id = mt.media_id".to_string();
+            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+            if let Some(id) = folder_id {
+                sql.push_str(" JOIN folder_media fm ON fm.media_id = mt.media_id AND fm.folder_id = ?");
+                params_vec.push(Box::new(id.as_bytes().to_vec()));
+            }
+            sql.push_str(" WHERE mt.is_auto = 1");
+            if let Some((cond, param)) = scope_condition(scope, "m") {
+                sql.push_str(" AND ");
+                sql.push_str(&cond);
+                params_vec.push(param);
+            }
 
-            let mut stmt = conn
-                .prepare(sql)
+            let count: i64 = conn
+                .query_row(&sql, rusqlite::params_from_iter(params_vec.iter()), |row| row.get(0))
                 .map_err(|e| DomainError::Database(e.to_string()))?;
 
-            let count: i64 = match folder_id {
-                Some(id) => stmt
-                    .query_row(params![id.as_bytes()], |row| row.get(0))
-                    .map_err(|e| DomainError::Database(e.to_string()))?,
-                None => stmt
-                    .query_row([], |row| row.get(0))
-                    .map_err(|e| DomainError::Database(e.to_string()))?,
-            };
-
             Ok(count as usize)
         })
     }
@@ -212,7 +217,11 @@ impl SqliteRepository {
     pub(crate) fn get_manual_positives_impl(&self, tag_id: i64) -> Result<Vec<Uuid>, DomainError> {
         self.with_conn(|conn| {
             let mut stmt = conn
-                .prepare("SELECT media_id FROM media_tags WHERE tag_id = ?1 AND is_auto = 0")
+                .prepare(
+                    "SELECT mt.media_id FROM media_tags mt
+                     JOIN media m ON m.id = mt.media_id
+                     WHERE mt.tag_id = ?1 AND mt.is_auto = 0 AND m.deleted_at IS NULL",
+                )
                 .map_err(|e| DomainError::Database(e.to_string()))?;
 
             let rows = stmt
@@ -500,6 +509,30 @@ mod tests {
         }).unwrap();
     }
 
+    #[test]
+    fn test_tag_counts_are_scoped() {
+        let db = TestDb::new("test_tag_counts_scoped");
+        let owner = Uuid::new_v4();
+        let media_id = Uuid::new_v4();
+        insert_test_media(&db.repo, media_id);
+        db.repo
+            .with_conn(|conn| {
+                conn.execute("UPDATE media SET owner_id = ?1", params![owner.as_bytes()]).unwrap();
+                conn.execute("INSERT INTO tags (id, name) VALUES (1, 'Nature')", []).unwrap();
+                Ok(())
+            })
+            .unwrap();
+        db.repo.update_media_tags_impl(media_id, vec!["Trip".to_string()]).unwrap();
+        db.repo.update_auto_tags_impl(1, &[(media_id, 0.9)], None).unwrap();
+
+        let member = |id| LibraryScope { user_id: Some(id), all_owners: false };
+        assert_eq!(db.repo.get_all_tags_impl(&member(owner)).unwrap().len(), 2);
+        assert_eq!(db.repo.count_auto_tags_impl(&member(owner), None).unwrap(), 1);
+        // Someone else's private items are not counted
+        assert!(db.repo.get_all_tags_impl(&member(Uuid::new_v4())).unwrap().is_empty());
+        assert_eq!(db.repo.count_auto_tags_impl(&member(Uuid::new_v4()), None).unwrap(), 0);
+    }
+
     #[test]
     fn test_manual_tag_protection() {
         let db = TestDb::new("test_protection");
@@ -893,7 +926,7 @@ mod tests {
         }).unwrap();
 
         // 4. Global count should be 0
-        assert_eq!(db.repo.count_auto_tags_impl(None).unwrap(), 0);
+        assert_eq!(db.repo.count_auto_tags_impl(&LibraryScope::ALL, None).unwrap(), 0);
 
         // 5. Add auto-tag
         db.repo
@@ -902,12 +935,12 @@ mod tests {
 
         // 6. Verify counts
         assert_eq!(
-            db.repo.count_auto_tags_impl(None).unwrap(),
+            db.repo.count_auto_tags_impl(&LibraryScope::ALL, None).unwrap(),
             1,
             "Global count should be 1"
         );
         assert_eq!(
-            db.repo.count_auto_tags_impl(Some(folder_id)).unwrap(),
+            db.repo.count_auto_tags_impl(&LibraryScope::ALL, Some(folder_id)).unwrap(),
             1,
             "Folder count should be 1"
         );
@@ -925,7 +958,7 @@ mod tests {
             })
             .unwrap();
         assert_eq!(
-            db.repo.count_auto_tags_impl(Some(other_folder)).unwrap(),
+            db.repo.count_auto_tags_impl(&LibraryScope::ALL, Some(other_folder)).unwrap(),
             0,
             "Other folder count should be 0"
         );
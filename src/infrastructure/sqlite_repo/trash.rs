@@ -0,0 +1,221 @@
+use crate::domain::{DomainError, LibraryScope, MediaSummary, TrashedMedia};
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use uuid::Uuid;
+
+use super::{load_tags_bulk, owner_from_row, scope_condition, SqliteRepository};
+
+impl SqliteRepository {
+    pub(crate) fn trash_media_impl(&self, ids: &[Uuid]) -> Result<usize, DomainError> {
+        self.with_conn(|conn| {
+            let now = Utc::now().to_rfc3339();
+            let mut trashed = 0usize;
+            for id in ids {
+                trashed += conn
+                    .execute(
+                        "UPDATE media SET deleted_at = ?2 WHERE id = ?1 AND deleted_at IS NULL",
+                        params![id.as_bytes(), now],
+                    )
+                    .map_err(|e| DomainError::Database(e.to_string()))?;
+            }
+            Ok(trashed)
+        })
+    }
+
+    pub(crate) fn restore_media_impl(&self, ids: &[Uuid]) -> Result<usize, DomainError> {
+        self.with_conn(|conn| {
+            let mut restored = 0usize;
+            for id in ids {
+                restored += conn
+                    .execute(
+                        "UPDATE media SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+                        params![id.as_bytes()],
+                    )
+                    .map_err(|e| DomainError::Database(e.to_string()))?;
+            }
+            Ok(restored)
+        })
+    }
+
+    pub(crate) fn find_trashed_impl(
+        &self,
+        scope: &LibraryScope,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<TrashedMedia>, DomainError> {
+        self.with_conn(|conn| {
+            let mut sql = "SELECT m.id, m.filename, m.original_filename, m.media_type, m.uploaded_at, m.original_date,
+                            (f.media_id IS NOT NULL) as is_favorite, m.size_bytes, m.deleted_at, m.owner_id, m.processing
+                     FROM media m
+                     LEFT JOIN favorites f ON f.media_id = m.id AND f.user_id = ?
+                     WHERE m.deleted_at IS NOT NULL"
+                .to_string();
+            let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> =
+                vec![Box::new(scope.favorites_key().as_bytes().to_vec())];
+            if let Some((cond, param)) = scope_condition(scope, "m") {
+                sql.push_str(" AND ");
+                sql.push_str(&cond);
+                params_vec.push(param);
+            }
+            sql.push_str(" ORDER BY m.deleted_at DESC LIMIT ? OFFSET ?");
+            params_vec.push(Box::new(limit as i64));
+            params_vec.push(Box::new(offset as i64));
+
+            let mut stmt = conn
+                .prepare(&sql)
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            let parse_date = |idx: usize, value: String| {
+                DateTime::parse_from_rfc3339(&value)
+                    .map(|d| d.with_timezone(&Utc))
+                    .map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            idx,
+                            rusqlite::types::Type::Text,
+                            Box::new(e),
+                        )
+                    })
+            };
+
+            let rows = stmt
+                .query_map(rusqlite::params_from_iter(params_vec.iter()), |row| {
+                    let id_bytes: Vec<u8> = row.get(0)?;
+                    let id = Uuid::from_slice(&id_bytes).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            0,
+                            rusqlite::types::Type::Blob,
+                            Box::new(e),
+                        )
+                    })?;
+
+                    Ok((
+                        id_bytes,
+                        TrashedMedia {
+                            media: MediaSummary {
+                                id,
+                                filename: row.get(1)?,
+                                original_filename: row.get(2)?,
+                                media_type: row.get(3)?,
+                                owner_id: owner_from_row(row.get(9)?),
+                                uploaded_at: parse_date(4, row.get(4)?)?,
+                                original_date: parse_date(5, row.get(5)?)?,
+                                is_favorite: row.get(6)?,
+                                size_bytes: row.get(7)?,
+                                tags: vec![],
+                                processing: row.get(10)?,
+                            },
+                            deleted_at: parse_date(8, row.get(8)?)?,
+                        },
+                    ))
+                })
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            let items_with_ids = rows
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            let id_bytes_list: Vec<Vec<u8>> =
+                items_with_ids.iter().map(|(id, _)| id.clone()).collect();
+            let tags_map = load_tags_bulk(conn, &id_bytes_list);
+
+            Ok(items_with_ids
+                .into_iter()
+                .map(|(id_bytes, mut item)| {
+                    if let Some(tags) = tags_map.get(&id_bytes) {
+                        item.media.tags = tags.clone();
+                    }
+                    item
+                })
+                .collect())
+        })
+    }
+
+    pub(crate) fn find_trashed_before_impl(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> Result<Vec<Uuid>, DomainError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id FROM media
+                     WHERE deleted_at IS NOT NULL AND deleted_at <= ?1
+                     ORDER BY deleted_at",
+                )
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            let rows = stmt
+                .query_map(params![cutoff.to_rfc3339()], |row| row.get::<_, Vec<u8>>(0))
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+
+            let mut ids = Vec::new();
+            for row in rows {
+                let bytes = row.map_err(|e| DomainError::Database(e.to_string()))?;
+                if let Ok(id) = Uuid::from_slice(&bytes) {
+                    ids.push(id);
+                }
+            }
+            Ok(ids)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::domain::LibraryScope;
+    use crate::infrastructure::SqliteRepository;
+    use chrono::{Duration, Utc};
+    use rusqlite::params;
+    use uuid::Uuid;
+
+    fn insert_media(repo: &SqliteRepository, id: Uuid) {
+        repo.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO media (id, filename, original_filename, size_bytes, phash, uploaded_at, original_date)
+                 VALUES (?1, 'f.jpg', 'f.jpg', 1, 'ph', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')",
+                params![id.as_bytes()],
+            )
+            .unwrap();
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_trash_hides_and_restore_returns() {
+        let db = TestDb::new("test_trash_restore");
+        let kept = Uuid::new_v4();
+        let trashed = Uuid::new_v4();
+        insert_media(&db.repo, kept);
+        insert_media(&db.repo, trashed);
+
+        assert_eq!(db.repo.trash_media_impl(&[trashed]).unwrap(), 1);
+        // Trashing twice is a no-op
+        assert_eq!(db.repo.trash_media_impl(&[trashed]).unwrap(), 0);
+
+        let live = db.repo.find_all_impl(&LibraryScope::ALL, 10, 0, None, false, None, false, "date").unwrap();
+        assert_eq!(live.iter().map(|m| m.id).collect::<Vec<_>>(), vec![kept]);
+        assert_eq!(db.repo.media_counts_impl(&LibraryScope::ALL).unwrap().total, 1);
+
+        let bin = db.repo.find_trashed_impl(&LibraryScope::ALL, 10, 0).unwrap();
+        assert_eq!(bin.len(), 1);
+        assert_eq!(bin[0].media.id, trashed);
+
+        assert_eq!(db.repo.restore_media_impl(&[trashed, kept]).unwrap(), 1);
+        assert!(db.repo.find_trashed_impl(&LibraryScope::ALL, 10, 0).unwrap().is_empty());
+        assert_eq!(db.repo.media_counts_impl(&LibraryScope::ALL).unwrap().total, 2);
+    }
+
+    #[test]
+    fn test_find_trashed_before_cutoff() {
+        let db = TestDb::new("test_trash_cutoff");
+        let id = Uuid::new_v4();
+        insert_media(&db.repo, id);
+        db.repo.trash_media_impl(&[id]).unwrap();
+
+        let past = Utc::now() - Duration::days(1);
+        assert!(db.repo.find_trashed_before_impl(past).unwrap().is_empty());
+        let future = Utc::now() + Duration::seconds(1);
+        assert_eq!(db.repo.find_trashed_before_impl(future).unwrap(), vec![id]);
+    }
+}
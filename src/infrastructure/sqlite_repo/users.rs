@@ -0,0 +1,345 @@
+use crate::domain::{DomainError, Role, User};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use uuid::Uuid;
+
+use super::SqliteRepository;
+
+const USER_COLUMNS: &str = "id, username, role, created_at";
+
+fn user_from_row(row: &Row) -> rusqlite::Result<User> {
+    let id_bytes: Vec<u8> = row.get(0)?;
+    let id = Uuid::from_slice(&id_bytes).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Blob, Box::new(e))
+    })?;
+    let role_str: String = row.get(2)?;
+    let created_at_str: String = row.get(3)?;
+    let created_at = DateTime::parse_from_rfc3339(&created_at_str)
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e))
+        })?
+        .with_timezone(&Utc);
+
+    Ok(User {
+        id,
+        username: row.get(1)?,
+        // Unknown roles get the least privilege rather than failing the whole query
+        role: Role::parse(&role_str).unwrap_or(Role::Viewer),
+        created_at,
+    })
+}
+
+fn expect_one(conn: &Connection, sql: &str, params: impl rusqlite::Params) -> Result<(), DomainError> {
+    let updated = conn
+        .execute(sql, params)
+        .map_err(|e| DomainError::Database(e.to_string()))?;
+    if updated == 0 {
+        return Err(DomainError::NotFound);
+    }
+    Ok(())
+}
+
+impl SqliteRepository {
+    pub(crate) fn count_users_impl(&self) -> Result<usize, DomainError> {
+        self.with_conn(|conn| {
+            let count: i64 = conn
+                .query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(count as usize)
+        })
+    }
+
+    pub(crate) fn create_user_impl(&self, user: &User, password_hash: &str) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO users (id, username, password_hash, role, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    user.id.as_bytes(),
+                    user.username,
+                    password_hash,
+                    user.role.as_str(),
+                    user.created_at.to_rfc3339()
+                ],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn find_user_by_id_impl(&self, id: Uuid) -> Result<Option<User>, DomainError> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM users WHERE id = ?1", USER_COLUMNS),
+                params![id.as_bytes()],
+                user_from_row,
+            )
+            .optional()
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn find_user_by_username_impl(
+        &self,
+        username: &str,
+    ) -> Result<Option<(User, String)>, DomainError> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                &format!("SELECT {}, password_hash FROM users WHERE username = ?1", USER_COLUMNS),
+                params![username],
+                |row| Ok((user_from_row(row)?, row.get(4)?)),
+            )
+            .optional()
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn list_users_impl(&self) -> Result<Vec<User>, DomainError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT {} FROM users ORDER BY username COLLATE NOCASE",
+                    USER_COLUMNS
+                ))
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            let rows = stmt
+                .query_map([], user_from_row)
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn update_user_role_impl(&self, id: Uuid, role: Role) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            expect_one(
+                conn,
+                "UPDATE users SET role = ?2 WHERE id = ?1",
+                params![id.as_bytes(), role.as_str()],
+            )
+        })
+    }
+
+    pub(crate) fn update_user_password_impl(
+        &self,
+        id: Uuid,
+        password_hash: &str,
+    ) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            expect_one(
+                conn,
+                "UPDATE users SET password_hash = ?2 WHERE id = ?1",
+                params![id.as_bytes(), password_hash],
+            )
+        })
+    }
+
+    pub(crate) fn delete_user_impl(&self, id: Uuid) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute("BEGIN", [])
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            let result = (|| {
+                let id = id.as_bytes();
+                // Nothing the user uploaded is lost: it becomes part of the shared library
+                conn.execute("UPDATE media SET owner_id = NULL WHERE owner_id = ?1", params![id])?;
+                conn.execute("UPDATE folders SET owner_id = NULL WHERE owner_id = ?1", params![id])?;
+                conn.execute("DELETE FROM favorites WHERE user_id = ?1", params![id])?;
+                conn.execute("DELETE FROM user_identities WHERE user_id = ?1", params![id])?;
+                conn.execute("DELETE FROM sessions WHERE user_id = ?1", params![id])?;
+                conn.execute("DELETE FROM api_tokens WHERE user_id = ?1", params![id])?;
+                conn.execute("DELETE FROM shares WHERE created_by = ?1", params![id])?;
+                conn.execute("DELETE FROM users WHERE id = ?1", params![id])
+            })();
+            match result {
+                Ok(0) => {
+                    let _ = conn.execute("ROLLBACK", []);
+                    Err(DomainError::NotFound)
+                }
+                Ok(_) => {
+                    conn.execute("COMMIT", [])
+                        .map_err(|e| DomainError::Database(e.to_string()))?;
+                    Ok(())
+                }
+                Err(e) => {
+                    let _ = conn.execute("ROLLBACK", []);
+                    Err(DomainError::Database(e.to_string()))
+                }
+            }
+        })
+    }
+
+    pub(crate) fn find_user_by_identity_impl(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<Option<User>, DomainError> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                "SELECT u.id, u.username, u.role, u.created_at FROM users u
+                 JOIN user_identities i ON i.user_id = u.id
+                 WHERE i.issuer = ?1 AND i.subject = ?2",
+                params![issuer, subject],
+                user_from_row,
+            )
+            .optional()
+            .map_err(|e| DomainError::Database(e.to_string()))
+        })
+    }
+
+    pub(crate) fn link_identity_impl(
+        &self,
+        user_id: Uuid,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<bool, DomainError> {
+        self.with_conn(|conn| {
+            let inserted = conn
+                .execute(
+                    "INSERT OR IGNORE INTO user_identities (issuer, subject, user_id, created_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![issuer, subject, user_id.as_bytes(), Utc::now().to_rfc3339()],
+                )
+                .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(inserted > 0)
+        })
+    }
+
+    pub(crate) fn claim_anonymous_favorites_impl(&self, id: Uuid) -> Result<(), DomainError> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE OR IGNORE favorites SET user_id = ?1 WHERE user_id = zeroblob(16)",
+                params![id.as_bytes()],
+            )
+            .map_err(|e| DomainError::Database(e.to_string()))?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestDb;
+    use crate::domain::{LibraryScope, Role, User};
+    use chrono::Utc;
+    use rusqlite::params;
+    use uuid::Uuid;
+
+    fn user(name: &str, role: Role) -> User {
+        User { id: Uuid::new_v4(), username: name.to_string(), role, created_at: Utc::now() }
+    }
+
+    fn insert_media(db: &TestDb, id: Uuid, owner: Option<Uuid>) {
+        db.repo
+            .with_conn(|conn| {
+                conn.execute(
+                    "INSERT INTO media (id, filename, original_filename, size_bytes, phash, uploaded_at, original_date, owner_id)
+                     VALUES (?1, 'f.jpg', 'f.jpg', 1, 'ph', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z', ?2)",
+                    params![id.as_bytes(), owner.map(|o| o.as_bytes().to_vec())],
+                )
+                .unwrap();
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_user_crud() {
+        let db = TestDb::new("test_user_crud");
+        let alice = user("Alice", Role::Member);
+        db.repo.create_user_impl(&alice, "hash1").unwrap();
+        assert_eq!(db.repo.count_users_impl().unwrap(), 1);
+
+        // Usernames are unique regardless of case
+        assert!(db.repo.create_user_impl(&user("alice", Role::Viewer), "x").is_err());
+
+        let (found, hash) = db.repo.find_user_by_username_impl("ALICE").unwrap().unwrap();
+        assert_eq!(found.id, alice.id);
+        assert_eq!(hash, "hash1");
+
+        db.repo.update_user_role_impl(alice.id, Role::Admin).unwrap();
+        db.repo.update_user_password_impl(alice.id, "hash2").unwrap();
+        assert_eq!(db.repo.find_user_by_id_impl(alice.id).unwrap().unwrap().role, Role::Admin);
+        assert_eq!(db.repo.find_user_by_username_impl("alice").unwrap().unwrap().1, "hash2");
+
+        assert!(db.repo.update_user_role_impl(Uuid::new_v4(), Role::Admin).is_err());
+        assert_eq!(db.repo.list_users_impl().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_scope_filters_by_owner() {
+        let db = TestDb::new("test_user_scope");
+        let alice = user("alice", Role::Member);
+        let bob = user("bob", Role::Member);
+        db.repo.create_user_impl(&alice, "h").unwrap();
+        db.repo.create_user_impl(&bob, "h").unwrap();
+
+        let shared = Uuid::new_v4();
+        let alices = Uuid::new_v4();
+        let bobs = Uuid::new_v4();
+        insert_media(&db, shared, None);
+        insert_media(&db, alices, Some(alice.id));
+        insert_media(&db, bobs, Some(bob.id));
+
+        let scope = LibraryScope::for_user(&alice);
+        let mut visible: Vec<Uuid> = db
+            .repo
+            .find_all_impl(&scope, 10, 0, None, false, None, false, "date")
+            .unwrap()
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        visible.sort();
+        let mut expected = vec![shared, alices];
+        expected.sort();
+        assert_eq!(visible, expected);
+        assert_eq!(db.repo.media_counts_impl(&scope).unwrap().total, 2);
+        assert_eq!(db.repo.media_counts_impl(&LibraryScope::ALL).unwrap().total, 3);
+
+        // Favorites are per user
+        db.repo.set_favorite_impl(&scope, shared, true).unwrap();
+        assert!(db.repo.is_favorite_impl(&scope, shared).unwrap());
+        assert!(!db.repo.is_favorite_impl(&LibraryScope::for_user(&bob), shared).unwrap());
+
+        // Deleting a user hands their media to the shared library
+        db.repo.delete_user_impl(alice.id).unwrap();
+        assert!(db.repo.find_by_id_impl(alices).unwrap().unwrap().owner_id.is_none());
+        assert!(!db.repo.is_favorite_impl(&scope, shared).unwrap());
+        assert!(matches!(
+            db.repo.delete_user_impl(alice.id),
+            Err(crate::domain::DomainError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn test_claim_anonymous_favorites() {
+        let db = TestDb::new("test_claim_favorites");
+        let id = Uuid::new_v4();
+        insert_media(&db, id, None);
+        db.repo.set_favorite_impl(&LibraryScope::ALL, id, true).unwrap();
+
+        let admin = user("admin", Role::Admin);
+        db.repo.create_user_impl(&admin, "h").unwrap();
+        db.repo.claim_anonymous_favorites_impl(admin.id).unwrap();
+
+        assert!(db.repo.is_favorite_impl(&LibraryScope::for_user(&admin), id).unwrap());
+        assert!(!db.repo.is_favorite_impl(&LibraryScope::ALL, id).unwrap());
+    }
+
+    #[test]
+    fn test_identity_links() {
+        let db = TestDb::new("test_identity_links");
+        let alice = user("alice", Role::Member);
+        db.repo.create_user_impl(&alice, "").unwrap();
+        let idp = "https://id.example.com";
+
+        assert!(db.repo.link_identity_impl(alice.id, idp, "sub-1").unwrap());
+        assert_eq!(db.repo.find_user_by_identity_impl(idp, "sub-1").unwrap().unwrap().id, alice.id);
+        assert!(db.repo.find_user_by_identity_impl("https://other.example.com", "sub-1").unwrap().is_none());
+
+        // One identity per user and issuer, and one user per identity
+        assert!(!db.repo.link_identity_impl(alice.id, idp, "sub-2").unwrap());
+        assert!(!db.repo.link_identity_impl(Uuid::new_v4(), idp, "sub-1").unwrap());
+
+        db.repo.delete_user_impl(alice.id).unwrap();
+        assert!(db.repo.find_user_by_identity_impl(idp, "sub-1").unwrap().is_none());
+    }
+}
@@ -14,15 +14,18 @@ use std::path::PathBuf;
 use tokio::net::TcpListener;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use infrastructure::{SqliteRepository, OrtProcessor, PhashGenerator};
-use application::{UploadMediaUseCase, SearchSimilarUseCase, ListMediaUseCase, DeleteMediaUseCase, GroupMediaUseCase, TagLearningUseCase, FixThumbnailsUseCase};
-use presentation::{AppState, AuthConfig, app_router};
+use infrastructure::{SqliteRepository, OrtProcessor, PhashGenerator, LocalStorage, S3Config, S3Storage, OidcConfig, OidcClient};
+use domain::{MediaRepository, MediaStorage, Role};
+use application::{UploadMediaUseCase, SearchSimilarUseCase, ListMediaUseCase, DeleteMediaUseCase, GroupMediaUseCase, DuplicatesUseCase, TagLearningUseCase, FixThumbnailsUseCase, RenditionsUseCase, RenditionSizes, ThumbnailFormat, CheckExternalMediaUseCase, ImportMediaUseCase, WatchFolderUseCase, VerifyIntegrityUseCase, TrashUseCase, UsersUseCase, SessionsUseCase, ApiTokensUseCase, SharesUseCase, OidcUseCase, AuditUseCase, JobsUseCase, ResumableUploadsUseCase};
+use presentation::{AppState, AuthConfig, TrustedProxies, app_router};
+use presentation::auth::ProxyAuth;
 
 use tower_http::services::{ServeDir, ServeFile};
 use tower_http::cors::{CorsLayer, AllowOrigin};
+use tower::Layer;
 use axum::extract::DefaultBodyLimit;
 use axum::Router;
-use axum::http::{HeaderValue, Method};
+use axum::http::{HeaderName, HeaderValue, Method};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -44,21 +47,119 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let model_path = std::env::var("MODEL_PATH").unwrap_or_else(|_| "assets/models/mobilenetv3.onnx".to_string());
     let upload_dir = PathBuf::from(std::env::var("UPLOAD_DIR").unwrap_or_else(|_| "uploads".to_string()));
     let thumbnail_dir = PathBuf::from(std::env::var("THUMBNAIL_DIR").unwrap_or_else(|_| "thumbnails".to_string()));
+    let import_dir = std::env::var("IMPORT_DIR").ok()
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from);
+    let library_mode = std::env::var("LIBRARY_MODE")
+        .map(|v| matches!(v.trim(), "1" | "true" | "yes"))
+        .unwrap_or(false);
+    let pair_raw_with_jpeg = std::env::var("PAIR_RAW_WITH_JPEG")
+        .map(|v| matches!(v.trim(), "1" | "true" | "yes"))
+        .unwrap_or(false);
+    let watch_dir = std::env::var("WATCH_DIR").ok()
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from);
+    let watch_interval = std::env::var("WATCH_INTERVAL_SECS").ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .filter(|&s| s > 0)
+        .unwrap_or(30);
+    // Max differing perceptual-hash bits for an upload to be rejected as a duplicate (0 = exact)
+    let duplicate_threshold = std::env::var("DUPLICATE_THRESHOLD").ok()
+        .and_then(|s| s.trim().parse::<u32>().ok())
+        .unwrap_or(domain::DEFAULT_DUPLICATE_THRESHOLD);
+    // Hours between integrity scans of stored originals (0 = disabled)
+    let integrity_interval = std::env::var("INTEGRITY_SCAN_INTERVAL_HOURS").ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or(168);
+    // Days a deleted item stays in the trash before it is purged (0 = keep until emptied)
+    let trash_retention_days = std::env::var("TRASH_RETENTION_DAYS").ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or(30);
+    // Days a login session stays valid
+    let session_ttl_days = std::env::var("SESSION_TTL_DAYS").ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .filter(|&d| d > 0)
+        .unwrap_or(30);
+    // Background jobs (grouping, auto-tagging, thumbnail repair) run at once
+    let job_workers = std::env::var("JOB_WORKERS").ok()
+        .and_then(|s| s.trim().parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(2);
+    // Days audit log entries are kept (0 = forever)
+    let audit_retention_days = std::env::var("AUDIT_RETENTION_DAYS").ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or(365);
+    // Chunks of resumable (tus) uploads are collected here until the upload completes
+    let partial_upload_dir = PathBuf::from(std::env::var("PARTIAL_UPLOAD_DIR").unwrap_or_else(|_| "partial_uploads".to_string()));
+    // Hours a resumable upload may go without a chunk before it is deleted
+    let resumable_expiry_hours = std::env::var("RESUMABLE_UPLOAD_EXPIRY_HOURS").ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .filter(|&h| h > 0)
+        .unwrap_or(24);
+    // Edge of the square grid thumbnail, and longest edges of the previews shown in
+    // the viewer, in pixels
+    let thumbnail_size = std::env::var("THUMBNAIL_SIZE").ok()
+        .and_then(|s| s.trim().parse::<u32>().ok())
+        .filter(|&s| s > 0)
+        .unwrap_or(224);
+    let preview_sizes = std::env::var("PREVIEW_SIZES").ok()
+        .map(|s| s.split(',').filter_map(|size| size.trim().parse::<u32>().ok()).collect())
+        .unwrap_or_else(|| vec![720, 2048]);
+    // Encodings stored besides JPEG and served to browsers that accept them
+    let thumbnail_formats = std::env::var("THUMBNAIL_FORMATS").ok()
+        .map(|s| s.split(',').filter_map(ThumbnailFormat::parse).collect())
+        .unwrap_or_default();
+    let mut rendition_sizes = RenditionSizes::new(thumbnail_size, preview_sizes).with_formats(thumbnail_formats);
+    // Short looping MP4s of videos and animated GIFs, played when hovering them in the grid
+    let animated_previews = std::env::var("ANIMATED_PREVIEWS")
+        .map(|v| matches!(v.trim(), "1" | "true" | "yes"))
+        .unwrap_or(false);
+    if animated_previews {
+        rendition_sizes = rendition_sizes.with_animated_previews();
+    }
+    // Largest single upload, multipart or resumable
+    let max_upload_bytes: u64 = 10 * 1024 * 1024 * 1024; // 10GB
     let port = 3000;
 
-    // Authentication — optional, enabled when GALLERY_PASSWORD is set
-    let auth_config = std::env::var("GALLERY_PASSWORD").ok().and_then(|pw| {
-        let pw = pw.trim().to_string();
-        if pw.is_empty() {
-            None
-        } else {
-            println!("Authentication enabled (GALLERY_PASSWORD is set)");
-            Some(AuthConfig::new(pw))
+    let admin_password = std::env::var("GALLERY_PASSWORD").ok()
+        .map(|pw| pw.trim().to_string())
+        .filter(|pw| !pw.is_empty());
+    let admin_username = std::env::var("GALLERY_ADMIN_USER").ok()
+        .map(|u| u.trim().to_string())
+        .filter(|u| !u.is_empty())
+        .unwrap_or_else(|| "admin".to_string());
+
+    // Single sign-on through an OpenID Connect provider, enabled by OIDC_ISSUER
+    let env_value = |name: &str| std::env::var(name).ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    let oidc_config = env_value("OIDC_ISSUER").map(|issuer| OidcConfig {
+        issuer,
+        client_id: env_value("OIDC_CLIENT_ID").expect("OIDC_CLIENT_ID must be set when OIDC_ISSUER is set"),
+        client_secret: env_value("OIDC_CLIENT_SECRET"),
+        redirect_url: env_value("OIDC_REDIRECT_URL").expect("OIDC_REDIRECT_URL must be set when OIDC_ISSUER is set"),
+        scopes: env_value("OIDC_SCOPES").unwrap_or_else(|| "openid profile email".to_string()),
+    });
+    let oidc_default_role = env_value("OIDC_DEFAULT_ROLE")
+        .map(|r| Role::parse(&r).unwrap_or_else(|| panic!("Unknown OIDC_DEFAULT_ROLE '{}' (expected admin, member or viewer)", r)))
+        .unwrap_or(Role::Member);
+    let oidc_admin_group = env_value("OIDC_ADMIN_GROUP");
+
+    // Reverse proxies whose X-Forwarded-For is believed, and optionally whose username
+    // header replaces the login (Authelia, oauth2-proxy, ...)
+    let trusted_proxies = TrustedProxies::parse(&env_value("TRUSTED_PROXIES").unwrap_or_default())
+        .unwrap_or_else(|e| panic!("TRUSTED_PROXIES: {}", e));
+    let proxy_auth_header = env_value("AUTH_PROXY_HEADER").map(|h| {
+        if trusted_proxies.is_empty() {
+            panic!("TRUSTED_PROXIES must be set when AUTH_PROXY_HEADER is set");
         }
+        HeaderName::try_from(h.as_str()).unwrap_or_else(|_| panic!("Invalid AUTH_PROXY_HEADER '{}'", h))
     });
-    if auth_config.is_none() {
-        println!("Warning: No GALLERY_PASSWORD set — running without authentication");
-    }
+    let proxy_auth_role = env_value("AUTH_PROXY_DEFAULT_ROLE")
+        .map(|r| Role::parse(&r).unwrap_or_else(|| panic!("Unknown AUTH_PROXY_DEFAULT_ROLE '{}' (expected admin, member or viewer)", r)))
+        .unwrap_or(Role::Member);
 
     // Ensure directories exist
     if !upload_dir.exists() {
@@ -72,6 +173,51 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Initializing Database...");
     let repo = Arc::new(SqliteRepository::new(&db_path)?);
 
+    // Authentication — enabled once any user account exists. GALLERY_PASSWORD creates
+    // the first admin (GALLERY_ADMIN_USER) when there are none yet.
+    let users_use_case = Arc::new(UsersUseCase::new(repo.clone()));
+    if let Some(ref pw) = admin_password {
+        if let Some(admin) = users_use_case.bootstrap_admin(&admin_username, pw)? {
+            println!("Created admin account '{}' from GALLERY_PASSWORD", admin.username);
+        }
+    }
+    let sessions_use_case = Arc::new(SessionsUseCase::new(repo.clone(), session_ttl_days));
+    let api_tokens_use_case = Arc::new(ApiTokensUseCase::new(repo.clone()));
+    let shares_use_case = Arc::new(SharesUseCase::new(repo.clone()));
+    let audit_use_case = Arc::new(AuditUseCase::new(repo.clone(), audit_retention_days));
+    let auth_config = if repo.count_users()? > 0 || oidc_config.is_some() || proxy_auth_header.is_some() {
+        println!("Authentication enabled");
+        let mut config = AuthConfig::new(
+            sessions_use_case.clone(),
+            api_tokens_use_case.clone(),
+            admin_username,
+        );
+        if let Some(header) = proxy_auth_header {
+            println!("Trusting the {} header from TRUSTED_PROXIES", header);
+            config = config.with_proxy_auth(ProxyAuth {
+                header,
+                trusted: trusted_proxies.clone(),
+                default_role: proxy_auth_role,
+                users: users_use_case.clone(),
+            });
+        }
+        match oidc_config {
+            Some(oidc) => {
+                println!("Single sign-on enabled (OIDC issuer {})", oidc.issuer);
+                Some(config.with_oidc(Arc::new(OidcUseCase::new(
+                    Arc::new(OidcClient::new(oidc)?),
+                    repo.clone(),
+                    oidc_default_role,
+                    oidc_admin_group,
+                ))))
+            }
+            None => Some(config),
+        }
+    } else {
+        println!("Warning: No GALLERY_PASSWORD set — running without authentication");
+        None
+    };
+
     println!("Initializing AI Processor (Loading {})...", model_path);
     let ai = match OrtProcessor::new(&model_path) {
         Ok(processor) => Arc::new(processor),
@@ -84,14 +230,46 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Initializing Hasher...");
     let hasher = Arc::new(PhashGenerator::new());
 
+    // Storage backend for originals and thumbnails — local directories unless STORAGE_BACKEND=s3
+    let s3_config = match std::env::var("STORAGE_BACKEND").unwrap_or_default().trim() {
+        "s3" => Some(S3Config {
+            bucket: std::env::var("S3_BUCKET").expect("S3_BUCKET must be set when STORAGE_BACKEND=s3"),
+            region: std::env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            endpoint: std::env::var("S3_ENDPOINT").ok().filter(|e| !e.trim().is_empty()),
+            access_key_id: std::env::var("S3_ACCESS_KEY_ID").unwrap_or_default(),
+            secret_access_key: std::env::var("S3_SECRET_ACCESS_KEY").unwrap_or_default(),
+        }),
+        "" | "local" => None,
+        other => panic!("Unknown STORAGE_BACKEND '{}' (expected 'local' or 's3')", other),
+    };
+    let (storage, thumbnails): (Arc<dyn MediaStorage>, Arc<dyn MediaStorage>) = match &s3_config {
+        Some(config) => {
+            println!("Initializing S3 storage (bucket {})...", config.bucket);
+            (
+                Arc::new(S3Storage::new(config, "originals")?),
+                Arc::new(S3Storage::new(config, "thumbnails")?),
+            )
+        }
+        None => (
+            Arc::new(LocalStorage::new(upload_dir.clone())),
+            Arc::new(LocalStorage::new(thumbnail_dir.clone())),
+        ),
+    };
+
     // Initialize Use Cases
-    let upload_use_case = Arc::new(UploadMediaUseCase::new(
+    let mut upload_use_case = UploadMediaUseCase::new(
         repo.clone(),
         ai.clone(),
         hasher.clone(),
-        upload_dir.clone(),
-        thumbnail_dir.clone(),
-    ));
+        storage.clone(),
+        thumbnails.clone(),
+        duplicate_threshold,
+    )
+    .with_renditions(rendition_sizes.clone());
+    if pair_raw_with_jpeg {
+        upload_use_case = upload_use_case.with_raw_jpeg_pairing();
+    }
+    let upload_use_case = Arc::new(upload_use_case);
 
     let search_use_case = Arc::new(SearchSimilarUseCase::new(
         repo.clone(),
@@ -104,14 +282,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let delete_use_case = Arc::new(DeleteMediaUseCase::new(
         repo.clone(),
-        upload_dir.clone(),
-        thumbnail_dir.clone(),
+        storage.clone(),
+        thumbnails.clone(),
     ));
 
     let group_use_case = Arc::new(GroupMediaUseCase::new(
         repo.clone(),
     ));
 
+    let duplicates_use_case = Arc::new(DuplicatesUseCase::new(
+        repo.clone(),
+        group_use_case.clone(),
+        delete_use_case.clone(),
+    ));
+
+    let trash_use_case = Arc::new(TrashUseCase::new(
+        repo.clone(),
+        delete_use_case.clone(),
+        trash_retention_days,
+    ));
+
     let tag_learning_use_case = Arc::new(TagLearningUseCase::new(
         repo.clone(),
     ));
@@ -120,8 +310,61 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         repo.clone(),
         ai.clone(),
         hasher.clone(),
-        upload_dir.clone(),
-        thumbnail_dir.clone(),
+        storage.clone(),
+        thumbnails.clone(),
+    ).with_renditions(rendition_sizes.clone()));
+
+    let renditions_use_case = Arc::new(RenditionsUseCase::new(
+        repo.clone(),
+        storage.clone(),
+        thumbnails.clone(),
+        rendition_sizes,
+    ));
+
+    let jobs_use_case = Arc::new(JobsUseCase::new(
+        repo.clone(),
+        upload_use_case.clone(),
+        fix_thumbnails_use_case.clone(),
+        tag_learning_use_case.clone(),
+        group_use_case,
+    ));
+
+    let resumable_uploads_use_case = Arc::new(ResumableUploadsUseCase::new(
+        repo.clone(),
+        upload_use_case.clone(),
+        partial_upload_dir,
+        max_upload_bytes,
+        resumable_expiry_hours,
+    ));
+
+    let import_use_case = Arc::new(ImportMediaUseCase::new(
+        repo.clone(),
+        upload_use_case.clone(),
+        import_dir,
+        library_mode,
+    ));
+    if library_mode {
+        println!("Library mode enabled: imported originals are referenced in place");
+    }
+
+    let check_external_use_case = Arc::new(CheckExternalMediaUseCase::new(
+        repo.clone(),
+    ));
+
+    let watch_folder_use_case = watch_dir.map(|dir| {
+        std::fs::create_dir_all(&dir).expect("Failed to create watch directory");
+        let reject_dir = std::env::var("WATCH_REJECT_DIR").ok()
+            .map(|d| d.trim().to_string())
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| dir.join(".rejected"));
+        println!("Watching {} for new media (rejects go to {})", dir.display(), reject_dir.display());
+        Arc::new(WatchFolderUseCase::new(upload_use_case.clone(), dir, reject_dir))
+    });
+
+    let verify_integrity_use_case = Arc::new(VerifyIntegrityUseCase::new(
+        repo.clone(),
+        storage.clone(),
     ));
 
     let (tx, _) = tokio::sync::broadcast::channel(100);
@@ -129,6 +372,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize Background Tasks
     let task_runner = application::TaskRunner::new(
         fix_thumbnails_use_case.clone(),
+        renditions_use_case.clone(),
+        check_external_use_case,
+        watch_folder_use_case,
+        watch_interval,
+        verify_integrity_use_case,
+        integrity_interval,
+        trash_use_case.clone(),
+        sessions_use_case.clone(),
+        audit_use_case.clone(),
+        jobs_use_case.clone(),
+        resumable_uploads_use_case.clone(),
+        job_workers,
         tx.clone(),
     );
     task_runner.start();
@@ -139,14 +394,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         search_use_case,
         list_use_case,
         delete_use_case,
-        group_use_case,
+        duplicates_use_case,
+        trash_use_case,
         tag_learning_use_case,
-        fix_thumbnails_use_case,
+        import_use_case,
+        users_use_case,
+        sessions_use_case,
+        api_tokens_use_case,
+        shares_use_case,
+        audit_use_case,
+        jobs_use_case,
+        resumable_uploads_use_case,
+        renditions_use_case,
         repo: repo.clone(),
         upload_dir: upload_dir.clone(),
+        storage: storage.clone(),
+        thumbnails: thumbnails.clone(),
         auth_config: auth_config.clone(),
-        upload_semaphore: Arc::new(tokio::sync::Semaphore::new(2)),
         login_rate_limiter: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+        trusted_proxies,
         download_plans: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
         tx,
     };
@@ -157,17 +423,47 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // --- ROUTING ARCHITECTURE ---
 
     // 1. Group all backend logic (your existing routes) and attach the body limit
-    let api_routes = app_router(state)
-        .layer(DefaultBodyLimit::max(10 * 1024 * 1024 * 1024)); // 10GB
+    let api_routes = app_router(state.clone())
+        .layer(DefaultBodyLimit::max(max_upload_bytes as usize));
 
     // 2. Configure the React SPA fallback (always accessible — it serves the login page too)
     let serve_react_app = ServeDir::new("frontend/dist")
         .not_found_service(ServeFile::new("frontend/dist/index.html"));
 
     // 3. Static file routes for media — must be auth-protected with security headers
-    let static_uploads = Router::new()
-        .nest_service("/uploads", ServeDir::new(upload_dir))
-        .nest_service("/thumbnails", ServeDir::new(thumbnail_dir))
+    let negotiate_thumbnails = axum::middleware::from_fn_with_state(
+        state.renditions_use_case.clone(),
+        presentation::negotiate_thumbnail_format,
+    );
+    let authorize_originals =
+        axum::middleware::from_fn_with_state(state.repo.clone(), presentation::authorize_original);
+    let authorize_thumbnails =
+        axum::middleware::from_fn_with_state(state.repo.clone(), presentation::authorize_thumbnail);
+    let static_uploads = if s3_config.is_some() {
+        Router::new()
+            .nest_service(
+                "/uploads",
+                authorize_originals.layer(presentation::stored_originals_router(state)),
+            )
+            .nest_service(
+                "/thumbnails",
+                authorize_thumbnails
+                    .layer(negotiate_thumbnails.layer(presentation::stored_thumbnails_router(thumbnails))),
+            )
+    } else {
+        Router::new()
+            .nest_service(
+                "/uploads",
+                authorize_originals.layer(
+                    ServeDir::new(upload_dir).fallback(presentation::external_originals_router(state)),
+                ),
+            )
+            .nest_service(
+                "/thumbnails",
+                authorize_thumbnails.layer(negotiate_thumbnails.layer(ServeDir::new(thumbnail_dir))),
+            )
+    };
+    let static_uploads = static_uploads
         .layer(axum::middleware::map_response(|mut response: axum::response::Response| async move {
             let headers = response.headers_mut();
             headers.insert(
@@ -202,8 +498,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         if let Ok(header_value) = HeaderValue::from_str(&origin) {
             CorsLayer::new()
                 .allow_origin(AllowOrigin::exact(header_value))
-                .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
-                .allow_headers([axum::http::header::CONTENT_TYPE, axum::http::header::COOKIE])
+                .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::HEAD, Method::PATCH])
+                .allow_headers([
+                    axum::http::header::CONTENT_TYPE,
+                    axum::http::header::COOKIE,
+                    HeaderName::from_static("tus-resumable"),
+                    HeaderName::from_static("upload-length"),
+                    HeaderName::from_static("upload-offset"),
+                    HeaderName::from_static("upload-metadata"),
+                    HeaderName::from_static("x-upload-session"),
+                ])
+                .expose_headers([
+                    axum::http::header::LOCATION,
+                    HeaderName::from_static("tus-resumable"),
+                    HeaderName::from_static("upload-offset"),
+                    HeaderName::from_static("upload-length"),
+                    HeaderName::from_static("upload-expires"),
+                    HeaderName::from_static("x-media-id"),
+                ])
                 .allow_credentials(true)
         } else {
             eprintln!("Warning: Invalid CORS_ORIGIN value '{}', denying cross-origin", origin);
@@ -1,9 +1,9 @@
 use axum::{
     body::Body,
-    extract::{ConnectInfo, Multipart, State, Query, Path, ws::{WebSocket, WebSocketUpgrade, Message}},
-    http::{StatusCode, header, HeaderMap},
-    response::{Json, IntoResponse},
-    routing::{get, post, put},
+    extract::{ConnectInfo, FromRequestParts, Multipart, State, Query, Path, ws::{WebSocket, WebSocketUpgrade, Message}},
+    http::{StatusCode, header, request::Parts, HeaderMap},
+    response::{Json, IntoResponse, Redirect},
+    routing::{delete, get, head, post, put},
     Router,
 };
 use tokio::sync::broadcast;
@@ -15,23 +15,36 @@ use tokio_util::compat::{TokioAsyncWriteCompatExt, FuturesAsyncWriteCompatExt};
 
 use serde_json::json;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use tokio::sync::{Mutex, Semaphore};
+use tokio::sync::Mutex;
 use std::path::PathBuf;
-use tokio_util::io::ReaderStream;
+use tokio_util::io::{ReaderStream, StreamReader};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
 use tracing::{error, info, warn};
 
 use uuid::Uuid;
 use tokio::io::AsyncWriteExt;
+use sha2::{Digest, Sha256};
 
 use crate::application::{
-    DeleteMediaUseCase, FixThumbnailsUseCase, GroupMediaUseCase, ListMediaUseCase,
-    SearchSimilarUseCase, TagLearningUseCase, UploadMediaUseCase,
+    Accepted, ApiTokensUseCase, AuditUseCase, DeleteMediaUseCase, DuplicateCriteria, DuplicatesUseCase,
+    ImportEvent, ImportMediaUseCase, JobEvent, JobsUseCase,
+    ImportReport, ListMediaUseCase, RenditionsUseCase, ResumableUploadsUseCase, SearchSimilarUseCase, SessionsUseCase, SharesUseCase,
+    TagLearningUseCase,
+    TrashUseCase, UploadMediaUseCase, UploadStage, UsersUseCase,
+    media_id_of_key, stream_original, thumbnail_key, ThumbnailFormat,
 };
-use crate::domain::{DomainError, MediaItem, MediaRepository};
-use crate::presentation::auth::AuthConfig;
+use crate::domain::{
+    ApiToken, DomainError, Folder, Job, JobKind, JobState, LibraryScope, MediaItem, MediaRepository, MediaStorage,
+    Role, Session,
+    ShareLink, ShareTarget, TokenScope,
+};
+use crate::presentation::auth::{extract_cookie, peer_ip, AuthConfig, CurrentSession, CurrentUser};
+use crate::presentation::proxy::TrustedProxies;
 
 /// Maximum page limit for list endpoints.
 const MAX_PAGE_LIMIT: usize = 200;
@@ -39,6 +52,9 @@ const MAX_PAGE_LIMIT: usize = 200;
 /// Maximum number of files in a single upload request.
 const MAX_UPLOAD_FILES: usize = 1_000;
 
+/// Version of the tus resumable upload protocol spoken by `/tus`.
+const TUS_VERSION: &str = "1.0.0";
+
 /// Maximum uncompressed size per zip archive part (~2 GB). When the total
 /// exceeds this, files are split into roughly equal-sized archives.
 const MAX_ZIP_BYTES: u64 = 2 * 1024 * 1024 * 1024;
@@ -71,20 +87,165 @@ pub struct DownloadPlan {
 pub enum WsMessage {
     MediaCreated { item: serde_json::Value },
     MediaUpdated { id: Uuid, item: serde_json::Value },
-    MediaDeleted { id: Uuid },
+    MediaTrashed { ids: Vec<Uuid> },
+    MediaRestored { ids: Vec<Uuid> },
     MediaBatchDeleted { ids: Vec<Uuid> },
-    MediaTagsUpdated { ids: Vec<Uuid>, tags: Vec<String> },
+    /// `owner` is the owner of every listed item, so a batch spanning several owners
+    /// is sent as one message per owner.
+    MediaTagsUpdated {
+        ids: Vec<Uuid>,
+        tags: Vec<String>,
+        #[serde(skip)]
+        owner: Option<Uuid>,
+    },
     FolderCreated { folder: serde_json::Value },
-    FolderDeleted { id: Uuid },
-    FolderRenamed { id: Uuid, name: String },
+    FolderDeleted {
+        id: Uuid,
+        #[serde(skip)]
+        owner: Option<Uuid>,
+    },
+    FolderRenamed {
+        id: Uuid,
+        name: String,
+        #[serde(skip)]
+        owner: Option<Uuid>,
+    },
     FoldersReordered { ids: Vec<Uuid> },
-    MediaAddedToFolder { folder_id: Uuid, media_ids: Vec<Uuid> },
-    MediaRemovedFromFolder { folder_id: Uuid, media_ids: Vec<Uuid> },
+    /// `owner` is the owner of the folder.
+    MediaAddedToFolder {
+        folder_id: Uuid,
+        media_ids: Vec<Uuid>,
+        #[serde(skip)]
+        owner: Option<Uuid>,
+    },
+    MediaRemovedFromFolder {
+        folder_id: Uuid,
+        media_ids: Vec<Uuid>,
+        #[serde(skip)]
+        owner: Option<Uuid>,
+    },
     UploadComplete,
     TagLearningComplete { tag_name: String },
     FullRefresh,
     ThumbnailFixStarted,
     ThumbnailFixCompleted { count: usize },
+    ImportStarted { total: usize },
+    ImportProgress { report: ImportReport },
+    ImportCompleted { report: ImportReport },
+    ExternalMediaMissing { ids: Vec<Uuid> },
+    IntegrityIssues { corrupted: Vec<Uuid>, missing: Vec<Uuid> },
+    JobQueued { id: Uuid, kind: String, run_after: chrono::DateTime<chrono::Utc> },
+    JobStarted { id: Uuid, kind: String },
+    JobProgress { id: Uuid, done: usize, total: usize },
+    /// Results are fetched from `/api/jobs/{id}`; they can be large.
+    JobFinished { id: Uuid, kind: String, state: JobState, error: Option<String> },
+    /// A step in handling one uploaded file. Only sent to the sockets of `session`.
+    UploadProgress {
+        #[serde(skip)]
+        session: Uuid,
+        /// The file's name as uploaded, while its request is being handled.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<String>,
+        /// Set once the file is stored.
+        media_id: Option<Uuid>,
+        #[serde(flatten)]
+        stage: UploadStage,
+    },
+}
+
+impl WsMessage {
+    /// Serialize for the broadcast channel.
+    pub fn to_broadcast(&self) -> Option<WsBroadcast> {
+        let upload_session = match self {
+            WsMessage::UploadProgress { session, .. } => Some(*session),
+            _ => None,
+        };
+        let owner = match self {
+            WsMessage::MediaCreated { item } | WsMessage::MediaUpdated { item, .. } => owner_of(item),
+            WsMessage::FolderCreated { folder } => owner_of(folder),
+            WsMessage::MediaTagsUpdated { owner, .. }
+            | WsMessage::FolderDeleted { owner, .. }
+            | WsMessage::FolderRenamed { owner, .. }
+            | WsMessage::MediaAddedToFolder { owner, .. }
+            | WsMessage::MediaRemovedFromFolder { owner, .. } => *owner,
+            _ => None,
+        };
+        let json = serde_json::to_string(self).ok()?;
+        Some(WsBroadcast { upload_session, owner, json: Arc::from(json) })
+    }
+}
+
+/// `owner_id` of a serialized item or folder.
+fn owner_of(value: &serde_json::Value) -> Option<Uuid> {
+    value.get("owner_id")?.as_str()?.parse().ok()
+}
+
+/// A serialized `WsMessage` on its way to the sockets. Messages with an upload session
+/// only go to the sockets that connected with it (`/api/ws?upload_session=...`), and
+/// messages about a private item or folder only to users who can see it.
+#[derive(Clone, Debug)]
+pub struct WsBroadcast {
+    pub upload_session: Option<Uuid>,
+    /// Owner of the item or folder the message carries, if it is private.
+    pub owner: Option<Uuid>,
+    pub json: Arc<str>,
+}
+
+impl WsBroadcast {
+    /// Whether a socket of `scope`, connected with `upload_session`, gets this message.
+    fn is_for(&self, scope: &LibraryScope, upload_session: Option<Uuid>) -> bool {
+        (self.upload_session.is_none() || self.upload_session == upload_session) && scope.can_see(self.owner)
+    }
+}
+
+impl From<JobEvent> for WsMessage {
+    fn from(event: JobEvent) -> Self {
+        match event {
+            JobEvent::Queued(job) => WsMessage::JobQueued {
+                id: job.id,
+                kind: job.kind.name().to_string(),
+                run_after: job.run_after,
+            },
+            JobEvent::Started(job) => WsMessage::JobStarted { id: job.id, kind: job.kind.name().to_string() },
+            JobEvent::Progress { id, done, total } => WsMessage::JobProgress { id, done, total },
+            JobEvent::Finished(job) => WsMessage::JobFinished {
+                id: job.id,
+                kind: job.kind.name().to_string(),
+                state: job.state,
+                error: job.error,
+            },
+            JobEvent::MediaUpdated(item) => WsMessage::MediaUpdated {
+                id: item.id,
+                item: serde_json::to_value(&item).unwrap(),
+            },
+            JobEvent::MediaRemoved(id) => WsMessage::MediaBatchDeleted { ids: vec![id] },
+            JobEvent::UploadStage { session, media_id, stage } => WsMessage::UploadProgress {
+                session,
+                file: None,
+                media_id: Some(media_id),
+                stage,
+            },
+        }
+    }
+}
+
+impl From<ImportEvent> for WsMessage {
+    fn from(event: ImportEvent) -> Self {
+        match event {
+            ImportEvent::Started { total } => WsMessage::ImportStarted { total },
+            ImportEvent::MediaCreated(item) => WsMessage::MediaCreated {
+                item: serde_json::to_value(&item).unwrap(),
+            },
+            ImportEvent::FolderCreated(folder) => WsMessage::FolderCreated {
+                folder: serde_json::to_value(&folder).unwrap(),
+            },
+            // Imports only mirror into shared folders
+            ImportEvent::MediaAddedToFolder { folder_id, media_id } => {
+                WsMessage::MediaAddedToFolder { folder_id, media_ids: vec![media_id], owner: None }
+            }
+            ImportEvent::Progress(report) => WsMessage::ImportProgress { report },
+        }
+    }
 }
 
 // App State
@@ -94,35 +255,100 @@ pub struct AppState {
     pub search_use_case: Arc<SearchSimilarUseCase>,
     pub list_use_case: Arc<ListMediaUseCase>,
     pub delete_use_case: Arc<DeleteMediaUseCase>,
-    pub group_use_case: Arc<GroupMediaUseCase>,
+    pub duplicates_use_case: Arc<DuplicatesUseCase>,
+    pub trash_use_case: Arc<TrashUseCase>,
     pub tag_learning_use_case: Arc<TagLearningUseCase>,
-    pub fix_thumbnails_use_case: Arc<FixThumbnailsUseCase>,
+    pub import_use_case: Arc<ImportMediaUseCase>,
+    pub users_use_case: Arc<UsersUseCase>,
+    pub sessions_use_case: Arc<SessionsUseCase>,
+    pub api_tokens_use_case: Arc<ApiTokensUseCase>,
+    pub shares_use_case: Arc<SharesUseCase>,
+    pub audit_use_case: Arc<AuditUseCase>,
+    pub jobs_use_case: Arc<JobsUseCase>,
+    pub resumable_uploads_use_case: Arc<ResumableUploadsUseCase>,
+    pub renditions_use_case: Arc<RenditionsUseCase>,
     pub repo: Arc<dyn MediaRepository>,
     pub upload_dir: PathBuf,
+    /// Where originals are stored (local `UPLOAD_DIR` or object storage).
+    pub storage: Arc<dyn MediaStorage>,
+    /// Where thumbnails are stored.
+    pub thumbnails: Arc<dyn MediaStorage>,
     pub auth_config: Option<AuthConfig>,
-    pub upload_semaphore: Arc<Semaphore>,
     pub login_rate_limiter: Arc<Mutex<HashMap<IpAddr, (u32, Instant)>>>,
+    /// Reverse proxies whose `X-Forwarded-For` is believed when finding the client IP.
+    pub trusted_proxies: TrustedProxies,
     pub download_plans: Arc<Mutex<HashMap<String, DownloadPlan>>>,
-    pub tx: broadcast::Sender<Arc<str>>,
+    pub tx: broadcast::Sender<WsBroadcast>,
 }
 
 impl AppState {
-    pub fn broadcast(&self, msg: WsMessage) {
-        if let Ok(json) = serde_json::to_string(&msg) {
-            let _ = self.tx.send(Arc::from(json));
+    pub fn broadcast(&self, mut msg: WsMessage) {
+        // Favorites are per user once accounts exist, so the flag in a broadcast item
+        // would be the wrong one for most listeners
+        if self.auth_config.is_some() {
+            if let WsMessage::MediaUpdated { item, .. } = &mut msg {
+                if let Some(fields) = item.as_object_mut() {
+                    fields.remove("is_favorite");
+                }
+            }
+        }
+        if let Some(message) = msg.to_broadcast() {
+            let _ = self.tx.send(message);
         }
     }
+
+    /// Append to the audit log on behalf of the requesting user.
+    pub fn audit(
+        &self,
+        user: &CurrentUser,
+        ip: &ClientIp,
+        action: &str,
+        target_ids: Vec<String>,
+        detail: Option<String>,
+    ) {
+        self.audit_use_case.record(user.0.as_ref(), ip.0.map(|ip| ip.to_string()), action, target_ids, detail);
+    }
 }
 
+/// Address of the client behind any trusted reverse proxies. `None` when the server
+/// was not started with peer addresses (tests).
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        Ok(ClientIp(
+            parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|info| state.trusted_proxies.client_ip(info.0.ip(), &parts.headers)),
+        ))
+    }
+}
+
+fn id_strings(ids: &[Uuid]) -> Vec<String> {
+    ids.iter().map(Uuid::to_string).collect()
+}
+
+
+#[derive(Deserialize)]
+struct WsParams {
+    /// Id the client also sends as `X-Upload-Session`, to receive progress of its uploads.
+    upload_session: Option<Uuid>,
+}
 
 async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
+    user: CurrentUser,
+    Query(params): Query<WsParams>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    let scope = user.scope();
+    ws.on_upgrade(move |socket| handle_socket(socket, state, scope, params.upload_session))
 }
 
-async fn handle_socket(mut socket: WebSocket, state: AppState) {
+async fn handle_socket(mut socket: WebSocket, state: AppState, scope: LibraryScope, upload_session: Option<Uuid>) {
     let mut rx = state.tx.subscribe();
     let mut heartbeat_interval = tokio::time::interval(Duration::from_secs(30));
     
@@ -133,8 +359,11 @@ async fn handle_socket(mut socket: WebSocket, state: AppState) {
         tokio::select! {
             res = rx.recv() => {
                 match res {
-                    Ok(json) => {
-                        if socket.send(Message::Text(json.to_string().into())).await.is_err() {
+                    Ok(message) => {
+                        if !message.is_for(&scope, upload_session) {
+                            continue;
+                        }
+                        if socket.send(Message::Text(message.json.to_string().into())).await.is_err() {
                             break;
                         }
                     }
@@ -186,6 +415,7 @@ pub struct Pagination {
 
 async fn list_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Query(pagination): Query<Pagination>,
 ) -> Result<impl IntoResponse, DomainError> {
     let page = pagination.page.unwrap_or(1);
@@ -202,7 +432,7 @@ async fn list_handler(
         .filter(|s| !s.is_empty())
         .map(|s| s.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect());
 
-    let results = state.list_use_case.execute(page, limit, pagination.media_type.as_deref(), favorite, tags, sort_asc, sort_by).await?;
+    let results = state.list_use_case.execute(&user.scope(), page, limit, pagination.media_type.as_deref(), favorite, tags, sort_asc, sort_by).await?;
 
     Ok(Json(results))
 }
@@ -233,7 +463,7 @@ impl IntoResponse for DomainError {
     fn into_response(self) -> axum::response::Response {
         // Log the error for debugging
         match &self {
-            DomainError::DuplicateMedia | DomainError::NotFound => {},
+            DomainError::DuplicateMedia(_) | DomainError::NotFound | DomainError::Forbidden => {},
             DomainError::Database(e) => error!("Database Error: {}", e),
             DomainError::Ai(e) => error!("AI Error: {}", e),
             DomainError::Hashing(e) => error!("Hashing Error: {}", e),
@@ -242,14 +472,19 @@ impl IntoResponse for DomainError {
         }
 
         let (status, message) = match self {
-            DomainError::DuplicateMedia => (StatusCode::CONFLICT, "Media already exists".to_string()),
+            DomainError::DuplicateMedia(id) => {
+                // Tell the client which existing item it collided with
+                let body = Json(json!({ "error": "Media already exists", "duplicate_of": id }));
+                return (StatusCode::CONFLICT, body).into_response();
+            },
             DomainError::NotFound => (StatusCode::NOT_FOUND, "Media not found".to_string()),
+            DomainError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden".to_string()),
             DomainError::Database(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string()),
             DomainError::Ai(e) => (StatusCode::BAD_REQUEST, e), // AI errors are usually client-data-related (not enough examples)
             DomainError::Hashing(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string()),
             DomainError::Io(e) => {
                 // Keep user-facing messages, genericize internal ones
-                let user_facing_prefixes = ["No file", "Folder", "Too many", "File type"];
+                let user_facing_prefixes = ["No file", "Folder", "Too many", "File type", "Import", "Duplicate", "User", "Password", "Token", "Share", "Job", "Upload"];
                 if user_facing_prefixes.iter().any(|p| e.starts_with(p)) {
                     (StatusCode::INTERNAL_SERVER_ERROR, e)
                 } else {
@@ -271,12 +506,45 @@ pub fn app_router(state: AppState) -> Router {
         .route("/login", post(login_handler))
         .route("/logout", post(logout_handler))
         .route("/auth-check", get(auth_check_handler))
+        .route("/oidc/login", get(oidc_login_handler))
+        .route("/oidc/callback", get(oidc_callback_handler))
+        .with_state(state.clone());
+
+    // Public share links (no auth middleware; each handler checks the link)
+    let share_routes = Router::new()
+        .route("/s/{slug}", get(share_info_handler))
+        .route("/s/{slug}/unlock", post(unlock_share_handler))
+        .route("/s/{slug}/media", get(share_media_handler))
+        .route("/s/{slug}/media/{id}", get(share_original_handler))
+        .route("/s/{slug}/thumbnails/{id}", get(share_thumbnail_handler))
+        .route("/s/{slug}/preview/{id}", get(share_preview_handler))
+        .route("/s/{slug}/download", get(share_download_handler))
+        .route("/s/{slug}/download/{part_id}", get(share_download_stream_handler))
         .with_state(state.clone());
 
     // Protected API routes
     let protected_routes = Router::new()
         .route("/ws", get(ws_handler))
+        .route("/me", get(me_handler))
+        .route("/users", get(list_users_handler).post(create_user_handler))
+        .route("/audit", get(list_audit_handler))
+        .route("/jobs", get(list_jobs_handler))
+        .route("/jobs/{id}", get(get_job_handler))
+        .route("/jobs/{id}/cancel", post(cancel_job_handler))
+        .route("/jobs/{id}/retry", post(retry_job_handler))
+        .route("/users/{id}", put(update_user_handler).delete(delete_user_handler))
+        .route("/sessions", get(list_sessions_handler))
+        .route("/sessions/{id}", delete(revoke_session_handler))
+        .route("/tokens", get(list_tokens_handler).post(create_token_handler))
+        .route("/tokens/{id}", delete(revoke_token_handler))
+        .route("/shares", get(list_shares_handler).post(create_share_handler))
+        .route("/shares/{id}", delete(delete_share_handler))
         .route("/upload", post(upload_handler))
+        .route("/tus", post(create_resumable_handler).options(tus_options_handler))
+        .route(
+            "/tus/{id}",
+            head(resumable_offset_handler).patch(append_resumable_handler).delete(terminate_resumable_handler),
+        )
         .route("/search", post(search_handler))
         .route("/media", get(list_handler))
         .route("/media/batch-delete", post(batch_delete_handler))
@@ -285,12 +553,21 @@ pub fn app_router(state: AppState) -> Router {
         .route("/media/download/stream/{part_id}", get(batch_download_stream_handler))
         .route("/media/group", post(group_media_handler))
         .route("/media/fix-thumbnails", post(fix_thumbnails_handler))
+        .route("/duplicates", get(list_duplicates_handler))
+        .route("/duplicates/resolve", post(resolve_duplicates_handler))
+        .route("/trash", get(list_trash_handler).delete(empty_trash_handler))
+        .route("/trash/restore", post(restore_trash_handler))
+        .route("/trash/purge", post(purge_trash_handler))
+        .route("/trash/{id}", delete(purge_trash_item_handler))
+        .route("/import", post(import_handler))
 
         .route("/media/{id}", get(get_media_handler).delete(delete_handler))
         .route("/media/{id}/favorite", post(toggle_favorite_handler))
         .route("/media/{id}/tags", put(update_tags_handler))
         .route("/media/batch-tags", put(batch_update_tags_handler))
         .route("/media/{id}/similar", get(search_by_id_handler))
+        .route("/media/{id}/companion", get(companion_handler))
+        .route("/media/{id}/preview/{size}", get(preview_handler))
         .route("/tags", get(list_tags_handler))
         .route("/tags/models", get(list_trained_tags_handler))
         .route("/tags/count", get(get_auto_tags_count_handler))
@@ -319,61 +596,307 @@ pub fn app_router(state: AppState) -> Router {
 
     Router::new()
         .merge(auth_routes)
+        .merge(share_routes)
         .merge(protected_routes)
 }
 
+/// Serves originals referenced in place (library mode). Mounted as the fallback of the
+/// static `/uploads` route, so `/uploads/<absolute path>` works like any other upload.
+/// Only paths that are registered as a media item's filename are served.
+pub fn external_originals_router(state: AppState) -> Router {
+    Router::new()
+        .route("/{*path}", get(external_original_handler))
+        .with_state(state)
+}
+
+async fn external_original_handler(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    request: axum::extract::Request,
+) -> Result<axum::response::Response, DomainError> {
+    let filename = format!("/{}", path.trim_start_matches('/'));
+    if !state.repo.exists_by_filename(&filename)? {
+        return Err(DomainError::NotFound);
+    }
+
+    let response = ServeFile::new(&filename)
+        .oneshot(request)
+        .await
+        .map_err(|e| -> DomainError { match e {} })?;
+    Ok(response.map(Body::new))
+}
+
+/// Serves `/uploads` from object storage. Keys missing from storage fall through to
+/// originals referenced in place, like the `ServeDir` fallback does for local storage.
+pub fn stored_originals_router(state: AppState) -> Router {
+    Router::new()
+        .route("/{*path}", get(stored_original_handler))
+        .with_state(state)
+}
+
+async fn stored_original_handler(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    request: axum::extract::Request,
+) -> Result<axum::response::Response, DomainError> {
+    if state.storage.exists(&path).await? {
+        return stored_object_response(state.storage.as_ref(), &path, request.headers()).await;
+    }
+    external_original_handler(State(state), Path(path), request).await
+}
+
+/// Serves `/thumbnails` from object storage.
+pub fn stored_thumbnails_router(thumbnails: Arc<dyn MediaStorage>) -> Router {
+    Router::new()
+        .route("/{*path}", get(stored_thumbnail_handler))
+        .with_state(thumbnails)
+}
+
+async fn stored_thumbnail_handler(
+    State(thumbnails): State<Arc<dyn MediaStorage>>,
+    Path(path): Path<String>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, DomainError> {
+    stored_object_response(thumbnails.as_ref(), &path, &headers).await
+}
+
+/// Only serve originals (and companions) of items the user can see. Files no item
+/// references are left to the static routes, which answer them with 404 anyway.
+pub async fn authorize_original(
+    State(repo): State<Arc<dyn MediaRepository>>,
+    user: CurrentUser,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Result<axum::response::Response, DomainError> {
+    let scope = user.scope();
+    if !scope.all_owners {
+        let key = decoded_key(&request)?;
+        let owner = match repo.find_file_owner(&key)? {
+            Some(owner) => Some(owner),
+            None => repo.find_file_owner(&format!("/{}", key))?,
+        };
+        if let Some(owner) = owner {
+            if !scope.can_see(owner) {
+                return Err(DomainError::NotFound);
+            }
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+/// Only serve thumbnails and previews of items the user can see.
+pub async fn authorize_thumbnail(
+    State(repo): State<Arc<dyn MediaRepository>>,
+    user: CurrentUser,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Result<axum::response::Response, DomainError> {
+    let scope = user.scope();
+    if !scope.all_owners {
+        let id = media_id_of_key(&decoded_key(&request)?).ok_or(DomainError::NotFound)?;
+        let item = repo.find_by_id(id)?.ok_or(DomainError::NotFound)?;
+        if !scope.can_see(item.owner_id) {
+            return Err(DomainError::NotFound);
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+/// Storage key of a static file request, relative to the route it is nested under.
+fn decoded_key(request: &axum::extract::Request) -> Result<String, DomainError> {
+    percent_encoding::percent_decode_str(request.uri().path().trim_start_matches('/'))
+        .decode_utf8()
+        .map(|key| key.into_owned())
+        .map_err(|_| DomainError::NotFound)
+}
+
+/// Serve a thumbnail in the best encoding the client accepts. Clients always request
+/// the JPEG key; an AVIF copy of it is served in its place when one exists.
+pub async fn negotiate_thumbnail_format(
+    State(renditions): State<Arc<RenditionsUseCase>>,
+    mut request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let key = request.uri().path().trim_start_matches('/').to_string();
+    let negotiated = renditions.negotiate(&key, accept_header(request.headers())).await;
+    if negotiated != key {
+        if let Ok(uri) = format!("/{}", negotiated).parse() {
+            *request.uri_mut() = uri;
+        }
+    }
+
+    let mut response = next.run(request).await;
+    response.headers_mut().insert(header::VARY, header::HeaderValue::from_static("Accept"));
+    response
+}
+
+fn accept_header(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::ACCEPT).and_then(|value| value.to_str().ok())
+}
+
+/// Serve a stored object, or the single byte range asked for by a `Range` header so
+/// videos can seek without downloading everything before the playback position.
+async fn stored_object_response(
+    storage: &dyn MediaStorage,
+    key: &str,
+    request_headers: &HeaderMap,
+) -> Result<axum::response::Response, DomainError> {
+    let content_type = mime_guess::from_path(key).first_or_octet_stream().to_string();
+    let range = request_headers.get(header::RANGE).and_then(|value| value.to_str().ok());
+    if let Some(range) = range {
+        let size = storage.size(key).await?;
+        match byte_range(range, size) {
+            Some(Some(range)) => {
+                let headers = [
+                    (header::CONTENT_TYPE, content_type),
+                    (header::CONTENT_LENGTH, (range.end - range.start).to_string()),
+                    (header::CONTENT_RANGE, format!("bytes {}-{}/{}", range.start, range.end - 1, size)),
+                    (header::ACCEPT_RANGES, "bytes".to_string()),
+                ];
+                let stream = storage.stream_range(key, range).await?;
+                return Ok((StatusCode::PARTIAL_CONTENT, headers, Body::from_stream(stream)).into_response());
+            }
+            Some(None) => {
+                let headers = [(header::CONTENT_RANGE, format!("bytes */{}", size))];
+                return Ok((StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response());
+            }
+            // Multiple or malformed ranges: send the whole object
+            None => {}
+        }
+    }
+
+    let (size, stream) = storage.stream(key).await?;
+    let headers = [
+        (header::CONTENT_TYPE, content_type),
+        (header::CONTENT_LENGTH, size.to_string()),
+        (header::ACCEPT_RANGES, "bytes".to_string()),
+    ];
+    Ok((headers, Body::from_stream(stream)).into_response())
+}
+
+/// Bytes of an object of `size` selected by a single-range `Range` header value:
+/// `bytes=<first>-<last>`, `bytes=<first>-` or `bytes=-<suffix length>`. `None` when the
+/// header is not one such range, `Some(None)` when it selects nothing of the object.
+fn byte_range(value: &str, size: u64) -> Option<Option<std::ops::Range<u64>>> {
+    let (first, last) = value.trim().strip_prefix("bytes=")?.split_once('-')?;
+    let (first, last) = (first.trim(), last.trim());
+    let range = if first.is_empty() {
+        let suffix: u64 = last.parse().ok()?;
+        size.saturating_sub(suffix)..size
+    } else {
+        let first: u64 = first.parse().ok()?;
+        let end = match last {
+            "" => size,
+            last => {
+                let last: u64 = last.parse().ok()?;
+                if last < first {
+                    return None;
+                }
+                last.saturating_add(1).min(size)
+            }
+        };
+        first..end
+    };
+    Some(Some(range).filter(|range| range.start < range.end))
+}
+
+/// Address of the client behind any trusted reverse proxies.
+fn client_ip(state: &AppState, addr: SocketAddr, headers: &HeaderMap) -> IpAddr {
+    state.trusted_proxies.client_ip(addr.ip(), headers)
+}
+
+/// Count a password attempt from `ip`. Returns false once the IP is over the limit.
+async fn allow_login_attempt(state: &AppState, ip: IpAddr) -> bool {
+    let mut limiter = state.login_rate_limiter.lock().await;
+    let now = Instant::now();
+
+    let entry = limiter.entry(ip).or_insert((0, now));
+    if now.duration_since(entry.1).as_secs() > RATE_LIMIT_WINDOW_SECS {
+        // Window expired, reset
+        *entry = (0, now);
+    }
+
+    if entry.0 >= MAX_LOGIN_ATTEMPTS {
+        return false;
+    }
+
+    entry.0 += 1;
+    true
+}
+
+fn too_many_attempts() -> axum::response::Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(json!({ "error": "Too many login attempts. Try again later." })),
+    )
+        .into_response()
+}
+
 #[derive(Deserialize)]
 pub struct LoginRequest {
+    /// Defaults to the bootstrap admin, so the single-password login keeps working.
+    pub username: Option<String>,
     pub password: String,
 }
 
 async fn login_handler(
     State(state): State<AppState>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(body): Json<LoginRequest>,
 ) -> impl IntoResponse {
-    // Rate limiting by IP
-    {
-        let ip = addr.ip();
-        let mut limiter = state.login_rate_limiter.lock().await;
-        let now = Instant::now();
-
-        let entry = limiter.entry(ip).or_insert((0, now));
-        if now.duration_since(entry.1).as_secs() > RATE_LIMIT_WINDOW_SECS {
-            // Window expired, reset
-            *entry = (0, now);
-        }
-
-        if entry.0 >= MAX_LOGIN_ATTEMPTS {
-            return (
-                StatusCode::TOO_MANY_REQUESTS,
-                Json(json!({ "error": "Too many login attempts. Try again later." })),
-            )
-                .into_response();
-        }
-
-        entry.0 += 1;
+    let ip = client_ip(&state, addr, &headers);
+    if !allow_login_attempt(&state, ip).await {
+        return too_many_attempts();
     }
 
     match &state.auth_config {
         Some(config) => {
-            if config.verify_password(&body.password) {
-                let token = config.generate_token();
-                let cookie = format!(
-                    "gallery_session={}; Path=/; HttpOnly; SameSite=Strict; Secure; Max-Age={}",
-                    token,
-                    60 * 60 * 24 * 30 // 30 days
+            let username = body
+                .username
+                .filter(|u| !u.trim().is_empty())
+                .unwrap_or_else(|| config.default_username.clone());
+            let users = state.users_use_case.clone();
+            let attempted = username.clone();
+            // Argon2 is deliberately slow; keep it off the async workers
+            let user = tokio::task::spawn_blocking(move || users.authenticate(&username, &body.password))
+                .await
+                .ok()
+                .and_then(|result| result.ok())
+                .flatten();
+
+            if let Some(user) = user {
+                let user_agent = headers
+                    .get(header::USER_AGENT)
+                    .and_then(|ua| ua.to_str().ok());
+                let (session, token) = match config.sessions.create(user.id, user_agent, Some(ip.to_string())) {
+                    Ok(created) => created,
+                    Err(e) => return e.into_response(),
+                };
+                state.audit_use_case.record(
+                    Some(&user),
+                    Some(ip.to_string()),
+                    "auth.login",
+                    vec![session.id.to_string()],
+                    Some("password".to_string()),
                 );
                 (
                     StatusCode::OK,
-                    [(header::SET_COOKIE, cookie)],
-                    Json(json!({ "ok": true })),
+                    [(header::SET_COOKIE, session_cookie(config, &token))],
+                    Json(json!({ "ok": true, "user": user })),
                 )
                     .into_response()
             } else {
+                state.audit_use_case.record(
+                    None,
+                    Some(ip.to_string()),
+                    "auth.login_failed",
+                    vec![],
+                    Some(attempted),
+                );
                 (
                     StatusCode::UNAUTHORIZED,
-                    Json(json!({ "error": "Invalid password" })),
+                    Json(json!({ "error": "Invalid username or password" })),
                 )
                     .into_response()
             }
@@ -385,12 +908,123 @@ async fn login_handler(
     }
 }
 
+/// `Set-Cookie` value for a new session.
+fn session_cookie(config: &AuthConfig, token: &str) -> String {
+    format!(
+        "gallery_session={}; Path=/; HttpOnly; SameSite=Strict; Secure; Max-Age={}",
+        token,
+        config.sessions.ttl_secs()
+    )
+}
+
+/// Cookie binding an OIDC login to the browser that started it, so nobody can sign a
+/// victim into their own account by sending them a callback link.
+const OIDC_STATE_COOKIE: &str = "gallery_oidc";
+
+/// Send the browser to the identity provider's login page.
+async fn oidc_login_handler(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, DomainError> {
+    let oidc = state
+        .auth_config
+        .as_ref()
+        .and_then(|config| config.oidc.clone())
+        .ok_or(DomainError::NotFound)?;
+    // Every started login is held in memory until it completes or expires, so one
+    // client must not be able to use up the slots for everyone
+    if !allow_login_attempt(&state, client_ip(&state, addr, &headers)).await {
+        return Ok(too_many_attempts());
+    }
+    let (login_state, url) = oidc.begin().await?;
+    // Lax, not Strict: the provider sends the browser back with a cross-site redirect
+    let cookie = format!(
+        "{}={}; Path=/api/oidc; HttpOnly; SameSite=Lax; Secure; Max-Age=600",
+        OIDC_STATE_COOKIE, login_state
+    );
+    Ok(([(header::SET_COOKIE, cookie)], Redirect::to(&url)).into_response())
+}
+
+#[derive(Deserialize)]
+struct OidcCallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+/// The provider redirects here after login. Starts a session and returns to the gallery;
+/// failures land on the login page with `?sso_error=1`.
+async fn oidc_callback_handler(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(query): Query<OidcCallbackQuery>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    let Some(config) = state.auth_config.as_ref() else {
+        return DomainError::NotFound.into_response();
+    };
+    let Some(oidc) = config.oidc.clone() else {
+        return DomainError::NotFound.into_response();
+    };
+
+    let bound_state = extract_cookie(&headers, OIDC_STATE_COOKIE);
+    let result = async {
+        if let Some(error) = query.error {
+            return Err(DomainError::Io(format!("Provider returned '{}'", error)));
+        }
+        let (Some(code), Some(login_state)) = (query.code, query.state) else {
+            return Err(DomainError::Io("Callback without code or state".to_string()));
+        };
+        if bound_state.as_deref() != Some(login_state.as_str()) {
+            return Err(DomainError::Io("Login was started in another browser".to_string()));
+        }
+        let user = oidc.complete(&login_state, &code).await?;
+        let user_agent = headers.get(header::USER_AGENT).and_then(|ua| ua.to_str().ok());
+        let ip = client_ip(&state, addr, &headers);
+        let (session, token) = config.sessions.create(user.id, user_agent, Some(ip.to_string()))?;
+        state.audit_use_case.record(
+            Some(&user),
+            Some(ip.to_string()),
+            "auth.login",
+            vec![session.id.to_string()],
+            Some("oidc".to_string()),
+        );
+        Ok(token)
+    }
+    .await;
+
+    let mut response = match result {
+        Ok(token) => {
+            ([(header::SET_COOKIE, session_cookie(config, &token))], Redirect::to("/")).into_response()
+        }
+        Err(e) => {
+            warn!("OIDC login failed: {}", e);
+            Redirect::to("/?sso_error=1").into_response()
+        }
+    };
+    let clear_state = format!(
+        "{}=; Path=/api/oidc; HttpOnly; SameSite=Lax; Secure; Max-Age=0",
+        OIDC_STATE_COOKIE
+    );
+    if let Ok(value) = clear_state.parse() {
+        response.headers_mut().append(header::SET_COOKIE, value);
+    }
+    response
+}
+
 async fn logout_handler(
     State(state): State<AppState>,
+    ip: ClientIp,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    // Invalidate all sessions
+    // End only this device's session; other devices stay signed in
     if let Some(ref config) = state.auth_config {
-        config.invalidate_sessions();
+        if let Some((session, user)) = config.session_from_headers(&headers) {
+            if config.sessions.revoke(user.id, session.id).is_ok() {
+                state.audit(&CurrentUser(Some(user)), &ip, "auth.logout", vec![session.id.to_string()], None);
+            }
+        }
     }
     let cookie = "gallery_session=; Path=/; HttpOnly; SameSite=Strict; Secure; Max-Age=0";
     (
@@ -406,23 +1040,16 @@ async fn auth_check_handler(
 ) -> impl IntoResponse {
     match &state.auth_config {
         Some(config) => {
-            // Check the cookie manually
-            let token = req
-                .headers()
-                .get(header::COOKIE)
-                .and_then(|v| v.to_str().ok())
-                .and_then(|cookies| {
-                    cookies.split(';').find_map(|part| {
-                        let part = part.trim();
-                        part.strip_prefix("gallery_session=").map(|v| v.to_string())
-                    })
-                });
-            if let Some(token) = token {
-                if config.verify_token(&token) {
-                    return (StatusCode::OK, Json(json!({ "authenticated": true, "required": true }))).into_response();
-                }
+            // Check the proxy header and cookie manually
+            let user = config
+                .proxy_user(peer_ip(&req), req.headers())
+                .or_else(|| config.session_from_headers(req.headers()).map(|(_, user)| user));
+            if let Some(user) = user {
+                return (StatusCode::OK, Json(json!({ "authenticated": true, "required": true, "user": user }))).into_response();
             }
-            (StatusCode::UNAUTHORIZED, Json(json!({ "authenticated": false, "required": true }))).into_response()
+            // `oidc` tells the login page to offer single sign-on
+            let oidc = config.oidc.is_some();
+            (StatusCode::UNAUTHORIZED, Json(json!({ "authenticated": false, "required": true, "oidc": oidc }))).into_response()
         }
         None => {
             // No auth configured
@@ -433,8 +1060,9 @@ async fn auth_check_handler(
 
 async fn stats_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
 ) -> Result<impl IntoResponse, DomainError> {
-    let counts = state.repo.media_counts()?;
+    let counts = state.repo.media_counts(&user.scope())?;
 
     // Disk space for the volume containing the upload directory
     let (disk_free_bytes, disk_total_bytes) = get_disk_space(&state.upload_dir);
@@ -506,14 +1134,24 @@ struct UploadResult {
     media: Option<MediaItem>,
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
+    /// Existing item this upload was rejected as a duplicate of.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duplicate_of: Option<Uuid>,
+    filename: String,
+}
+
+/// An uploaded file streamed to a temp file, with the SHA-256 computed on the way in.
+struct ReceivedFile {
     filename: String,
+    path: PathBuf,
+    content_hash: String,
 }
 
 /// Stream a multipart field to a temp file chunk-by-chunk.
-/// Returns (temp_path, total_bytes_written).
+/// Returns (temp_path, content_hash).
 async fn stream_field_to_temp(
     mut field: axum::extract::multipart::Field<'_>,
-) -> Result<(PathBuf, u64), DomainError> {
+) -> Result<(PathBuf, String), DomainError> {
     let temp_dir = std::env::temp_dir();
     let temp_id = Uuid::new_v4();
     let temp_path = temp_dir.join(format!("gallerynet_upload_{}.tmp", temp_id));
@@ -522,7 +1160,7 @@ async fn stream_field_to_temp(
         .await
         .map_err(|e| DomainError::Io(format!("Failed to create temp file: {}", e)))?;
 
-    let mut total: u64 = 0;
+    let mut hasher = Sha256::new();
     while let Some(chunk) = field
         .chunk()
         .await
@@ -531,19 +1169,22 @@ async fn stream_field_to_temp(
         file.write_all(&chunk)
             .await
             .map_err(|e| DomainError::Io(format!("Failed to write temp file: {}", e)))?;
-        total += chunk.len() as u64;
+        hasher.update(&chunk);
     }
 
     file.flush().await.map_err(|e| DomainError::Io(e.to_string()))?;
-    Ok((temp_path, total))
+    Ok((temp_path, hex::encode(hasher.finalize())))
 }
 
 async fn upload_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
+    headers: HeaderMap,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, DomainError> {
+    let session = upload_session(&headers);
     // Collect all file fields — stream each to a temp file to avoid buffering in RAM
-    let mut pending: Vec<(String, PathBuf)> = Vec::new();
+    let mut pending: Vec<ReceivedFile> = Vec::new();
 
     while let Some(field) = multipart.next_field().await.map_err(|e| DomainError::Io(e.to_string()))? {
         let name = field.name().unwrap_or("").to_string();
@@ -554,8 +1195,8 @@ async fn upload_handler(
         // Cap the number of files per upload
         if pending.len() >= MAX_UPLOAD_FILES {
             // Clean up already-created temp files
-            for (_, path) in &pending {
-                let _ = tokio::fs::remove_file(path).await;
+            for received in &pending {
+                let _ = tokio::fs::remove_file(&received.path).await;
             }
             return Err(DomainError::Io(format!(
                 "Too many files in upload (max {})",
@@ -564,80 +1205,300 @@ async fn upload_handler(
         }
 
         let filename = field.file_name().unwrap_or("unknown").to_string();
-        let (temp_path, _size) = stream_field_to_temp(field).await?;
-        pending.push((filename, temp_path));
+        let (path, content_hash) = stream_field_to_temp(field).await?;
+        pending.push(ReceivedFile { filename, path, content_hash });
     }
 
     if pending.is_empty() {
         return Err(DomainError::Io("No file uploaded".to_string()));
     }
 
+    // Storing the originals is quick. Thumbnails, hashes and embeddings are computed by
+    // `process_upload` jobs, which broadcast `MediaUpdated` once an item is ready.
     if pending.len() == 1 {
-        let (filename, temp_path) = pending.into_iter().next().unwrap();
-        let data = tokio::fs::read(&temp_path).await.map_err(|e| DomainError::Io(e.to_string()))?;
-        let _ = tokio::fs::remove_file(&temp_path).await;
-        let media: MediaItem = state.upload_use_case.execute(filename, &data).await?;
-        state.broadcast(WsMessage::MediaCreated { 
-            item: serde_json::to_value(&media).unwrap() 
+        let received = pending.into_iter().next().unwrap();
+        let media = accept_upload(&state, &user, received, session).await?;
+        return Ok((StatusCode::ACCEPTED, Json(serde_json::to_value(media).unwrap())));
+    }
+
+    let mut results = Vec::with_capacity(pending.len());
+    for received in pending {
+        let filename = received.filename.clone();
+        results.push(match accept_upload(&state, &user, received, session).await {
+            Ok(media) => UploadResult { media: Some(media), error: None, duplicate_of: None, filename },
+            Err(e) => {
+                let duplicate_of = match e {
+                    DomainError::DuplicateMedia(id) => Some(id),
+                    _ => None,
+                };
+                UploadResult { media: None, error: Some(e.to_string()), duplicate_of, filename }
+            }
         });
-        return Ok((StatusCode::CREATED, Json(serde_json::to_value(media).unwrap())));
     }
 
+    state.broadcast(WsMessage::UploadComplete);
+    Ok((StatusCode::ACCEPTED, Json(serde_json::to_value(results).unwrap())))
+}
+
+/// Move one uploaded temp file into storage as a `processing` item and queue the rest
+/// of the work.
+async fn accept_upload(
+    state: &AppState,
+    user: &CurrentUser,
+    received: ReceivedFile,
+    session: Option<Uuid>,
+) -> Result<MediaItem, DomainError> {
+    let filename = received.filename.clone();
+    let result = state
+        .upload_use_case
+        .accept(user.owner_id(), received.filename, &received.path, received.content_hash)
+        .await;
+    // Only left behind if the upload was rejected
+    let _ = tokio::fs::remove_file(&received.path).await;
+
+    report_accepted(state, session, &filename, &result);
+    let accepted = result?;
+    if let Accepted::Created(media) = &accepted {
+        queue_upload_processing(state, user, media, session)?;
+    }
+    Ok(accepted.into_media())
+}
+
+/// Announce an accepted upload and queue its thumbnail, hashes and embedding.
+fn queue_upload_processing(
+    state: &AppState,
+    user: &CurrentUser,
+    media: &MediaItem,
+    session: Option<Uuid>,
+) -> Result<(), DomainError> {
+    state.broadcast(WsMessage::MediaCreated {
+        item: serde_json::to_value(media).unwrap(),
+    });
+    let kind = JobKind::ProcessUpload { media_id: media.id, upload_session: session };
+    let job = state.jobs_use_case.enqueue(kind, user.owner_id())?;
+    state.broadcast(JobEvent::Queued(job).into());
+    Ok(())
+}
+
+/// `X-Upload-Session` of an upload request: the client that wants its progress.
+fn upload_session(headers: &HeaderMap) -> Option<Uuid> {
+    headers
+        .get("x-upload-session")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Tell the uploading client whether `file` was stored.
+fn report_accepted(
+    state: &AppState,
+    session: Option<Uuid>,
+    file: &str,
+    result: &Result<Accepted, DomainError>,
+) {
+    let Some(session) = session else { return };
+    let (media_id, stage) = match result {
+        Ok(Accepted::Created(media)) => (Some(media.id), UploadStage::Received),
+        // Nothing left to process for the other half of a RAW+JPEG pair
+        Ok(Accepted::Paired(media)) => (Some(media.id), UploadStage::Saved),
+        Err(DomainError::DuplicateMedia(existing)) => (None, UploadStage::Duplicate { existing: *existing }),
+        Err(e) => (None, UploadStage::Failed { error: e.to_string() }),
+    };
+    state.broadcast(WsMessage::UploadProgress { session, file: Some(file.to_string()), media_id, stage });
+}
+
+// --- Resumable uploads (tus 1.0.0 with the creation, expiration and termination extensions) ---
+
+/// Response carrying `Tus-Resumable`, which the protocol requires on every response.
+fn tus_response(status: StatusCode, headers: &[(&'static str, String)]) -> axum::response::Response {
+    let mut response = status.into_response();
+    let map = response.headers_mut();
+    map.insert("tus-resumable", header::HeaderValue::from_static(TUS_VERSION));
+    for (name, value) in headers {
+        if let Ok(value) = header::HeaderValue::from_str(value) {
+            map.insert(*name, value);
+        }
+    }
+    response
+}
+
+/// 412 unless the client speaks our protocol version.
+fn check_tus_version(headers: &HeaderMap) -> Option<axum::response::Response> {
+    match headers.get("tus-resumable").and_then(|v| v.to_str().ok()) {
+        Some(TUS_VERSION) => None,
+        _ => Some(tus_response(
+            StatusCode::PRECONDITION_FAILED,
+            &[("tus-version", TUS_VERSION.to_string())],
+        )),
+    }
+}
 
-    // Multiple files — process concurrently, return array of results
-    let mut handles = Vec::with_capacity(pending.len());
-    for (filename, temp_path) in pending {
-        let use_case = state.upload_use_case.clone();
-        let semaphore = state.upload_semaphore.clone(); 
-        let app_state = state.clone();
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
 
-        handles.push(tokio::spawn(async move {
-            let _permit = semaphore.acquire().await.unwrap();
+/// `Upload-Expires` uses the HTTP date format.
+fn http_date(date: chrono::DateTime<chrono::Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
 
-            let data = tokio::fs::read(&temp_path).await;
-            let _ = tokio::fs::remove_file(&temp_path).await;
-            let data = match data {
-                Ok(d) => d,
-                Err(e) => return UploadResult {
-                    media: None,
-                    error: Some(format!("Failed to read temp file: {}", e)),
-                    filename,
-                },
+/// Decode `Upload-Metadata`: comma-separated `key base64(value)` pairs, value optional.
+fn parse_upload_metadata(value: &str) -> HashMap<String, String> {
+    use base64::Engine;
+    value
+        .split(',')
+        .filter_map(|pair| {
+            let mut parts = pair.trim().splitn(2, ' ');
+            let key = parts.next().filter(|k| !k.is_empty())?;
+            let value = match parts.next() {
+                Some(encoded) => {
+                    let bytes = base64::engine::general_purpose::STANDARD.decode(encoded.trim()).ok()?;
+                    String::from_utf8(bytes).ok()?
+                }
+                None => String::new(),
             };
-            match use_case.execute(filename.clone(), &data).await {
-                Ok(media) => {
-                    app_state.broadcast(WsMessage::MediaCreated { 
-                        item: serde_json::to_value(&media).unwrap() 
-                    });
-                    UploadResult { media: Some(media), error: None, filename }
-                },
-                Err(e) => {
-                    let err_msg: String = e.to_string();
-                    UploadResult { media: None, error: Some(err_msg), filename }
-                },
-            }
+            Some((key.to_string(), value))
+        })
+        .collect()
+}
+
+async fn tus_options_handler(State(state): State<AppState>) -> axum::response::Response {
+    tus_response(
+        StatusCode::NO_CONTENT,
+        &[
+            ("tus-version", TUS_VERSION.to_string()),
+            ("tus-extension", "creation,expiration,termination".to_string()),
+            ("tus-max-size", state.resumable_uploads_use_case.max_size().to_string()),
+        ],
+    )
+}
+
+/// Announce an upload. The file name comes from the `filename` (or `name`) metadata.
+async fn create_resumable_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, DomainError> {
+    if let Some(response) = check_tus_version(&headers) {
+        return Ok(response);
+    }
+    let Some(length) = header_u64(&headers, "upload-length") else {
+        return Ok(tus_response(StatusCode::BAD_REQUEST, &[]));
+    };
+    if length > state.resumable_uploads_use_case.max_size() {
+        return Ok(tus_response(StatusCode::PAYLOAD_TOO_LARGE, &[]));
+    }
+    let metadata = headers
+        .get("upload-metadata")
+        .and_then(|v| v.to_str().ok())
+        .map(parse_upload_metadata)
+        .unwrap_or_default();
+    let filename = metadata.get("filename").or_else(|| metadata.get("name")).cloned().unwrap_or_default();
+
+    let upload = state.resumable_uploads_use_case.create(user.owner_id(), &filename, length).await?;
+    Ok(tus_response(
+        StatusCode::CREATED,
+        &[
+            ("location", format!("/api/tus/{}", upload.id)),
+            ("upload-offset", "0".to_string()),
+            ("upload-expires", http_date(upload.expires_at)),
+        ],
+    ))
+}
+
+/// Where to resume: the number of bytes received so far.
+async fn resumable_offset_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, DomainError> {
+    if let Some(response) = check_tus_version(&headers) {
+        return Ok(response);
+    }
+    let (upload, offset) = state.resumable_uploads_use_case.status(id, user.owner_id()).await?;
+    Ok(tus_response(
+        StatusCode::OK,
+        &[
+            ("upload-offset", offset.to_string()),
+            ("upload-length", upload.length.to_string()),
+            ("upload-expires", http_date(upload.expires_at)),
+            ("cache-control", "no-store".to_string()),
+        ],
+    ))
+}
+
+/// Append a chunk. The chunk that completes the upload also stores it like `/upload`
+/// does; the new item's id is returned in `X-Media-Id`.
+async fn append_resumable_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<axum::response::Response, DomainError> {
+    use futures_util::TryStreamExt;
+
+    if let Some(response) = check_tus_version(&headers) {
+        return Ok(response);
+    }
+    let content_type = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok());
+    if content_type != Some("application/offset+octet-stream") {
+        return Ok(tus_response(StatusCode::UNSUPPORTED_MEDIA_TYPE, &[]));
+    }
+    let Some(offset) = header_u64(&headers, "upload-offset") else {
+        return Ok(tus_response(StatusCode::BAD_REQUEST, &[]));
+    };
 
-        }));
+    let session = upload_session(&headers);
+    let uploads = &state.resumable_uploads_use_case;
+    let owner = user.owner_id();
+    let (upload, received) = uploads.status(id, owner).await?;
+    if offset != received {
+        return Ok(tus_response(StatusCode::CONFLICT, &[("upload-offset", received.to_string())]));
     }
 
-    let mut results = Vec::with_capacity(handles.len());
-    for handle in handles {
-        match handle.await {
-            Ok(result) => results.push(result),
-            Err(e) => results.push(UploadResult {
-                media: None,
-                error: Some(format!("Task panicked: {}", e)),
-                filename: "unknown".to_string(),
-            }),
+    let chunk = StreamReader::new(body.into_data_stream().map_err(std::io::Error::other));
+    // An empty chunk at the end retries a completion that failed
+    let (upload, offset) = if offset < upload.length {
+        uploads.append(id, owner, offset, chunk).await?
+    } else {
+        (upload, offset)
+    };
+
+    let mut headers = vec![
+        ("upload-offset", offset.to_string()),
+        ("upload-expires", http_date(upload.expires_at)),
+    ];
+    if offset == upload.length {
+        let result = uploads.complete(id, owner).await;
+        report_accepted(&state, session, &upload.filename, &result);
+        let accepted = result?;
+        if let Accepted::Created(media) = &accepted {
+            queue_upload_processing(&state, &user, media, session)?;
         }
+        headers.push(("x-media-id", accepted.media().id.to_string()));
     }
+    Ok(tus_response(StatusCode::NO_CONTENT, &headers))
+}
 
-    state.broadcast(WsMessage::UploadComplete);
-    Ok((StatusCode::CREATED, Json(serde_json::to_value(results).unwrap())))
+async fn terminate_resumable_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, DomainError> {
+    if let Some(response) = check_tus_version(&headers) {
+        return Ok(response);
+    }
+    state.resumable_uploads_use_case.terminate(id, user.owner_id()).await?;
+    Ok(tus_response(StatusCode::NO_CONTENT, &[]))
 }
 
 async fn search_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, DomainError> {
     let mut file_bytes: Option<Vec<u8>> = None;
@@ -660,7 +1521,7 @@ async fn search_handler(
         // Convert similarity (0-100) to max_distance (2.0 - 0.0)
         let max_distance = 2.0 * (1.0 - (similarity / 100.0));
 
-        let results = state.search_use_case.execute(&data, limit, max_distance).await?;
+        let results = state.search_use_case.execute(&user.scope(), &data, limit, max_distance).await?;
         return Ok(Json(results));
     }
 
@@ -675,6 +1536,7 @@ pub struct SimilarQuery {
 
 async fn search_by_id_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Path(id): Path<Uuid>,
     Query(params): Query<SimilarQuery>,
 ) -> Result<impl IntoResponse, DomainError> {
@@ -686,27 +1548,116 @@ async fn search_by_id_handler(
     // Clamp to valid range just in case
     let max_distance = max_distance.max(0.0).min(2.0);
 
-    let results = state.search_use_case.execute_by_id(id, limit, max_distance).await?;
+    visible_media(&state, &user, id)?;
+    let results = state.search_use_case.execute_by_id(&user.scope(), id, limit, max_distance).await?;
     Ok(Json(results))
 }
 
+/// Look up a media item the current user can see. Other users' private items are
+/// reported as not found.
+fn visible_media(state: &AppState, user: &CurrentUser, id: Uuid) -> Result<MediaItem, DomainError> {
+    state.repo.find_by_id(id)?
+        .filter(|item| user.scope().can_see(item.owner_id))
+        .ok_or(DomainError::NotFound)
+}
+
+/// Fails with `Forbidden` unless the current user may change every listed item that exists.
+fn ensure_can_modify_media(state: &AppState, user: &CurrentUser, ids: &[Uuid]) -> Result<(), DomainError> {
+    for id in ids {
+        if let Some(item) = state.repo.find_by_id(*id)? {
+            user.ensure_can_modify(item.owner_id)?;
+        }
+    }
+    Ok(())
+}
+
+/// The listed items that exist, grouped by owner.
+fn ids_by_owner(state: &AppState, ids: &[Uuid]) -> Result<BTreeMap<Option<Uuid>, Vec<Uuid>>, DomainError> {
+    let mut groups: BTreeMap<Option<Uuid>, Vec<Uuid>> = BTreeMap::new();
+    for id in ids {
+        if let Some(item) = state.repo.find_by_id(*id)? {
+            groups.entry(item.owner_id).or_default().push(*id);
+        }
+    }
+    Ok(groups)
+}
+
+fn visible_folder(state: &AppState, user: &CurrentUser, id: Uuid) -> Result<Folder, DomainError> {
+    state.repo.get_folder(id)?
+        .filter(|folder| user.scope().can_see(folder.owner_id))
+        .ok_or(DomainError::NotFound)
+}
+
+fn modifiable_folder(state: &AppState, user: &CurrentUser, id: Uuid) -> Result<Folder, DomainError> {
+    let folder = visible_folder(state, user, id)?;
+    user.ensure_can_modify(folder.owner_id)?;
+    Ok(folder)
+}
+
 async fn get_media_handler(
     State(state): State<AppState>,
-
+    user: CurrentUser,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, DomainError> {
-    match state.repo.find_by_id(id)? {
-        Some(item) => Ok(Json(serde_json::to_value(item).unwrap())),
-        None => Err(DomainError::NotFound),
-    }
+    let mut item = visible_media(&state, &user, id)?;
+    item.is_favorite = state.repo.is_favorite(&user.scope(), id)?;
+    Ok(Json(serde_json::to_value(item).unwrap()))
+}
+
+/// Download the other half of a RAW+JPEG pair.
+async fn companion_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<Uuid>,
+) -> Result<axum::response::Response, DomainError> {
+    visible_media(&state, &user, id)?;
+    let companion = state.repo.find_companion(id)?.ok_or(DomainError::NotFound)?;
+    let (size, stream) = state.storage.stream(&companion.filename).await?;
+
+    let content_type = mime_guess::from_path(&companion.original_filename)
+        .first_or_octet_stream()
+        .to_string();
+    let headers = [
+        (header::CONTENT_TYPE, content_type),
+        (
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", sanitize_filename(&companion.original_filename)),
+        ),
+        (header::CONTENT_LENGTH, size.to_string()),
+    ];
+    Ok((headers, Body::from_stream(stream)).into_response())
+}
+
+/// A downscaled copy of an image for display, at the configured size closest to
+/// `size` pixels on its longest edge, in the best encoding the client accepts.
+async fn preview_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((id, size)): Path<(Uuid, u32)>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, DomainError> {
+    let item = visible_media(&state, &user, id)?;
+    let key = state.renditions_use_case.preview(&item, size, accept_header(&headers)).await?;
+    let mut response = stored_object_response(state.thumbnails.as_ref(), &key, &headers).await?;
+    // Regenerated under the same key when the item is reprocessed, so not immutable
+    response.headers_mut().insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_static("private, max-age=86400"),
+    );
+    response.headers_mut().insert(header::VARY, header::HeaderValue::from_static("Accept"));
+    Ok(response)
 }
 
 async fn delete_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, DomainError> {
+    user.ensure_can_modify(visible_media(&state, &user, id)?.owner_id)?;
     state.delete_use_case.execute(id).await?;
-    state.broadcast(WsMessage::MediaDeleted { id });
+    state.audit(&user, &ip, "media.delete", vec![id.to_string()], None);
+    state.broadcast(WsMessage::MediaTrashed { ids: vec![id] });
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -717,10 +1668,12 @@ pub struct FavoriteRequest {
 
 async fn toggle_favorite_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Path(id): Path<Uuid>,
     Json(body): Json<FavoriteRequest>,
 ) -> Result<impl IntoResponse, DomainError> {
-    state.repo.set_favorite(id, body.favorite)?;
+    visible_media(&state, &user, id)?;
+    state.repo.set_favorite(&user.scope(), id, body.favorite)?;
     if let Some(mut item) = state.repo.find_by_id(id)? {
         item.exif_json = None;
         state.broadcast(WsMessage::MediaUpdated { id, item: serde_json::to_value(item).unwrap() });
@@ -730,8 +1683,9 @@ async fn toggle_favorite_handler(
 
 async fn list_tags_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
 ) -> Result<impl IntoResponse, DomainError> {
-    let tags = state.repo.get_all_tags()?;
+    let tags = state.repo.get_all_tags(&user.scope())?;
     Ok(Json(tags))
 }
 
@@ -749,9 +1703,10 @@ pub struct CountAutoTagsQuery {
 
 async fn get_auto_tags_count_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Query(query): Query<CountAutoTagsQuery>,
 ) -> Result<impl IntoResponse, DomainError> {
-    let count = state.repo.count_auto_tags(query.folder_id)?;
+    let count = state.repo.count_auto_tags(&user.scope(), query.folder_id)?;
     Ok(Json(json!({ "count": count })))
 }
 
@@ -760,17 +1715,14 @@ pub struct LearnTagRequest {
     pub tag_name: String,
 }
 
+/// Queue training of one tag's model. The job's result holds `auto_tagged_count`.
 async fn learn_tag_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Json(body): Json<LearnTagRequest>,
 ) -> Result<impl IntoResponse, DomainError> {
-    let use_case = state.tag_learning_use_case.clone();
-    let tag_name = body.tag_name.clone();
-    let count = tokio::task::spawn_blocking(move || use_case.learn_tag(&tag_name))
-        .await
-        .map_err(|e| DomainError::Ai(e.to_string()))??;
-    state.broadcast(WsMessage::TagLearningComplete { tag_name: body.tag_name });
-    Ok(Json(json!({ "auto_tagged_count": count })))
+    state.repo.get_tag_id_by_name(&body.tag_name)?.ok_or(DomainError::NotFound)?;
+    queue_job(&state, &user, JobKind::LearnTag { tag_name: body.tag_name })
 }
 
 
@@ -779,21 +1731,24 @@ pub struct AutoTagRequest {
     pub folder_id: Option<Uuid>,
 }
 
+/// Queue an auto-tagging run. The job reports progress per tag model; its result
+/// holds the auto-tag counts before and after.
 async fn auto_tag_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
     Json(body): Json<AutoTagRequest>,
 ) -> Result<impl IntoResponse, DomainError> {
-    let use_case = state.tag_learning_use_case.clone();
     let folder_id = body.folder_id;
-    let result = tokio::task::spawn_blocking(move || use_case.run_auto_tagging(folder_id))
-        .await
-        .map_err(|e| DomainError::Ai(e.to_string()))??;
-    state.broadcast(WsMessage::UploadComplete);
-    Ok(Json(json!({
-        "before": result.before,
-        "after": result.after,
-        "models_processed": result.models_processed
-    })))
+    let (status, job) = queue_job(&state, &user, JobKind::AutoTag { folder_id })?;
+    state.audit(
+        &user,
+        &ip,
+        "tags.auto_tag",
+        folder_id.map(|id| id.to_string()).into_iter().collect(),
+        Some(format!("job {}", job.id)),
+    );
+    Ok((status, job))
 }
 
 #[derive(Deserialize)]
@@ -803,6 +1758,8 @@ pub struct ApplyTagRequest {
 
 async fn apply_tag_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
     Path(id): Path<i64>,
     Json(body): Json<ApplyTagRequest>,
 ) -> Result<impl IntoResponse, DomainError> {
@@ -811,7 +1768,15 @@ async fn apply_tag_handler(
     let count = tokio::task::spawn_blocking(move || use_case.apply_tag_model(id, folder_id))
         .await
         .map_err(|e| DomainError::Ai(e.to_string()))??;
-    if let Ok(Some(name)) = state.repo.get_tag_name_by_id(id) {
+    let tag_name = state.repo.get_tag_name_by_id(id).ok().flatten();
+    state.audit(
+        &user,
+        &ip,
+        "tags.apply",
+        folder_id.map(|id| id.to_string()).into_iter().collect(),
+        Some(format!("{} ({} items)", tag_name.as_deref().unwrap_or("unknown tag"), count)),
+    );
+    if let Some(name) = tag_name {
         state.broadcast(WsMessage::TagLearningComplete { tag_name: name });
     }
     Ok(Json(json!({ "auto_tagged_count": count })))
@@ -824,9 +1789,11 @@ struct UpdateTagsRequest {
 
 async fn update_tags_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Path(id): Path<Uuid>,
     Json(body): Json<UpdateTagsRequest>,
 ) -> Result<impl IntoResponse, DomainError> {
+    user.ensure_can_modify(visible_media(&state, &user, id)?.owner_id)?;
     state.repo.update_media_tags(id, body.tags.clone())?;
     if let Some(mut item) = state.repo.find_by_id(id)? {
         item.exif_json = None;
@@ -843,19 +1810,24 @@ struct BatchUpdateTagsRequest {
 
 async fn batch_update_tags_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
     Json(body): Json<BatchUpdateTagsRequest>,
 ) -> Result<impl IntoResponse, DomainError> {
+    ensure_can_modify_media(&state, &user, &body.ids)?;
     state.repo.update_media_tags_batch(&body.ids, &body.tags)?;
-    state.broadcast(WsMessage::MediaTagsUpdated { ids: body.ids, tags: body.tags });
+    state.audit(&user, &ip, "media.batch_tags", id_strings(&body.ids), Some(body.tags.join(", ")));
+    for (owner, ids) in ids_by_owner(&state, &body.ids)? {
+        state.broadcast(WsMessage::MediaTagsUpdated { ids, tags: body.tags.clone(), owner });
+    }
     Ok(StatusCode::OK)
 }
 
 fn create_download_plan<T: HasFilenames>(
     items: Vec<T>,
-    upload_dir: &std::path::Path,
     base_name: &str,
 ) -> DownloadPlan {
-    let entries = prepare_zip_entries(&items, upload_dir);
+    let entries = prepare_zip_entries(&items);
     
     let mut parts = Vec::new();
     let mut current_part_media_ids = Vec::new();
@@ -902,24 +1874,58 @@ fn create_download_plan<T: HasFilenames>(
     }
 }
 
-async fn batch_download_plan_handler(
-    State(state): State<AppState>,
-    Json(ids): Json<Vec<Uuid>>,
-) -> Result<impl IntoResponse, DomainError> {
-    if ids.is_empty() {
-        return Err(DomainError::Io("No files requested".to_string()));
-    }
+/// Keep a plan for its parts to be streamed later and describe it to the client.
+async fn register_download_plan(state: &AppState, plan: DownloadPlan) -> Json<serde_json::Value> {
+    let plan_id = plan.id.clone();
+    let parts = plan.parts.clone();
 
-    // Deduplicate IDs
-    let unique_ids: Vec<Uuid> = {
-        let mut seen = std::collections::HashSet::new();
-        ids.into_iter().filter(|id| seen.insert(*id)).collect()
-    };
+    {
+        let mut plans = state.download_plans.lock().await;
+        let now = Instant::now();
+        plans.retain(|_, p| p.expires_at > now);
+        plans.insert(plan_id.clone(), plan);
+    }
 
-    // Look up all requested media items
+    Json(json!({
+        "plan_id": plan_id,
+        "parts": parts
+    }))
+}
+
+/// Sanitize a folder or share name for use in zip filenames.
+fn archive_base_name(name: &str, fallback: &str) -> String {
+    let safe_name: String = name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let safe_name = safe_name.trim_matches(|c| c == '_').to_string();
+    if safe_name.is_empty() { fallback.to_string() } else { safe_name }
+}
+
+async fn batch_download_plan_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Json(ids): Json<Vec<Uuid>>,
+) -> Result<impl IntoResponse, DomainError> {
+    if ids.is_empty() {
+        return Err(DomainError::Io("No files requested".to_string()));
+    }
+
+    // Deduplicate IDs
+    let unique_ids: Vec<Uuid> = {
+        let mut seen = std::collections::HashSet::new();
+        ids.into_iter().filter(|id| seen.insert(*id)).collect()
+    };
+
+    // Look up all requested media items
     let mut items = Vec::new();
     for id in &unique_ids {
-        if let Some(item) = state.repo.find_by_id(*id)? {
+        if let Ok(item) = visible_media(&state, &user, *id) {
             items.push(item);
         }
     }
@@ -929,25 +1935,13 @@ async fn batch_download_plan_handler(
     }
 
     let base_name = format!("gallerynet_{}", items.len());
-    let plan = create_download_plan(items, &state.upload_dir, &base_name);
-    let plan_id = plan.id.clone();
-    let parts = plan.parts.clone();
-
-    {
-        let mut plans = state.download_plans.lock().await;
-        let now = Instant::now();
-        plans.retain(|_, p| p.expires_at > now);
-        plans.insert(plan_id.clone(), plan);
-    }
-
-    Ok(Json(json!({
-        "plan_id": plan_id,
-        "parts": parts
-    })))
+    let plan = create_download_plan(items, &base_name);
+    Ok(register_download_plan(&state, plan).await)
 }
 
 async fn batch_download_stream_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Path(part_id): Path<String>,
 ) -> Result<impl IntoResponse, DomainError> {
     let part = {
@@ -961,7 +1955,7 @@ async fn batch_download_stream_handler(
 
     let mut items = Vec::new();
     for id in &part.media_ids {
-        if let Some(item) = state.repo.find_by_id(*id)? {
+        if let Ok(item) = visible_media(&state, &user, *id) {
             items.push(item);
         }
     }
@@ -970,7 +1964,16 @@ async fn batch_download_stream_handler(
         return Err(DomainError::NotFound);
     }
 
-    let entries = prepare_zip_entries(&items, &state.upload_dir);
+    Ok(zip_part_response(state.storage.clone(), &part, items))
+}
+
+/// Stream one part of a download plan as a zip archive.
+fn zip_part_response<T: HasFilenames>(
+    storage: Arc<dyn MediaStorage>,
+    part: &DownloadPart,
+    items: Vec<T>,
+) -> (HeaderMap, Body) {
+    let entries = prepare_zip_entries(&items);
     let items_count = items.len();
     let filename = part.filename.clone();
 
@@ -987,12 +1990,12 @@ async fn batch_download_stream_handler(
             }
             
             let builder = ZipEntryBuilder::new(entry.zip_name.clone().into(), Compression::Stored);
-            match tokio::fs::File::open(&entry.disk_path).await {
-                Ok(file) => {
+            match stream_original(storage.as_ref(), &entry.filename).await {
+                Ok((_, stream)) => {
                     match zip.write_entry_stream(builder).await {
                         Ok(mut entry_writer) => {
-                            let mut buf_file = tokio::io::BufReader::with_capacity(128 * 1024, file);
-                            if let Err(e) = tokio::io::copy(&mut buf_file, &mut (&mut entry_writer).compat_write()).await {
+                            let mut reader = StreamReader::new(stream);
+                            if let Err(e) = tokio::io::copy(&mut reader, &mut (&mut entry_writer).compat_write()).await {
                                 if e.kind() == std::io::ErrorKind::BrokenPipe {
                                     info!("Client disconnected, aborting download: {}", filename);
                                     return;
@@ -1008,7 +2011,7 @@ async fn batch_download_stream_handler(
                     }
                 }
                 Err(e) => {
-                    error!("Failed to open {} for streaming: {}", entry.disk_path.display(), e);
+                    error!("Failed to open {} for streaming: {}", entry.filename, e);
                 }
             }
         }
@@ -1030,16 +2033,20 @@ async fn batch_download_stream_handler(
         format!("attachment; filename=\"{}\"", part.filename).parse().unwrap()
     );
 
-    Ok((headers, body))
+    (headers, body)
 }
 
 async fn batch_delete_handler(
 
     State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
     Json(ids): Json<Vec<Uuid>>,
 ) -> Result<impl IntoResponse, DomainError> {
+    ensure_can_modify_media(&state, &user, &ids)?;
     let deleted = state.delete_use_case.execute_batch(&ids).await?;
-    state.broadcast(WsMessage::MediaBatchDeleted { ids });
+    state.audit(&user, &ip, "media.batch_delete", id_strings(&ids), None);
+    state.broadcast(WsMessage::MediaTrashed { ids });
     Ok(Json(json!({ "deleted": deleted })))
 }
 
@@ -1047,8 +2054,8 @@ async fn batch_delete_handler(
 struct ZipEntry {
     /// Sanitized, deduplicated filename for inside the archive.
     zip_name: String,
-    /// Path on disk to read from.
-    disk_path: PathBuf,
+    /// Stored filename of the original (see `stream_original`).
+    filename: String,
     /// File size in bytes (used for splitting).
     size: u64,
 }
@@ -1057,7 +2064,7 @@ struct ZipEntry {
 /// Stream a zip archive of the given items incrementally using async_zip.
 async fn stream_zip_response<I>(
     items: I,
-    upload_dir: PathBuf,
+    storage: Arc<dyn MediaStorage>,
     outer_name: String,
 ) -> Result<axum::response::Response, DomainError>
 where
@@ -1067,7 +2074,7 @@ where
     let items_vec: Vec<I::Item> = items.into_iter().collect();
     let name_for_log = outer_name.clone();
     
-    let entries = prepare_zip_entries(items_vec, &upload_dir);
+    let entries = prepare_zip_entries(items_vec);
     let items_count = entries.len();
 
     let (writer, reader) = tokio::io::duplex(16 * 1024 * 1024); // 16MB buffer
@@ -1081,12 +2088,12 @@ where
                 info!("[{}/{}] Streaming: {}", i + 1, items_count, entry.zip_name);
             }
             let builder = ZipEntryBuilder::new(entry.zip_name.clone().into(), Compression::Stored);
-            match tokio::fs::File::open(&entry.disk_path).await {
-                Ok(file) => {
+            match stream_original(storage.as_ref(), &entry.filename).await {
+                Ok((_, stream)) => {
                     match zip.write_entry_stream(builder).await {
                         Ok(mut entry_writer) => {
-                            let mut buf_file = tokio::io::BufReader::with_capacity(128 * 1024, file);
-                            if let Err(e) = tokio::io::copy(&mut buf_file, &mut (&mut entry_writer).compat_write()).await {
+                            let mut reader = StreamReader::new(stream);
+                            if let Err(e) = tokio::io::copy(&mut reader, &mut (&mut entry_writer).compat_write()).await {
                                 if e.kind() == std::io::ErrorKind::BrokenPipe {
                                     info!("Client disconnected, aborting zip stream: {}", name_for_log);
                                     return;
@@ -1102,7 +2109,7 @@ where
                     }
                 }
                 Err(e) => {
-                    error!("Failed to open {} for streaming: {}", entry.disk_path.display(), e);
+                    error!("Failed to open {} for streaming: {}", entry.filename, e);
                 }
             }
         }
@@ -1131,7 +2138,7 @@ where
 }
 
 /// Prepare `ZipEntry` list from items, handling filename sanitization and deduplication.
-fn prepare_zip_entries<I>(items: I, upload_dir: &std::path::Path) -> Vec<ZipEntry>
+fn prepare_zip_entries<I>(items: I) -> Vec<ZipEntry>
 where
     I: IntoIterator,
     I::Item: HasFilenames,
@@ -1150,9 +2157,9 @@ where
             };
             *entry += 1;
 
-            let disk_path = upload_dir.join(item.disk_filename());
-            let size = disk_path.metadata().map(|m| m.len()).unwrap_or(0);
-            ZipEntry { zip_name, disk_path, size }
+            let filename = item.disk_filename().to_string();
+            let size = item.size_bytes().max(0) as u64;
+            ZipEntry { zip_name, filename, size }
         })
         .collect()
 }
@@ -1161,30 +2168,35 @@ where
 trait HasFilenames {
     fn original_filename(&self) -> &str;
     fn disk_filename(&self) -> &str;
+    fn size_bytes(&self) -> i64;
     fn id(&self) -> Uuid;
 }
 
 impl HasFilenames for MediaItem {
     fn original_filename(&self) -> &str { &self.original_filename }
     fn disk_filename(&self) -> &str { &self.filename }
+    fn size_bytes(&self) -> i64 { self.size_bytes }
     fn id(&self) -> Uuid { self.id }
 }
 
 impl HasFilenames for crate::domain::MediaSummary {
     fn original_filename(&self) -> &str { &self.original_filename }
     fn disk_filename(&self) -> &str { &self.filename }
+    fn size_bytes(&self) -> i64 { self.size_bytes }
     fn id(&self) -> Uuid { self.id }
 }
 
 impl<T: HasFilenames> HasFilenames for &T {
     fn original_filename(&self) -> &str { (*self).original_filename() }
     fn disk_filename(&self) -> &str { (*self).disk_filename() }
+    fn size_bytes(&self) -> i64 { (*self).size_bytes() }
     fn id(&self) -> Uuid { (*self).id() }
 }
 
 async fn batch_download_handler(
 
     State(state): State<AppState>,
+    user: CurrentUser,
     Json(ids): Json<Vec<Uuid>>,
 ) -> Result<impl IntoResponse, DomainError> {
     if ids.is_empty() {
@@ -1200,7 +2212,7 @@ async fn batch_download_handler(
     // Look up all requested media items
     let mut items = Vec::new();
     for id in &unique_ids {
-        if let Some(item) = state.repo.find_by_id(*id)? {
+        if let Ok(item) = visible_media(&state, &user, *id) {
             items.push(item);
         }
     }
@@ -1212,14 +2224,7 @@ async fn batch_download_handler(
     // Single file — serve directly without zipping
     if items.len() == 1 {
         let item = &items[0];
-        let file_path = state.upload_dir.join(&item.filename);
-        let file = tokio::fs::File::open(&file_path)
-            .await
-            .map_err(|e| DomainError::Io(e.to_string()))?;
-        
-        let metadata = file.metadata().await
-            .map_err(|e| DomainError::Io(e.to_string()))?;
-        let size = metadata.len();
+        let (size, stream) = stream_original(state.storage.as_ref(), &item.filename).await?;
 
         let content_type = mime_guess::from_path(&item.original_filename)
             .first_or_octet_stream()
@@ -1234,17 +2239,17 @@ async fn batch_download_handler(
             ),
             (header::CONTENT_LENGTH, size.to_string()),
         ];
-        return Ok((headers, Body::from_stream(ReaderStream::new(file))).into_response());
+        return Ok((headers, Body::from_stream(stream)).into_response());
     }
 
 
     // Multiple files — build zip archive(s), splitting by size
     let file_count = items.len();
-    let upload_dir = state.upload_dir.clone();
+    let storage = state.storage.clone();
     let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
     let outer_name = format!("gallerynet_{}_{}.zip", file_count, timestamp);
 
-    Ok(stream_zip_response(items, upload_dir, outer_name).await?)
+    Ok(stream_zip_response(items, storage, outer_name).await?)
 }
 
 
@@ -1255,8 +2260,11 @@ pub struct GroupRequest {
     pub similarity: Option<f32>, // Alternative: 0-100% similarity
 }
 
+/// Queue grouping of similar items. The job's result holds the groups, limited to
+/// what the requesting user may see.
 async fn group_media_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Json(body): Json<GroupRequest>,
 ) -> Result<impl IntoResponse, DomainError> {
     let mut threshold = 0.2; // Default distance: fairly similar
@@ -1271,38 +2279,291 @@ async fn group_media_handler(
     // Clamp
     threshold = threshold.max(0.0).min(2.0);
 
-    let groups = state.group_use_case.execute(body.folder_id, threshold).await?;
-    Ok(Json(groups))
+    queue_job(&state, &user, JobKind::GroupMedia { folder_id: body.folder_id, threshold })
+}
+
+#[derive(Deserialize)]
+pub struct DuplicatesQuery {
+    pub folder_id: Option<Uuid>,
+    /// Max differing phash bits. Without any criterion, phash matching at the default threshold is used.
+    pub phash_distance: Option<u32>,
+    /// Max embedding cosine distance (0.0 - 2.0).
+    pub embedding_distance: Option<f32>,
+}
+
+impl DuplicatesQuery {
+    /// Without any criterion, phash matching at the default threshold is used.
+    fn criteria(&self) -> DuplicateCriteria {
+        if self.phash_distance.is_none() && self.embedding_distance.is_none() {
+            DuplicateCriteria::default()
+        } else {
+            DuplicateCriteria {
+                phash_distance: self.phash_distance,
+                embedding_distance: self.embedding_distance.map(|d| d.clamp(0.0, 2.0)),
+            }
+        }
+    }
+}
+
+async fn list_duplicates_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<DuplicatesQuery>,
+) -> Result<impl IntoResponse, DomainError> {
+    let clusters = state
+        .duplicates_use_case
+        .find_clusters(&user.scope(), query.folder_id, query.criteria())
+        .await?;
+    Ok(Json(clusters))
+}
+
+#[derive(Deserialize)]
+pub struct ResolveDuplicatesRequest {
+    pub keep: Uuid,
+    pub delete: Vec<Uuid>,
+    /// The parameters the cluster was listed with; the items must still form one cluster.
+    #[serde(flatten)]
+    pub query: DuplicatesQuery,
+}
+
+async fn resolve_duplicates_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
+    Json(body): Json<ResolveDuplicatesRequest>,
+) -> Result<impl IntoResponse, DomainError> {
+    ensure_can_modify_media(&state, &user, &[&[body.keep], body.delete.as_slice()].concat())?;
+    let kept = state
+        .duplicates_use_case
+        .resolve(&user.scope(), body.query.folder_id, body.query.criteria(), body.keep, &body.delete)
+        .await?;
+    state.audit(
+        &user,
+        &ip,
+        "duplicates.resolve",
+        id_strings(&body.delete),
+        Some(format!("kept {}", kept.id)),
+    );
+    state.broadcast(WsMessage::MediaTrashed { ids: body.delete.clone() });
+    state.broadcast(WsMessage::MediaUpdated {
+        id: kept.id,
+        item: serde_json::to_value(&kept).unwrap(),
+    });
+    Ok(Json(json!({ "kept": kept, "deleted": body.delete })))
+}
+
+#[derive(Deserialize)]
+pub struct TrashQuery {
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+async fn list_trash_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<TrashQuery>,
+) -> Result<impl IntoResponse, DomainError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(20).min(MAX_PAGE_LIMIT);
+    Ok(Json(state.trash_use_case.list(&user.scope(), page, limit)?))
+}
+
+async fn restore_trash_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
+    Json(ids): Json<Vec<Uuid>>,
+) -> Result<impl IntoResponse, DomainError> {
+    ensure_can_modify_media(&state, &user, &ids)?;
+    let restored = state.trash_use_case.restore(&ids)?;
+    if !restored.is_empty() {
+        state.audit(&user, &ip, "trash.restore", id_strings(&restored), None);
+        state.broadcast(WsMessage::MediaRestored { ids: restored.clone() });
+    }
+    Ok(Json(json!({ "restored": restored.len() })))
+}
+
+async fn purge_trash_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
+    Json(ids): Json<Vec<Uuid>>,
+) -> Result<impl IntoResponse, DomainError> {
+    ensure_can_modify_media(&state, &user, &ids)?;
+    let purged = state.trash_use_case.purge(&ids).await?;
+    if !purged.is_empty() {
+        state.audit(&user, &ip, "trash.purge", id_strings(&purged), None);
+        state.broadcast(WsMessage::MediaBatchDeleted { ids: purged.clone() });
+    }
+    Ok(Json(json!({ "deleted": purged.len() })))
+}
+
+async fn purge_trash_item_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, DomainError> {
+    user.ensure_can_modify(visible_media(&state, &user, id)?.owner_id)?;
+    state.trash_use_case.purge_one(id).await?;
+    state.audit(&user, &ip, "trash.purge", vec![id.to_string()], None);
+    state.broadcast(WsMessage::MediaBatchDeleted { ids: vec![id] });
+    Ok(StatusCode::NO_CONTENT)
 }
 
+async fn empty_trash_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
+) -> Result<impl IntoResponse, DomainError> {
+    let purged = state.trash_use_case.empty().await?;
+    if !purged.is_empty() {
+        state.audit(&user, &ip, "trash.empty", id_strings(&purged), None);
+        state.broadcast(WsMessage::MediaBatchDeleted { ids: purged.clone() });
+    }
+    Ok(Json(json!({ "deleted": purged.len() })))
+}
+
+/// Queue a repair of items without thumbnails. The job's result holds `fixed_count`.
 async fn fix_thumbnails_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
 ) -> Result<impl IntoResponse, DomainError> {
-    state.broadcast(WsMessage::ThumbnailFixStarted);
-    let result = state.fix_thumbnails_use_case.execute().await;
-    match result {
-        Ok(fixed_items) => {
-            let fixed_count = fixed_items.len();
-            
-            for item in fixed_items {
-                if let Ok(json_item) = serde_json::to_value(&item) {
-                    state.broadcast(WsMessage::MediaUpdated { 
-                        id: item.id, 
-                        item: json_item 
-                    });
-                }
-            }
+    queue_job(&state, &user, JobKind::FixThumbnails)
+}
 
-            state.broadcast(WsMessage::ThumbnailFixCompleted { count: fixed_count });
-            Ok(Json(json!({ "fixed_count": fixed_count })))
-        }
-        Err(e) => {
-            state.broadcast(WsMessage::ThumbnailFixCompleted { count: 0 });
-            Err(e)
-        }
+// ==================== Background jobs ====================
+
+/// Queue a job for the `TaskRunner` workers and answer `202 Accepted` with it.
+fn queue_job(
+    state: &AppState,
+    user: &CurrentUser,
+    kind: JobKind,
+) -> Result<(StatusCode, Json<Job>), DomainError> {
+    let job = state.jobs_use_case.enqueue(kind, user.owner_id())?;
+    state.broadcast(JobEvent::Queued(job.clone()).into());
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// A job the user may see and control: their own, or any for admins.
+fn visible_job(state: &AppState, user: &CurrentUser, id: Uuid) -> Result<Job, DomainError> {
+    let job = state.jobs_use_case.get(id)?;
+    match &user.0 {
+        Some(u) if u.role != Role::Admin && job.created_by != Some(u.id) => Err(DomainError::NotFound),
+        _ => Ok(job),
     }
 }
 
+#[derive(Deserialize)]
+struct JobsQuery {
+    page: Option<usize>,
+    limit: Option<usize>,
+}
+
+/// Jobs of the current user; admins (and everyone without auth) see all jobs.
+async fn list_jobs_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(query): Query<JobsQuery>,
+) -> Result<impl IntoResponse, DomainError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(20).clamp(1, MAX_PAGE_LIMIT);
+    let created_by = user.0.as_ref().filter(|u| u.role != Role::Admin).map(|u| u.id);
+    let mut jobs = state.jobs_use_case.list(created_by, page, limit)?;
+    // Fetch a job by id for its result
+    for job in &mut jobs {
+        job.result = None;
+    }
+    Ok(Json(jobs))
+}
+
+async fn get_job_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, DomainError> {
+    Ok(Json(visible_job(&state, &user, id)?))
+}
+
+async fn cancel_job_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, DomainError> {
+    visible_job(&state, &user, id)?;
+    let job = state.jobs_use_case.cancel(id)?;
+    if job.state == JobState::Cancelled {
+        state.broadcast(JobEvent::Finished(job.clone()).into());
+    }
+    Ok(Json(job))
+}
+
+async fn retry_job_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, DomainError> {
+    visible_job(&state, &user, id)?;
+    let job = state.jobs_use_case.retry(id)?;
+    state.broadcast(JobEvent::Queued(job.clone()).into());
+    Ok(Json(job))
+}
+
+#[derive(Deserialize)]
+pub struct ImportRequest {
+    /// Sub-directory of IMPORT_DIR to import (default: the whole directory)
+    pub path: Option<String>,
+    /// Mirror sub-directories into virtual folders
+    #[serde(default)]
+    pub mirror_folders: bool,
+}
+
+/// Start a server-side import of IMPORT_DIR. Runs in the background; progress is
+/// reported over the WebSocket.
+async fn import_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
+    Json(body): Json<ImportRequest>,
+) -> Result<impl IntoResponse, DomainError> {
+    let source = state.import_use_case.resolve_source(body.path.as_deref())?;
+    if state.import_use_case.is_running() {
+        return Err(DomainError::Io("Import already running".to_string()));
+    }
+    state.audit(&user, &ip, "import.start", vec![], Some(source.display().to_string()));
+
+    let app_state = state.clone();
+    let import_source = source.clone();
+    tokio::spawn(async move {
+        info!("Starting import of {}", import_source.display());
+        let result = app_state
+            .import_use_case
+            .execute(&import_source, body.mirror_folders, |event| {
+                app_state.broadcast(event.into())
+            })
+            .await;
+        match result {
+            Ok(report) => {
+                info!(
+                    "Import of {} finished: {} imported, {} skipped, {} duplicates, {} failed",
+                    import_source.display(),
+                    report.imported,
+                    report.skipped,
+                    report.duplicates,
+                    report.failed
+                );
+                app_state.broadcast(WsMessage::ImportCompleted { report });
+            }
+            Err(e) => error!("Import of {} failed: {}", import_source.display(), e),
+        }
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "started": true, "path": source.display().to_string() })),
+    ))
+}
+
 // ==================== Folder endpoints ====================
 
 #[derive(Deserialize)]
@@ -1312,6 +2573,7 @@ struct CreateFolderRequest {
 
 async fn create_folder_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Json(body): Json<CreateFolderRequest>,
 ) -> Result<impl IntoResponse, DomainError> {
     let name = body.name.trim();
@@ -1319,7 +2581,7 @@ async fn create_folder_handler(
         return Err(DomainError::Io("Folder name cannot be empty".to_string()));
     }
     let id = Uuid::new_v4();
-    let folder = state.repo.create_folder(id, name)?;
+    let folder = state.repo.create_folder(id, name, user.owner_id())?;
     state.broadcast(WsMessage::FolderCreated { 
         folder: serde_json::to_value(&folder).unwrap() 
     });
@@ -1328,39 +2590,50 @@ async fn create_folder_handler(
 
 async fn list_folders_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
 ) -> Result<impl IntoResponse, DomainError> {
-    let folders = state.repo.list_folders()?;
+    let folders = state.repo.list_folders(&user.scope())?;
     Ok(Json(folders))
 }
 
 async fn delete_folder_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, DomainError> {
+    let folder = modifiable_folder(&state, &user, id)?;
     state.repo.delete_folder(id)?;
-    state.broadcast(WsMessage::FolderDeleted { id });
+    state.audit(&user, &ip, "folder.delete", vec![id.to_string()], Some(folder.name));
+    state.broadcast(WsMessage::FolderDeleted { id, owner: folder.owner_id });
     Ok(StatusCode::NO_CONTENT)
 }
 
 async fn rename_folder_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Path(id): Path<Uuid>,
     Json(body): Json<CreateFolderRequest>,
 ) -> Result<impl IntoResponse, DomainError> {
+    let folder = modifiable_folder(&state, &user, id)?;
     let name = body.name.trim();
     if name.is_empty() {
         return Err(DomainError::Io("Folder name cannot be empty".to_string()));
     }
     state.repo.rename_folder(id, name)?;
-    state.broadcast(WsMessage::FolderRenamed { id, name: name.to_string() });
+    state.broadcast(WsMessage::FolderRenamed { id, name: name.to_string(), owner: folder.owner_id });
     Ok(StatusCode::NO_CONTENT)
 }
 
 /// Accepts an ordered array of folder IDs and sets their sort_order accordingly.
 async fn reorder_folders_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Json(folder_ids): Json<Vec<Uuid>>,
 ) -> Result<impl IntoResponse, DomainError> {
+    for id in &folder_ids {
+        visible_folder(&state, &user, *id)?;
+    }
     let order: Vec<(Uuid, i64)> = folder_ids.clone()
         .into_iter()
         .enumerate()
@@ -1385,9 +2658,11 @@ struct FolderPagination {
 
 async fn list_folder_media_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Path(folder_id): Path<Uuid>,
     Query(pagination): Query<FolderPagination>,
 ) -> Result<impl IntoResponse, DomainError> {
+    visible_folder(&state, &user, folder_id)?;
     let page = pagination.page.unwrap_or(1).max(1);
     let limit = pagination.limit.unwrap_or(20).min(MAX_PAGE_LIMIT);
     let offset = (page - 1) * limit;
@@ -1399,17 +2674,22 @@ async fn list_folder_media_handler(
         .filter(|s| !s.is_empty())
         .map(|s| s.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect());
 
-    let results = state.repo.find_all_in_folder(folder_id, limit, offset, pagination.media_type.as_deref(), favorite, tags, sort_asc, sort_by)?;
+    let results = state.repo.find_all_in_folder(&user.scope(), folder_id, limit, offset, pagination.media_type.as_deref(), favorite, tags, sort_asc, sort_by)?;
     Ok(Json(results))
 }
 
 async fn add_to_folder_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Path(folder_id): Path<Uuid>,
     Json(media_ids): Json<Vec<Uuid>>,
 ) -> Result<impl IntoResponse, DomainError> {
+    let folder = modifiable_folder(&state, &user, folder_id)?;
+    for id in &media_ids {
+        visible_media(&state, &user, *id)?;
+    }
     let added = state.repo.add_media_to_folder(folder_id, &media_ids)?;
-    state.broadcast(WsMessage::MediaAddedToFolder { folder_id, media_ids });
+    state.broadcast(WsMessage::MediaAddedToFolder { folder_id, media_ids, owner: folder.owner_id });
     Ok(Json(json!({ "added": added })))
 }
 
@@ -1420,59 +2700,516 @@ struct RemoveFromFolderRequest {
 
 async fn remove_from_folder_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Path(folder_id): Path<Uuid>,
     Json(body): Json<RemoveFromFolderRequest>,
 ) -> Result<impl IntoResponse, DomainError> {
+    let folder = modifiable_folder(&state, &user, folder_id)?;
     let removed = state.repo.remove_media_from_folder(folder_id, &body.media_ids)?;
-    state.broadcast(WsMessage::MediaRemovedFromFolder { folder_id, media_ids: body.media_ids });
+    state.broadcast(WsMessage::MediaRemovedFromFolder {
+        folder_id,
+        media_ids: body.media_ids,
+        owner: folder.owner_id,
+    });
     Ok(Json(json!({ "removed": removed })))
 }
 
 async fn download_folder_handler(
     State(state): State<AppState>,
+    user: CurrentUser,
     Path(folder_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, DomainError> {
     // Get folder details (for name)
-    let folder = state.repo.get_folder(folder_id)?
-        .ok_or(DomainError::NotFound)?;
+    let folder = visible_folder(&state, &user, folder_id)?;
 
     // Get all media in the folder
-    let items = state.repo.get_folder_media_files(folder_id)?;
+    let scope = user.scope();
+    let mut items = state.repo.get_folder_media_files(folder_id)?;
+    items.retain(|item| scope.can_see(item.owner_id));
 
     if items.is_empty() {
         return Err(DomainError::Io("Folder is empty".to_string()));
     }
 
-    // Sanitize folder name for the zip filename
-    let safe_name: String = folder.name.chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
-                c
-            } else {
-                '_'
-            }
+    let plan = create_download_plan(items, &archive_base_name(&folder.name, "folder"));
+    Ok(register_download_plan(&state, plan).await)
+}
+
+// ==================== User endpoints ====================
+
+async fn me_handler(user: CurrentUser) -> impl IntoResponse {
+    Json(user.0)
+}
+
+#[derive(Serialize)]
+struct SessionResponse {
+    #[serde(flatten)]
+    session: Session,
+    /// The session this request was made with.
+    current: bool,
+}
+
+/// Signed-in devices of the current user. Empty when authentication is disabled.
+async fn list_sessions_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    current: CurrentSession,
+) -> Result<impl IntoResponse, DomainError> {
+    let Some(user) = user.0 else {
+        return Ok(Json(Vec::new()));
+    };
+    let sessions = state
+        .sessions_use_case
+        .list(user.id)?
+        .into_iter()
+        .map(|session| SessionResponse { current: current.0 == Some(session.id), session })
+        .collect();
+    Ok(Json(sessions))
+}
+
+/// Sign out one of the current user's devices.
+async fn revoke_session_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, DomainError> {
+    let owner = user.0.as_ref().ok_or(DomainError::NotFound)?;
+    state.sessions_use_case.revoke(owner.id, id)?;
+    state.audit(&user, &ip, "session.revoke", vec![id.to_string()], None);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct CreateTokenRequest {
+    name: String,
+    scope: Option<TokenScope>,
+}
+
+#[derive(Serialize)]
+struct CreatedTokenResponse {
+    #[serde(flatten)]
+    token: ApiToken,
+    /// Shown only once; send it as `Authorization: Bearer <token>`.
+    secret: String,
+}
+
+/// API tokens of the current user. Empty when authentication is disabled.
+async fn list_tokens_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> Result<impl IntoResponse, DomainError> {
+    match user.0 {
+        Some(user) => Ok(Json(state.api_tokens_use_case.list(user.id)?)),
+        None => Ok(Json(Vec::new())),
+    }
+}
+
+async fn create_token_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
+    Json(body): Json<CreateTokenRequest>,
+) -> Result<impl IntoResponse, DomainError> {
+    // Without accounts there is nothing to authenticate a token against
+    let owner = user.0.as_ref().ok_or(DomainError::Forbidden)?;
+    let (token, secret) = state.api_tokens_use_case.create(
+        owner.id,
+        &body.name,
+        body.scope.unwrap_or(TokenScope::Read),
+    )?;
+    state.audit(
+        &user,
+        &ip,
+        "token.create",
+        vec![token.id.to_string()],
+        Some(format!("{} ({})", token.name, token.scope.as_str())),
+    );
+    Ok((StatusCode::CREATED, Json(CreatedTokenResponse { token, secret })))
+}
+
+async fn revoke_token_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, DomainError> {
+    let owner = user.0.as_ref().ok_or(DomainError::NotFound)?;
+    state.api_tokens_use_case.revoke(owner.id, id)?;
+    state.audit(&user, &ip, "token.revoke", vec![id.to_string()], None);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ==================== Share links ====================
+
+/// Cookie proving a visitor entered a share's password. Scoped to the share's path.
+const SHARE_COOKIE: &str = "gallery_share";
+
+#[derive(Deserialize)]
+struct CreateShareRequest {
+    folder_id: Option<Uuid>,
+    media_id: Option<Uuid>,
+    password: Option<String>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    allow_download: bool,
+}
+
+/// Links of the current user; admins (and everyone without auth) see all links.
+async fn list_shares_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+) -> Result<impl IntoResponse, DomainError> {
+    let created_by = user.0.as_ref().filter(|u| u.role != Role::Admin).map(|u| u.id);
+    Ok(Json(state.shares_use_case.list(created_by)?))
+}
+
+async fn create_share_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
+    Json(body): Json<CreateShareRequest>,
+) -> Result<impl IntoResponse, DomainError> {
+    let target = match (body.folder_id, body.media_id) {
+        (Some(id), None) => {
+            visible_folder(&state, &user, id)?;
+            ShareTarget::Folder(id)
+        }
+        (None, Some(id)) => {
+            visible_media(&state, &user, id)?;
+            ShareTarget::Media(id)
+        }
+        _ => return Err(DomainError::Io("Share needs either a folder_id or a media_id".to_string())),
+    };
+    let share = state.shares_use_case.create(
+        user.owner_id(),
+        target,
+        body.password.as_deref(),
+        body.expires_at,
+        body.allow_download,
+    )?;
+    let target_id = match share.target {
+        ShareTarget::Folder(id) | ShareTarget::Media(id) => id,
+    };
+    state.audit(&user, &ip, "share.create", vec![share.id.to_string(), target_id.to_string()], None);
+    Ok((StatusCode::CREATED, Json(share)))
+}
+
+async fn delete_share_handler(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    ip: ClientIp,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, DomainError> {
+    let share = state.shares_use_case.find(id)?;
+    user.ensure_can_modify(share.created_by)?;
+    state.shares_use_case.delete(id)?;
+    state.audit(&user, &ip, "share.delete", vec![id.to_string()], None);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// What a share visitor learns about an item. Storage keys and owners stay private.
+#[derive(Serialize)]
+struct SharedMedia {
+    id: Uuid,
+    original_filename: String,
+    media_type: String,
+    original_date: chrono::DateTime<chrono::Utc>,
+    size_bytes: i64,
+}
+
+/// Resolve a share for an anonymous visitor, honouring expiry and the password cookie.
+fn open_share(state: &AppState, slug: &str, headers: &HeaderMap) -> Result<ShareLink, DomainError> {
+    let key = extract_cookie(headers, SHARE_COOKIE);
+    state.shares_use_case.open(slug, key.as_deref())
+}
+
+/// An item of an open share, or `NotFound` if the share does not expose it.
+fn shared_media(state: &AppState, share: &ShareLink, id: Uuid) -> Result<MediaItem, DomainError> {
+    if !state.shares_use_case.contains(share, id)? {
+        return Err(DomainError::NotFound);
+    }
+    state.repo.find_by_id(id)?.ok_or(DomainError::NotFound)
+}
+
+async fn share_info_handler(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    let share = match open_share(&state, &slug, &headers) {
+        Ok(share) => share,
+        Err(DomainError::Forbidden) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "Password required", "password_required": true })),
+            )
+                .into_response()
+        }
+        Err(e) => return e.into_response(),
+    };
+    let title = match state.shares_use_case.title(&share) {
+        Ok(title) => title,
+        Err(e) => return e.into_response(),
+    };
+    let kind = match share.target {
+        ShareTarget::Folder(_) => "folder",
+        ShareTarget::Media(_) => "media",
+    };
+    Json(json!({
+        "title": title,
+        "kind": kind,
+        "allow_download": share.allow_download,
+        "expires_at": share.expires_at,
+    }))
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct UnlockShareRequest {
+    password: String,
+}
+
+async fn unlock_share_handler(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(slug): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<UnlockShareRequest>,
+) -> axum::response::Response {
+    if !allow_login_attempt(&state, client_ip(&state, addr, &headers)).await {
+        return too_many_attempts();
+    }
+
+    let shares = state.shares_use_case.clone();
+    let share_slug = slug.clone();
+    // Argon2 is deliberately slow; keep it off the async workers
+    let key = match tokio::task::spawn_blocking(move || shares.unlock(&share_slug, &body.password)).await {
+        Ok(Ok(key)) => key,
+        Ok(Err(e)) => return e.into_response(),
+        Err(_) => return DomainError::Io("Unlock task failed".to_string()).into_response(),
+    };
+
+    match key {
+        Some(key) => {
+            let cookie = format!(
+                "{}={}; Path=/api/s/{}; HttpOnly; SameSite=Strict; Secure",
+                SHARE_COOKIE, key, slug
+            );
+            (StatusCode::OK, [(header::SET_COOKIE, cookie)], Json(json!({ "ok": true }))).into_response()
+        }
+        None => (StatusCode::UNAUTHORIZED, Json(json!({ "error": "Wrong password" }))).into_response(),
+    }
+}
+
+async fn share_media_handler(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    Query(pagination): Query<FolderPagination>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, DomainError> {
+    let share = open_share(&state, &slug, &headers)?;
+    let page = pagination.page.unwrap_or(1).max(1);
+    let limit = pagination.limit.unwrap_or(20).min(MAX_PAGE_LIMIT);
+    let items: Vec<SharedMedia> = state
+        .shares_use_case
+        .items(&share, page, limit)?
+        .into_iter()
+        .map(|item| SharedMedia {
+            id: item.id,
+            original_filename: item.original_filename,
+            media_type: item.media_type,
+            original_date: item.original_date,
+            size_bytes: item.size_bytes,
         })
         .collect();
-    let safe_name = safe_name.trim_matches(|c| c == '_').to_string();
-    let safe_name = if safe_name.is_empty() { "folder".to_string() } else { safe_name };
+    Ok(Json(items))
+}
 
-    let plan = create_download_plan(items, &state.upload_dir, &safe_name);
-    let plan_id = plan.id.clone();
-    let parts = plan.parts.clone();
+async fn share_thumbnail_handler(
+    State(state): State<AppState>,
+    Path((slug, id)): Path<(String, Uuid)>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, DomainError> {
+    let share = open_share(&state, &slug, &headers)?;
+    shared_media(&state, &share, id)?;
+    let key = state
+        .renditions_use_case
+        .negotiate(&thumbnail_key(id, ThumbnailFormat::Jpeg), accept_header(&headers))
+        .await;
+    let mut response = stored_object_response(state.thumbnails.as_ref(), &key, &headers).await?;
+    response.headers_mut().insert(header::VARY, header::HeaderValue::from_static("Accept"));
+    Ok(response)
+}
+
+/// The largest preview of a shared item, for links that do not allow downloading the
+/// original.
+async fn share_preview_handler(
+    State(state): State<AppState>,
+    Path((slug, id)): Path<(String, Uuid)>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, DomainError> {
+    let share = open_share(&state, &slug, &headers)?;
+    let item = shared_media(&state, &share, id)?;
+    let key = state.renditions_use_case.preview(&item, u32::MAX, accept_header(&headers)).await?;
+    let mut response = stored_object_response(state.thumbnails.as_ref(), &key, &headers).await?;
+    response.headers_mut().insert(header::VARY, header::HeaderValue::from_static("Accept"));
+    Ok(response)
+}
+
+async fn share_original_handler(
+    State(state): State<AppState>,
+    Path((slug, id)): Path<(String, Uuid)>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, DomainError> {
+    let share = open_share(&state, &slug, &headers)?;
+    if !share.allow_download {
+        return Err(DomainError::Forbidden);
+    }
+    let item = shared_media(&state, &share, id)?;
+    let (size, stream) = stream_original(state.storage.as_ref(), &item.filename).await?;
+    let content_type = mime_guess::from_path(&item.filename).first_or_octet_stream().to_string();
+    let headers = [
+        (header::CONTENT_TYPE, content_type),
+        (header::CONTENT_LENGTH, size.to_string()),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+    ];
+    Ok((headers, Body::from_stream(stream)))
+}
+
+async fn share_download_handler(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, DomainError> {
+    let share = open_share(&state, &slug, &headers)?;
+    if !share.allow_download {
+        return Err(DomainError::Forbidden);
+    }
+    let items = state.shares_use_case.files(&share)?;
+    if items.is_empty() {
+        return Err(DomainError::NotFound);
+    }
+    let title = state.shares_use_case.title(&share)?;
+    let plan = create_download_plan(items, &archive_base_name(&title, "shared"));
+    Ok(register_download_plan(&state, plan).await)
+}
 
-    {
-        let mut plans = state.download_plans.lock().await;
-        let now = Instant::now();
-        plans.retain(|_, p| p.expires_at > now);
-        plans.insert(plan_id.clone(), plan);
+async fn share_download_stream_handler(
+    State(state): State<AppState>,
+    Path((slug, part_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, DomainError> {
+    let share = open_share(&state, &slug, &headers)?;
+    if !share.allow_download {
+        return Err(DomainError::Forbidden);
     }
+    let part = {
+        let plans = state.download_plans.lock().await;
+        plans.values()
+            .flat_map(|p| p.parts.iter())
+            .find(|p| p.id == part_id)
+            .cloned()
+            .ok_or(DomainError::NotFound)?
+    };
 
-    Ok(Json(json!({
-        "plan_id": plan_id,
-        "parts": parts
-    })))
+    let items: Vec<MediaItem> = part
+        .media_ids
+        .iter()
+        .filter_map(|id| shared_media(&state, &share, *id).ok())
+        .collect();
+    if items.is_empty() {
+        return Err(DomainError::NotFound);
+    }
+
+    Ok(zip_part_response(state.storage.clone(), &part, items))
+}
+
+#[derive(Deserialize)]
+struct CreateUserRequest {
+    username: String,
+    password: String,
+    role: Option<Role>,
+}
+
+#[derive(Deserialize)]
+struct UpdateUserRequest {
+    role: Option<Role>,
+    password: Option<String>,
+}
+
+async fn list_users_handler(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, DomainError> {
+    Ok(Json(state.users_use_case.list()?))
+}
+
+async fn create_user_handler(
+    State(state): State<AppState>,
+    admin: CurrentUser,
+    ip: ClientIp,
+    Json(body): Json<CreateUserRequest>,
+) -> Result<impl IntoResponse, DomainError> {
+    let role = body.role.unwrap_or(Role::Member);
+    let user = state.users_use_case.create(&body.username, &body.password, role)?;
+    state.audit(
+        &admin,
+        &ip,
+        "user.create",
+        vec![user.id.to_string()],
+        Some(format!("{} ({})", user.username, user.role.as_str())),
+    );
+    Ok((StatusCode::CREATED, Json(user)))
 }
 
+async fn update_user_handler(
+    State(state): State<AppState>,
+    admin: CurrentUser,
+    ip: ClientIp,
+    Path(id): Path<Uuid>,
+    Json(body): Json<UpdateUserRequest>,
+) -> Result<impl IntoResponse, DomainError> {
+    let user = state.users_use_case.update(id, body.role, body.password.as_deref())?;
+    let mut changes = Vec::new();
+    if let Some(role) = body.role {
+        changes.push(format!("role {}", role.as_str()));
+    }
+    if body.password.is_some() {
+        changes.push("password".to_string());
+    }
+    state.audit(&admin, &ip, "user.update", vec![id.to_string()], Some(changes.join(", ")));
+    Ok(Json(user))
+}
+
+async fn delete_user_handler(
+    State(state): State<AppState>,
+    admin: CurrentUser,
+    ip: ClientIp,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, DomainError> {
+    let username = state.repo.find_user_by_id(id)?.map(|u| u.username);
+    state.users_use_case.delete(id)?;
+    state.audit(&admin, &ip, "user.delete", vec![id.to_string()], username);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct AuditQuery {
+    page: Option<usize>,
+    limit: Option<usize>,
+    action: Option<String>,
+}
+
+/// Audit log, newest first. Admin only.
+async fn list_audit_handler(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<impl IntoResponse, DomainError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(50).clamp(1, MAX_PAGE_LIMIT);
+    let action = query.action.as_deref().filter(|a| !a.is_empty());
+    Ok(Json(state.audit_use_case.list(action, page, limit)?))
+}
 
 #[cfg(test)]
 mod tests {
@@ -1542,6 +3279,27 @@ mod tests {
         assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
     }
 
+    #[test]
+    fn upload_metadata_parsing() {
+        // "holiday.mp4" and "video/mp4", base64 encoded
+        let metadata = parse_upload_metadata("filename aG9saWRheS5tcDQ=,filetype dmlkZW8vbXA0, is_confidential");
+        assert_eq!(metadata["filename"], "holiday.mp4");
+        assert_eq!(metadata["filetype"], "video/mp4");
+        assert_eq!(metadata["is_confidential"], "");
+        assert!(parse_upload_metadata("filename !!!").is_empty());
+    }
+
+    #[test]
+    fn tus_version_is_checked() {
+        let mut headers = HeaderMap::new();
+        let response = check_tus_version(&headers).unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(response.headers()["tus-version"], TUS_VERSION);
+
+        headers.insert("tus-resumable", TUS_VERSION.parse().unwrap());
+        assert!(check_tus_version(&headers).is_none());
+    }
+
     #[test]
     fn rate_limiter_constants_valid() {
         assert!(MAX_LOGIN_ATTEMPTS > 0);
@@ -1550,21 +3308,216 @@ mod tests {
         assert!(RATE_LIMIT_WINDOW_SECS <= 3600);
     }
 
+    #[tokio::test]
+    async fn thumbnails_are_served_in_accepted_formats() {
+        use tower::Layer;
+
+        let dir = tempfile::tempdir().unwrap();
+        let thumbnails: Arc<dyn MediaStorage> = Arc::new(crate::infrastructure::LocalStorage::new(dir.path().to_path_buf()));
+        let renditions = Arc::new(RenditionsUseCase::new(
+            Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+            thumbnails.clone(),
+            thumbnails.clone(),
+            crate::application::RenditionSizes::default().with_formats(vec![ThumbnailFormat::Avif]),
+        ));
+        let id = Uuid::new_v4();
+        thumbnails.put(&thumbnail_key(id, ThumbnailFormat::Jpeg), b"jpeg").await.unwrap();
+        thumbnails.put(&thumbnail_key(id, ThumbnailFormat::Avif), b"avif").await.unwrap();
+
+        let negotiate = axum::middleware::from_fn_with_state(renditions, negotiate_thumbnail_format);
+        let app = Router::new().nest_service(
+            "/thumbnails",
+            negotiate.layer(tower_http::services::ServeDir::new(dir.path())),
+        );
+        let get = |accept: &'static str| {
+            let request = axum::http::Request::builder()
+                .uri(format!("/thumbnails/{}", thumbnail_key(id, ThumbnailFormat::Jpeg)))
+                .header(header::ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let response = get("image/avif,image/webp,*/*").await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/avif");
+        assert_eq!(response.headers()[header::VARY], "Accept");
+        let body = axum::body::to_bytes(response.into_body(), 16).await.unwrap();
+        assert_eq!(&body[..], b"avif");
+
+        let response = get("*/*").await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/jpeg");
+    }
+
+    #[tokio::test]
+    async fn static_files_are_only_served_to_users_who_see_them() {
+        use tower::Layer;
+
+        let owner = Uuid::new_v4();
+        let id = Uuid::new_v4();
+        let item = MediaItem {
+            id,
+            filename: "ab/cd/private.jpg".to_string(),
+            original_filename: "private.jpg".to_string(),
+            media_type: "image".to_string(),
+            phash: "no_hash".to_string(),
+            content_hash: None,
+            owner_id: Some(owner),
+            uploaded_at: chrono::Utc::now(),
+            original_date: chrono::Utc::now(),
+            width: None,
+            height: None,
+            size_bytes: 0,
+            exif_json: None,
+            is_favorite: false,
+            tags: vec![],
+            processing: false,
+        };
+        let user = |id| crate::domain::User {
+            id,
+            username: id.to_string(),
+            role: Role::Member,
+            created_at: chrono::Utc::now(),
+        };
+        let db = crate::infrastructure::TestDb::new("static_files_scope_test");
+        let repo: Arc<dyn MediaRepository> = Arc::new(crate::infrastructure::SqliteRepository::new(&db.path).unwrap());
+        repo.create_user(&user(owner), "hash").unwrap();
+        repo.save_metadata_and_vector(&item, None).unwrap();
+
+        let serve = |body: &'static str| Router::new().route("/{*path}", get(move || async move { body }));
+        let app = Router::new()
+            .nest_service(
+                "/uploads",
+                axum::middleware::from_fn_with_state(repo.clone(), authorize_original).layer(serve("original")),
+            )
+            .nest_service(
+                "/thumbnails",
+                axum::middleware::from_fn_with_state(repo, authorize_thumbnail).layer(serve("thumbnail")),
+            );
+        let status = |path: String, user_id: Uuid| {
+            let request = axum::http::Request::builder()
+                .uri(path)
+                .extension(CurrentUser(Some(user(user_id))))
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+
+        let stranger = Uuid::new_v4();
+        let thumbnail = format!("/thumbnails/{}", thumbnail_key(id, ThumbnailFormat::Jpeg));
+        assert_eq!(status("/uploads/ab/cd/private.jpg".to_string(), owner).await, StatusCode::OK);
+        assert_eq!(status(thumbnail.clone(), owner).await, StatusCode::OK);
+        assert_eq!(status("/uploads/ab/cd/private.jpg".to_string(), stranger).await, StatusCode::NOT_FOUND);
+        assert_eq!(status(thumbnail, stranger).await, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn stored_objects_are_served_in_byte_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn MediaStorage> = Arc::new(crate::infrastructure::LocalStorage::new(dir.path().to_path_buf()));
+        storage.put("ab/cd/clip.mp4", b"0123456789").await.unwrap();
+        let app = Router::new().nest_service("/thumbnails", stored_thumbnails_router(storage));
+        let get = |range: Option<&'static str>| {
+            let mut request = axum::http::Request::builder().uri("/thumbnails/ab/cd/clip.mp4");
+            if let Some(range) = range {
+                request = request.header(header::RANGE, range);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = get(None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+
+        let response = get(Some("bytes=2-5")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 2-5/10");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "4");
+        let body = axum::body::to_bytes(response.into_body(), 16).await.unwrap();
+        assert_eq!(&body[..], b"2345");
+
+        let response = get(Some("bytes=-3")).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 7-9/10");
+        let response = get(Some("bytes=8-")).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 8-9/10");
+
+        let response = get(Some("bytes=10-")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */10");
+        // Several ranges at once are answered with the whole object
+        assert_eq!(get(Some("bytes=0-1,4-5")).await.unwrap().status(), StatusCode::OK);
+    }
+
     #[tokio::test]
     async fn test_broadcast_channel() {
         let (tx, mut rx) = broadcast::channel(16);
-        let msg = "test message";
-        tx.send(Arc::from(msg)).unwrap();
-        let received: Arc<str> = rx.recv().await.unwrap();
-        assert_eq!(msg, &*received);
+        tx.send(WsMessage::UploadComplete.to_broadcast().unwrap()).unwrap();
+        let received: WsBroadcast = rx.recv().await.unwrap();
+        assert_eq!(&*received.json, r#"{"type":"UploadComplete"}"#);
+        assert_eq!(received.upload_session, None);
+    }
+
+    #[test]
+    fn upload_progress_is_scoped_to_its_session() {
+        let session = Uuid::new_v4();
+        let media_id = Uuid::new_v4();
+        let msg = WsMessage::UploadProgress {
+            session,
+            file: None,
+            media_id: Some(media_id),
+            stage: UploadStage::ExtractingFrames,
+        };
+        let broadcast = msg.to_broadcast().unwrap();
+        assert_eq!(broadcast.upload_session, Some(session));
+        let json: serde_json::Value = serde_json::from_str(&broadcast.json).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "type": "UploadProgress",
+                "data": { "media_id": media_id, "stage": "extracting_frames" }
+            })
+        );
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(upload_session(&headers), None);
+        headers.insert("x-upload-session", session.to_string().parse().unwrap());
+        assert_eq!(upload_session(&headers), Some(session));
+    }
+
+    #[test]
+    fn private_items_are_only_broadcast_to_users_who_see_them() {
+        let owner = Uuid::new_v4();
+        let item = json!({ "id": Uuid::new_v4(), "owner_id": owner });
+        let private = WsMessage::MediaCreated { item }.to_broadcast().unwrap();
+        let shared = WsMessage::MediaCreated { item: json!({ "owner_id": null }) }.to_broadcast().unwrap();
+        assert_eq!(private.owner, Some(owner));
+
+        let member = |id| LibraryScope { user_id: Some(id), all_owners: false };
+        assert!(private.is_for(&member(owner), None));
+        assert!(!private.is_for(&member(Uuid::new_v4()), None));
+        assert!(private.is_for(&LibraryScope { user_id: Some(Uuid::new_v4()), all_owners: true }, None));
+        assert!(shared.is_for(&member(Uuid::new_v4()), None));
+    }
+
+    #[test]
+    fn private_folder_changes_are_only_broadcast_to_users_who_see_them() {
+        let owner = Uuid::new_v4();
+        let renamed = WsMessage::FolderRenamed { id: Uuid::new_v4(), name: "Vacation".to_string(), owner: Some(owner) }
+            .to_broadcast()
+            .unwrap();
+        assert!(!renamed.json.contains("owner"));
+
+        let member = |id| LibraryScope { user_id: Some(id), all_owners: false };
+        assert!(renamed.is_for(&member(owner), None));
+        assert!(!renamed.is_for(&member(Uuid::new_v4()), None));
     }
 
     #[tokio::test]
     async fn test_ws_message_serialization() {
         let id = Uuid::new_v4();
-        let msg = WsMessage::MediaDeleted { id };
+        let msg = WsMessage::MediaTrashed { ids: vec![id] };
         let json = serde_json::to_string(&msg).unwrap();
-        assert!(json.contains("MediaDeleted"));
+        assert!(json.contains("MediaTrashed"));
         assert!(json.contains(&id.to_string()));
     }
 
@@ -1580,8 +3533,9 @@ mod tests {
                 Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
                 Arc::new(crate::infrastructure::OrtProcessor::new_empty()),
                 Arc::new(crate::infrastructure::PhashGenerator::new()),
-                PathBuf::from("uploads"),
-                PathBuf::from("thumbnails"),
+                Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("uploads"))),
+                Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("thumbnails"))),
+                crate::domain::DEFAULT_DUPLICATE_THRESHOLD,
             )),
             search_use_case: Arc::new(crate::application::SearchSimilarUseCase::new(
                 Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
@@ -1592,27 +3546,113 @@ mod tests {
             )),
             delete_use_case: Arc::new(crate::application::DeleteMediaUseCase::new(
                 Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
-                PathBuf::from("uploads"),
-                PathBuf::from("thumbnails"),
+                Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("uploads"))),
+                Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("thumbnails"))),
+            )),
+            duplicates_use_case: Arc::new(crate::application::DuplicatesUseCase::new(
+                Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                Arc::new(crate::application::GroupMediaUseCase::new(
+                    Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                )),
+                Arc::new(crate::application::DeleteMediaUseCase::new(
+                    Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                    Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("uploads"))),
+                    Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("thumbnails"))),
+                )),
             )),
-            group_use_case: Arc::new(crate::application::GroupMediaUseCase::new(
+            trash_use_case: Arc::new(crate::application::TrashUseCase::new(
                 Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                Arc::new(crate::application::DeleteMediaUseCase::new(
+                    Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                    Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("uploads"))),
+                    Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("thumbnails"))),
+                )),
+                30,
             )),
             tag_learning_use_case: Arc::new(crate::application::TagLearningUseCase::new(
                 Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
             )),
-            fix_thumbnails_use_case: Arc::new(crate::application::FixThumbnailsUseCase::new(
+            import_use_case: Arc::new(crate::application::ImportMediaUseCase::new(
                 Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
-                Arc::new(crate::infrastructure::OrtProcessor::new_empty()),
-                Arc::new(crate::infrastructure::PhashGenerator::new()),
-                PathBuf::from("uploads"),
-                PathBuf::from("thumbnails"),
+                Arc::new(crate::application::UploadMediaUseCase::new(
+                    Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                    Arc::new(crate::infrastructure::OrtProcessor::new_empty()),
+                    Arc::new(crate::infrastructure::PhashGenerator::new()),
+                    Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("uploads"))),
+                    Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("thumbnails"))),
+                    crate::domain::DEFAULT_DUPLICATE_THRESHOLD,
+                )),
+                None,
+                false,
+            )),
+            users_use_case: Arc::new(crate::application::UsersUseCase::new(
+                Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+            )),
+            sessions_use_case: Arc::new(crate::application::SessionsUseCase::new(
+                Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                30,
+            )),
+            api_tokens_use_case: Arc::new(crate::application::ApiTokensUseCase::new(
+                Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+            )),
+            shares_use_case: Arc::new(crate::application::SharesUseCase::new(
+                Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+            )),
+            audit_use_case: Arc::new(crate::application::AuditUseCase::new(
+                Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                0,
+            )),
+            jobs_use_case: Arc::new(crate::application::JobsUseCase::new(
+                Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                Arc::new(crate::application::UploadMediaUseCase::new(
+                    Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                    Arc::new(crate::infrastructure::OrtProcessor::new_empty()),
+                    Arc::new(crate::infrastructure::PhashGenerator::new()),
+                    Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("uploads"))),
+                    Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("thumbnails"))),
+                    crate::domain::DEFAULT_DUPLICATE_THRESHOLD,
+                )),
+                Arc::new(crate::application::FixThumbnailsUseCase::new(
+                    Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                    Arc::new(crate::infrastructure::OrtProcessor::new_empty()),
+                    Arc::new(crate::infrastructure::PhashGenerator::new()),
+                    Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("uploads"))),
+                    Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("thumbnails"))),
+                )),
+                Arc::new(crate::application::TagLearningUseCase::new(
+                    Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                )),
+                Arc::new(crate::application::GroupMediaUseCase::new(
+                    Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                )),
+            )),
+            resumable_uploads_use_case: Arc::new(crate::application::ResumableUploadsUseCase::new(
+                Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                Arc::new(crate::application::UploadMediaUseCase::new(
+                    Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                    Arc::new(crate::infrastructure::OrtProcessor::new_empty()),
+                    Arc::new(crate::infrastructure::PhashGenerator::new()),
+                    Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("uploads"))),
+                    Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("thumbnails"))),
+                    crate::domain::DEFAULT_DUPLICATE_THRESHOLD,
+                )),
+                PathBuf::from("partial_uploads"),
+                1024,
+                24,
+            )),
+            renditions_use_case: Arc::new(crate::application::RenditionsUseCase::new(
+                Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
+                Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("uploads"))),
+                Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("thumbnails"))),
+                crate::application::RenditionSizes::default(),
             )),
             repo: Arc::new(crate::infrastructure::SqliteRepository::new_in_memory().unwrap()),
             upload_dir: PathBuf::from("uploads"),
+            storage: Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("uploads"))),
+            thumbnails: Arc::new(crate::infrastructure::LocalStorage::new(PathBuf::from("thumbnails"))),
             auth_config: None,
-            upload_semaphore: Arc::new(tokio::sync::Semaphore::new(2)),
             login_rate_limiter: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            trusted_proxies: TrustedProxies::default(),
             download_plans: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
             tx: tx.clone(),
         };
@@ -1629,7 +3669,7 @@ mod tests {
         let (mut ws_stream, _) = connect_async(ws_url).await.expect("Failed to connect");
 
         let id = Uuid::new_v4();
-        state.broadcast(WsMessage::MediaDeleted { id });
+        state.broadcast(WsMessage::MediaTrashed { ids: vec![id] });
 
         let msg = tokio::time::timeout(Duration::from_secs(1), ws_stream.next())
             .await
@@ -1638,7 +3678,7 @@ mod tests {
             .unwrap();
 
         if let WsMessageProto::Text(text) = msg {
-            assert!(text.contains("MediaDeleted"));
+            assert!(text.contains("MediaTrashed"));
             assert!(text.contains(&id.to_string()));
         } else {
             panic!("Received wrong WS message type");
@@ -1,108 +1,241 @@
 use axum::{
-    extract::Request,
-    http::{header, StatusCode},
+    extract::{ConnectInfo, FromRequestParts, Request},
+    http::{header, request::Parts, HeaderMap, HeaderName, Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
-use hmac::{Hmac, Mac};
-use sha2::Sha256;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use uuid::Uuid;
 
-type HmacSha256 = Hmac<Sha256>;
+use super::proxy::TrustedProxies;
+use crate::application::{ApiTokensUseCase, OidcUseCase, SessionsUseCase, UsersUseCase};
+use crate::domain::{ApiToken, DomainError, LibraryScope, Role, Session, TokenScope, User};
+
+/// Authentication done by a reverse proxy (Authelia, oauth2-proxy, ...) that passes the
+/// signed-in username in a header.
+#[derive(Clone)]
+pub struct ProxyAuth {
+    /// Header carrying the username, e.g. `Remote-User`.
+    pub header: HeaderName,
+    /// Only requests coming straight from these addresses may set the header.
+    pub trusted: TrustedProxies,
+    /// Role of accounts created for usernames seen for the first time.
+    pub default_role: Role,
+    pub users: Arc<UsersUseCase>,
+}
 
 /// Shared auth configuration.
 #[derive(Clone)]
 pub struct AuthConfig {
-    /// The password users must provide to log in.
-    pub password: String,
-    /// A random secret key used to sign session tokens (generated at startup).
-    pub secret: Arc<[u8]>,
-    /// Session generation counter — bumped on logout to invalidate all existing tokens.
-    pub generation: Arc<AtomicU64>,
+    /// Resolves session cookies to their session and user.
+    pub sessions: Arc<SessionsUseCase>,
+    /// Resolves `Authorization: Bearer` API tokens.
+    pub tokens: Arc<ApiTokensUseCase>,
+    /// Account used when a login request carries no username (the bootstrap admin).
+    pub default_username: String,
+    /// Single sign-on through an OpenID Connect provider, offered next to passwords.
+    pub oidc: Option<Arc<OidcUseCase>>,
+    /// Trust a username header set by an authenticating reverse proxy.
+    pub proxy: Option<ProxyAuth>,
 }
 
 impl AuthConfig {
-    pub fn new(password: String) -> Self {
-        use rand::RngCore;
-        let mut secret = [0u8; 32];
-        rand::thread_rng().fill_bytes(&mut secret);
-        Self {
-            password,
-            secret: Arc::from(secret.as_slice()),
-            generation: Arc::new(AtomicU64::new(0)),
+    pub fn new(
+        sessions: Arc<SessionsUseCase>,
+        tokens: Arc<ApiTokensUseCase>,
+        default_username: String,
+    ) -> Self {
+        Self { sessions, tokens, default_username, oidc: None, proxy: None }
+    }
+
+    pub fn with_oidc(mut self, oidc: Arc<OidcUseCase>) -> Self {
+        self.oidc = Some(oidc);
+        self
+    }
+
+    pub fn with_proxy_auth(mut self, proxy: ProxyAuth) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// The user named by the proxy auth header, if the request came directly from a
+    /// trusted proxy. `peer` is the TCP peer, never an address taken from a header.
+    pub fn proxy_user(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<User> {
+        let proxy = self.proxy.as_ref()?;
+        if !peer.is_some_and(|ip| proxy.trusted.contains(ip)) {
+            return None;
+        }
+        let username = headers.get(&proxy.header)?.to_str().ok()?.trim();
+        if username.is_empty() {
+            return None;
         }
+        proxy.users.find_or_create_external(username, proxy.default_role).ok()
     }
 
-    /// Constant-time password verification using XOR fold.
-    pub fn verify_password(&self, candidate: &str) -> bool {
-        let a = self.password.as_bytes();
-        let b = candidate.as_bytes();
-        if a.len() != b.len() {
-            // Still do work to avoid leaking length via timing,
-            // but always return false for different lengths.
-            let _ = a.iter().fold(0u8, |acc, &x| acc | x);
-            return false;
+    /// Resolve the session and user from the session cookie of a request, if any.
+    pub fn session_from_headers(&self, headers: &HeaderMap) -> Option<(Session, User)> {
+        let token = extract_token(headers)?;
+        self.sessions.authenticate(&token).ok().flatten()
+    }
+
+    /// Resolve the API token and its owner from the `Authorization` header, if any.
+    pub fn token_from_headers(&self, headers: &HeaderMap) -> Option<(ApiToken, User)> {
+        let secret = extract_bearer(headers)?;
+        self.tokens.authenticate(&secret).ok().flatten()
+    }
+}
+
+/// The authenticated user, inserted by `require_auth`. Holds `None` when authentication
+/// is disabled, in which case everything is allowed.
+#[derive(Clone, Default)]
+pub struct CurrentUser(pub Option<User>);
+
+impl CurrentUser {
+    pub fn scope(&self) -> LibraryScope {
+        self.0.as_ref().map(LibraryScope::for_user).unwrap_or(LibraryScope::ALL)
+    }
+
+    /// Owner recorded on media and folders this user creates.
+    pub fn owner_id(&self) -> Option<Uuid> {
+        self.0.as_ref().map(|u| u.id)
+    }
+
+    /// Members may change their own and shared items, admins everything.
+    pub fn can_modify(&self, owner_id: Option<Uuid>) -> bool {
+        match &self.0 {
+            None => true,
+            Some(user) => match user.role {
+                Role::Admin => true,
+                Role::Member => owner_id.is_none() || owner_id == Some(user.id),
+                Role::Viewer => false,
+            },
         }
-        a.iter()
-            .zip(b.iter())
-            .fold(0u8, |acc, (&x, &y)| acc | (x ^ y))
-            == 0
-    }
-
-    /// Generate a session token (HMAC of the password + generation with the server secret).
-    /// Token is invalidated on server restart (new secret) or explicit logout (bumped generation).
-    pub fn generate_token(&self) -> String {
-        let gen = self.generation.load(Ordering::SeqCst);
-        let mut mac = HmacSha256::new_from_slice(&self.secret)
-            .expect("HMAC can take key of any size");
-        mac.update(self.password.as_bytes());
-        mac.update(&gen.to_le_bytes());
-        hex::encode(mac.finalize().into_bytes())
-    }
-
-    /// Verify a session token (constant-time comparison).
-    pub fn verify_token(&self, token: &str) -> bool {
-        let gen = self.generation.load(Ordering::SeqCst);
-        let mut mac = HmacSha256::new_from_slice(&self.secret)
-            .expect("HMAC can take key of any size");
-        mac.update(self.password.as_bytes());
-        mac.update(&gen.to_le_bytes());
-        let expected_bytes = mac.finalize().into_bytes();
-        if let Ok(token_bytes) = hex::decode(token) {
-            token_bytes.len() == expected_bytes.len()
-                && token_bytes
-                    .iter()
-                    .zip(expected_bytes.iter())
-                    .fold(0u8, |acc, (a, b)| acc | (a ^ b))
-                    == 0
+    }
+
+    pub fn ensure_can_modify(&self, owner_id: Option<Uuid>) -> Result<(), DomainError> {
+        if self.can_modify(owner_id) {
+            Ok(())
         } else {
-            false
+            Err(DomainError::Forbidden)
         }
     }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for CurrentUser {
+    type Rejection = Infallible;
 
-    /// Invalidate all existing sessions by bumping the generation counter.
-    pub fn invalidate_sessions(&self) {
-        self.generation.fetch_add(1, Ordering::SeqCst);
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<CurrentUser>().cloned().unwrap_or_default())
     }
 }
 
-/// Extract the session token from the `gallery_session` cookie.
-fn extract_token(req: &Request) -> Option<String> {
-    let cookie_header = req.headers().get(header::COOKIE)?.to_str().ok()?;
+/// Id of the session the request was made with, inserted by `require_auth`.
+#[derive(Clone, Copy, Default)]
+pub struct CurrentSession(pub Option<Uuid>);
+
+impl<S: Send + Sync> FromRequestParts<S> for CurrentSession {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<CurrentSession>().copied().unwrap_or_default())
+    }
+}
+
+/// Extract the value of cookie `name`.
+pub(crate) fn extract_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    let cookie_header = headers.get(header::COOKIE)?.to_str().ok()?;
     for part in cookie_header.split(';') {
         let part = part.trim();
-        if let Some(val) = part.strip_prefix("gallery_session=") {
+        if let Some(val) = part.strip_prefix(name).and_then(|rest| rest.strip_prefix('=')) {
             return Some(val.to_string());
         }
     }
     None
 }
 
-/// Axum middleware that checks for a valid session cookie.
-/// Returns 401 if not authenticated.
+/// Extract the session token from the `gallery_session` cookie.
+pub(crate) fn extract_token(headers: &HeaderMap) -> Option<String> {
+    extract_cookie(headers, "gallery_session")
+}
+
+/// Extract an API token from an `Authorization: Bearer` header.
+pub(crate) fn extract_bearer(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (kind, token) = value.trim().split_once(' ')?;
+    kind.eq_ignore_ascii_case("bearer").then(|| token.trim().to_string())
+}
+
+/// POST endpoints that only read the library, so viewers may use them.
+const VIEWER_POST_PATHS: &[&str] = &["/search", "/media/group", "/media/download", "/media/download/plan"];
+
+fn is_read_request(method: &Method, path: &str) -> bool {
+    method == Method::GET || (method == Method::POST && VIEWER_POST_PATHS.contains(&path))
+}
+
+/// Plain uploads and every step of a resumable (tus) upload.
+fn is_upload_request(method: &Method, path: &str) -> bool {
+    (method == Method::POST && path == "/upload") || path == "/tus" || path.starts_with("/tus/")
+}
+
+/// Everyone may create API tokens and sign out their own devices and tokens.
+fn is_own_credentials(method: &Method, path: &str) -> bool {
+    (method == Method::POST && path == "/tokens")
+        || (method == Method::DELETE && (path.starts_with("/sessions/") || path.starts_with("/tokens/")))
+}
+
+/// Whether `role` may call `method path` (relative to the API mount).
+pub(crate) fn role_allows(role: Role, method: &Method, path: &str) -> bool {
+    let admin_only = path == "/users"
+        || path.starts_with("/users/")
+        || path == "/import"
+        || path == "/audit"
+        || path == "/media/fix-thumbnails"
+        || path == "/tags/auto-tag"
+        || (path.starts_with("/tags/") && path.ends_with("/apply"))
+        || path.starts_with("/duplicates")
+        || (path == "/trash" && method == Method::DELETE);
+
+    match role {
+        Role::Admin => true,
+        Role::Member => !admin_only,
+        Role::Viewer => {
+            !admin_only
+                && (is_read_request(method, path)
+                    || (method == Method::POST && path.starts_with("/media/") && path.ends_with("/favorite"))
+                    || is_own_credentials(method, path)
+                    // Cancel or retry their own jobs, e.g. a grouping; the handler checks ownership
+                    || (method == Method::POST && path.starts_with("/jobs/")))
+        }
+    }
+}
+
+/// Whether an API token with `scope` may call `method path`. Applies on top of
+/// `role_allows` for the token's owner.
+pub(crate) fn scope_allows(scope: TokenScope, method: &Method, path: &str) -> bool {
+    // Sessions and tokens are managed from a signed-in browser, so a leaked token
+    // cannot mint new ones
+    if path == "/sessions" || path.starts_with("/sessions/") || path == "/tokens" || path.starts_with("/tokens/") {
+        return false;
+    }
+    match scope {
+        TokenScope::Admin => true,
+        TokenScope::Upload => is_read_request(method, path) || is_upload_request(method, path),
+        TokenScope::Read => is_read_request(method, path),
+    }
+}
+
+/// TCP peer of a request, when the server was started with connect info.
+pub(crate) fn peer_ip(req: &Request) -> Option<IpAddr> {
+    req.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip())
+}
+
+/// Axum middleware that checks for a valid API token, proxy auth header or session
+/// cookie and the user's role (and the token's scope). Returns 401 if not authenticated
+/// and 403 if the request is not allowed.
 pub async fn require_auth(
-    req: Request,
+    mut req: Request,
     next: Next,
 ) -> Response {
     // AuthConfig is stored as an extension on the request by the layer
@@ -110,12 +243,36 @@ pub async fn require_auth(
 
     match auth_config {
         Some(config) => {
-            if let Some(token) = extract_token(&req) {
-                if config.verify_token(&token) {
-                    return next.run(req).await;
+            // A bearer token takes precedence; a bad one is never retried as a cookie
+            if extract_bearer(req.headers()).is_some() {
+                let Some((token, user)) = config.token_from_headers(req.headers()) else {
+                    return StatusCode::UNAUTHORIZED.into_response();
+                };
+                let (method, path) = (req.method(), req.uri().path());
+                if !role_allows(user.role, method, path) || !scope_allows(token.scope, method, path) {
+                    return StatusCode::FORBIDDEN.into_response();
+                }
+                req.extensions_mut().insert(CurrentUser(Some(user)));
+                return next.run(req).await;
+            }
+
+            if let Some(user) = config.proxy_user(peer_ip(&req), req.headers()) {
+                if !role_allows(user.role, req.method(), req.uri().path()) {
+                    return StatusCode::FORBIDDEN.into_response();
                 }
+                req.extensions_mut().insert(CurrentUser(Some(user)));
+                return next.run(req).await;
             }
-            StatusCode::UNAUTHORIZED.into_response()
+
+            let Some((session, user)) = config.session_from_headers(req.headers()) else {
+                return StatusCode::UNAUTHORIZED.into_response();
+            };
+            if !role_allows(user.role, req.method(), req.uri().path()) {
+                return StatusCode::FORBIDDEN.into_response();
+            }
+            req.extensions_mut().insert(CurrentUser(Some(user)));
+            req.extensions_mut().insert(CurrentSession(Some(session.id)));
+            next.run(req).await
         }
         // If no auth config (password not set), allow all requests
         None => next.run(req).await,
@@ -125,62 +282,171 @@ pub async fn require_auth(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::application::hash_password;
+    use crate::domain::MediaRepository;
+    use crate::infrastructure::{SqliteRepository, TestDb};
+    use chrono::Utc;
 
-    fn test_config() -> AuthConfig {
-        AuthConfig {
-            password: "test_password".to_string(),
-            secret: Arc::from([1u8; 32].as_slice()),
-            generation: Arc::new(AtomicU64::new(0)),
-        }
+    fn test_config(db: &TestDb) -> (AuthConfig, User) {
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let user = User {
+            id: Uuid::new_v4(),
+            username: "admin".to_string(),
+            role: Role::Admin,
+            created_at: Utc::now(),
+        };
+        repo.create_user(&user, &hash_password("test_password").unwrap()).unwrap();
+        let config = AuthConfig::new(
+            Arc::new(SessionsUseCase::new(repo.clone(), 30)),
+            Arc::new(ApiTokensUseCase::new(repo)),
+            "admin".to_string(),
+        );
+        (config, user)
+    }
+
+    fn cookie_headers(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            format!("theme=dark; gallery_session={}", token).parse().unwrap(),
+        );
+        headers
     }
 
     #[test]
-    fn verify_password_correct() {
-        let config = test_config();
-        assert!(config.verify_password("test_password"));
+    fn session_cookie_resolves_user() {
+        let db = TestDb::new("auth_session_test");
+        let (config, user) = test_config(&db);
+        let (session, token) = config.sessions.create(user.id, Some("Firefox"), None).unwrap();
+        let (found, found_user) = config.session_from_headers(&cookie_headers(&token)).unwrap();
+        assert_eq!(found.id, session.id);
+        assert_eq!(found_user.id, user.id);
     }
 
     #[test]
-    fn verify_password_wrong() {
-        let config = test_config();
-        assert!(!config.verify_password("wrong_password"));
+    fn revoked_session_rejected() {
+        let db = TestDb::new("auth_revoke_test");
+        let (config, user) = test_config(&db);
+        let (session, token) = config.sessions.create(user.id, None, None).unwrap();
+        let (_, other_token) = config.sessions.create(user.id, None, None).unwrap();
+
+        config.sessions.revoke(user.id, session.id).unwrap();
+
+        assert!(config.session_from_headers(&cookie_headers(&token)).is_none());
+        // Other devices stay signed in
+        assert!(config.session_from_headers(&cookie_headers(&other_token)).is_some());
     }
 
     #[test]
-    fn verify_password_different_length() {
-        let config = test_config();
-        assert!(!config.verify_password("short"));
-        assert!(!config.verify_password("this_is_a_much_longer_password_than_expected"));
+    fn invalid_token_rejected() {
+        let db = TestDb::new("auth_invalid_test");
+        let (config, user) = test_config(&db);
+        config.sessions.create(user.id, None, None).unwrap();
+        assert!(config.session_from_headers(&HeaderMap::new()).is_none());
+        assert!(config.session_from_headers(&cookie_headers("")).is_none());
+        assert!(config.session_from_headers(&cookie_headers("deadbeef")).is_none());
+        assert!(config.session_from_headers(&cookie_headers(&user.id.to_string())).is_none());
     }
 
     #[test]
-    fn token_generation_and_verification() {
-        let config = test_config();
-        let token = config.generate_token();
-        assert!(config.verify_token(&token));
+    fn proxy_header_only_trusted_from_proxies() {
+        let db = TestDb::new("auth_proxy_test");
+        let (config, admin) = test_config(&db);
+        let repo = Arc::new(SqliteRepository::new(&db.path).unwrap());
+        let config = config.with_proxy_auth(ProxyAuth {
+            header: HeaderName::from_static("remote-user"),
+            trusted: TrustedProxies::parse("10.0.0.0/8").unwrap(),
+            default_role: Role::Viewer,
+            users: Arc::new(UsersUseCase::new(repo)),
+        });
+        let proxy: IpAddr = "10.0.0.2".parse().unwrap();
+        let named = |name: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("remote-user", name.parse().unwrap());
+            headers
+        };
+
+        assert_eq!(config.proxy_user(Some(proxy), &named("admin")).unwrap().id, admin.id);
+        // Anyone else could simply send the header themselves
+        assert!(config.proxy_user(Some("203.0.113.9".parse().unwrap()), &named("admin")).is_none());
+        assert!(config.proxy_user(None, &named("admin")).is_none());
+        assert!(config.proxy_user(Some(proxy), &named(" ")).is_none());
+
+        // New names get an account on first sight, and keep it
+        let kim = config.proxy_user(Some(proxy), &named("kim")).unwrap();
+        assert_eq!(kim.role, Role::Viewer);
+        assert_eq!(config.proxy_user(Some(proxy), &named("kim")).unwrap().id, kim.id);
     }
 
     #[test]
-    fn token_invalidation_after_bump() {
-        let config = test_config();
-        let token_before = config.generate_token();
-        assert!(config.verify_token(&token_before));
+    fn role_permissions() {
+        let get = Method::GET;
+        let post = Method::POST;
+        let delete = Method::DELETE;
 
-        config.invalidate_sessions();
+        assert!(role_allows(Role::Admin, &get, "/users"));
+        assert!(!role_allows(Role::Member, &get, "/users"));
+        assert!(role_allows(Role::Admin, &get, "/audit"));
+        assert!(!role_allows(Role::Member, &get, "/audit"));
+        assert!(!role_allows(Role::Member, &post, "/tags/3/apply"));
+        assert!(!role_allows(Role::Member, &delete, "/trash"));
+        assert!(role_allows(Role::Member, &delete, "/trash/abc"));
+        assert!(role_allows(Role::Member, &post, "/upload"));
 
-        // Old token no longer valid
-        assert!(!config.verify_token(&token_before));
+        assert!(role_allows(Role::Viewer, &get, "/media"));
+        assert!(role_allows(Role::Viewer, &post, "/search"));
+        assert!(role_allows(Role::Viewer, &post, "/media/abc/favorite"));
+        assert!(!role_allows(Role::Viewer, &post, "/upload"));
+        assert!(!role_allows(Role::Viewer, &post, "/tus"));
+        assert!(!role_allows(Role::Viewer, &delete, "/media/abc"));
+        assert!(role_allows(Role::Viewer, &delete, "/sessions/abc"));
+        assert!(role_allows(Role::Viewer, &post, "/tokens"));
+        assert!(!role_allows(Role::Viewer, &post, "/tokens/abc"));
+        assert!(!role_allows(Role::Viewer, &get, "/duplicates"));
+    }
 
-        // New token works
-        let token_after = config.generate_token();
-        assert!(config.verify_token(&token_after));
+    #[test]
+    fn token_scopes() {
+        let get = Method::GET;
+        let post = Method::POST;
+
+        assert!(scope_allows(TokenScope::Read, &get, "/media"));
+        assert!(scope_allows(TokenScope::Read, &post, "/media/download/plan"));
+        assert!(!scope_allows(TokenScope::Read, &post, "/upload"));
+        assert!(!scope_allows(TokenScope::Read, &post, "/media/abc/favorite"));
+        assert!(scope_allows(TokenScope::Upload, &post, "/upload"));
+        assert!(scope_allows(TokenScope::Upload, &Method::PATCH, "/tus/abc"));
+        assert!(!scope_allows(TokenScope::Read, &Method::HEAD, "/tus/abc"));
+        assert!(!scope_allows(TokenScope::Upload, &Method::DELETE, "/media/abc"));
+        assert!(scope_allows(TokenScope::Admin, &Method::DELETE, "/media/abc"));
+        // No token may manage credentials
+        assert!(!scope_allows(TokenScope::Admin, &get, "/tokens"));
+        assert!(!scope_allows(TokenScope::Admin, &post, "/tokens"));
+        assert!(!scope_allows(TokenScope::Admin, &Method::DELETE, "/sessions/abc"));
     }
 
     #[test]
-    fn invalid_token_rejected() {
-        let config = test_config();
-        assert!(!config.verify_token("not_hex"));
-        assert!(!config.verify_token("deadbeef"));
-        assert!(!config.verify_token(""));
+    fn bearer_header_parsing() {
+        let mut headers = HeaderMap::new();
+        assert!(extract_bearer(&headers).is_none());
+        headers.insert(header::AUTHORIZATION, "Bearer gn_abc".parse().unwrap());
+        assert_eq!(extract_bearer(&headers).as_deref(), Some("gn_abc"));
+        headers.insert(header::AUTHORIZATION, "Basic Zm9vOmJhcg==".parse().unwrap());
+        assert!(extract_bearer(&headers).is_none());
+    }
+
+    #[test]
+    fn current_user_permissions() {
+        let user = |role| User { id: Uuid::new_v4(), username: "u".to_string(), role, created_at: Utc::now() };
+        let member = CurrentUser(Some(user(Role::Member)));
+        let own = member.owner_id();
+
+        assert!(CurrentUser(None).can_modify(Some(Uuid::new_v4())));
+        assert!(member.can_modify(None));
+        assert!(member.can_modify(own));
+        assert!(!member.can_modify(Some(Uuid::new_v4())));
+        assert!(CurrentUser(Some(user(Role::Admin))).can_modify(Some(Uuid::new_v4())));
+        assert!(!CurrentUser(Some(user(Role::Viewer))).can_modify(None));
+        assert!(!member.scope().all_owners);
     }
 }
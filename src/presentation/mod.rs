@@ -1,5 +1,7 @@
 pub mod api;
 pub mod auth;
+pub mod proxy;
 
 pub use api::*;
 pub use auth::AuthConfig;
+pub use proxy::TrustedProxies;
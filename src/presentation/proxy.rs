@@ -0,0 +1,126 @@
+use axum::http::HeaderMap;
+use std::net::IpAddr;
+
+/// Reverse proxies whose `X-Forwarded-For` (and auth header) we believe, as a list of
+/// addresses and CIDR networks. Empty means every peer is a client.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(Vec<(IpAddr, u8)>);
+
+impl TrustedProxies {
+    /// Parse a comma-separated list such as `10.0.0.0/8, 172.17.0.1, ::1`.
+    pub fn parse(list: &str) -> Result<Self, String> {
+        let mut networks = Vec::new();
+        for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (addr, prefix) = match entry.split_once('/') {
+                Some((addr, prefix)) => (addr, Some(prefix)),
+                None => (entry, None),
+            };
+            let addr: IpAddr = addr
+                .parse()
+                .map_err(|_| format!("Invalid trusted proxy address '{}'", entry))?;
+            let max = if addr.is_ipv4() { 32 } else { 128 };
+            let prefix = match prefix {
+                Some(p) => p
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|&p| p <= max)
+                    .ok_or_else(|| format!("Invalid trusted proxy prefix '{}'", entry))?,
+                None => max,
+            };
+            networks.push((addr.to_canonical(), prefix));
+        }
+        Ok(Self(networks))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.0.iter().any(|&(network, prefix)| match (network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        })
+    }
+
+    /// The client's address. Requests from a trusted proxy are traced back through
+    /// `X-Forwarded-For`, right to left, to the first hop that is not a trusted proxy;
+    /// anything further left was written by the client and cannot be believed.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let mut client = peer.to_canonical();
+        if !self.contains(client) {
+            return client;
+        }
+        let hops = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect::<Vec<_>>();
+        for hop in hops.into_iter().rev() {
+            let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+                break;
+            };
+            client = ip.to_canonical();
+            if !self.contains(client) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forwarded(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", value.parse().unwrap());
+        headers
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn networks() {
+        let proxies = TrustedProxies::parse("10.0.0.0/8, 192.168.1.5,fd00::/8").unwrap();
+        assert!(proxies.contains(ip("10.1.2.3")));
+        assert!(proxies.contains(ip("::ffff:10.1.2.3")));
+        assert!(proxies.contains(ip("192.168.1.5")));
+        assert!(!proxies.contains(ip("192.168.1.6")));
+        assert!(proxies.contains(ip("fd12::1")));
+        assert!(!proxies.contains(ip("fe80::1")));
+        assert!(TrustedProxies::parse("0.0.0.0/0").unwrap().contains(ip("8.8.8.8")));
+        assert!(TrustedProxies::parse("").unwrap().is_empty());
+        assert!(TrustedProxies::parse("10.0.0.0/33").is_err());
+        assert!(TrustedProxies::parse("proxy.local").is_err());
+    }
+
+    #[test]
+    fn forwarded_for_is_only_believed_from_trusted_proxies() {
+        let proxies = TrustedProxies::parse("10.0.0.0/8").unwrap();
+        // Straight from the internet: the header is the client's own claim
+        assert_eq!(proxies.client_ip(ip("203.0.113.9"), &forwarded("1.2.3.4")), ip("203.0.113.9"));
+        // Through our proxy
+        assert_eq!(proxies.client_ip(ip("10.0.0.2"), &forwarded("198.51.100.7")), ip("198.51.100.7"));
+        // A spoofed entry left of the real client is ignored, as are chained trusted hops
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.2"), &forwarded("6.6.6.6, 198.51.100.7, 10.0.0.3")),
+            ip("198.51.100.7")
+        );
+        // No header, or garbage in it, leaves the last address we could trust
+        assert_eq!(proxies.client_ip(ip("10.0.0.2"), &HeaderMap::new()), ip("10.0.0.2"));
+        assert_eq!(proxies.client_ip(ip("10.0.0.2"), &forwarded("nonsense")), ip("10.0.0.2"));
+    }
+}